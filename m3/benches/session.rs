@@ -0,0 +1,58 @@
+// Copyright 2024 Irreducible Inc.
+
+use alloy::rpc::types::EIP1186AccountProofResponse;
+use binius_hal::make_portable_backend;
+use binius_mp3::{ProveOptions, ProverConfig, ProverSession, SelfCheck};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+fn bench_session(c: &mut Criterion) {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("../tests/eip1186_proofs_1.bin")).unwrap();
+    // Two blocks sharing 20 of their 48 accounts, and every node above them.
+    let previous_block = account_proofs[..48].to_vec();
+    let block = account_proofs[28..].to_vec();
+    let config = ProverConfig::default();
+    let options = ProveOptions {
+        self_check: SelfCheck::None,
+        ..Default::default()
+    };
+    let backend = make_portable_backend();
+
+    let mut group = c.benchmark_group("session");
+    group.sample_size(10);
+    group.bench_function("prove", |b| {
+        b.iter(|| binius_mp3::prove(block.clone(), &config, &options, &backend).unwrap())
+    });
+    // The nodes shared with the previous block are decoded, permuted and hashed only once.
+    group.bench_function("prove_in_warm_session", |b| {
+        b.iter_batched(
+            || {
+                let mut session = ProverSession::new();
+                binius_mp3::prove_in_session(
+                    &mut session,
+                    previous_block.clone(),
+                    &config,
+                    &options,
+                    &backend,
+                )
+                .unwrap();
+                session
+            },
+            |mut session| {
+                binius_mp3::prove_in_session(
+                    &mut session,
+                    block.clone(),
+                    &config,
+                    &options,
+                    &backend,
+                )
+                .unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_session);
+criterion_main!(benches);
@@ -0,0 +1,267 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+use binius_core::witness::MultilinearExtensionIndex;
+use binius_math::MultilinearPoly;
+
+// failures reported per constraint and per tuple, beyond which they are only counted
+pub(crate) const MAX_REPORTED: usize = 8;
+
+/// Where a failing value was produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowLocation {
+    /// Namespace of the oracle, e.g. `hash_trans` or `branch_trans`
+    pub namespace: String,
+    /// Row of the table
+    pub row: usize,
+    /// Account proof and node the row was appended for, if it belongs to a function table
+    pub origin: Option<RowOrigin>,
+    /// Address of the originating account proof
+    pub address: Option<Address>,
+}
+
+/// A channel tuple, with pointers and timestamps given as exponents of the generator.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChannelTuple {
+    /// (addr, val, ts) on the mem channel
+    Mem { addr: u32, val: u8, ts: u32 },
+    /// (start_ptr, key_ptr_byte, rlp_ptr, ts; key_ptr_parity) on the state and post_state channels
+    State {
+        start_ptr: u32,
+        key_ptr_byte: u32,
+        rlp_ptr: u32,
+        ts: u32,
+        key_ptr_parity: bool,
+    },
+    /// Any other tuple, one underlier per flushed oracle
+    Raw(Vec<u128>),
+}
+
+/// A tuple whose pushes and pulls on a channel do not cancel out.
+#[derive(Debug, Clone)]
+pub struct UnmatchedTuple {
+    /// Name of the `ChannelIds` entry
+    pub channel: &'static str,
+    pub tuple: ChannelTuple,
+    /// Pushes minus pulls
+    pub multiplicity: i64,
+    /// Rows that flushed the tuple, `None` for boundaries
+    pub sources: Vec<Option<RowLocation>>,
+}
+
+impl std::fmt::Display for UnmatchedTuple {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {:?} unmatched {:+} times, flushed by",
+            self.channel, self.tuple, self.multiplicity
+        )?;
+        for source in &self.sources {
+            match source {
+                Some(location) => write!(f, " {}[{}]", location.namespace, location.row)?,
+                None => write!(f, " boundary")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct Locator<'a> {
+    pub(crate) constraint_system: &'a ConstraintSystem<B128>,
+    pub(crate) row_origins: &'a RowOrigins,
+    pub(crate) addresses: &'a [Address],
+}
+
+impl Locator<'_> {
+    pub(crate) fn label(&self, oracle_id: OracleId) -> String {
+        self.constraint_system.oracles.oracle(oracle_id).label()
+    }
+
+    pub(crate) fn locate(&self, oracle_id: OracleId, row: usize) -> RowLocation {
+        let label = self.label(oracle_id);
+        // tables push a single namespace, gadgets they call may push more
+        let namespace = label.split("::").next().unwrap_or_default().to_string();
+        let origin = TableType::from_namespace(&namespace)
+            .and_then(|table_type| self.row_origins.origin(table_type, row));
+        RowLocation {
+            namespace,
+            row,
+            origin,
+            address: origin.map(|origin| self.addresses[origin.account]),
+        }
+    }
+}
+
+pub(crate) fn evaluate_oracle(
+    witness: &MultilinearExtensionIndex<U, B128>,
+    oracle_id: OracleId,
+    row: usize,
+) -> Result<B128, anyhow::Error> {
+    Ok(witness
+        .get_multilin_poly(oracle_id)?
+        .evaluate_on_hypercube(row)?)
+}
+
+// maps powers of the generator back to their exponents
+struct Logarithms(HashMap<u32, u32>);
+
+impl Logarithms {
+    fn new(len: usize) -> Self {
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let mut power = B32::ONE;
+        let mut logarithms = HashMap::with_capacity(len);
+        for exponent in 0..len as u32 {
+            logarithms.insert(power.to_underlier(), exponent);
+            power *= g;
+        }
+        Self(logarithms)
+    }
+
+    fn get(&self, underlier: u128) -> Option<u32> {
+        self.0.get(&(underlier as u32)).copied()
+    }
+}
+
+fn limb(value: u128, index: usize) -> u128 {
+    value >> (32 * index) & 0xffff_ffff
+}
+
+fn decode_mem(logarithms: &Logarithms, block: u128) -> Option<ChannelTuple> {
+    Some(ChannelTuple::Mem {
+        addr: logarithms.get(limb(block, 0))?,
+        val: u8::try_from(limb(block, 1)).ok()?,
+        ts: logarithms.get(limb(block, 2))?,
+    })
+}
+
+fn decode_state(logarithms: &Logarithms, block0: u128, block1: u128) -> Option<ChannelTuple> {
+    Some(ChannelTuple::State {
+        start_ptr: logarithms.get(limb(block0, 0))?,
+        key_ptr_byte: logarithms.get(limb(block0, 1))?,
+        rlp_ptr: logarithms.get(limb(block0, 2))?,
+        ts: logarithms.get(limb(block0, 3))?,
+        key_ptr_parity: block1 == 1,
+    })
+}
+
+fn decode_tuple(
+    channel_ids: &ChannelIds,
+    logarithms: &Logarithms,
+    channel_id: ChannelId,
+    values: Vec<u128>,
+) -> ChannelTuple {
+    let decoded = match values.as_slice() {
+        [block] if channel_id == channel_ids.mem => decode_mem(logarithms, *block),
+        [block0, block1]
+            if channel_id == channel_ids.state || channel_id == channel_ids.post_state =>
+        {
+            decode_state(logarithms, *block0, *block1)
+        }
+        _ => None,
+    };
+    decoded.unwrap_or(ChannelTuple::Raw(values))
+}
+
+// every read moves the timestamp of its address one power further, so the final timestamps
+// populate_mem pulls are the largest the memory holds; the timestamps of the states count the
+// walks forking at a node, fewer than the keys, which memory holds as well
+fn check_final_timestamps(
+    locator: &Locator,
+    witness: &MultilinearExtensionIndex<U, B128>,
+    logarithms: &Logarithms,
+    mem_len: usize,
+) -> Result<(), anyhow::Error> {
+    for flush in &locator.constraint_system.flushes {
+        let namespace = locator.locate(flush.oracles[0], 0).namespace;
+        if namespace != "populate_mem" || !matches!(flush.direction, FlushDirection::Pull) {
+            continue;
+        }
+        for row in 0..flush.count {
+            let block = evaluate_oracle(witness, flush.oracles[0], row)?.to_underlier();
+            if logarithms.get(limb(block, 2)).is_none() {
+                return Err(anyhow!(
+                    "Address {row} is read {mem_len} times or more, past the timestamps the audit \
+                     maps back"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replays every flush of the constraint system and every boundary per channel, and returns
+/// the tuples whose pushes and pulls do not balance.
+pub(crate) fn audit_channels(
+    locator: &Locator,
+    channel_ids: &ChannelIds,
+    boundaries: &[Boundary<B128>],
+    witness: &MultilinearExtensionIndex<U, B128>,
+    mem_len: usize,
+) -> Result<Vec<UnmatchedTuple>, anyhow::Error> {
+    // sources are located only once a tuple turns out unmatched
+    type Sources = Vec<Option<(OracleId, usize)>>;
+    let mut channels: HashMap<(ChannelId, Vec<u128>), (i64, Sources)> = HashMap::new();
+
+    for flush in &locator.constraint_system.flushes {
+        let sign = match flush.direction {
+            FlushDirection::Push => 1,
+            FlushDirection::Pull => -1,
+        };
+        for row in 0..flush.count {
+            let values = flush
+                .oracles
+                .iter()
+                .map(|&oracle_id| Ok(evaluate_oracle(witness, oracle_id, row)?.to_underlier()))
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+            let (multiplicity, sources) = channels.entry((flush.channel_id, values)).or_default();
+            *multiplicity += sign;
+            if sources.len() < MAX_REPORTED {
+                sources.push(Some((flush.oracles[0], row)));
+            }
+        }
+    }
+    for boundary in boundaries {
+        let sign = match boundary.direction {
+            FlushDirection::Push => 1,
+            FlushDirection::Pull => -1,
+        };
+        let values = boundary
+            .values
+            .iter()
+            .map(|value| value.to_underlier())
+            .collect();
+        let (multiplicity, sources) = channels.entry((boundary.channel_id, values)).or_default();
+        *multiplicity += sign * boundary.multiplicity as i64;
+        if sources.len() < MAX_REPORTED {
+            sources.push(None);
+        }
+    }
+
+    // pointers are below the memory size, and so are the timestamps, which
+    // check_final_timestamps makes sure of; values that cannot be mapped back are reported raw
+    let logarithms = Logarithms::new(mem_len);
+    check_final_timestamps(locator, witness, &logarithms, mem_len)?;
+    let channel_names = channel_ids.names();
+    let mut unmatched = channels
+        .into_iter()
+        .filter(|(_, (multiplicity, _))| *multiplicity != 0)
+        .map(|((channel_id, values), (multiplicity, sources))| {
+            let channel = channel_names
+                .iter()
+                .find(|(id, _)| *id == channel_id)
+                .map_or("unknown", |(_, name)| name);
+            UnmatchedTuple {
+                channel,
+                tuple: decode_tuple(channel_ids, &logarithms, channel_id, values),
+                multiplicity,
+                sources: sources
+                    .into_iter()
+                    .map(|source| source.map(|(oracle_id, row)| locator.locate(oracle_id, row)))
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    unmatched
+        .sort_by(|left, right| (left.channel, &left.tuple).cmp(&(right.channel, &right.tuple)));
+    Ok(unmatched)
+}
@@ -0,0 +1,185 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+const ADDRESS_LEN: usize = 20;
+const INDEX_ENTRY_LEN: usize = ADDRESS_LEN + 4;
+const OFFSET_LEN: usize = 8;
+
+/// Several serialized [`prove`] outputs together with an index of the addresses each one
+/// covers.
+///
+/// The index and the offsets of the proofs precede the proofs, so a single proof can be located
+/// and sliced out without deserializing the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MPTProofBundle {
+    // sorted by address
+    index: Vec<(Address, u32)>,
+    // offsets into `proofs` of every proof and of the end of the last one
+    offsets: Vec<usize>,
+    proofs: Vec<u8>,
+}
+
+impl Default for MPTProofBundle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MPTProofBundle {
+    const BUNDLE_VERSION: u16 = 1;
+    const HEADER_LEN: usize = 16;
+    const MAGIC_NUMBER: [u8; 6] = *b"MPTBDL";
+
+    pub fn new() -> Self {
+        Self {
+            index: vec![],
+            offsets: vec![0],
+            proofs: vec![],
+        }
+    }
+
+    /// Appends a serialized proof of the given addresses.
+    pub fn push(
+        &mut self,
+        proof: &[u8],
+        addresses: impl IntoIterator<Item = Address>,
+    ) -> Result<(), anyhow::Error> {
+        let proof_index = u32::try_from(self.len())?;
+        self.index
+            .extend(addresses.into_iter().map(|address| (address, proof_index)));
+        self.index.sort();
+        if let Some(pair) = self.index.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            let address = pair[0].0;
+            self.index.retain(|(_, entry)| *entry != proof_index);
+            return Err(anyhow!("Address {address} is in several proofs"));
+        }
+        self.proofs.extend_from_slice(proof);
+        self.offsets.push(self.proofs.len());
+        Ok(())
+    }
+
+    /// Number of proofs.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The serialized proof at `proof_index`, to be passed to [`verify`].
+    pub fn proof(&self, proof_index: usize) -> Option<&[u8]> {
+        let (start, end) = (
+            *self.offsets.get(proof_index)?,
+            *self.offsets.get(proof_index + 1)?,
+        );
+        Some(&self.proofs[start..end])
+    }
+
+    /// Index of the proof covering `address`.
+    pub fn proof_index(&self, address: &Address) -> Option<usize> {
+        self.index
+            .binary_search_by(|(entry, _)| entry.cmp(address))
+            .ok()
+            .map(|position| self.index[position].1 as usize)
+    }
+
+    /// Addresses covered by the proof at `proof_index`.
+    pub fn addresses(&self, proof_index: usize) -> impl Iterator<Item = &Address> {
+        self.index
+            .iter()
+            .filter(move |(_, entry)| *entry as usize == proof_index)
+            .map(|(address, _)| address)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut result = Vec::with_capacity(
+            Self::HEADER_LEN
+                + self.index.len() * INDEX_ENTRY_LEN
+                + self.offsets.len() * OFFSET_LEN
+                + self.proofs.len(),
+        );
+
+        result.write_all(&Self::MAGIC_NUMBER)?;
+        result.write_all(&Self::BUNDLE_VERSION.to_le_bytes())?;
+        result.write_all(&u32::try_from(self.len())?.to_le_bytes())?;
+        result.write_all(&u32::try_from(self.index.len())?.to_le_bytes())?;
+        for (address, proof_index) in &self.index {
+            result.write_all(address.as_slice())?;
+            result.write_all(&proof_index.to_le_bytes())?;
+        }
+        for offset in &self.offsets {
+            result.write_all(&(*offset as u64).to_le_bytes())?;
+        }
+        result.extend_from_slice(&self.proofs);
+        Ok(result)
+    }
+
+    pub fn from_bytes(mut data: Vec<u8>) -> Result<Self, anyhow::Error> {
+        if data.len() < Self::HEADER_LEN {
+            return Err(anyhow!("Byte array is too short."));
+        }
+        let magic_number = &data[0..6];
+        if magic_number != Self::MAGIC_NUMBER {
+            return Err(anyhow!(
+                "The data does not seem to be a proof bundle {:?}. Expected bundle to start with {:?}",
+                magic_number,
+                Self::MAGIC_NUMBER
+            ));
+        }
+        let version = u16::from_le_bytes([data[6], data[7]]);
+        if version != Self::BUNDLE_VERSION {
+            return Err(anyhow!(
+                "Bundle version {version} not supported by this verifier. Supported version = {}",
+                Self::BUNDLE_VERSION
+            ));
+        }
+        let proof_count = u32::from_le_bytes(data[8..12].try_into()?) as usize;
+        let address_count = u32::from_le_bytes(data[12..16].try_into()?) as usize;
+
+        let offsets_start = Self::HEADER_LEN + address_count * INDEX_ENTRY_LEN;
+        let proofs_start = offsets_start + (proof_count + 1) * OFFSET_LEN;
+        if data.len() < proofs_start {
+            return Err(anyhow!("Bundle index is truncated."));
+        }
+
+        let index = data[Self::HEADER_LEN..offsets_start]
+            .chunks_exact(INDEX_ENTRY_LEN)
+            .map(|entry| {
+                let (address, proof_index) = entry.split_at(ADDRESS_LEN);
+                (
+                    Address::from_slice(address),
+                    u32::from_le_bytes(proof_index.try_into().expect("4 bytes")),
+                )
+            })
+            .collect::<Vec<_>>();
+        let offsets = data[offsets_start..proofs_start]
+            .chunks_exact(OFFSET_LEN)
+            .map(|offset| usize::try_from(u64::from_le_bytes(offset.try_into().expect("8 bytes"))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let proofs = data.split_off(proofs_start);
+        if offsets[0] != 0
+            || offsets.windows(2).any(|pair| pair[0] > pair[1])
+            || offsets[proof_count] != proofs.len()
+        {
+            return Err(anyhow!("Bundle offsets do not match the proofs."));
+        }
+        if index.windows(2).any(|pair| pair[0].0 >= pair[1].0)
+            || index
+                .iter()
+                .any(|(_, proof_index)| *proof_index as usize >= proof_count)
+        {
+            return Err(anyhow!(
+                "Bundle index is not sorted or refers to missing proofs."
+            ));
+        }
+
+        Ok(Self {
+            index,
+            offsets,
+            proofs,
+        })
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+/// Parameters used by [`prove`]. Everything the verifier needs is recorded in the proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProverConfig {
+    /// Log2 of the inverse Reed–Solomon code rate
+    pub log_inv_rate: usize,
+    /// Target security level of the proof
+    pub security_bits: usize,
+    /// Hash function for the Fiat–Shamir transcript and the commitments
+    pub hash: HashFunction,
+    /// Encoding of the statement inside the proof
+    pub statement_compression: StatementCompression,
+}
+
+impl Default for ProverConfig {
+    fn default() -> Self {
+        Self {
+            log_inv_rate: LOG_INVERSE_RATE,
+            security_bits: SECURITY_BITS,
+            hash: HashFunction::default(),
+            statement_compression: StatementCompression::default(),
+        }
+    }
+}
+
+impl ProverConfig {
+    // accepts exactly the parameters of this config, used to check our own proofs
+    pub(crate) fn self_check_policy(&self) -> VerifierPolicy {
+        VerifierPolicy {
+            min_security_bits: self.security_bits,
+            log_inv_rates: self.log_inv_rate..=self.log_inv_rate,
+            allowed_hashes: vec![self.hash],
+            max_accounts: None,
+            max_proof_size: None,
+        }
+    }
+
+    pub(crate) fn header(&self, account_count: usize) -> ProofHeader {
+        ProofHeader {
+            hash: self.hash,
+            log_inv_rate: self.log_inv_rate,
+            security_bits: self.security_bits,
+            account_count,
+        }
+    }
+}
+
+/// Check performed by [`prove`] before returning.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelfCheck {
+    /// Return the proof unchecked
+    None,
+    /// Verify the serialized proof
+    #[default]
+    Verify,
+    /// Validate the witness against the constraint system before proving, which reports
+    /// the failing constraint or channel instead of a failed verification
+    ValidateWitness,
+}
+
+/// Prover-side options that do not affect the proof.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProveOptions {
+    pub self_check: SelfCheck,
+    /// Replay every channel before proving and fail with the unmatched tuples, if any
+    pub audit_channels: bool,
+}
+
+impl ProveOptions {
+    /// Validates the witness and audits every channel before proving, so a broken witness is
+    /// reported by constraint and by unmatched tuple
+    pub fn audited() -> Self {
+        Self {
+            self_check: SelfCheck::ValidateWitness,
+            audit_channels: true,
+        }
+    }
+}
+
+// the parameters of a proof, stored after the version in the serialized proof
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ProofHeader {
+    pub(crate) hash: HashFunction,
+    pub(crate) log_inv_rate: usize,
+    pub(crate) security_bits: usize,
+    // the accounts of the statement, so the policy bounds them before the body is decoded
+    pub(crate) account_count: usize,
+}
+
+impl ProofHeader {
+    pub(crate) const LEN: usize = 8;
+    // the account count is the last field, a little-endian u32
+    pub(crate) const ACCOUNT_COUNT_OFFSET: usize = 4;
+
+    pub(crate) fn to_bytes(self) -> Result<[u8; Self::LEN], anyhow::Error> {
+        let log_inv_rate = u8::try_from(self.log_inv_rate)?;
+        let [security_bits_lo, security_bits_hi] = u16::try_from(self.security_bits)?.to_le_bytes();
+        let [count_0, count_1, count_2, count_3] = u32::try_from(self.account_count)?.to_le_bytes();
+        Ok([
+            self.hash.to_byte(),
+            log_inv_rate,
+            security_bits_lo,
+            security_bits_hi,
+            count_0,
+            count_1,
+            count_2,
+            count_3,
+        ])
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        if bytes.len() < Self::LEN {
+            return Err(anyhow!("Proof header is too short."));
+        }
+        Ok(Self {
+            hash: HashFunction::from_byte(bytes[0])?,
+            log_inv_rate: bytes[1] as usize,
+            security_bits: u16::from_le_bytes([bytes[2], bytes[3]]) as usize,
+            account_count: u32::from_le_bytes(
+                bytes[Self::ACCOUNT_COUNT_OFFSET..Self::LEN].try_into()?,
+            ) as usize,
+        })
+    }
+}
+
+/// Limits a verifier enforces on a proof before doing any heavy work.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifierPolicy {
+    /// Proofs declaring fewer security bits are rejected
+    pub min_security_bits: usize,
+    /// Accepted log2 inverse code rates. The default of 1 to 4 takes the prover's default of 2
+    /// and the rates around it; the security level is checked on its own against
+    /// `min_security_bits`, so the range only bounds the shape of the proof, as a lower rate
+    /// needs more queries and a higher one a larger committed domain
+    pub log_inv_rates: std::ops::RangeInclusive<usize>,
+    /// Accepted hash functions
+    pub allowed_hashes: Vec<HashFunction>,
+    /// Maximum number of accounts in the statement, unbounded if `None`
+    pub max_accounts: Option<usize>,
+    /// Maximum size of the serialized proof in bytes, unbounded if `None`
+    pub max_proof_size: Option<usize>,
+}
+
+impl Default for VerifierPolicy {
+    fn default() -> Self {
+        Self {
+            min_security_bits: SECURITY_BITS,
+            log_inv_rates: 1..=LOG_INVERSE_RATE + 2,
+            allowed_hashes: vec![
+                HashFunction::Groestl256,
+                HashFunction::Keccak256,
+                HashFunction::Sha256,
+            ],
+            max_accounts: None,
+            max_proof_size: None,
+        }
+    }
+}
+
+impl VerifierPolicy {
+    pub(crate) fn check_proof_size(&self, proof_size: usize) -> Result<(), anyhow::Error> {
+        match self.max_proof_size {
+            Some(max_proof_size) if proof_size > max_proof_size => Err(anyhow!(
+                "Proof size {proof_size} exceeds the policy maximum of {max_proof_size} bytes"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_header(&self, header: &ProofHeader) -> Result<(), anyhow::Error> {
+        if header.security_bits < self.min_security_bits {
+            return Err(anyhow!(
+                "Proof declares {} security bits, policy requires at least {}",
+                header.security_bits,
+                self.min_security_bits
+            ));
+        }
+        if !self.log_inv_rates.contains(&header.log_inv_rate) {
+            return Err(anyhow!(
+                "Proof declares log inverse rate {}, policy allows {:?}",
+                header.log_inv_rate,
+                self.log_inv_rates
+            ));
+        }
+        if !self.allowed_hashes.contains(&header.hash) {
+            return Err(anyhow!(
+                "Proof uses hash function {:?}, policy allows {:?}",
+                header.hash,
+                self.allowed_hashes
+            ));
+        }
+        match self.max_accounts {
+            Some(max_accounts) if header.account_count > max_accounts => Err(anyhow!(
+                "Statement has {} accounts, policy allows at most {max_accounts}",
+                header.account_count
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+use binius_core::constraint_system::validate::validate_witness;
+use binius_core::witness::MultilinearExtensionIndex;
+
+/// A zerocheck constraint that does not vanish on some row.
+#[derive(Debug, Clone)]
+pub struct ConstraintFailure {
+    pub location: RowLocation,
+    /// Index of the constraint within its constraint set
+    pub constraint_index: usize,
+    /// Labels of the oracles the constraint is expressed over
+    pub oracles: Vec<String>,
+}
+
+impl std::fmt::Display for ConstraintFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{}]: constraint {} over {:?} does not vanish",
+            self.location.namespace, self.location.row, self.constraint_index, self.oracles
+        )
+    }
+}
+
+/// Result of [`diagnose`].
+#[derive(Debug, Default)]
+pub struct Diagnosis {
+    /// Error returned by `validate_witness`, if any
+    pub validation_error: Option<String>,
+    pub constraint_failures: Vec<ConstraintFailure>,
+    /// Oracles asserted non-zero that vanish on some row
+    pub zero_oracles: Vec<RowLocation>,
+    pub channel_imbalances: Vec<UnmatchedTuple>,
+}
+
+impl Diagnosis {
+    pub fn is_ok(&self) -> bool {
+        self.validation_error.is_none()
+            && self.constraint_failures.is_empty()
+            && self.zero_oracles.is_empty()
+            && self.channel_imbalances.is_empty()
+    }
+}
+
+/// Builds the witness for the given account proofs without proving, validates it, and maps
+/// every failure back to the table, row and account proof it originates from.
+#[instrument("binius_mp3::diagnose", skip_all, level = "debug")]
+pub fn diagnose(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+) -> Result<Diagnosis, anyhow::Error> {
+    let (mpt, advice, addresses) = process_tracked(alloy_account_proofs);
+    diagnose_advice(mpt, advice, &addresses)
+}
+
+/// A fault [`diagnose_with_fault`] injects into the witness. Only meant for testing the
+/// diagnostics.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The first byte of the root is read once more than the tables read it, so the memory
+    /// table pulls it one timestamp past the last read
+    ExtraRootRead,
+    /// The verifier pulls the final state of the walk of the account at this index one
+    /// timestamp past the one the walk ends at
+    LateFinalState(usize),
+}
+
+/// Same as [`diagnose`], with `fault` injected into the witness.
+#[doc(hidden)]
+pub fn diagnose_with_fault(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    fault: Fault,
+) -> Result<Diagnosis, anyhow::Error> {
+    let (mut mpt, mut advice, addresses) = process_tracked(alloy_account_proofs);
+    match fault {
+        Fault::ExtraRootRead => {
+            // the root is the first node laid out, after the root hash and the keys
+            let root_ptr = 1 + 32 + 32 * mpt.keys.len() as u32;
+            mpt.state.mem.process_timestamp(root_ptr);
+        }
+        Fault::LateFinalState(account) => {
+            let final_ts = &mut advice.ptr_ts_pairs[account].final_ts;
+            *final_ts = (B32::new(*final_ts) * B32::MULTIPLICATIVE_GENERATOR).to_underlier();
+        }
+    }
+    diagnose_advice(mpt, advice, &addresses)
+}
+
+/// Same as [`diagnose`], for the update [`prove_update`] would prove, without the checks it
+/// makes on the host that the update changes nothing but the values. Only meant for testing
+/// that the circuit catches the updates those checks reject.
+#[doc(hidden)]
+pub fn diagnose_unchecked_update(
+    pre_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    post_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+) -> Result<Diagnosis, anyhow::Error> {
+    let (statement, pre_account_proofs, post_account_proofs) = update_statement_and_account_proofs(
+        pre_alloy_account_proofs,
+        post_alloy_account_proofs,
+        false,
+    )?;
+    let addresses = pre_account_proofs
+        .iter()
+        .map(|account_proof| account_proof.address)
+        .collect::<Vec<_>>();
+    let mut mpt = MPT::new(statement);
+    mpt.track_row_origins();
+    let advice = mpt.process_update_proofs(pre_account_proofs, post_account_proofs);
+    diagnose_advice(mpt, advice, &addresses)
+}
+
+/// The rows the walks of the account proofs append, with their timestamps. Only meant for
+/// testing that the walks of the keys, which are split across threads, append the rows of a
+/// sequential walk.
+#[doc(hidden)]
+#[derive(PartialEq)]
+pub struct TracedRows(Vec<Row>);
+
+impl TracedRows {
+    pub fn row_count(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// The rows [`prove`] appends for the walks of the account proofs, or the ones of walking the
+/// keys one after the other if `in_order` is set.
+#[doc(hidden)]
+pub fn traced_rows(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    in_order: bool,
+) -> TracedRows {
+    let (statement, account_proofs) = get_statement_and_account_proofs(alloy_account_proofs);
+    let mut mpt = MPT::new(statement);
+    TracedRows(mpt.traced_rows(account_proofs, in_order))
+}
+
+// walks the account proofs, tracking the origins of the rows
+fn process_tracked(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+) -> (MPT, Advice, Vec<Address>) {
+    let (statement, account_proofs) = get_statement_and_account_proofs(alloy_account_proofs);
+    let addresses = account_proofs
+        .iter()
+        .map(|account_proof| account_proof.address)
+        .collect::<Vec<_>>();
+    let mut mpt = MPT::new(statement);
+    mpt.track_row_origins();
+
+    let advice = mpt.process_account_proofs(account_proofs);
+    (mpt, advice, addresses)
+}
+
+// builds the witness of a walk that tracked its row origins, then validates and locates it
+fn diagnose_advice(
+    mut mpt: MPT,
+    advice: Advice,
+    addresses: &[Address],
+) -> Result<Diagnosis, anyhow::Error> {
+    let row_origins = mpt.row_origins.take().unwrap_or_default();
+
+    let allocator = bumpalo::Bump::new();
+    let mut builder = ConstraintSystemBuilder::new_with_witness(&allocator);
+    let mem_len = advice.table_heights.mem_len();
+    let (boundaries, _statement, _accounts, channel_ids) = mpt.build(&mut builder, advice)?;
+    let witness = builder.take_witness()?;
+    let constraint_system = builder.build()?;
+
+    let locator = Locator {
+        constraint_system: &constraint_system,
+        row_origins: &row_origins,
+        addresses,
+    };
+
+    Ok(Diagnosis {
+        validation_error: validate_witness(&constraint_system, &boundaries, &witness)
+            .err()
+            .map(|err| err.to_string()),
+        constraint_failures: check_constraints(&locator, &witness)?,
+        zero_oracles: check_non_zero_oracles(&locator, &witness)?,
+        channel_imbalances: audit_channels(&locator, &channel_ids, &boundaries, &witness, mem_len)?,
+    })
+}
+
+fn evaluate(expr: &ArithExpr<B128>, vars: &[B128]) -> B128 {
+    match expr {
+        ArithExpr::Const(value) => *value,
+        ArithExpr::Var(index) => vars[*index],
+        ArithExpr::Add(left, right) => evaluate(left, vars) + evaluate(right, vars),
+        ArithExpr::Mul(left, right) => evaluate(left, vars) * evaluate(right, vars),
+        ArithExpr::Pow(base, exp) => evaluate(base, vars).pow([*exp]),
+    }
+}
+
+// the variables an expression reads, in order
+fn variables(expr: &ArithExpr<B128>, variables_read: &mut Vec<usize>) {
+    match expr {
+        ArithExpr::Const(_) => (),
+        ArithExpr::Var(index) => {
+            if !variables_read.contains(index) {
+                variables_read.push(*index);
+            }
+        }
+        ArithExpr::Add(left, right) | ArithExpr::Mul(left, right) => {
+            variables(left, variables_read);
+            variables(right, variables_read);
+        }
+        ArithExpr::Pow(base, _) => variables(base, variables_read),
+    }
+}
+
+pub(crate) fn check_constraints(
+    locator: &Locator,
+    witness: &MultilinearExtensionIndex<U, B128>,
+) -> Result<Vec<ConstraintFailure>, anyhow::Error> {
+    let mut failures = vec![];
+    for constraint_set in &locator.constraint_system.table_constraints {
+        // the constraints of a set share its oracles, each of them reads only some
+        let oracles = constraint_set
+            .constraints
+            .iter()
+            .map(|constraint| {
+                let mut variables_read = vec![];
+                variables(&constraint.composition, &mut variables_read);
+                variables_read.sort();
+                variables_read
+                    .into_iter()
+                    .map(|index| locator.label(constraint_set.oracle_ids[index]))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut reported = vec![0; constraint_set.constraints.len()];
+        for row in 0..1 << constraint_set.n_vars {
+            let vars = constraint_set
+                .oracle_ids
+                .iter()
+                .map(|&oracle_id| evaluate_oracle(witness, oracle_id, row))
+                .collect::<Result<Vec<_>, _>>()?;
+            for (constraint_index, constraint) in constraint_set.constraints.iter().enumerate() {
+                if reported[constraint_index] == MAX_REPORTED
+                    || evaluate(&constraint.composition, &vars) == B128::ZERO
+                {
+                    continue;
+                }
+                reported[constraint_index] += 1;
+                failures.push(ConstraintFailure {
+                    location: locator.locate(constraint_set.oracle_ids[0], row),
+                    constraint_index,
+                    oracles: oracles[constraint_index].clone(),
+                });
+            }
+        }
+    }
+    Ok(failures)
+}
+
+fn check_non_zero_oracles(
+    locator: &Locator,
+    witness: &MultilinearExtensionIndex<U, B128>,
+) -> Result<Vec<RowLocation>, anyhow::Error> {
+    let mut zero_oracles = vec![];
+    for &oracle_id in &locator.constraint_system.non_zero_oracle_ids {
+        let n_vars = locator.constraint_system.oracles.oracle(oracle_id).n_vars();
+        let mut reported = 0;
+        for row in 0..1 << n_vars {
+            if reported == MAX_REPORTED {
+                break;
+            }
+            if evaluate_oracle(witness, oracle_id, row)? == B128::ZERO {
+                reported += 1;
+                zero_oracles.push(locator.locate(oracle_id, row));
+            }
+        }
+    }
+    Ok(zero_oracles)
+}
@@ -0,0 +1,94 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+// heuristic: derived columns, such as the 128-bit flush blocks, take a 128-bit value per
+// committed column and row
+const DERIVED_BYTES_PER_CELL: usize = 16;
+// digest size of the Merkle commitments
+const DIGEST_BYTES: usize = 32;
+// committed values are packed into 128-bit codeword symbols
+const SYMBOL_BYTES: usize = 16;
+const LOG_SYMBOL_BITS: usize = 7;
+// heuristic: log2 of the symbols folded per FRI round, each round opening a coset of them
+const LOG_FOLD_ARITY: usize = 4;
+
+/// Resources [`prove`] is expected to use for a batch of account proofs.
+#[derive(Clone, Debug)]
+pub struct ProofEstimate {
+    /// Size of every table, including memory and the lookup tables
+    pub table_stats: Vec<TableStats>,
+    /// Committed bits over all tables, including memory and the lookup tables
+    pub committed_bits: usize,
+    /// Rough peak memory of the prover in bytes, from the table sizes
+    pub peak_memory_bytes: usize,
+    /// Rough size of the serialized proof in bytes, from a model of the FRI queries
+    pub proof_size_bytes: usize,
+}
+
+/// Walks the account proofs to count the rows of every table, without building the witness,
+/// hashing the nodes or proving, and derives the resources proving them with `config` would
+/// take.
+///
+/// Table heights and committed bits are exact, the latter being measured on the constraint
+/// system of every table. Peak memory adds, per table, the committed columns, their
+/// Reed-Solomon codeword and a 128-bit derived value per committed cell. Proof size counts the
+/// queries FRI makes at `config.security_bits` in the unique decoding regime, each opening a
+/// coset and a Merkle path per folding round; the sumcheck transcripts, logarithmic in the
+/// table heights, are left out.
+#[instrument("binius_mp3::estimate", skip_all, level = "debug")]
+pub fn estimate(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    config: &ProverConfig,
+) -> Result<ProofEstimate, anyhow::Error> {
+    let (statement, account_proofs) = get_statement_and_account_proofs(alloy_account_proofs);
+    let account_count = account_proofs.len();
+    let statement_len = bincode::serialized_size(&statement)? as usize;
+
+    let mut mpt = MPT::new(statement);
+    let table_stats = mpt.count_table_heights(account_proofs).stats()?;
+    let committed_bytes = table_stats
+        .iter()
+        .map(|table_stats| table_stats.committed_bytes)
+        .sum::<usize>();
+    let committed_cells = table_stats
+        .iter()
+        .map(|table_stats| table_stats.committed_columns << table_stats.n_vars)
+        .sum::<usize>();
+
+    // the committed columns, their codeword and the derived columns are all held at once
+    let peak_memory_bytes = committed_bytes * ((1 << config.log_inv_rate) + 1)
+        + committed_cells * DERIVED_BYTES_PER_CELL;
+
+    // every query opens a coset and its Merkle path in the codeword and in every folded one
+    let query_count = query_count(config.security_bits, config.log_inv_rate);
+    let codeword_log_len = ((committed_bytes * 8).next_power_of_two().trailing_zeros() as usize)
+        .saturating_sub(LOG_SYMBOL_BITS)
+        + config.log_inv_rate;
+    let query_len = (config.log_inv_rate..=codeword_log_len)
+        .rev()
+        .step_by(LOG_FOLD_ARITY)
+        .map(|log_len| {
+            let log_coset_len = LOG_FOLD_ARITY.min(log_len);
+            (SYMBOL_BYTES << log_coset_len) + (log_len - log_coset_len) * DIGEST_BYTES
+        })
+        .sum::<usize>();
+    let advice_len = account_count * std::mem::size_of::<PtrTsPair>();
+
+    Ok(ProofEstimate {
+        table_stats,
+        committed_bits: committed_bytes * 8,
+        peak_memory_bytes,
+        proof_size_bytes: MPTProof::HEADER_LEN
+            + query_count * query_len
+            + advice_len
+            + statement_len,
+    })
+}
+
+// the queries bringing the soundness error to 2^-security_bits, each query passing a codeword
+// far from the code with probability (1 + rate) / 2
+fn query_count(security_bits: usize, log_inv_rate: usize) -> usize {
+    let per_query_bits = -(0.5 * (1.0 + 0.5f64.powi(log_inv_rate as i32))).log2();
+    (security_bits as f64 / per_query_bits).ceil() as usize
+}
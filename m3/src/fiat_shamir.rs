@@ -0,0 +1,87 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+use digest::{Digest, Output};
+use p3_symmetric::PseudoCompressionFunction;
+use std::marker::PhantomData;
+
+/// Hash function used for the Fiat–Shamir transcript and the Merkle commitments of a proof.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HashFunction {
+    #[default]
+    Groestl256,
+    Keccak256,
+    Sha256,
+}
+
+impl HashFunction {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Self::Groestl256 => 0,
+            Self::Keccak256 => 1,
+            Self::Sha256 => 2,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Result<Self, anyhow::Error> {
+        match byte {
+            0 => Ok(Self::Groestl256),
+            1 => Ok(Self::Keccak256),
+            2 => Ok(Self::Sha256),
+            _ => Err(anyhow!("Unknown hash function identifier {byte}")),
+        }
+    }
+}
+
+// compresses two digests by hashing their concatenation
+#[derive(Debug)]
+pub(crate) struct DigestByteCompression<H>(PhantomData<H>);
+
+impl<H> Clone for DigestByteCompression<H> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<H> Default for DigestByteCompression<H> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<H: Digest> PseudoCompressionFunction<Output<H>, 2> for DigestByteCompression<H> {
+    fn compress(&self, input: [Output<H>; 2]) -> Output<H> {
+        H::new()
+            .chain_update(&input[0])
+            .chain_update(&input[1])
+            .finalize()
+    }
+}
+
+pub(crate) type Keccak256ByteCompression = DigestByteCompression<sha3::Keccak256>;
+pub(crate) type Sha256ByteCompression = DigestByteCompression<sha2::Sha256>;
+
+// binds `$hash` and `$compress` to the concrete types selected by a HashFunction; callers
+// pick names other than `Hash`, which would shadow the crate's digest alias
+macro_rules! with_hash_function {
+    ($hash_function:expr, |$hash:ident, $compress:ident| $body:expr) => {
+        match $hash_function {
+            HashFunction::Groestl256 => {
+                type $hash = groestl_crypto::Groestl256;
+                type $compress = Groestl256ByteCompression;
+                $body
+            }
+            HashFunction::Keccak256 => {
+                type $hash = sha3::Keccak256;
+                type $compress = $crate::fiat_shamir::Keccak256ByteCompression;
+                $body
+            }
+            HashFunction::Sha256 => {
+                type $hash = sha2::Sha256;
+                type $compress = $crate::fiat_shamir::Sha256ByteCompression;
+                $body
+            }
+        }
+    };
+}
+pub(crate) use with_hash_function;
@@ -0,0 +1,300 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+/// Where [`Keccak256`] laid out a message, its padding and its digest in its memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keccak256Region {
+    /// Address of the first byte of the message
+    pub preimage_ptr: u32,
+    /// Length of the message in bytes
+    pub len: usize,
+    /// Address of the first byte of the digest, right after the padding
+    pub digest_ptr: u32,
+}
+
+impl Keccak256Region {
+    // blocks the padded message takes
+    fn blocks(&self) -> u8 {
+        (self.len / HASH_R + 1) as u8
+    }
+}
+
+/// The channels [`Keccak256`] reads its memory on and absorbs the messages on.
+///
+/// A circuit sharing its memory with the gadget hands it its own memory channel, and a circuit
+/// starting the absorptions itself, rather than from the boundaries, its own absorb channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keccak256Channels {
+    /// Channel of the reads of the memory the messages are laid out in
+    pub mem: ChannelId,
+    /// Channel the absorption of every message from the zero state is pushed to
+    pub absorb_block: ChannelId,
+}
+
+impl Keccak256Channels {
+    pub fn new(builder: &mut ConstraintSystemBuilder<OptimalUnderlier, BinaryField128b>) -> Self {
+        Self {
+            mem: builder.add_channel(),
+            absorb_block: builder.add_channel(),
+        }
+    }
+}
+
+/// In-circuit keccak-256 over the messages hashed with [`Keccak256::hash`] or
+/// [`Keccak256::hash_at`], built with the same sponge tables the trie nodes are hashed with.
+///
+/// Every message is laid out in memory, followed by its padding and its digest. The circuit
+/// absorbs each padded message from the zero state and checks the state it ends in against the
+/// digest. The boundaries [`Keccak256::build`] returns read the padding and the digests from
+/// memory, and the messages too unless the preimages are private, so prover and verifier
+/// instantiate the gadget with the same regions, in the same order.
+pub struct Keccak256 {
+    state: MPTState,
+    sponge: KeccakSponge,
+    regions: Vec<Keccak256Region>,
+    public_preimage: bool,
+}
+
+impl Default for Keccak256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keccak256 {
+    pub fn new() -> Self {
+        Self::with_memory(&[])
+    }
+
+    /// The gadget over the memory of another circuit, holding `bytes` from address 0. The other
+    /// circuit reads the memory with [`Keccak256::read`] for its own tables. The gadget builds
+    /// the table populating the memory, so it has to be built after every other read of it.
+    pub fn with_memory(bytes: &[u8]) -> Self {
+        let mut state = MPTState::new();
+        state.mem.append(bytes);
+        Self {
+            state,
+            sponge: KeccakSponge::new(),
+            regions: vec![],
+            public_preimage: true,
+        }
+    }
+
+    /// Keeps the messages out of the boundaries, so the verifier only learns their lengths and
+    /// their digests.
+    pub fn with_private_preimage(mut self) -> Self {
+        self.public_preimage = false;
+        self
+    }
+
+    /// The memory the messages are laid out in.
+    pub fn memory(&self) -> &[u8] {
+        &self.state.mem[0..self.state.mem.cursor()]
+    }
+
+    /// Reads the byte at `ptr` for a table of the circuit sharing the memory, and returns the
+    /// timestamp the table reads it at.
+    pub fn read(&mut self, ptr: u32) -> Result<BinaryField32b, anyhow::Error> {
+        if ptr >= self.state.mem.cursor() {
+            return Err(anyhow!("The read at {ptr} is past the memory"));
+        }
+        Ok(self.state.mem.process_timestamp(ptr))
+    }
+
+    /// Lays out `message` in memory, after the messages hashed before it, and returns where
+    /// it and its digest are.
+    pub fn hash(&mut self, message: &[u8]) -> Result<Keccak256Region, anyhow::Error> {
+        let mut padded = message.to_vec();
+        padded.extend(MPT::get_padding_bytes(message.len()));
+        let mut state = [0; HASH_B];
+        for block in padded.chunks(HASH_R) {
+            for (state, byte) in state.iter_mut().zip(block) {
+                *state ^= byte;
+            }
+            state = keccak_f(state);
+        }
+        self.expect_digest(message, state[..32].try_into().unwrap())
+    }
+
+    /// Lays out a message of `len` bytes the verifier does not know, with its padding and
+    /// `digest`, for the verifier of private preimages.
+    pub fn hash_private(
+        &mut self,
+        len: usize,
+        digest: [u8; 32],
+    ) -> Result<Keccak256Region, anyhow::Error> {
+        self.expect_digest(&vec![0; len], digest)
+    }
+
+    fn expect_digest(
+        &mut self,
+        message: &[u8],
+        digest: [u8; 32],
+    ) -> Result<Keccak256Region, anyhow::Error> {
+        check_blocks(message.len())?;
+        let preimage_ptr = self.state.mem.cursor();
+        self.state.mem.append(message);
+        self.state
+            .mem
+            .append(&MPT::get_padding_bytes(message.len()));
+        self.state.mem.append(&digest);
+        self.hash_at(preimage_ptr, message.len())
+    }
+
+    /// Hashes the message of `len` bytes memory already holds at `preimage_ptr`, followed by
+    /// its padding and its digest. Only the padding is checked here, the digest is checked by
+    /// the circuit.
+    pub fn hash_at(
+        &mut self,
+        preimage_ptr: u32,
+        len: usize,
+    ) -> Result<Keccak256Region, anyhow::Error> {
+        check_blocks(len)?;
+        let padding = MPT::get_padding_bytes(len);
+        let digest_ptr = preimage_ptr as usize + len + padding.len();
+        if digest_ptr + 32 > self.state.mem.len() {
+            return Err(anyhow!(
+                "The message of {len} bytes at {preimage_ptr} and its digest end past the memory"
+            ));
+        }
+        let digest_ptr = digest_ptr as u32;
+        if self.state.mem[digest_ptr - padding.len() as u32..digest_ptr] != padding[..] {
+            return Err(anyhow!(
+                "The message of {len} bytes at {preimage_ptr} is not followed by its padding"
+            ));
+        }
+        let region = Keccak256Region {
+            preimage_ptr,
+            len,
+            digest_ptr,
+        };
+        self.regions.push(region);
+        Ok(region)
+    }
+
+    /// The digest of the message laid out at `region`.
+    pub fn digest(&self, region: &Keccak256Region) -> [u8; 32] {
+        self.state.mem[region.digest_ptr..region.digest_ptr + 32]
+            .try_into()
+            .unwrap()
+    }
+
+    /// Where the messages hashed so far are, in the order they were hashed.
+    pub fn regions(&self) -> &[Keccak256Region] {
+        &self.regions
+    }
+
+    /// Adds the sponge and memory tables hashing the messages to `builder`, populating their
+    /// witness if it has one, and returns the boundaries the verifier checks them against.
+    pub fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<OptimalUnderlier, BinaryField128b>,
+    ) -> Result<Vec<Boundary<BinaryField128b>>, anyhow::Error> {
+        let channels = Keccak256Channels::new(builder);
+        self.build_with(builder, channels)
+    }
+
+    /// As [`Keccak256::build`], over the channels of the caller. The memory table pushes and
+    /// pulls every address of the memory on `channels.mem`, so the other reads of the memory
+    /// balance against it too.
+    #[instrument("binius_mp3::keccak256", skip_all, level = "debug")]
+    pub fn build_with(
+        mut self,
+        builder: &mut ConstraintSystemBuilder<OptimalUnderlier, BinaryField128b>,
+        channels: Keccak256Channels,
+    ) -> Result<Vec<Boundary<BinaryField128b>>, anyhow::Error> {
+        if self.regions.is_empty() {
+            return Err(anyhow!("Expected at least one message to hash"));
+        }
+        // the keccak_f channel only connects the sponge tables, so it is the gadget's own
+        let sponge_channels = SpongeChannels {
+            mem: channels.mem,
+            keccak_f: builder.add_channel(),
+            absorb_block: channels.absorb_block,
+        };
+
+        self.regions
+            .iter()
+            .for_each(|region| self.sponge.count_preimage(region.len));
+        let table_heights = self.sponge.table_heights();
+        self.state.mem.pad();
+        let mem_n_vars = self.state.mem.len().trailing_zeros() as usize;
+
+        if builder.witness().is_some() {
+            // the verifier reads the public bytes before the tables do
+            for region in &self.regions {
+                self.public_range(region).for_each(|index| {
+                    self.state.mem.process_timestamp(index);
+                });
+            }
+            self.sponge.allocate();
+            let mut rows = vec![];
+            for region in &self.regions {
+                KeccakSponge::absorb_block(
+                    &self.state,
+                    &mut rows,
+                    [0; HASH_B],
+                    region.preimage_ptr,
+                    region.digest_ptr - 1,
+                    region.blocks(),
+                    &|_, perm_input| Permutation::new(perm_input),
+                );
+            }
+            rows.into_iter()
+                .for_each(|row| self.sponge.append_row(&mut self.state, row));
+        }
+
+        let boundaries = self.create_boundaries(&channels);
+        self.sponge.build(builder, sponge_channels, table_heights)?;
+        PopulateMemTable::new().build(builder, channels.mem, &mut self.state, mem_n_vars)?;
+        Ok(boundaries)
+    }
+
+    // the padded message and the digest, or only the padding and the digest when the preimage
+    // is private
+    fn public_range(&self, region: &Keccak256Region) -> std::ops::Range<u32> {
+        let start = match self.public_preimage {
+            true => region.preimage_ptr,
+            false => region.preimage_ptr + region.len as u32,
+        };
+        start..region.digest_ptr + 32
+    }
+
+    // push the absorption of every message from the zero state, and read the public bytes of
+    // the padded message and the digest the absorption ends at
+    fn create_boundaries(&self, channels: &Keccak256Channels) -> Vec<Boundary<B128>> {
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let mut boundaries = vec![];
+        for region in &self.regions {
+            // the base row reads the digest after the byte at hash_ptr, the last of the padding
+            let hash_ptr = region.digest_ptr - 1;
+            let mut initial_blocks = vec![
+                g.pow([region.preimage_ptr as u64]) * basis(32, 0)
+                    + g.pow([hash_ptr as u64]) * basis(32, 1),
+            ];
+            initial_blocks.extend([B128::zero(); 12]);
+            boundaries.push(Boundary {
+                values: initial_blocks,
+                channel_id: channels.absorb_block,
+                direction: FlushDirection::Push,
+                multiplicity: 1,
+            });
+            boundaries.extend(self.public_range(region).flat_map(|index| {
+                verify_mem_read((g.pow([index as u64]), self.state.mem[index]), channels.mem)
+            }));
+        }
+        boundaries
+    }
+}
+
+// the absorption counts the blocks of the padded message in a byte
+fn check_blocks(len: usize) -> Result<(), anyhow::Error> {
+    match len / HASH_R < u8::MAX as usize {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "The message of {len} bytes is too long to hash in {} blocks",
+            u8::MAX
+        )),
+    }
+}
@@ -3,9 +3,10 @@
 use binius_circuits::builder::ConstraintSystemBuilder;
 use binius_core::{
     constraint_system::channel::{Boundary, ChannelId, FlushDirection},
-    constraint_system::Proof,
+    constraint_system::{ConstraintSystem, Proof},
     fiat_shamir::HasherChallenger,
     oracle::ConstraintSet,
+    oracle::MultilinearPolyOracle,
     oracle::OracleId,
     tower::CanonicalTowerFamily,
 };
@@ -20,6 +21,7 @@ use binius_hal::ComputationBackend;
 use binius_math::{ArithExpr, IsomorphicEvaluationDomainFactory};
 type U = OptimalUnderlier;
 
+use bincode::Options;
 use bytemuck::Pod;
 use itertools::izip;
 use rayon::prelude::*;
@@ -32,7 +34,7 @@ pub(crate) use crate::utils::macros::{
 };
 use alloy::{
     primitives::Address,
-    primitives::{keccak256, Bytes},
+    primitives::{keccak256, Bytes, U256},
     rlp::Decodable,
     rpc::types::EIP1186AccountProofResponse,
 };
@@ -52,15 +54,44 @@ type B32 = BinaryField32b;
 type B64 = BinaryField64b;
 type B128 = BinaryField128b;
 
+mod audit;
+mod bundle;
+mod config;
+mod diagnose;
+mod estimate;
+mod fiat_shamir;
+mod keccak256;
 mod mpt;
+mod sharding;
 mod tables;
 mod utils;
 
+use audit::*;
+use bundle::*;
+use config::*;
+use diagnose::*;
+use fiat_shamir::*;
 use mpt::*;
+use sharding::*;
 use tables::*;
 use utils::*;
 
-pub use mpt::TableType;
+pub use audit::{ChannelTuple, RowLocation, UnmatchedTuple};
+pub use bundle::MPTProofBundle;
+pub use config::{ProveOptions, ProverConfig, SelfCheck, VerifierPolicy};
+pub use diagnose::{
+    diagnose, diagnose_unchecked_update, diagnose_with_fault, traced_rows, ConstraintFailure,
+    Diagnosis, Fault, TracedRows,
+};
+pub use estimate::{estimate, ProofEstimate};
+pub use fiat_shamir::HashFunction;
+pub use keccak256::{Keccak256, Keccak256Channels, Keccak256Region};
+pub use mpt::{
+    hidden_accounts_commitment, AccountCode, AccountFields, AccountPredicate, AccountPredicates,
+    AccountUpdate, AccountsWithCode, BalanceSum, DisclosedAccounts, ProverSession, RowOrigin,
+    StateUpdate, StatementCompression, TableStats, TableType,
+};
+pub use sharding::{prove_sharded, verify_sharded, verify_sharded_accounts};
 pub use tracing::instrument;
 
 const LOG_INVERSE_RATE: usize = 2;
@@ -68,6 +99,26 @@ const SECURITY_BITS: usize = 100;
 
 pub fn prove(
     alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
+    prove_in_session(
+        &mut ProverSession::new(),
+        alloy_account_proofs,
+        config,
+        options,
+        backend,
+    )
+}
+
+/// Same as [`prove`], but reuses the nodes cached in `session` by previous calls and caches
+/// the nodes of this call for the next ones.
+pub fn prove_in_session(
+    session: &mut ProverSession,
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    config: &ProverConfig,
+    options: &ProveOptions,
     backend: &impl ComputationBackend,
 ) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
     let _scope = tracing::debug_span!("binius_mp3::prove", n_accounts = alloy_account_proofs.len())
@@ -76,103 +127,396 @@ pub fn prove(
     let (statement, account_proofs) = get_statement_and_account_proofs(alloy_account_proofs);
     let mut mpt = MPT::new(statement);
 
+    mpt.session = std::mem::take(session);
+    let advice = mpt.process_account_proofs(account_proofs);
+    *session = std::mem::take(&mut mpt.session);
+
+    prove_mpt(mpt, advice, config, options, backend)
+}
+
+/// Proves that the post-state root is obtained from the pre-state root by updating exactly the
+/// accounts of the given proofs, which are for the same addresses under either root.
+///
+/// Updates must keep the shape of the trie, besides the leaves of inserted and deleted accounts
+/// and the nodes they split or collapse: an account is inserted into an empty child of a branch,
+/// or where its path parts from the path of a leaf or an extension, which is split into a branch
+/// behind an extension over the nibbles both paths share; deleting an account reverses either.
+/// None of the nodes on the paths, nor the child of an extension split, may be inlined into its
+/// parent, and the nodes split or collapsed may not be on the path of another account. Proofs
+/// breaking these rules, of accounts absent from both tries, or whose nodes change kind are
+/// rejected with an error.
+pub fn prove_update(
+    pre_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    post_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
+    let _scope = tracing::debug_span!(
+        "binius_mp3::prove_update",
+        n_accounts = pre_alloy_account_proofs.len()
+    )
+    .entered();
+
+    let (statement, pre_account_proofs, post_account_proofs) =
+        get_update_statement_and_account_proofs(
+            pre_alloy_account_proofs,
+            post_alloy_account_proofs,
+        )?;
+    let mut mpt = MPT::new(statement);
+    let advice = mpt.process_update_proofs(pre_account_proofs, post_account_proofs);
+
+    prove_mpt(mpt, advice, config, options, backend)
+}
+
+/// Proves that every predicate holds for the account it is on, given the proofs of the
+/// accounts, without disclosing their values.
+///
+/// Every account has exactly one predicate, and a proof for every predicate is required.
+/// Predicates that do not hold are rejected with an error.
+pub fn prove_predicates(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    predicates: Vec<AccountPredicate>,
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
+    let _scope = tracing::debug_span!(
+        "binius_mp3::prove_predicates",
+        n_accounts = alloy_account_proofs.len()
+    )
+    .entered();
+
+    let (statement, account_proofs) =
+        get_predicate_statement_and_account_proofs(alloy_account_proofs, predicates)?;
+    let mut mpt = MPT::new(statement);
+    let advice = mpt.process_account_proofs(account_proofs);
+
+    prove_mpt(mpt, advice, config, options, backend)
+}
+
+/// Proves the sum of the balances of the accounts of the given proofs, without disclosing
+/// their values.
+///
+/// Values that are not accounts, and balances whose sum exceeds 256 bits, are rejected with
+/// an error.
+pub fn prove_balance_sum(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
+    let _scope = tracing::debug_span!(
+        "binius_mp3::prove_balance_sum",
+        n_accounts = alloy_account_proofs.len()
+    )
+    .entered();
+
+    let (statement, account_proofs) =
+        get_balance_sum_statement_and_account_proofs(alloy_account_proofs)?;
+    let mut mpt = MPT::new(statement);
+    let advice = mpt.process_account_proofs(account_proofs);
+
+    prove_mpt(mpt, advice, config, options, backend)
+}
+
+/// Same as [`prove`], but only the accounts of `disclosed` are disclosed. The statement
+/// commits to the addresses of the other accounts by their [`hidden_accounts_commitment`]
+/// with `salt`, which should be random and kept secret.
+///
+/// A proof for every disclosed address is required. Hiding more than about a thousand
+/// accounts is rejected with an error.
+pub fn prove_hidden(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    disclosed: &[Address],
+    salt: [u8; 32],
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
+    let _scope = tracing::debug_span!(
+        "binius_mp3::prove_hidden",
+        n_accounts = alloy_account_proofs.len()
+    )
+    .entered();
+
+    let (statement, account_proofs, hidden_keys) =
+        get_hidden_statement_and_account_proofs(alloy_account_proofs, disclosed, &salt)?;
+    let mut mpt = MPT::new(statement);
+    mpt.open_hidden_accounts(hidden_keys, salt);
+    let advice = mpt.process_account_proofs(account_proofs);
+
+    prove_mpt(mpt, advice, config, options, backend)
+}
+
+/// Same as [`prove`], and also proves that every code of `codes` hashes to the code hash of
+/// its account, absorbing it in-circuit.
+///
+/// A proof for the address of every code is required. Codes not matching the code hash of
+/// their account, or longer than about 34 KB, are rejected with an error.
+pub fn prove_bytecode(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    codes: Vec<(Address, Vec<u8>)>,
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
+    let _scope = tracing::debug_span!(
+        "binius_mp3::prove_bytecode",
+        n_accounts = alloy_account_proofs.len()
+    )
+    .entered();
+
+    let (statement, account_proofs) =
+        get_bytecode_statement_and_account_proofs(alloy_account_proofs, codes)?;
+    let mut mpt = MPT::new(statement);
     let advice = mpt.process_account_proofs(account_proofs);
 
+    prove_mpt(mpt, advice, config, options, backend)
+}
+
+// builds and proves the constraint system of processed account proofs
+fn prove_mpt(
+    mpt: MPT,
+    advice: Advice,
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, MPTProofInfo), anyhow::Error> {
     let allocator = bumpalo::Bump::new();
     let mut builder = ConstraintSystemBuilder::new_with_witness(&allocator);
 
-    let (_boundaries, statement) = mpt.build(&mut builder, advice.clone())?;
+    let (boundaries, statement, _accounts, channel_ids) =
+        mpt.build(&mut builder, advice.clone())?;
 
     let witness = builder.take_witness()?;
     let constraint_system = builder.build()?;
 
-    // println!("validating");
-    // use binius_core::constraint_system::validate::validate_witness;
-    // validate_witness(&constraint_system, &_boundaries, &witness).unwrap();
+    if options.audit_channels {
+        let _scope = tracing::debug_span!("audit_channels").entered();
+        let locator = Locator {
+            constraint_system: &constraint_system,
+            row_origins: &RowOrigins::default(),
+            addresses: &[],
+        };
+        let unmatched = audit_channels(
+            &locator,
+            &channel_ids,
+            &boundaries,
+            &witness,
+            advice.table_heights.mem_len(),
+        )?;
+        if !unmatched.is_empty() {
+            let report = unmatched
+                .iter()
+                .map(|unmatched_tuple| unmatched_tuple.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(anyhow!("Unbalanced channels:\n{report}"));
+        }
+    }
+
+    if options.self_check == SelfCheck::ValidateWitness {
+        let _scope = tracing::debug_span!("validate_witness").entered();
+        if let Err(err) = binius_core::constraint_system::validate::validate_witness(
+            &constraint_system,
+            &boundaries,
+            &witness,
+        ) {
+            // name the tables and rows of the failing constraints, if a constraint fails
+            let locator = Locator {
+                constraint_system: &constraint_system,
+                row_origins: &RowOrigins::default(),
+                addresses: &[],
+            };
+            let report = check_constraints(&locator, &witness)?
+                .iter()
+                .map(|failure| failure.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(anyhow!("{err}\n{report}"));
+        }
+    }
+
+    let committed_oracle_bits = committed_oracle_bits(&constraint_system);
 
     let domain_factory = IsomorphicEvaluationDomainFactory::<B8>::default();
-    let proof = binius_core::constraint_system::prove::<
-        OptimalUnderlier,
-        CanonicalTowerFamily,
-        _,
-        groestl_crypto::Groestl256,
-        Groestl256ByteCompression,
-        HasherChallenger<groestl_crypto::Groestl256>,
-        _,
-    >(
-        &constraint_system,
-        LOG_INVERSE_RATE,
-        SECURITY_BITS,
-        witness,
-        &domain_factory,
-        &backend,
-    )?;
+    let proof = with_hash_function!(config.hash, |H, C| {
+        binius_core::constraint_system::prove::<
+            OptimalUnderlier,
+            CanonicalTowerFamily,
+            _,
+            H,
+            C,
+            HasherChallenger<H>,
+            _,
+        >(
+            &constraint_system,
+            config.log_inv_rate,
+            config.security_bits,
+            witness,
+            &domain_factory,
+            &backend,
+        )?
+    });
 
     let info = MPTProofInfo {
         transcript_len: proof.transcript.len(),
         advice_len: proof.advice.len(),
-        table_heights: advice.table_heights.as_list(),
+        table_stats: advice.table_heights.stats()?,
+        committed_columns: committed_oracle_bits.len(),
+        committed_bits: committed_oracle_bits.iter().sum(),
     };
 
+    let statement = EncodedStatement::encode(statement, config.statement_compression);
     let serialized_proof = MPTProof {
+        header: config.header(statement.account_count()),
         proof_transcript: proof.transcript,
         proof_advice: proof.advice,
         advice,
         statement,
     }
     .to_bytes()?;
-    verify(serialized_proof.clone())?;
+    if options.self_check == SelfCheck::Verify {
+        verify_with_policy(serialized_proof.clone(), &config.self_check_policy())?;
+    }
 
     Ok((serialized_proof, info))
 }
 
-#[instrument("binius_mp3::verify", skip_all, level = "debug")]
 pub fn verify(proof: Vec<u8>) -> Result<(), anyhow::Error> {
+    verify_with_policy(proof, &VerifierPolicy::default())
+}
+
+/// Same as [`verify`], but rejects proofs outside the given policy before reconstructing
+/// the constraint system.
+#[instrument("binius_mp3::verify_with_policy", skip_all, level = "debug")]
+pub fn verify_with_policy(proof: Vec<u8>, policy: &VerifierPolicy) -> Result<(), anyhow::Error> {
+    verify_statement(proof, policy).map(|_| ())
+}
+
+/// Same as [`verify`], and returns the fields of the proven accounts.
+///
+/// The fields are the payloads the verifier pulls from the `account_fields` channel, at the
+/// pointers of the values it reads into memory, so they are the fields the circuit decodes.
+pub fn verify_accounts(proof: Vec<u8>) -> Result<Vec<AccountFields>, anyhow::Error> {
+    let (statement, accounts) = verify_statement(proof, &VerifierPolicy::default())?;
+    statement.into_account_fields(accounts)
+}
+
+/// Verifies a proof made by [`prove_update`] and returns the update it proves.
+pub fn verify_update(proof: Vec<u8>) -> Result<StateUpdate, anyhow::Error> {
+    verify_statement(proof, &VerifierPolicy::default())?
+        .0
+        .into_update()
+        .ok_or_else(|| anyhow!("Not an update proof"))
+}
+
+/// Verifies a proof made by [`prove_predicates`] and returns the predicates it proves.
+pub fn verify_predicates(proof: Vec<u8>) -> Result<AccountPredicates, anyhow::Error> {
+    verify_statement(proof, &VerifierPolicy::default())?
+        .0
+        .into_predicates()
+        .ok_or_else(|| anyhow!("Not a predicate proof"))
+}
+
+/// Verifies a proof made by [`prove_balance_sum`] and returns the sum it proves.
+pub fn verify_balance_sum(proof: Vec<u8>) -> Result<BalanceSum, anyhow::Error> {
+    verify_statement(proof, &VerifierPolicy::default())?
+        .0
+        .into_balance_sum()
+        .ok_or_else(|| anyhow!("Not a balance sum proof"))
+}
+
+/// Verifies a proof made by [`prove_hidden`] and returns the accounts it discloses, along
+/// with the commitment to the hidden ones.
+pub fn verify_hidden(proof: Vec<u8>) -> Result<DisclosedAccounts, anyhow::Error> {
+    let (statement, accounts) = verify_statement(proof, &VerifierPolicy::default())?;
+    statement
+        .into_disclosed(accounts)
+        .ok_or_else(|| anyhow!("Not a proof hiding accounts"))
+}
+
+/// Verifies a proof made by [`prove_bytecode`] and returns the accounts it proves, along with
+/// the code it proves some of them run.
+pub fn verify_bytecode(proof: Vec<u8>) -> Result<AccountsWithCode, anyhow::Error> {
+    let (statement, accounts) = verify_statement(proof, &VerifierPolicy::default())?;
+    statement
+        .into_codes(accounts)
+        .ok_or_else(|| anyhow!("Not a bytecode proof"))
+}
+
+// verifies the proof and returns the statement it proves, along with the fields of the accounts
+// whose values the verifier pulls from account_fields
+fn verify_statement(
+    proof: Vec<u8>,
+    policy: &VerifierPolicy,
+) -> Result<(Statement, Vec<AccountFields>), anyhow::Error> {
     tracing::info!("Proof size: {} bytes", proof.len());
 
+    policy.check_proof_size(proof.len())?;
+    policy.check_header(&MPTProof::read_header(&proof)?)?;
+
     let proof = MPTProof::from_bytes(proof)?;
-    let mpt = MPT::new(proof.statement);
+    let mpt = MPT::new(proof.statement.decode());
 
     let mut builder = ConstraintSystemBuilder::new();
 
-    let (boundaries, _statement) = mpt.build(&mut builder, proof.advice)?;
+    let (boundaries, statement, accounts, _channel_ids) = mpt.build(&mut builder, proof.advice)?;
 
     let constraint_system = builder.build()?;
 
-    binius_core::constraint_system::verify::<
-        OptimalUnderlier,
-        CanonicalTowerFamily,
-        groestl_crypto::Groestl256,
-        Groestl256ByteCompression,
-        HasherChallenger<groestl_crypto::Groestl256>,
-    >(
-        &constraint_system,
-        LOG_INVERSE_RATE,
-        SECURITY_BITS,
-        boundaries,
-        Proof {
-            transcript: proof.proof_transcript,
-            advice: proof.proof_advice,
-        },
-    )?;
-
-    Ok(())
+    let ProofHeader {
+        hash,
+        log_inv_rate,
+        security_bits,
+    } = proof.header;
+    with_hash_function!(hash, |H, C| {
+        binius_core::constraint_system::verify::<
+            OptimalUnderlier,
+            CanonicalTowerFamily,
+            H,
+            C,
+            HasherChallenger<H>,
+        >(
+            &constraint_system,
+            log_inv_rate,
+            security_bits,
+            boundaries,
+            Proof {
+                transcript: proof.proof_transcript,
+                advice: proof.proof_advice,
+            },
+        )?
+    });
+
+    Ok((statement, accounts))
 }
 
+/// Offset in a serialized proof of the account count its header declares, a little-endian `u32`
+pub const PROOF_ACCOUNT_COUNT_OFFSET: usize =
+    MPTProof::HEADER_OFFSET + ProofHeader::ACCOUNT_COUNT_OFFSET;
+
 #[derive(Serialize, Debug, Deserialize)]
 struct MPTProof {
+    // stored in the header rather than the bincode body
+    #[serde(skip)]
+    header: ProofHeader,
     proof_transcript: Vec<u8>,
     proof_advice: Vec<u8>,
     advice: Advice,
-    statement: Statement,
+    statement: EncodedStatement,
 }
 
 impl MPTProof {
-    const MPT_PROOF_VERSION: u16 = 3;
+    const MPT_PROOF_VERSION: u16 = 6;
+    // the header follows the magic number and the version
+    const HEADER_OFFSET: usize = 8;
+    const HEADER_LEN: usize = Self::HEADER_OFFSET + ProofHeader::LEN;
     const MAGIC_NUMBER: [u8; 6] = *b"BINIUS";
 
-    pub fn from_bytes(data: Vec<u8>) -> Result<Self, anyhow::Error> {
-        if data.len() < 8 {
+    pub fn read_header(data: &[u8]) -> Result<ProofHeader, anyhow::Error> {
+        if data.len() < Self::HEADER_LEN {
             return Err(anyhow!("Byte array is too short."));
         }
 
@@ -193,10 +537,32 @@ impl MPTProof {
             ));
         }
 
-        let proof_data = &data[8..];
-        let deserialized_struct: Self = bincode::deserialize(proof_data)?;
+        ProofHeader::from_bytes(&data[Self::HEADER_OFFSET..Self::HEADER_LEN])
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, anyhow::Error> {
+        let header = Self::read_header(&data)?;
+
+        let proof_data = &data[Self::HEADER_LEN..];
+        // the same encoding as bincode::deserialize, bounded by the size of the proof so a
+        // forged length cannot allocate more than the proof holds
+        let deserialized_struct: Self = bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(proof_data.len() as u64)
+            .deserialize(proof_data)?;
+        if deserialized_struct.statement.account_count() != header.account_count {
+            return Err(anyhow!(
+                "The header declares {} accounts, the statement has {}",
+                header.account_count,
+                deserialized_struct.statement.account_count()
+            ));
+        }
 
-        Ok(deserialized_struct)
+        Ok(Self {
+            header,
+            ..deserialized_struct
+        })
     }
 
     pub fn to_bytes(&self) -> Result<Vec<u8>, anyhow::Error> {
@@ -204,6 +570,7 @@ impl MPTProof {
 
         result.write_all(&Self::MAGIC_NUMBER)?;
         result.write_all(&Self::MPT_PROOF_VERSION.to_le_bytes())?;
+        result.write_all(&self.header.to_bytes()?)?;
         let serialized_proof = bincode::serialize(&self)?;
 
         result.extend(serialized_proof);
@@ -249,6 +616,10 @@ pub struct MPTProofInfo {
     pub transcript_len: usize,
     /// Proof advice size
     pub advice_len: usize,
-    /// Metadata related to tables
-    pub table_heights: HashMap<TableType, (usize, usize)>,
+    /// Size of every table, including memory and the lookup tables
+    pub table_stats: Vec<TableStats>,
+    /// Committed oracles of the constraint system that was proven
+    pub committed_columns: usize,
+    /// Size of the committed oracles in bits
+    pub committed_bits: usize,
 }
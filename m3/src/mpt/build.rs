@@ -38,13 +38,18 @@ impl MPT {
             );
         }
 
+        // the values of predicate and balance sum statements are withheld, and accounts an
+        // update inserts have no pre value
+        let withheld_values = statement.withholds_values();
         let ptr_val_iter = ptr_ts_pairs
             .iter()
             .zip(statement.addr_val_pairs.iter())
-            .map(|(ptr_ts_pair, addr_val_pair)| {
+            .enumerate()
+            .filter(|&(i, _)| !withheld_values && statement.in_pre_state(i))
+            .map(|(_, (ptr_ts_pair, addr_val_pair))| {
                 let leaf_val_bytes = &addr_val_pair.value;
                 let ptr = ptr_ts_pair.rlp_ptr;
-                let val = rlp_encode_long_bytestring(leaf_val_bytes);
+                let val = rlp_encode_bytestring(leaf_val_bytes);
                 (ptr, val)
             });
 
@@ -67,15 +72,239 @@ impl MPT {
             .collect()
     }
 
+    // pull the fields account_fields decodes from every value, which the verifier reads itself,
+    // and return the fields of the payloads pulled
+    fn create_account_field_boundaries(
+        account_fields_channel_id: usize,
+        statement: &Statement,
+        ptr_ts_pairs: &[PtrTsPair],
+    ) -> Result<(Vec<Boundary<B128>>, Vec<AccountFields>), anyhow::Error> {
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let mut boundaries = vec![];
+        let mut accounts = vec![];
+        for (i, (ptr_ts_pair, addr_val_pair)) in
+            izip!(ptr_ts_pairs, &statement.addr_val_pairs).enumerate()
+        {
+            // accounts an update inserts have no pre value
+            if !statement.in_pre_state(i) {
+                continue;
+            }
+            let value_ptr = ptr_ts_pair.rlp_ptr as u64;
+            // the string header of the value is two bytes long
+            let list_ptr = value_ptr + 2;
+            let ranges = account_field_ranges(&addr_val_pair.value)?;
+            for (field, range) in izip!(AccountField::ALL, ranges.clone()) {
+                boundaries.push(Boundary {
+                    values: vec![
+                        g.pow([value_ptr]) * basis(32, 0)
+                            + g.pow([list_ptr + range.start as u64]) * basis(32, 1)
+                            + g.pow([list_ptr + range.end as u64]) * basis(32, 2)
+                            + field.tag() * basis(32, 3),
+                    ],
+                    channel_id: account_fields_channel_id,
+                    direction: FlushDirection::Pull,
+                    multiplicity: 1,
+                });
+            }
+            accounts.push(AccountFields::from_payloads(
+                &addr_val_pair.address,
+                ranges.map(|range| &addr_val_pair.value[range]),
+            )?);
+        }
+        Ok((boundaries, accounts))
+    }
+
+    // push what every predicate asks of the fields of its account, and pull the nonces the
+    // predicates fix, reading their payloads in place of the withheld values
+    fn create_predicate_boundaries(
+        channel_ids: &ChannelIds,
+        statement: &Statement,
+        predicates: &[AccountPredicate],
+        ptr_ts_pairs: &[PtrTsPair],
+    ) -> Result<Vec<Boundary<B128>>, anyhow::Error> {
+        if predicates.len() != ptr_ts_pairs.len()
+            || predicates.len() != statement.addr_val_pairs.len()
+            || !izip!(predicates, statement.addresses())
+                .all(|(predicate, address)| predicate.address().as_slice() == address)
+        {
+            return Err(anyhow!(
+                "Expected a predicate on every address of the statement"
+            ));
+        }
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let push = |channel_id, values| Boundary {
+            values,
+            channel_id,
+            direction: FlushDirection::Push,
+            multiplicity: 1,
+        };
+        let mut boundaries = vec![];
+        for (set_id, (predicate, ptr_ts_pair)) in izip!(predicates, ptr_ts_pairs).enumerate() {
+            let value_ptr = ptr_ts_pair.rlp_ptr;
+            let value_block = g.pow([value_ptr as u64]) * basis(32, 0);
+            match predicate {
+                AccountPredicate::BalanceAtLeast(_, amount) => {
+                    let [amount_lo, amount_hi] = u256_halves(*amount);
+                    boundaries.push(push(
+                        channel_ids.balance_at_least,
+                        vec![value_block, B128::new(amount_lo), B128::new(amount_hi)],
+                    ));
+                }
+                AccountPredicate::NonceEquals(_, nonce) => {
+                    let (offset, payload) = nonce_payload(*nonce);
+                    let payload_ptr = value_ptr + offset;
+                    boundaries.push(Boundary {
+                        values: vec![account_field_tuple(
+                            value_ptr,
+                            AccountField::Nonce,
+                            payload_ptr..payload_ptr + payload.len() as u32,
+                        )],
+                        channel_id: channel_ids.account_fields,
+                        direction: FlushDirection::Pull,
+                        multiplicity: 1,
+                    });
+                    boundaries.extend(payload.iter().enumerate().flat_map(|(index, &byte)| {
+                        verify_mem_read(
+                            (g.pow([(payload_ptr + index as u32) as u64]), byte),
+                            channel_ids.mem,
+                        )
+                    }));
+                }
+                AccountPredicate::CodeHashIn(_, code_hashes) => {
+                    let set_id = set_id as u32;
+                    boundaries.push(push(
+                        channel_ids.code_hash_in,
+                        vec![value_block + B32::new(set_id) * basis(32, 1)],
+                    ));
+                    boundaries.extend(code_hashes.iter().map(|code_hash| {
+                        push(
+                            channel_ids.code_hash_set,
+                            code_hash_set_tuple(set_id, code_hash).to_vec(),
+                        )
+                    }));
+                }
+                AccountPredicate::IsContract(_) => {
+                    boundaries.push(push(channel_ids.is_contract, vec![value_block]));
+                }
+            }
+        }
+        Ok(boundaries)
+    }
+
+    // push every value whose balance is summed, and pass the sum from zero to the one of the
+    // statement
+    fn create_balance_sum_boundaries(
+        channel_ids: &ChannelIds,
+        statement: &Statement,
+        ptr_ts_pairs: &[PtrTsPair],
+    ) -> Vec<Boundary<B128>> {
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let boundary = |channel_id, direction, values: Vec<B128>| Boundary {
+            values,
+            channel_id,
+            direction,
+            multiplicity: 1,
+        };
+        let mut boundaries = ptr_ts_pairs
+            .iter()
+            .map(|ptr_ts_pair| {
+                boundary(
+                    channel_ids.balance_sum,
+                    FlushDirection::Push,
+                    vec![g.pow([ptr_ts_pair.rlp_ptr as u64]) * basis(32, 0)],
+                )
+            })
+            .collect::<Vec<_>>();
+        boundaries.push(boundary(
+            channel_ids.partial_balance_sum,
+            FlushDirection::Push,
+            partial_balance_sum_tuple(U256::ZERO).to_vec(),
+        ));
+        boundaries.push(boundary(
+            channel_ids.partial_balance_sum,
+            FlushDirection::Pull,
+            partial_balance_sum_tuple(statement.balance_sum().unwrap()).to_vec(),
+        ));
+        boundaries
+    }
+
+    // push the absorption of the hidden keys and the salt, and read the commitment it ends at
+    fn create_commitment_boundaries(
+        channel_ids: &ChannelIds,
+        hidden: &HiddenAccounts,
+        disclosed_count: usize,
+    ) -> Vec<Boundary<B128>> {
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let (preimage_ptr, commitment_ptr) =
+            MPT::commitment_mem_offsets(disclosed_count, disclosed_count + hidden.count);
+        // the absorption starts from the zero state, like the ones hash_trans pushes
+        let mut initial_blocks = vec![
+            g.pow([preimage_ptr as u64]) * basis(32, 0)
+                + g.pow([commitment_ptr as u64]) * basis(32, 1),
+        ];
+        initial_blocks.extend([B128::zero(); 12]);
+        let mut boundaries = vec![Boundary {
+            values: initial_blocks,
+            channel_id: channel_ids.absorb_block,
+            direction: FlushDirection::Push,
+            multiplicity: 1,
+        }];
+        boundaries.extend(
+            std::iter::successors(Some(g.pow([commitment_ptr as u64 + 1])), |prev| {
+                Some(*prev * g)
+            })
+            .zip(hidden.commitment)
+            .flat_map(|(addr, val)| verify_mem_read((addr, val), channel_ids.mem)),
+        );
+        boundaries
+    }
+
+    // read every code and its padding, and push the value whose code hash it is absorbed into
+    fn create_bytecode_boundaries(
+        channel_ids: &ChannelIds,
+        statement: &Statement,
+        codes: &[Bytecode],
+        ptr_ts_pairs: &[PtrTsPair],
+    ) -> Result<Vec<Boundary<B128>>, anyhow::Error> {
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let mut boundaries = vec![];
+        for (code_ptr, bytecode) in izip!(MPT::code_mem_offsets(statement), codes) {
+            if bytecode.account >= statement.addr_val_pairs.len() {
+                return Err(anyhow!("Code of account {} out of range", bytecode.account));
+            }
+            let padded_code = [
+                bytecode.code.as_slice(),
+                &MPT::get_padding_bytes(bytecode.code.len()),
+            ]
+            .concat();
+            boundaries.extend(
+                std::iter::successors(Some(g.pow([code_ptr as u64])), |prev| Some(*prev * g))
+                    .zip(padded_code)
+                    .flat_map(|(addr, val)| verify_mem_read((addr, val), channel_ids.mem)),
+            );
+            boundaries.push(Boundary {
+                values: vec![bytecode_tuple(
+                    ptr_ts_pairs[bytecode.account].rlp_ptr,
+                    code_ptr,
+                )],
+                channel_id: channel_ids.bytecode,
+                direction: FlushDirection::Push,
+                multiplicity: 1,
+            });
+        }
+        Ok(boundaries)
+    }
+
     // what do we need here?
     fn create_state_boundaries(
         state_channel_id: usize,
+        root_mem_offset: u32,
         ptr_ts_pairs: &[PtrTsPair],
+        ends_at_value: impl Fn(usize) -> bool,
     ) -> Vec<Boundary<B128>> {
         let g = B32::MULTIPLICATIVE_GENERATOR;
         let mut boundaries = vec![];
-        let root_mem_offset = 0;
-        let keys_mem_offset = root_mem_offset + 1 + 32;
+        let keys_mem_offset = 1 + 32;
         // create initial state
         {
             let key_ptr = NibPtr {
@@ -105,6 +334,11 @@ impl MPT {
         }
         // create final states
         for (i, PtrTsPair { rlp_ptr, final_ts }) in ptr_ts_pairs.iter().enumerate() {
+            // the walk of a key absent from the trie ends at an empty child, where node_insert or
+            // node_delete pulls its final state
+            if !ends_at_value(i) {
+                continue;
+            }
             let start_ptr = keys_mem_offset + i as u32 * 32;
             let key_ptr = NibPtr {
                 byte: start_ptr + 32,
@@ -134,42 +368,201 @@ impl MPT {
         boundaries
     }
 
+    // the post state walk of an update starts at the post state root and ends at the post
+    // values, which node_diff is granted to find where the pre values are; the leaves of inserted
+    // and deleted accounts are held to end with their values instead
+    fn create_update_boundaries(
+        channel_ids: &ChannelIds,
+        statement: &Statement,
+        key_count: usize,
+        ptr_ts_pairs: &[PtrTsPair],
+        post_ptr_ts_pairs: &[PtrTsPair],
+    ) -> Result<Vec<Boundary<B128>>, anyhow::Error> {
+        let post_state = statement.post_state().unwrap();
+        if post_ptr_ts_pairs.len() != key_count || post_state.values.len() != key_count {
+            return Err(anyhow!("Expected {key_count} post values"));
+        }
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        use std::iter::successors;
+        let post_root_mem_offset = MPT::post_root_mem_offset(key_count);
+        let mut boundaries = MPT::create_state_boundaries(
+            channel_ids.post_state,
+            post_root_mem_offset,
+            post_ptr_ts_pairs,
+            |i| statement.in_post_state(i),
+        );
+
+        // verify the post root hash rlp follows the keys
+        let post_root_ptr = g.pow([post_root_mem_offset as u64]);
+        let post_root_hash_rlp = std::iter::once(0xa0).chain(post_state.root_hash);
+        boundaries.extend(
+            successors(Some(post_root_ptr), |prev| Some(*prev * g))
+                .zip(post_root_hash_rlp)
+                .flat_map(|(addr, val)| verify_mem_read((addr, val), channel_ids.mem)),
+        );
+        // verify the templates of the virtual branches follow it
+        for (_, template_ptr) in MPT::template_mem_offsets(statement) {
+            let template_addr = g.pow([template_ptr as u64]);
+            boundaries.extend(
+                successors(Some(template_addr), |prev| Some(*prev * g))
+                    .zip(EMPTY_BRANCH)
+                    .flat_map(|(addr, val)| verify_mem_read((addr, val), channel_ids.mem)),
+            );
+        }
+
+        for (i, (ptr_ts_pair, post_ptr_ts_pair, addr_val_pair, post_value, change)) in izip!(
+            ptr_ts_pairs,
+            post_ptr_ts_pairs,
+            &statement.addr_val_pairs,
+            &post_state.values,
+            &post_state.changes,
+        )
+        .enumerate()
+        {
+            match change {
+                ValueChange::Insert if !addr_val_pair.value.is_empty() => {
+                    return Err(anyhow!("Pre value of an inserted account"))
+                }
+                ValueChange::Delete if !post_value.is_empty() => {
+                    return Err(anyhow!("Post value of a deleted account"))
+                }
+                _ => (),
+            }
+            let rlp_post_value = rlp_encode_bytestring(post_value);
+            match change {
+                // a value left in place was read with the pre values
+                ValueChange::Update if post_ptr_ts_pair.rlp_ptr == ptr_ts_pair.rlp_ptr => {
+                    if *post_value != addr_val_pair.value {
+                        return Err(anyhow!("Post value read from the pre value"));
+                    }
+                }
+                // a deleted account has no post value
+                ValueChange::Delete => (),
+                ValueChange::Update | ValueChange::Insert => {
+                    let starting_addr = g.pow([post_ptr_ts_pair.rlp_ptr as u64]);
+                    boundaries.extend(
+                        successors(Some(starting_addr), |prev| Some(*prev * g))
+                            .zip(rlp_post_value.iter())
+                            .flat_map(|(addr, &val)| verify_mem_read((addr, val), channel_ids.mem)),
+                    );
+                }
+            }
+            let value_end_ptr = |ptr_ts_pair: &PtrTsPair, value: &[u8]| {
+                ptr_ts_pair.rlp_ptr as u64 + rlp_encode_bytestring(value).len() as u64
+            };
+            let (channel_id, values) = match change {
+                // grant the post value to differ from the pre value
+                ValueChange::Update => (
+                    channel_ids.node_diff_window,
+                    vec![
+                        g.pow([post_ptr_ts_pair.rlp_ptr as u64]) * basis(32, 0)
+                            + g.pow([value_end_ptr(post_ptr_ts_pair, post_value)]) * basis(32, 1)
+                            + g.pow([ptr_ts_pair.rlp_ptr as u64]) * basis(32, 2),
+                    ],
+                ),
+                // the leaf node_insert or node_delete hashes ends with the value read
+                ValueChange::Insert | ValueChange::Delete => {
+                    let start_ptr = 1 + 32 + i as u64 * 32;
+                    let leaf_end_ptr = match change {
+                        ValueChange::Insert => value_end_ptr(post_ptr_ts_pair, post_value),
+                        _ => value_end_ptr(ptr_ts_pair, &addr_val_pair.value),
+                    };
+                    (
+                        channel_ids.leaf_end,
+                        vec![
+                            g.pow([start_ptr]) * basis(32, 0)
+                                + g.pow([leaf_end_ptr]) * basis(32, 1),
+                        ],
+                    )
+                }
+            };
+            boundaries.push(Boundary {
+                values,
+                channel_id,
+                direction: FlushDirection::Push,
+                multiplicity: 1,
+            });
+        }
+
+        // consume the window node_diff grants for the reference to the post state root
+        boundaries.push(Boundary {
+            values: vec![
+                post_root_ptr * basis(32, 0)
+                    + g.pow([post_root_mem_offset as u64 + 1 + 32]) * basis(32, 1),
+            ],
+            channel_id: channel_ids.node_diff_window,
+            direction: FlushDirection::Pull,
+            multiplicity: 1,
+        });
+        Ok(boundaries)
+    }
+
     // prover and verifier invoked
     #[instrument(skip_all, name = "build", level = "debug")]
     pub fn build(
         mut self,
         builder: &mut ConstraintSystemBuilder<U, B128>,
         advice: Advice,
-    ) -> Result<(Vec<Boundary<B128>>, Statement), anyhow::Error> {
+    ) -> Result<
+        (
+            Vec<Boundary<B128>>,
+            Statement,
+            Vec<AccountFields>,
+            ChannelIds,
+        ),
+        anyhow::Error,
+    > {
         // generate channels
-        let channel_ids = ChannelIds {
-            mem: builder.add_channel(),
-            state: builder.add_channel(),
-            skip_list_header: builder.add_channel(),
-            skip_list_header_offset_lookup: builder.add_channel(),
-            keccak_f: builder.add_channel(),
-            absorb_block: builder.add_channel(),
-            get_child: builder.add_channel(),
-            get_child_offset_lookup: builder.add_channel(),
-            branch_trans_shift_lookup: builder.add_channel(),
-            check_nib: builder.add_channel(),
-        };
+        let channel_ids = ChannelIds::new(builder);
 
         let table_heights = advice.table_heights;
+        let is_update = self.is_update();
+        if table_heights.update.is_some() != is_update {
+            return Err(anyhow!(
+                "Update tables do not match whether the statement has a post state"
+            ));
+        }
+        let has_predicates = self.has_predicates();
+        if table_heights.predicates.is_some() != has_predicates {
+            return Err(anyhow!(
+                "Predicate tables do not match whether the statement has predicates"
+            ));
+        }
+        let has_balance_sum = self.has_balance_sum();
+        if table_heights.balance_sum_table.is_some() != has_balance_sum
+            || table_heights.account_fields_sink_table.is_some()
+                != self.statement.withholds_values()
+        {
+            return Err(anyhow!(
+                "Balance sum tables do not match whether the statement has a balance sum"
+            ));
+        }
+        let has_codes = self.statement.codes().is_some();
+        if table_heights.bytecode_table.is_some() != has_codes {
+            return Err(anyhow!(
+                "Bytecode table does not match whether the statement has codes"
+            ));
+        }
+        let account_count = self.statement.account_count();
+        if advice.ptr_ts_pairs.len() != account_count {
+            return Err(anyhow!("Expected {account_count} final states"));
+        }
+        if let Some(post_state) = self.statement.post_state() {
+            if post_state.changes.len() != account_count {
+                return Err(anyhow!("Expected {account_count} value changes"));
+            }
+        }
 
         self.skip_list_header_table
             .build(builder, &channel_ids, table_heights.skip_list_header)?;
-        self.keccak_f_table
-            .build(builder, &channel_ids, table_heights.keccak_f)?;
-        self.absorb_block_base_table.build(
+        self.sponge.build(
             builder,
-            &channel_ids,
-            table_heights.absorb_block_base_table,
-        )?;
-        self.absorb_block_recursive_table.build(
-            builder,
-            &channel_ids,
-            table_heights.absorb_block_recursive_table,
+            (&channel_ids).into(),
+            [
+                table_heights.keccak_f,
+                table_heights.absorb_block_base_table,
+                table_heights.absorb_block_recursive_table,
+            ],
         )?;
         self.hash_trans_table
             .build(builder, &channel_ids, table_heights.hash_trans_table)?;
@@ -202,9 +595,108 @@ impl MPT {
         )?;
         self.fork_state_table
             .build(builder, &channel_ids, table_heights.fork_state_table)?;
-        self.populate_mem_table.build(
+        self.branch_value_trans_table.build(
             builder,
             &channel_ids,
+            table_heights.branch_value_trans_table,
+        )?;
+        self.account_fields_table.build(
+            builder,
+            &channel_ids,
+            table_heights.account_fields_table,
+        )?;
+        if let Some(predicates) = table_heights.predicates {
+            self.balance_at_least_table.build(
+                builder,
+                &channel_ids,
+                predicates.balance_at_least_table,
+            )?;
+            self.is_contract_table
+                .build(builder, &channel_ids, predicates.is_contract_table)?;
+            self.code_hash_in_table
+                .build(builder, &channel_ids, predicates.code_hash_in_table)?;
+            self.code_hash_set_sink_table.build(
+                builder,
+                &channel_ids,
+                predicates.code_hash_set_sink_table,
+            )?;
+        }
+        if let Some(balance_sum_table) = table_heights.balance_sum_table {
+            self.balance_sum_table
+                .build(builder, &channel_ids, balance_sum_table)?;
+        }
+        if let Some(bytecode_table) = table_heights.bytecode_table {
+            self.bytecode_table
+                .build(builder, &channel_ids, bytecode_table)?;
+        }
+        if let Some(account_fields_sink_table) = table_heights.account_fields_sink_table {
+            self.account_fields_sink_table.build(
+                builder,
+                &channel_ids,
+                account_fields_sink_table,
+            )?;
+        }
+        if let Some(update) = table_heights.update {
+            // the transition tables of the post state walk flush the post state channel in
+            // place of the state channel
+            let post_channel_ids = ChannelIds {
+                state: channel_ids.post_state,
+                ..channel_ids
+            };
+            builder.push_namespace("post_walk");
+            self.post_walk.branch_trans_table.build(
+                builder,
+                &post_channel_ids,
+                update.post_branch_trans_table,
+            )?;
+            self.post_walk.ext_leaf_trans_table.build(
+                builder,
+                &post_channel_ids,
+                update.post_ext_leaf_trans_table,
+            )?;
+            self.post_walk.fork_state_table.build(
+                builder,
+                &post_channel_ids,
+                update.post_fork_state_table,
+            )?;
+            self.post_walk.branch_value_trans_table.build(
+                builder,
+                &post_channel_ids,
+                update.post_branch_value_trans_table,
+            )?;
+            builder.pop_namespace();
+
+            self.node_diff_table
+                .build(builder, &channel_ids, update.node_diff_table)?;
+            self.node_diff_byte_table
+                .build(builder, &channel_ids, update.node_diff_byte_table)?;
+            self.node_diff_hash_table
+                .build(builder, &channel_ids, update.node_diff_hash_table)?;
+            self.node_diff_value_table.build(
+                builder,
+                &channel_ids,
+                update.node_diff_value_table,
+            )?;
+            self.node_diff_slot_table
+                .build(builder, &channel_ids, update.node_diff_slot_table)?;
+            self.node_insert_table
+                .build(builder, &channel_ids, update.node_insert_table)?;
+            self.node_delete_table
+                .build(builder, &channel_ids, update.node_delete_table)?;
+            self.node_split_table
+                .build(builder, &channel_ids, update.node_split_table)?;
+            self.node_collapse_table
+                .build(builder, &channel_ids, update.node_collapse_table)?;
+            self.node_graft_table
+                .build(builder, &channel_ids, update.node_graft_table)?;
+            self.node_prune_table
+                .build(builder, &channel_ids, update.node_prune_table)?;
+            self.list_end_table
+                .build(builder, &channel_ids, update.list_end_table)?;
+        }
+        self.populate_mem_table.build(
+            builder,
+            channel_ids.mem,
             &mut self.state,
             table_heights.populate_mem,
         )?;
@@ -219,17 +711,72 @@ impl MPT {
 
         // generate boundaries
         let mut boundaries = vec![];
+        // the fields of the accounts whose values the boundaries pull from account_fields
+        let mut accounts = vec![];
         boundaries.extend(MPT::create_state_boundaries(
             channel_ids.state,
+            0,
             &advice.ptr_ts_pairs,
+            |i| self.statement.in_pre_state(i),
         ));
+        // the keys of hidden accounts are only known to the prover
+        let disclosed_count = self.statement.addr_val_pairs.len();
         boundaries.extend(MPT::create_mem_boundaries(
             channel_ids.mem,
             &self.statement,
-            &self.keys,
+            &self.keys[..disclosed_count],
             &advice.ptr_ts_pairs,
         ));
+        if let Some(hidden) = self.statement.hidden() {
+            boundaries.extend(MPT::create_commitment_boundaries(
+                &channel_ids,
+                hidden,
+                disclosed_count,
+            ));
+        }
+        match &self.statement.mode {
+            StatementMode::Predicates(predicates) => {
+                boundaries.extend(MPT::create_predicate_boundaries(
+                    &channel_ids,
+                    &self.statement,
+                    predicates,
+                    &advice.ptr_ts_pairs,
+                )?)
+            }
+            StatementMode::BalanceSum(_) => boundaries.extend(MPT::create_balance_sum_boundaries(
+                &channel_ids,
+                &self.statement,
+                &advice.ptr_ts_pairs,
+            )),
+            _ => {
+                let (account_field_boundaries, account_fields) =
+                    MPT::create_account_field_boundaries(
+                        channel_ids.account_fields,
+                        &self.statement,
+                        &advice.ptr_ts_pairs,
+                    )?;
+                boundaries.extend(account_field_boundaries);
+                accounts = account_fields;
+            }
+        }
+        if let Some(codes) = self.statement.codes() {
+            boundaries.extend(MPT::create_bytecode_boundaries(
+                &channel_ids,
+                &self.statement,
+                codes,
+                &advice.ptr_ts_pairs,
+            )?);
+        }
+        if is_update {
+            boundaries.extend(MPT::create_update_boundaries(
+                &channel_ids,
+                &self.statement,
+                self.keys.len(),
+                &advice.ptr_ts_pairs,
+                &advice.post_ptr_ts_pairs,
+            )?);
+        }
 
-        Ok((boundaries, self.statement))
+        Ok((boundaries, self.statement, accounts, channel_ids))
     }
 }
@@ -0,0 +1,217 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+use alloy::primitives::b256;
+
+// keccak256(rlp("")), the storage root of an account without storage
+const EMPTY_STORAGE_ROOT: Hash =
+    b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421").0;
+
+// an account value ends with (0xa0, storage_root, 0xa0, code_hash)
+const HASH_ITEM_LEN: usize = 1 + 32;
+
+/// How the statement is encoded inside a serialized proof.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatementCompression {
+    /// Account values are stored verbatim.
+    #[default]
+    None,
+    /// The empty storage root and the empty code hash are replaced by a tag.
+    KnownHashes,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum KnownHash {
+    EmptyStorageRoot,
+    EmptyCodeHash,
+    Other(Hash),
+}
+
+impl KnownHash {
+    fn encode(hash: &[u8]) -> Self {
+        if hash == EMPTY_STORAGE_ROOT {
+            Self::EmptyStorageRoot
+        } else if hash == EMPTY_CODE_HASH {
+            Self::EmptyCodeHash
+        } else {
+            Self::Other(hash.try_into().expect("hash item is 32 bytes"))
+        }
+    }
+
+    fn decode(&self) -> &Hash {
+        match self {
+            Self::EmptyStorageRoot => &EMPTY_STORAGE_ROOT,
+            Self::EmptyCodeHash => &EMPTY_CODE_HASH,
+            Self::Other(hash) => hash,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum CompressedValue {
+    Raw(Vec<u8>),
+    // head is the list header followed by the nonce and balance items
+    Account {
+        head: Vec<u8>,
+        storage_root: KnownHash,
+        code_hash: KnownHash,
+    },
+}
+
+impl CompressedValue {
+    fn encode(value: Vec<u8>) -> Self {
+        let len = value.len();
+        if len < 2 * HASH_ITEM_LEN
+            || value[len - 2 * HASH_ITEM_LEN] != 0xa0
+            || value[len - HASH_ITEM_LEN] != 0xa0
+        {
+            return Self::Raw(value);
+        }
+        let storage_root =
+            KnownHash::encode(&value[len - 2 * HASH_ITEM_LEN + 1..len - HASH_ITEM_LEN]);
+        let code_hash = KnownHash::encode(&value[len - HASH_ITEM_LEN + 1..]);
+        if matches!(
+            (&storage_root, &code_hash),
+            (KnownHash::Other(_), KnownHash::Other(_))
+        ) {
+            return Self::Raw(value);
+        }
+        let mut head = value;
+        head.truncate(len - 2 * HASH_ITEM_LEN);
+        Self::Account {
+            head,
+            storage_root,
+            code_hash,
+        }
+    }
+
+    fn decode(self) -> Vec<u8> {
+        match self {
+            Self::Raw(value) => value,
+            Self::Account {
+                mut head,
+                storage_root,
+                code_hash,
+            } => {
+                head.reserve(2 * HASH_ITEM_LEN);
+                head.push(0xa0);
+                head.extend_from_slice(storage_root.decode());
+                head.push(0xa0);
+                head.extend_from_slice(code_hash.decode());
+                head
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CompressedAddrValPair {
+    address: Vec<u8>,
+    value: CompressedValue,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CompressedPostState {
+    root_hash: Hash,
+    values: Vec<CompressedValue>,
+    changes: Vec<ValueChange>,
+}
+
+// the mode of a compressed statement, whose post values are compressed like the pre values
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum CompressedMode {
+    Update(CompressedPostState),
+    Other(StatementMode),
+}
+
+// the statement as it appears in a serialized proof
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum EncodedStatement {
+    Plain(Statement),
+    Compressed {
+        root_hash: Hash,
+        addr_val_pairs: Vec<CompressedAddrValPair>,
+        mode: CompressedMode,
+    },
+}
+
+impl EncodedStatement {
+    pub(crate) fn encode(statement: Statement, compression: StatementCompression) -> Self {
+        match compression {
+            StatementCompression::None => Self::Plain(statement),
+            StatementCompression::KnownHashes => Self::Compressed {
+                root_hash: statement.root_hash,
+                addr_val_pairs: statement
+                    .addr_val_pairs
+                    .into_iter()
+                    .map(|AddrValPair { address, value }| CompressedAddrValPair {
+                        address,
+                        value: CompressedValue::encode(value),
+                    })
+                    .collect(),
+                mode: match statement.mode {
+                    StatementMode::Update(PostState {
+                        root_hash,
+                        values,
+                        changes,
+                    }) => CompressedMode::Update(CompressedPostState {
+                        root_hash,
+                        values: values.into_iter().map(CompressedValue::encode).collect(),
+                        changes,
+                    }),
+                    mode => CompressedMode::Other(mode),
+                },
+            },
+        }
+    }
+
+    pub(crate) fn account_count(&self) -> usize {
+        match self {
+            Self::Plain(statement) => statement.account_count(),
+            Self::Compressed {
+                addr_val_pairs,
+                mode,
+                ..
+            } => {
+                addr_val_pairs.len()
+                    + match mode {
+                        CompressedMode::Other(StatementMode::Hidden(hidden)) => hidden.count,
+                        _ => 0,
+                    }
+            }
+        }
+    }
+
+    // reconstructs the exact leaf values, so the memory boundaries are unaffected
+    pub(crate) fn decode(self) -> Statement {
+        match self {
+            Self::Plain(statement) => statement,
+            Self::Compressed {
+                root_hash,
+                addr_val_pairs,
+                mode,
+            } => Statement {
+                root_hash,
+                addr_val_pairs: addr_val_pairs
+                    .into_iter()
+                    .map(|CompressedAddrValPair { address, value }| AddrValPair {
+                        address,
+                        value: value.decode(),
+                    })
+                    .collect(),
+                mode: match mode {
+                    CompressedMode::Update(CompressedPostState {
+                        root_hash,
+                        values,
+                        changes,
+                    }) => StatementMode::Update(PostState {
+                        root_hash,
+                        values: values.into_iter().map(CompressedValue::decode).collect(),
+                        changes,
+                    }),
+                    CompressedMode::Other(mode) => mode,
+                },
+            },
+        }
+    }
+}
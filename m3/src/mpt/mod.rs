@@ -1,11 +1,18 @@
 // Copyright 2024 Irreducible Inc.
 
 use super::*;
+use std::ops::Range;
 
 mod build;
+mod compression;
 mod process_account_proofs;
+mod session;
 
-#[derive(strum_macros::Display, Debug, Clone, Copy)]
+pub(crate) use compression::EncodedStatement;
+pub use compression::StatementCompression;
+pub use session::ProverSession;
+
+#[derive(strum_macros::Display, Debug, Clone, Copy, PartialEq)]
 pub(crate) enum NodeKind {
     Branch,
     ExtLeaf,
@@ -13,9 +20,19 @@ pub(crate) enum NodeKind {
 
 type Hash = [u8; 32];
 
+// keccak256(""), the code hash of an account without code
+pub(crate) const EMPTY_CODE_HASH: Hash =
+    alloy::primitives::b256!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470").0;
+
+// a branch without children or value, the template of the virtual branch of a split or a
+// collapse
+pub(crate) const EMPTY_BRANCH: [u8; 18] = [
+    0xd1, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+    0x80, 0x80,
+];
+
 #[derive(Debug)]
 pub(crate) struct NodeInfo {
-    pub(crate) bytes: Vec<u8>,
     pub(crate) kind: NodeKind,
     pub(crate) position: u32,
 }
@@ -49,13 +66,105 @@ pub(crate) struct TableHeight {
     pub(crate) count: usize,
 }
 
-impl From<&TableHeight> for (usize, usize) {
-    fn from(value: &TableHeight) -> (usize, usize) {
-        (value.n_vars, value.count)
+impl TableHeight {
+    pub(crate) fn new<T: FunctionTable>(count: usize) -> Self {
+        Self {
+            n_vars: T::n_vars(count),
+            count,
+        }
+    }
+
+    fn stats<T: FunctionTable>(&self, table_type: TableType) -> Result<TableStats, anyhow::Error> {
+        Ok(TableStats::new(
+            table_type,
+            self.n_vars,
+            self.count,
+            T::committed_layout()?,
+        ))
+    }
+}
+
+// the committed columns of a table and their bits per row, measured on a constraint system
+// holding nothing but the table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CommittedLayout {
+    pub(crate) columns: usize,
+    pub(crate) bits_per_row: usize,
+}
+
+impl CommittedLayout {
+    pub(crate) fn measure(
+        n_vars: usize,
+        build: impl FnOnce(
+            &mut ConstraintSystemBuilder<U, B128>,
+            &ChannelIds,
+        ) -> Result<(), anyhow::Error>,
+    ) -> Result<Self, anyhow::Error> {
+        let mut builder = ConstraintSystemBuilder::new();
+        let channel_ids = ChannelIds::new(&mut builder);
+        build(&mut builder, &channel_ids)?;
+        let oracle_bits = committed_oracle_bits(&builder.build()?);
+        Ok(Self {
+            columns: oracle_bits.len(),
+            bits_per_row: oracle_bits.iter().sum::<usize>() >> n_vars,
+        })
+    }
+}
+
+// the size in bits of every committed oracle
+pub(crate) fn committed_oracle_bits(constraint_system: &ConstraintSystem<B128>) -> Vec<usize> {
+    constraint_system
+        .oracles
+        .iter()
+        .filter_map(|oracle| match oracle {
+            MultilinearPolyOracle::Committed {
+                n_vars,
+                tower_level,
+                ..
+            } => Some(1 << (n_vars + tower_level)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Size of a table of the constraint system.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableStats {
+    pub table_type: TableType,
+    /// Log2 of the table height
+    pub n_vars: usize,
+    /// Rows in use
+    pub count: usize,
+    /// Rows padding the table to a power of two
+    pub padding_rows: usize,
+    pub committed_columns: usize,
+    /// Size of the committed columns, padding included
+    pub committed_bytes: usize,
+    /// Part of `committed_bytes` spent on padding rows
+    pub padding_bytes: usize,
+}
+
+impl TableStats {
+    fn new(
+        table_type: TableType,
+        n_vars: usize,
+        count: usize,
+        committed_layout: CommittedLayout,
+    ) -> Self {
+        let padding_rows = (1 << n_vars) - count;
+        Self {
+            table_type,
+            n_vars,
+            count,
+            padding_rows,
+            committed_columns: committed_layout.columns,
+            committed_bytes: (1 << n_vars) * committed_layout.bits_per_row / 8,
+            padding_bytes: padding_rows * committed_layout.bits_per_row / 8,
+        }
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum TableType {
     SkipListHeader,
     KeccakF,
@@ -69,6 +178,113 @@ pub enum TableType {
     CheckNibBase,
     CheckNibRecursive,
     ForkStableTable,
+    BranchValueTrans,
+    Memory,
+    SkipListHeaderOffsetLookup,
+    GetChildOffsetLookup,
+    BranchTransShiftLookup,
+    PostBranchTrans,
+    PostExtLeafTrans,
+    PostForkState,
+    PostBranchValueTrans,
+    NodeDiff,
+    NodeDiffByte,
+    NodeDiffHash,
+    NodeDiffValue,
+    NodeDiffSlot,
+    NodeInsert,
+    NodeDelete,
+    NodeSplit,
+    NodeCollapse,
+    NodeGraft,
+    NodePrune,
+    ListEnd,
+    AccountFields,
+    BalanceAtLeast,
+    IsContract,
+    CodeHashIn,
+    AccountFieldsSink,
+    CodeHashSetSink,
+    BalanceSum,
+    Bytecode,
+}
+
+impl TableType {
+    pub(crate) const FUNCTION_TABLES: [TableType; 13] = [
+        TableType::SkipListHeader,
+        TableType::KeccakF,
+        TableType::AbsorbBlockBase,
+        TableType::AbsorbBlockRecursive,
+        TableType::HashTransTable,
+        TableType::GetChildBase,
+        TableType::GetChildRecursive,
+        TableType::BranchTrans,
+        TableType::ExtLeafTrans,
+        TableType::CheckNibBase,
+        TableType::CheckNibRecursive,
+        TableType::ForkStableTable,
+        TableType::BranchValueTrans,
+    ];
+
+    /// The namespace the table's oracles are created under
+    pub fn namespace(&self) -> &'static str {
+        match self {
+            TableType::SkipListHeader => "skip_list_header",
+            TableType::KeccakF => "keccak_f",
+            TableType::AbsorbBlockBase => "absorb_block_base",
+            TableType::AbsorbBlockRecursive => "absorb_block_recursive",
+            TableType::HashTransTable => "hash_trans",
+            TableType::GetChildBase => "get_child_base",
+            TableType::GetChildRecursive => "get_child_recursive",
+            TableType::BranchTrans => "branch_trans",
+            TableType::ExtLeafTrans => "ext_leaf_trans",
+            TableType::CheckNibBase => "check_nib_base",
+            TableType::CheckNibRecursive => "check_nib_recursive",
+            TableType::ForkStableTable => "fork_state",
+            TableType::BranchValueTrans => "branch_value_trans",
+            TableType::Memory => "populate_mem",
+            TableType::SkipListHeaderOffsetLookup => "skip_list_header_offset_lookup",
+            TableType::GetChildOffsetLookup => "get_child_offset_lookup",
+            TableType::BranchTransShiftLookup => "branch_trans_shift_lookup",
+            TableType::PostBranchTrans => "post_walk::branch_trans",
+            TableType::PostExtLeafTrans => "post_walk::ext_leaf_trans",
+            TableType::PostForkState => "post_walk::fork_state",
+            TableType::PostBranchValueTrans => "post_walk::branch_value_trans",
+            TableType::NodeDiff => "node_diff",
+            TableType::NodeDiffByte => "node_diff_byte",
+            TableType::NodeDiffHash => "node_diff_hash",
+            TableType::NodeDiffValue => "node_diff_value",
+            TableType::NodeDiffSlot => "node_diff_slot",
+            TableType::NodeInsert => "node_insert",
+            TableType::NodeDelete => "node_delete",
+            TableType::NodeSplit => "node_split",
+            TableType::NodeCollapse => "node_collapse",
+            TableType::NodeGraft => "node_graft",
+            TableType::NodePrune => "node_prune",
+            TableType::ListEnd => "list_end",
+            TableType::AccountFields => "account_fields",
+            TableType::BalanceAtLeast => "balance_at_least",
+            TableType::IsContract => "is_contract",
+            TableType::CodeHashIn => "code_hash_in",
+            TableType::AccountFieldsSink => "account_fields_sink",
+            TableType::CodeHashSetSink => "code_hash_set_sink",
+            TableType::BalanceSum => "balance_sum",
+            TableType::Bytecode => "bytecode",
+        }
+    }
+
+    // rows are only traced back to account proofs for the function tables
+    pub(crate) fn from_namespace(namespace: &str) -> Option<Self> {
+        Self::FUNCTION_TABLES
+            .into_iter()
+            .find(|table_type| table_type.namespace() == namespace)
+    }
+}
+
+impl std::fmt::Display for TableType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.namespace())
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -85,11 +301,113 @@ pub(crate) struct TableHeights {
     check_nib_base_table: TableHeight,
     check_nib_recursive_table: TableHeight,
     fork_state_table: TableHeight,
+    branch_value_trans_table: TableHeight,
+    account_fields_table: TableHeight,
     populate_mem: usize,
+    update: Option<UpdateTableHeights>,
+    predicates: Option<PredicateTableHeights>,
+    // the fields of withheld values no other table consumes
+    account_fields_sink_table: Option<TableHeight>,
+    balance_sum_table: Option<TableHeight>,
+    bytecode_table: Option<TableHeight>,
+}
+
+// the tables only update proofs use
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct UpdateTableHeights {
+    post_branch_trans_table: TableHeight,
+    post_ext_leaf_trans_table: TableHeight,
+    post_fork_state_table: TableHeight,
+    post_branch_value_trans_table: TableHeight,
+    node_diff_table: TableHeight,
+    node_diff_byte_table: TableHeight,
+    node_diff_hash_table: TableHeight,
+    node_diff_value_table: TableHeight,
+    node_diff_slot_table: TableHeight,
+    node_insert_table: TableHeight,
+    node_delete_table: TableHeight,
+    node_split_table: TableHeight,
+    node_collapse_table: TableHeight,
+    node_graft_table: TableHeight,
+    node_prune_table: TableHeight,
+    list_end_table: TableHeight,
+}
+
+impl UpdateTableHeights {
+    fn stats(&self) -> Result<Vec<TableStats>, anyhow::Error> {
+        let Self {
+            post_branch_trans_table,
+            post_ext_leaf_trans_table,
+            post_fork_state_table,
+            post_branch_value_trans_table,
+            node_diff_table,
+            node_diff_byte_table,
+            node_diff_hash_table,
+            node_diff_value_table,
+            node_diff_slot_table,
+            node_insert_table,
+            node_delete_table,
+            node_split_table,
+            node_collapse_table,
+            node_graft_table,
+            node_prune_table,
+            list_end_table,
+        } = &self;
+        Ok(vec![
+            post_branch_trans_table.stats::<BranchTransTable>(TableType::PostBranchTrans)?,
+            post_ext_leaf_trans_table.stats::<ExtLeafTransTable>(TableType::PostExtLeafTrans)?,
+            post_fork_state_table.stats::<ForkStateTable>(TableType::PostForkState)?,
+            post_branch_value_trans_table
+                .stats::<BranchValueTransTable>(TableType::PostBranchValueTrans)?,
+            node_diff_table.stats::<NodeDiffTable>(TableType::NodeDiff)?,
+            node_diff_byte_table.stats::<NodeDiffByteTable>(TableType::NodeDiffByte)?,
+            node_diff_hash_table.stats::<NodeDiffHashTable>(TableType::NodeDiffHash)?,
+            node_diff_value_table.stats::<NodeDiffValueTable>(TableType::NodeDiffValue)?,
+            node_diff_slot_table.stats::<NodeDiffSlotTable>(TableType::NodeDiffSlot)?,
+            node_insert_table.stats::<NodeInsertTable>(TableType::NodeInsert)?,
+            node_delete_table.stats::<NodeDeleteTable>(TableType::NodeDelete)?,
+            node_split_table.stats::<NodeSplitTable>(TableType::NodeSplit)?,
+            node_collapse_table.stats::<NodeCollapseTable>(TableType::NodeCollapse)?,
+            node_graft_table.stats::<NodeGraftTable>(TableType::NodeGraft)?,
+            node_prune_table.stats::<NodePruneTable>(TableType::NodePrune)?,
+            list_end_table.stats::<ListEndTable>(TableType::ListEnd)?,
+        ])
+    }
+}
+
+// the tables only predicate proofs use
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PredicateTableHeights {
+    balance_at_least_table: TableHeight,
+    is_contract_table: TableHeight,
+    code_hash_in_table: TableHeight,
+    code_hash_set_sink_table: TableHeight,
+}
+
+impl PredicateTableHeights {
+    fn stats(&self) -> Result<Vec<TableStats>, anyhow::Error> {
+        let Self {
+            balance_at_least_table,
+            is_contract_table,
+            code_hash_in_table,
+            code_hash_set_sink_table,
+        } = &self;
+        Ok(vec![
+            balance_at_least_table.stats::<BalanceAtLeastTable>(TableType::BalanceAtLeast)?,
+            is_contract_table.stats::<IsContractTable>(TableType::IsContract)?,
+            code_hash_in_table.stats::<CodeHashInTable>(TableType::CodeHashIn)?,
+            code_hash_set_sink_table.stats::<CodeHashSetSinkTable>(TableType::CodeHashSetSink)?,
+        ])
+    }
 }
 
 impl TableHeights {
-    pub(crate) fn as_list(&self) -> HashMap<TableType, (usize, usize)> {
+    pub(crate) fn mem_len(&self) -> usize {
+        1 << self.populate_mem
+    }
+
+    // the size of every table, including memory and the lookup tables
+    pub(crate) fn stats(&self) -> Result<Vec<TableStats>, anyhow::Error> {
         let Self {
             skip_list_header,
             keccak_f,
@@ -103,37 +421,109 @@ impl TableHeights {
             check_nib_base_table,
             check_nib_recursive_table,
             fork_state_table,
-            populate_mem: _,
+            branch_value_trans_table,
+            account_fields_table,
+            populate_mem,
+            update,
+            predicates,
+            account_fields_sink_table,
+            balance_sum_table,
+            bytecode_table,
         } = &self;
-        HashMap::from([
-            (TableType::SkipListHeader, skip_list_header.into()),
-            (TableType::KeccakF, keccak_f.into()),
-            (TableType::AbsorbBlockBase, absorb_block_base_table.into()),
-            (
-                TableType::AbsorbBlockRecursive,
-                absorb_block_recursive_table.into(),
+        // lookup tables hold every entry
+        let lookup_stats = |table_type, n_vars, committed_layout| {
+            TableStats::new(table_type, n_vars, 1 << n_vars, committed_layout)
+        };
+        let mut stats = vec![
+            skip_list_header.stats::<SkipListHeaderTable>(TableType::SkipListHeader)?,
+            keccak_f.stats::<KeccakFTable>(TableType::KeccakF)?,
+            absorb_block_base_table.stats::<AbsorbBlockBaseTable>(TableType::AbsorbBlockBase)?,
+            absorb_block_recursive_table
+                .stats::<AbsorbBlockRecursiveTable>(TableType::AbsorbBlockRecursive)?,
+            hash_trans_table.stats::<HashTransTable>(TableType::HashTransTable)?,
+            get_child_base_table.stats::<GetChildBaseTable>(TableType::GetChildBase)?,
+            get_child_recursive_table
+                .stats::<GetChildRecursiveTable>(TableType::GetChildRecursive)?,
+            branch_trans_table.stats::<BranchTransTable>(TableType::BranchTrans)?,
+            ext_leaf_trans_table.stats::<ExtLeafTransTable>(TableType::ExtLeafTrans)?,
+            check_nib_base_table.stats::<CheckNibBaseTable>(TableType::CheckNibBase)?,
+            check_nib_recursive_table
+                .stats::<CheckNibRecursiveTable>(TableType::CheckNibRecursive)?,
+            fork_state_table.stats::<ForkStateTable>(TableType::ForkStableTable)?,
+            branch_value_trans_table.stats::<BranchValueTransTable>(TableType::BranchValueTrans)?,
+            account_fields_table.stats::<AccountFieldsTable>(TableType::AccountFields)?,
+            // memory is padded with zero bytes, which are flushed like any other
+            TableStats::new(
+                TableType::Memory,
+                *populate_mem,
+                1 << populate_mem,
+                CommittedLayout::measure(*populate_mem, |builder, channel_ids| {
+                    PopulateMemTable::new().build(
+                        builder,
+                        channel_ids.mem,
+                        &mut MPTState::new(),
+                        *populate_mem,
+                    )
+                })?,
             ),
-            (TableType::HashTransTable, hash_trans_table.into()),
-            (TableType::GetChildBase, get_child_base_table.into()),
-            (
-                TableType::GetChildRecursive,
-                get_child_recursive_table.into(),
+            lookup_stats(
+                TableType::SkipListHeaderOffsetLookup,
+                SkipListHeaderOffsetLookupTable::N_VARS,
+                CommittedLayout::measure(
+                    SkipListHeaderOffsetLookupTable::N_VARS,
+                    |builder, channel_ids| {
+                        SkipListHeaderOffsetLookupTable::new().build(builder, channel_ids)
+                    },
+                )?,
             ),
-            (TableType::BranchTrans, branch_trans_table.into()),
-            (TableType::ExtLeafTrans, ext_leaf_trans_table.into()),
-            (TableType::CheckNibBase, check_nib_base_table.into()),
-            (
-                TableType::CheckNibRecursive,
-                check_nib_recursive_table.into(),
+            lookup_stats(
+                TableType::GetChildOffsetLookup,
+                GetChildOffsetLookupTable::N_VARS,
+                CommittedLayout::measure(
+                    GetChildOffsetLookupTable::N_VARS,
+                    |builder, channel_ids| {
+                        GetChildOffsetLookupTable::new().build(builder, channel_ids)
+                    },
+                )?,
             ),
-            (TableType::ForkStableTable, fork_state_table.into()),
-        ])
+            lookup_stats(
+                TableType::BranchTransShiftLookup,
+                BranchTransShiftLookupTable::N_VARS,
+                CommittedLayout::measure(
+                    BranchTransShiftLookupTable::N_VARS,
+                    |builder, channel_ids| {
+                        BranchTransShiftLookupTable::new().build(builder, channel_ids)
+                    },
+                )?,
+            ),
+        ];
+        if let Some(update) = update {
+            stats.extend(update.stats()?);
+        }
+        if let Some(predicates) = predicates {
+            stats.extend(predicates.stats()?);
+        }
+        if let Some(account_fields_sink_table) = account_fields_sink_table {
+            stats.push(
+                account_fields_sink_table
+                    .stats::<AccountFieldsSinkTable>(TableType::AccountFieldsSink)?,
+            );
+        }
+        if let Some(balance_sum_table) = balance_sum_table {
+            stats.push(balance_sum_table.stats::<BalanceSumTable>(TableType::BalanceSum)?);
+        }
+        if let Some(bytecode_table) = bytecode_table {
+            stats.push(bytecode_table.stats::<BytecodeTable>(TableType::Bytecode)?);
+        }
+        Ok(stats)
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Advice {
     ptr_ts_pairs: Vec<PtrTsPair>,
+    // empty unless the statement is an update
+    post_ptr_ts_pairs: Vec<PtrTsPair>,
     pub(crate) table_heights: TableHeights,
 }
 // statement is the root hash with a list of address, value pairs..
@@ -143,9 +533,396 @@ pub struct Advice {
 pub struct Statement {
     root_hash: Hash,
     addr_val_pairs: Vec<AddrValPair>,
+    mode: StatementMode,
+}
+
+// what a proof proves of the accounts besides that they are under the root; a proof proves one
+// of these at a time
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) enum StatementMode {
+    // the values of the accounts
+    #[default]
+    Accounts,
+    // the root and the values of the same addresses afterwards
+    Update(PostState),
+    // a predicate per address in place of its value, which is left empty
+    Predicates(Vec<AccountPredicate>),
+    // the commitment to the accounts left out of addr_val_pairs
+    Hidden(HiddenAccounts),
+    // the sum of the balances of the addresses in place of their values, which are left empty
+    BalanceSum(U256),
+    // the code of some of the accounts, which hashes to their code hash
+    Bytecode(Vec<Bytecode>),
+}
+
+// the hidden keys follow the disclosed ones in mem, and are hashed together with a salt into
+// the commitment
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct HiddenAccounts {
+    commitment: Hash,
+    count: usize,
+}
+
+// the code of the account at index in addr_val_pairs, which follows the keys in mem with its
+// padding
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Bytecode {
+    account: usize,
+    code: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct PostState {
+    root_hash: Hash,
+    // empty for the accounts the update deletes
+    values: Vec<Vec<u8>>,
+    changes: Vec<ValueChange>,
+}
+
+// how the update changes the value of an account; the value of an inserted account is empty
+// in addr_val_pairs, and the one of a deleted account in the post state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ValueChange {
+    Update,
+    // into an empty child of a branch, which the leaf of the account takes, or where its path
+    // parts from the path of a leaf or an extension, which it splits
+    Insert,
+    // leaving an empty child in a branch that keeps at least two other children, or collapsing
+    // a branch left with a single one
+    Delete,
+}
+
+/// The value of an account before and after an update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountUpdate {
+    pub address: Vec<u8>,
+    /// RLP encoded account under the pre-state root, `None` if the update inserts it
+    pub old_value: Option<Vec<u8>>,
+    /// RLP encoded account under the post-state root, `None` if the update deletes it
+    pub new_value: Option<Vec<u8>>,
+}
+
+/// The statement of an update proof: `post_root` is obtained from `pre_root` by updating the
+/// values of exactly these accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateUpdate {
+    pub pre_root: [u8; 32],
+    pub post_root: [u8; 32],
+    pub updates: Vec<AccountUpdate>,
+}
+
+/// The fields of an account, from the payloads the verifier pulls from the `account_fields`
+/// channel, which the circuit decodes from its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountFields {
+    pub address: Vec<u8>,
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: [u8; 32],
+    pub code_hash: [u8; 32],
+}
+
+impl AccountFields {
+    // decoded by the prover, at the ranges the circuit decodes the fields at
+    fn decode(address: &[u8], value: &[u8]) -> Result<Self, anyhow::Error> {
+        let ranges = account_field_ranges(value)?;
+        Self::from_payloads(address, ranges.map(|range| &value[range]))
+    }
+
+    // the payloads of the nonce, the balance, the storage root and the code hash
+    pub(crate) fn from_payloads(
+        address: &[u8],
+        [nonce, balance, storage_root, code_hash]: [&[u8]; 4],
+    ) -> Result<Self, anyhow::Error> {
+        if nonce.len() > 8 {
+            return Err(anyhow!("Nonce is longer than 8 bytes"));
+        }
+        Ok(Self {
+            address: address.to_vec(),
+            nonce: nonce
+                .iter()
+                .fold(0, |nonce, &byte| nonce << 8 | byte as u64),
+            balance: U256::try_from_be_slice(balance)
+                .ok_or_else(|| anyhow!("Balance is longer than 32 bytes"))?,
+            storage_root: storage_root.try_into()?,
+            code_hash: code_hash.try_into()?,
+        })
+    }
+}
+
+/// A property of an account, proven from its value without disclosing the value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountPredicate {
+    BalanceAtLeast(Address, U256),
+    NonceEquals(Address, u64),
+    /// The code hash is one of the set.
+    CodeHashIn(Address, Vec<[u8; 32]>),
+    /// The code hash is not the hash of empty code.
+    IsContract(Address),
+}
+
+impl AccountPredicate {
+    pub fn address(&self) -> Address {
+        match self {
+            Self::BalanceAtLeast(address, _)
+            | Self::NonceEquals(address, _)
+            | Self::CodeHashIn(address, _)
+            | Self::IsContract(address) => *address,
+        }
+    }
+
+    fn holds(&self, fields: &AccountFields) -> bool {
+        match self {
+            Self::BalanceAtLeast(_, amount) => fields.balance >= *amount,
+            Self::NonceEquals(_, nonce) => fields.nonce == *nonce,
+            Self::CodeHashIn(_, code_hashes) => code_hashes.contains(&fields.code_hash),
+            Self::IsContract(_) => fields.code_hash != EMPTY_CODE_HASH,
+        }
+    }
+}
+
+/// The statement of a proof hiding some of its accounts: the disclosed accounts and
+/// `hidden_count` more accounts are under `root_hash`, where `commitment` is the
+/// [`hidden_accounts_commitment`] of the addresses of the hidden ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisclosedAccounts {
+    pub root_hash: [u8; 32],
+    pub accounts: Vec<AccountFields>,
+    pub commitment: [u8; 32],
+    pub hidden_count: usize,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The keccak-256 hash of the keys of `addresses` in ascending order, followed by `salt`,
+/// which keeps the addresses from being guessed.
+pub fn hidden_accounts_commitment(addresses: &[Address], salt: &[u8; 32]) -> [u8; 32] {
+    let mut keys = addresses
+        .iter()
+        .map(|address| keccak256(address).0)
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys_commitment(&keys, salt)
+}
+
+fn keys_commitment(sorted_keys: &[Hash], salt: &Hash) -> Hash {
+    let mut preimage = sorted_keys.concat();
+    preimage.extend(salt);
+    keccak256(preimage).0
+}
+
+/// The statement of a balance sum proof: the balances of the accounts of `addresses` under
+/// `root_hash` add up to `total`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceSum {
+    pub root_hash: [u8; 32],
+    pub addresses: Vec<Vec<u8>>,
+    pub total: U256,
+}
+
+/// The code an account runs, which hashes to its code hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountCode {
+    pub address: Vec<u8>,
+    pub code: Vec<u8>,
+}
+
+/// The statement of a bytecode proof: the accounts under `root_hash`, some of which run the
+/// code of `codes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountsWithCode {
+    pub root_hash: [u8; 32],
+    pub accounts: Vec<AccountFields>,
+    pub codes: Vec<AccountCode>,
+}
+
+/// The statement of a predicate proof: every predicate holds for its account under
+/// `root_hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountPredicates {
+    pub root_hash: [u8; 32],
+    pub predicates: Vec<AccountPredicate>,
+}
+
+impl Statement {
+    pub(crate) fn root_hash(&self) -> &Hash {
+        &self.root_hash
+    }
+
+    pub(crate) fn addresses(&self) -> impl Iterator<Item = &[u8]> {
+        self.addr_val_pairs
+            .iter()
+            .map(|addr_val_pair| addr_val_pair.address.as_slice())
+    }
+
+    pub(crate) fn post_state(&self) -> Option<&PostState> {
+        match &self.mode {
+            StatementMode::Update(post_state) => Some(post_state),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn predicates(&self) -> Option<&[AccountPredicate]> {
+        match &self.mode {
+            StatementMode::Predicates(predicates) => Some(predicates),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn hidden(&self) -> Option<&HiddenAccounts> {
+        match &self.mode {
+            StatementMode::Hidden(hidden) => Some(hidden),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn balance_sum(&self) -> Option<U256> {
+        match self.mode {
+            StatementMode::BalanceSum(balance_sum) => Some(balance_sum),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn codes(&self) -> Option<&[Bytecode]> {
+        match &self.mode {
+            StatementMode::Bytecode(codes) => Some(codes),
+            _ => None,
+        }
+    }
+
+    // the values of predicate and balance sum statements are left empty
+    pub(crate) fn withholds_values(&self) -> bool {
+        matches!(
+            self.mode,
+            StatementMode::Predicates(_) | StatementMode::BalanceSum(_)
+        )
+    }
+
+    // how the update changes the value of the key at index, which any other proof leaves as is
+    pub(crate) fn value_change(&self, index: usize) -> ValueChange {
+        self.post_state()
+            .map_or(ValueChange::Update, |post_state| post_state.changes[index])
+    }
+
+    // the walk of a key absent from a trie ends at an empty child of a branch
+    pub(crate) fn in_pre_state(&self, index: usize) -> bool {
+        self.value_change(index) != ValueChange::Insert
+    }
+
+    pub(crate) fn in_post_state(&self, index: usize) -> bool {
+        self.value_change(index) != ValueChange::Delete
+    }
+
+    // the hidden accounts included
+    pub(crate) fn account_count(&self) -> usize {
+        self.addr_val_pairs.len() + self.hidden().map_or(0, |hidden| hidden.count)
+    }
+
+    // the fields build returns are the ones of every account unless the values are withheld or
+    // some of the accounts hidden
+    pub(crate) fn into_account_fields(
+        self,
+        accounts: Vec<AccountFields>,
+    ) -> Result<Vec<AccountFields>, anyhow::Error> {
+        match self.mode {
+            StatementMode::Predicates(_) | StatementMode::BalanceSum(_) => {
+                Err(anyhow!("The proof withholds the account values"))
+            }
+            StatementMode::Hidden(_) => Err(anyhow!("The proof hides some of the accounts")),
+            _ => Ok(accounts),
+        }
+    }
+
+    // the fields of the accounts of a statement proving nothing else of them
+    pub(crate) fn into_plain_account_fields(
+        self,
+        accounts: Vec<AccountFields>,
+    ) -> Result<Vec<AccountFields>, anyhow::Error> {
+        match self.mode {
+            StatementMode::Accounts => Ok(accounts),
+            _ => Err(anyhow!("Not a proof of accounts alone")),
+        }
+    }
+
+    pub(crate) fn into_predicates(self) -> Option<AccountPredicates> {
+        match self.mode {
+            StatementMode::Predicates(predicates) => Some(AccountPredicates {
+                root_hash: self.root_hash,
+                predicates,
+            }),
+            _ => None,
+        }
+    }
+
+    // accounts are the fields of the disclosed accounts build returns
+    pub(crate) fn into_disclosed(self, accounts: Vec<AccountFields>) -> Option<DisclosedAccounts> {
+        match self.mode {
+            StatementMode::Hidden(hidden) => Some(DisclosedAccounts {
+                root_hash: self.root_hash,
+                accounts,
+                commitment: hidden.commitment,
+                hidden_count: hidden.count,
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn into_balance_sum(self) -> Option<BalanceSum> {
+        match self.mode {
+            StatementMode::BalanceSum(total) => Some(BalanceSum {
+                root_hash: self.root_hash,
+                total,
+                addresses: self
+                    .addr_val_pairs
+                    .into_iter()
+                    .map(|addr_val_pair| addr_val_pair.address)
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+
+    // accounts are the fields of every account build returns
+    pub(crate) fn into_codes(self, accounts: Vec<AccountFields>) -> Option<AccountsWithCode> {
+        match self.mode {
+            StatementMode::Bytecode(codes) => Some(AccountsWithCode {
+                root_hash: self.root_hash,
+                codes: codes
+                    .into_iter()
+                    .map(|Bytecode { account, code }| AccountCode {
+                        address: accounts[account].address.clone(),
+                        code,
+                    })
+                    .collect(),
+                accounts,
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn into_update(self) -> Option<StateUpdate> {
+        let StatementMode::Update(post_state) = self.mode else {
+            return None;
+        };
+        Some(StateUpdate {
+            pre_root: self.root_hash,
+            post_root: post_state.root_hash,
+            updates: self
+                .addr_val_pairs
+                .into_iter()
+                .zip(post_state.values)
+                .zip(post_state.changes)
+                .map(
+                    |((AddrValPair { address, value }, new_value), change)| AccountUpdate {
+                        address,
+                        old_value: (change != ValueChange::Insert).then_some(value),
+                        new_value: (change != ValueChange::Delete).then_some(new_value),
+                    },
+                )
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub(crate) struct State {
     pub start_ptr: u32,
     pub key_ptr: NibPtr,
@@ -158,12 +935,53 @@ impl State {
             && left_state.key_ptr == right_state.key_ptr
             && left_state.rlp_ptr == right_state.rlp_ptr
     }
+
+    // the state a transition walked from the state of another key at the same node leads to
+    // from this one, which advances the key pointer by as many nibbles
+    pub(crate) fn rebase(&self, walked_state: &State) -> State {
+        State {
+            start_ptr: self.start_ptr,
+            key_ptr: NibPtr {
+                byte: self.start_ptr + (walked_state.key_ptr.byte - walked_state.start_ptr),
+                parity: walked_state.key_ptr.parity,
+            },
+            rlp_ptr: walked_state.rlp_ptr,
+            ts: self.ts,
+        }
+    }
+}
+
+/// The account proof and node a table row was appended for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowOrigin {
+    /// index of the account proof
+    pub account: usize,
+    /// index of the node within the account proof, which nodes inlined into it are part of
+    pub node: usize,
+}
+
+// the row counts of every table right before the rows of each origin were appended
+#[derive(Debug, Default)]
+pub(crate) struct RowOrigins {
+    snapshots: Vec<(RowOrigin, HashMap<TableType, usize>)>,
+}
+
+impl RowOrigins {
+    pub(crate) fn origin(&self, table_type: TableType, row: usize) -> Option<RowOrigin> {
+        // snapshots are sorted by row count for every table
+        let next = self
+            .snapshots
+            .partition_point(|(_, row_counts)| row_counts[&table_type] <= row);
+        let (origin, _) = self.snapshots.get(next.checked_sub(1)?)?;
+        Some(*origin)
+    }
 }
 
 // map from key to node info list
 pub(crate) type ProofData = std::collections::HashMap<usize, Vec<NodeInfo>>;
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub(crate) struct ChannelIds {
     pub mem: ChannelId,
     pub state: ChannelId,
@@ -175,32 +993,143 @@ pub(crate) struct ChannelIds {
     pub get_child_offset_lookup: ChannelId,
     pub branch_trans_shift_lookup: ChannelId,
     pub check_nib: ChannelId,
+    // the state channel of the post state walk of an update
+    pub post_state: ChannelId,
+    pub list_end: ChannelId,
+    pub node_diff: ChannelId,
+    pub node_diff_window: ChannelId,
+    // the ends of the leaves of inserted and deleted keys, which are the ends of their values
+    // the verifier reads
+    pub leaf_end: ChannelId,
+    // the fields of the account values, as decoded by account_fields
+    pub account_fields: ChannelId,
+    // the requests of the predicates, pushed by the verifier
+    pub balance_at_least: ChannelId,
+    pub is_contract: ChannelId,
+    pub code_hash_in: ChannelId,
+    pub code_hash_set: ChannelId,
+    // the values whose balances are summed, pushed by the verifier, and the sums of the
+    // balances added so far
+    pub balance_sum: ChannelId,
+    pub partial_balance_sum: ChannelId,
+    // the values whose code is absorbed, with the pointer to the code, pushed by the verifier
+    pub bytecode: ChannelId,
+}
+
+impl ChannelIds {
+    pub(crate) fn new(builder: &mut ConstraintSystemBuilder<U, B128>) -> Self {
+        Self {
+            mem: builder.add_channel(),
+            state: builder.add_channel(),
+            skip_list_header: builder.add_channel(),
+            skip_list_header_offset_lookup: builder.add_channel(),
+            keccak_f: builder.add_channel(),
+            absorb_block: builder.add_channel(),
+            get_child: builder.add_channel(),
+            get_child_offset_lookup: builder.add_channel(),
+            branch_trans_shift_lookup: builder.add_channel(),
+            check_nib: builder.add_channel(),
+            post_state: builder.add_channel(),
+            list_end: builder.add_channel(),
+            node_diff: builder.add_channel(),
+            node_diff_window: builder.add_channel(),
+            leaf_end: builder.add_channel(),
+            account_fields: builder.add_channel(),
+            balance_at_least: builder.add_channel(),
+            is_contract: builder.add_channel(),
+            code_hash_in: builder.add_channel(),
+            code_hash_set: builder.add_channel(),
+            balance_sum: builder.add_channel(),
+            partial_balance_sum: builder.add_channel(),
+            bytecode: builder.add_channel(),
+        }
+    }
+
+    pub(crate) fn names(&self) -> [(ChannelId, &'static str); 23] {
+        [
+            (self.mem, "mem"),
+            (self.state, "state"),
+            (self.skip_list_header, "skip_list_header"),
+            (
+                self.skip_list_header_offset_lookup,
+                "skip_list_header_offset_lookup",
+            ),
+            (self.keccak_f, "keccak_f"),
+            (self.absorb_block, "absorb_block"),
+            (self.get_child, "get_child"),
+            (self.get_child_offset_lookup, "get_child_offset_lookup"),
+            (self.branch_trans_shift_lookup, "branch_trans_shift_lookup"),
+            (self.check_nib, "check_nib"),
+            (self.post_state, "post_state"),
+            (self.list_end, "list_end"),
+            (self.node_diff, "node_diff"),
+            (self.node_diff_window, "node_diff_window"),
+            (self.leaf_end, "leaf_end"),
+            (self.account_fields, "account_fields"),
+            (self.balance_at_least, "balance_at_least"),
+            (self.is_contract, "is_contract"),
+            (self.code_hash_in, "code_hash_in"),
+            (self.code_hash_set, "code_hash_set"),
+            (self.balance_sum, "balance_sum"),
+            (self.partial_balance_sum, "partial_balance_sum"),
+            (self.bytecode, "bytecode"),
+        ]
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-pub(crate) enum Action {
-    Ignore,
-    Count,
-    Append,
+// a row recorded by a walk, or by the hashing of the commitment and the codes, to be appended
+// once the tables are allocated; the timestamps of the states the transitions carry are set when
+// the row is appended, as they depend on how often the walks of later keys fork the nodes
+#[derive(PartialEq)]
+pub(crate) enum Row {
+    SkipListHeader(SkipListHeaderAdvice),
+    KeccakF(KeccakFData),
+    AbsorbBlockBase(AbsorbBlockBaseData),
+    AbsorbBlockRecursive(Box<AbsorbBlockRecursiveData>),
+    HashTrans(HashTransData),
+    GetChildBase(GetChildBaseData),
+    GetChildRecursive(GetChildRecursiveData),
+    BranchTrans(BranchTransData),
+    ExtLeafTrans(ExtLeafTransAdvice),
+    CheckNibBase(CheckNibBaseData),
+    CheckNibRecursive(CheckNibRecursiveData),
+    ForkState(ForkStateData),
+    BranchValueTrans(BranchValueTransData),
+    NodeDiff(NodeDiffData),
+    NodeDiffByte(NodeDiffByteData),
+    NodeDiffHash(DiffPtrs),
+    NodeDiffValue(DiffPtrs),
+    NodeDiffSlot(NodeDiffSlotData),
+    NodeInsert(NodeSlotData),
+    NodeDelete(NodeSlotData),
+    NodeSplit(NodeSplitData),
+    NodeCollapse(NodeSplitData),
+    NodeGraft(NodeGraftData),
+    NodePrune(NodeGraftData),
+    ListEnd(ListEndData),
+    Bytecode(BytecodeData),
 }
 
 #[allow(clippy::upper_case_acronyms)]
 pub struct MPT {
     pub(crate) state: MPTState,
-    statement: Statement,
+    pub(crate) statement: Statement,
+    // the keys of the hidden accounts follow the disclosed ones for the prover
     keys: Vec<Hash>,
+    // the salt of the commitment to the hidden accounts, only known to the prover
+    salt: Option<Hash>,
     proof_data: Option<ProofData>,
+    post_proof_data: Option<ProofData>,
+    pub(crate) row_origins: Option<RowOrigins>,
+    // the rows of the walks, collected instead of appended when traced_rows is called
+    traced_rows: Option<Vec<Row>>,
+    pub(crate) session: ProverSession,
 
     // tables
     pub(crate) skip_list_header_table: SkipListHeaderTable,
     pub(crate) skip_list_header_count: usize,
     pub(crate) skip_list_header_offset_lookup_table: SkipListHeaderOffsetLookupTable,
-    pub(crate) keccak_f_table: KeccakFTable,
-    pub(crate) keccak_f_count: usize,
-    pub(crate) absorb_block_base_table: AbsorbBlockBaseTable,
-    pub(crate) absorb_block_base_count: usize,
-    pub(crate) absorb_block_recursive_table: AbsorbBlockRecursiveTable,
-    pub(crate) absorb_block_recursive_count: usize,
+    pub(crate) sponge: KeccakSponge,
     pub(crate) hash_trans_table: HashTransTable,
     pub(crate) hash_trans_count: usize,
     pub(crate) get_child_base_table: GetChildBaseTable,
@@ -211,6 +1140,8 @@ pub struct MPT {
     pub(crate) branch_trans_table: BranchTransTable,
     pub(crate) branch_trans_count: usize,
     pub(crate) branch_trans_shift_lookup_table: BranchTransShiftLookupTable,
+    pub(crate) branch_value_trans_table: BranchValueTransTable,
+    pub(crate) branch_value_trans_count: usize,
     pub(crate) ext_leaf_trans_table: ExtLeafTransTable,
     pub(crate) ext_leaf_trans_count: usize,
     pub(crate) check_nib_base_table: CheckNibBaseTable,
@@ -219,7 +1150,79 @@ pub struct MPT {
     pub(crate) check_nib_recursive_count: usize,
     pub(crate) fork_state_table: ForkStateTable,
     pub(crate) fork_state_count: usize,
+    pub(crate) account_fields_table: AccountFieldsTable,
     pub(crate) populate_mem_table: PopulateMemTable,
+
+    // tables of update proofs
+    pub(crate) post_walk: PostWalkTables,
+    pub(crate) node_diff_table: NodeDiffTable,
+    pub(crate) node_diff_count: usize,
+    pub(crate) node_diff_byte_table: NodeDiffByteTable,
+    pub(crate) node_diff_byte_count: usize,
+    pub(crate) node_diff_hash_table: NodeDiffHashTable,
+    pub(crate) node_diff_hash_count: usize,
+    pub(crate) node_diff_value_table: NodeDiffValueTable,
+    pub(crate) node_diff_value_count: usize,
+    pub(crate) node_diff_slot_table: NodeDiffSlotTable,
+    pub(crate) node_diff_slot_count: usize,
+    pub(crate) node_insert_table: NodeInsertTable,
+    pub(crate) node_insert_count: usize,
+    pub(crate) node_delete_table: NodeDeleteTable,
+    pub(crate) node_delete_count: usize,
+    pub(crate) node_split_table: NodeSplitTable,
+    pub(crate) node_split_count: usize,
+    pub(crate) node_collapse_table: NodeCollapseTable,
+    pub(crate) node_collapse_count: usize,
+    pub(crate) node_graft_table: NodeGraftTable,
+    pub(crate) node_graft_count: usize,
+    pub(crate) node_prune_table: NodePruneTable,
+    pub(crate) node_prune_count: usize,
+    pub(crate) list_end_table: ListEndTable,
+    pub(crate) list_end_count: usize,
+    pub(crate) diff_windows: DiffWindows,
+    // the splits and collapses, by key
+    pub(crate) reshapes: HashMap<usize, Reshape>,
+
+    // tables of predicate proofs
+    pub(crate) balance_at_least_table: BalanceAtLeastTable,
+    pub(crate) is_contract_table: IsContractTable,
+    pub(crate) code_hash_in_table: CodeHashInTable,
+    pub(crate) account_fields_sink_table: AccountFieldsSinkTable,
+    pub(crate) code_hash_set_sink_table: CodeHashSetSinkTable,
+
+    // tables of balance sum proofs
+    pub(crate) balance_sum_table: BalanceSumTable,
+
+    // tables of bytecode proofs
+    pub(crate) bytecode_table: BytecodeTable,
+}
+
+// the transition tables of the post state walk, swapped with the ones of the pre state walk
+// for the duration of the walk
+pub(crate) struct PostWalkTables {
+    pub(crate) branch_trans_table: BranchTransTable,
+    pub(crate) branch_trans_count: usize,
+    pub(crate) ext_leaf_trans_table: ExtLeafTransTable,
+    pub(crate) ext_leaf_trans_count: usize,
+    pub(crate) fork_state_table: ForkStateTable,
+    pub(crate) fork_state_count: usize,
+    pub(crate) branch_value_trans_table: BranchValueTransTable,
+    pub(crate) branch_value_trans_count: usize,
+}
+
+impl PostWalkTables {
+    fn new() -> Self {
+        Self {
+            branch_trans_table: BranchTransTable::new(),
+            branch_trans_count: 0,
+            ext_leaf_trans_table: ExtLeafTransTable::new(),
+            ext_leaf_trans_count: 0,
+            fork_state_table: ForkStateTable::new(),
+            fork_state_count: 0,
+            branch_value_trans_table: BranchValueTransTable::new(),
+            branch_value_trans_count: 0,
+        }
+    }
 }
 impl MPT {
     #[instrument(name = "new MPT", skip_all, level = "debug")]
@@ -231,25 +1234,19 @@ impl MPT {
             .collect();
 
         Self {
-            state: MPTState {
-                mem: Memory::new(),
-                skip_list_header_offset_lookup: SkipListHeaderOffsetLookup::new(),
-                get_child_offset_lookup: GetChildOffsetLookup::new(),
-                branch_trans_shift_lookup: BranchTransShiftLookup::new(),
-                state_final_tss: HashMap::new(),
-            },
+            state: MPTState::new(),
             statement,
             keys,
+            salt: None,
             proof_data: None,
+            post_proof_data: None,
+            row_origins: None,
+            traced_rows: None,
+            session: ProverSession::new(),
             // tables than impl FunctionTable (12 of these)
             skip_list_header_table: SkipListHeaderTable::new(),
             skip_list_header_count: 0,
-            keccak_f_table: KeccakFTable::new(),
-            keccak_f_count: 0,
-            absorb_block_base_table: AbsorbBlockBaseTable::new(),
-            absorb_block_base_count: 0,
-            absorb_block_recursive_table: AbsorbBlockRecursiveTable::new(),
-            absorb_block_recursive_count: 0,
+            sponge: KeccakSponge::new(),
             hash_trans_table: HashTransTable::new(),
             hash_trans_count: 0,
             get_child_base_table: GetChildBaseTable::new(),
@@ -258,6 +1255,8 @@ impl MPT {
             get_child_recursive_count: 0,
             branch_trans_table: BranchTransTable::new(),
             branch_trans_count: 0,
+            branch_value_trans_table: BranchValueTransTable::new(),
+            branch_value_trans_count: 0,
             ext_leaf_trans_table: ExtLeafTransTable::new(),
             ext_leaf_trans_count: 0,
             check_nib_base_table: CheckNibBaseTable::new(),
@@ -266,22 +1265,234 @@ impl MPT {
             check_nib_recursive_count: 0,
             fork_state_table: ForkStateTable::new(),
             fork_state_count: 0,
+            // table to decode the account values, a row per account
+            account_fields_table: AccountFieldsTable::new(),
             // table to populate memory
             populate_mem_table: PopulateMemTable::new(),
             // tables to populate lookups
             skip_list_header_offset_lookup_table: SkipListHeaderOffsetLookupTable::new(),
             get_child_offset_lookup_table: GetChildOffsetLookupTable::new(),
             branch_trans_shift_lookup_table: BranchTransShiftLookupTable::new(),
+            // tables of update proofs
+            post_walk: PostWalkTables::new(),
+            node_diff_table: NodeDiffTable::new(),
+            node_diff_count: 0,
+            node_diff_byte_table: NodeDiffByteTable::new(),
+            node_diff_byte_count: 0,
+            node_diff_hash_table: NodeDiffHashTable::new(),
+            node_diff_hash_count: 0,
+            node_diff_value_table: NodeDiffValueTable::new(),
+            node_diff_value_count: 0,
+            node_diff_slot_table: NodeDiffSlotTable::new(),
+            node_diff_slot_count: 0,
+            node_insert_table: NodeInsertTable::new(),
+            node_insert_count: 0,
+            node_delete_table: NodeDeleteTable::new(),
+            node_delete_count: 0,
+            node_split_table: NodeSplitTable::new(),
+            node_split_count: 0,
+            node_collapse_table: NodeCollapseTable::new(),
+            node_collapse_count: 0,
+            node_graft_table: NodeGraftTable::new(),
+            node_graft_count: 0,
+            node_prune_table: NodePruneTable::new(),
+            node_prune_count: 0,
+            list_end_table: ListEndTable::new(),
+            list_end_count: 0,
+            diff_windows: DiffWindows::default(),
+            reshapes: HashMap::new(),
+            // tables of predicate proofs
+            balance_at_least_table: BalanceAtLeastTable::new(),
+            is_contract_table: IsContractTable::new(),
+            code_hash_in_table: CodeHashInTable::new(),
+            account_fields_sink_table: AccountFieldsSinkTable::new(),
+            code_hash_set_sink_table: CodeHashSetSinkTable::new(),
+            // tables of balance sum proofs
+            balance_sum_table: BalanceSumTable::new(),
+            // tables of bytecode proofs
+            bytecode_table: BytecodeTable::new(),
         }
     }
 }
 
 impl MPT {
-    fn skip_short_byte_string(&self, ptr: u32) -> u32 {
+    pub(crate) fn is_update(&self) -> bool {
+        self.statement.post_state().is_some()
+    }
+
+    pub(crate) fn has_predicates(&self) -> bool {
+        self.statement.predicates().is_some()
+    }
+
+    pub(crate) fn has_balance_sum(&self) -> bool {
+        self.statement.balance_sum().is_some()
+    }
+
+    pub(crate) fn has_hidden_accounts(&self) -> bool {
+        self.statement.hidden().is_some()
+    }
+
+    // the prover of a statement with hidden accounts walks their keys too
+    pub(crate) fn open_hidden_accounts(&mut self, hidden_keys: Vec<Hash>, salt: Hash) {
+        assert_eq!(
+            self.statement.hidden().map(|hidden| hidden.count),
+            Some(hidden_keys.len())
+        );
+        self.keys.extend(hidden_keys);
+        self.salt = Some(salt);
+    }
+
+    // the hidden keys and the salt are the preimage of the commitment, which follows their
+    // padding in mem and is preceded by a 0xa0 byte like the hashes of the nodes
+    pub(crate) fn commitment_mem_offsets(disclosed_count: usize, key_count: usize) -> (u32, u32) {
+        let preimage_ptr = 1 + 32 + disclosed_count as u32 * 32;
+        let preimage_length = (key_count - disclosed_count + 1) * 32;
+        let total_blocks = preimage_length / HASH_R + 1;
+        (preimage_ptr, preimage_ptr + (total_blocks * HASH_R) as u32)
+    }
+
+    // the codes follow the keys in mem, each padded to whole blocks like the preimages of the
+    // nodes
+    pub(crate) fn code_mem_offsets(statement: &Statement) -> Vec<u32> {
+        let mut code_ptr = 1 + 32 + statement.account_count() as u32 * 32;
+        statement
+            .codes()
+            .unwrap_or_default()
+            .iter()
+            .map(|bytecode| {
+                let ptr = code_ptr;
+                code_ptr += ((bytecode.code.len() / HASH_R + 1) * HASH_R) as u32;
+                ptr
+            })
+            .collect()
+    }
+
+    // the post state root follows the keys in mem, like the pre state root precedes them
+    pub(crate) fn post_root_mem_offset(key_count: usize) -> u32 {
+        1 + 32 + key_count as u32 * 32
+    }
+
+    // the templates of empty branches follow the post state root, one per inserted or deleted
+    // key, which may split or collapse a node; each is padded to a block
+    pub(crate) fn template_mem_offsets(statement: &Statement) -> Vec<(usize, u32)> {
+        let key_count = statement.account_count();
+        let mut template_ptr = MPT::post_root_mem_offset(key_count) + 1 + 32;
+        (0..key_count)
+            .filter(|&i| statement.value_change(i) != ValueChange::Update)
+            .map(|i| {
+                let ptr = template_ptr;
+                template_ptr += HASH_R as u32;
+                (i, ptr)
+            })
+            .collect()
+    }
+
+    pub(crate) fn swap_walk_tables(&mut self) {
+        let post_walk = &mut self.post_walk;
+        std::mem::swap(
+            &mut self.branch_trans_table,
+            &mut post_walk.branch_trans_table,
+        );
+        std::mem::swap(
+            &mut self.branch_trans_count,
+            &mut post_walk.branch_trans_count,
+        );
+        std::mem::swap(
+            &mut self.ext_leaf_trans_table,
+            &mut post_walk.ext_leaf_trans_table,
+        );
+        std::mem::swap(
+            &mut self.ext_leaf_trans_count,
+            &mut post_walk.ext_leaf_trans_count,
+        );
+        std::mem::swap(&mut self.fork_state_table, &mut post_walk.fork_state_table);
+        std::mem::swap(&mut self.fork_state_count, &mut post_walk.fork_state_count);
+        std::mem::swap(
+            &mut self.branch_value_trans_table,
+            &mut post_walk.branch_value_trans_table,
+        );
+        std::mem::swap(
+            &mut self.branch_value_trans_count,
+            &mut post_walk.branch_value_trans_count,
+        );
+    }
+
+    // record which account and node every appended row comes from, used for diagnostics
+    pub(crate) fn track_row_origins(&mut self) {
+        self.row_origins = Some(RowOrigins::default());
+    }
+
+    fn table_row_counts(&self) -> HashMap<TableType, usize> {
+        HashMap::from([
+            (
+                TableType::SkipListHeader,
+                self.skip_list_header_table.row_count(),
+            ),
+            (TableType::KeccakF, self.sponge.keccak_f_table.row_count()),
+            (
+                TableType::AbsorbBlockBase,
+                self.sponge.absorb_block_base_table.row_count(),
+            ),
+            (
+                TableType::AbsorbBlockRecursive,
+                self.sponge.absorb_block_recursive_table.row_count(),
+            ),
+            (TableType::HashTransTable, self.hash_trans_table.row_count()),
+            (
+                TableType::GetChildBase,
+                self.get_child_base_table.row_count(),
+            ),
+            (
+                TableType::GetChildRecursive,
+                self.get_child_recursive_table.row_count(),
+            ),
+            (TableType::BranchTrans, self.branch_trans_table.row_count()),
+            (
+                TableType::BranchValueTrans,
+                self.branch_value_trans_table.row_count(),
+            ),
+            (
+                TableType::ExtLeafTrans,
+                self.ext_leaf_trans_table.row_count(),
+            ),
+            (
+                TableType::CheckNibBase,
+                self.check_nib_base_table.row_count(),
+            ),
+            (
+                TableType::CheckNibRecursive,
+                self.check_nib_recursive_table.row_count(),
+            ),
+            (
+                TableType::ForkStableTable,
+                self.fork_state_table.row_count(),
+            ),
+        ])
+    }
+
+    pub(crate) fn record_row_origin(&mut self, origin: RowOrigin) {
+        if self.row_origins.is_some() {
+            let row_counts = self.table_row_counts();
+            if let Some(row_origins) = &mut self.row_origins {
+                row_origins.snapshots.push((origin, row_counts));
+            }
+        }
+    }
+
+    pub(crate) fn skip_item(&self, ptr: u32) -> u32 {
         let val = self.state.mem[ptr];
-        debug_assert!((128..184).contains(&val));
-        let length = self.state.mem[ptr] - 128;
-        ptr + 1 + length as u32
+        match val {
+            0..=0x7f => ptr + 1,
+            0x80..=0xb7 => ptr + 1 + (val - 0x80) as u32,
+            0xb8..=0xbf => {
+                let len_len = val - 0xb7;
+                let len = self.state.mem[ptr + 1..ptr + 1 + len_len as u32]
+                    .iter()
+                    .fold(0, |len, &byte| len << 8 | byte as u32);
+                ptr + 1 + len_len as u32 + len
+            }
+            _ => self.skip_list(ptr),
+        }
     }
 
     pub(crate) fn increment(key_ptr: NibPtr) -> NibPtr {
@@ -297,6 +1508,18 @@ impl MPT {
         }
     }
 
+    // a child shorter than 32 bytes is inlined into its parent, so the reference the walk
+    // reaches is the list header of the child rather than the 0xa0 header of its hash
+    pub(crate) fn is_inlined(&self, rlp_ptr: u32) -> bool {
+        self.state.mem[rlp_ptr] >= 0xc0
+    }
+
+    // the header of an empty child, of a hash, or of a list shorter than 32 bytes, which is
+    // hashed otherwise
+    pub(crate) fn is_child_reference(val: u8) -> bool {
+        matches!(val, 0x80 | 0xa0 | 0xc0..=0xde)
+    }
+
     pub(crate) fn skip_list(&self, ptr: u32) -> u32 {
         let u8_val = self.state.mem[ptr];
         debug_assert!(192 <= u8_val);
@@ -325,9 +1548,39 @@ pub(crate) struct MPTState {
     pub(crate) state_final_tss: HashMap<u32, B32>,
 }
 
+impl MPTState {
+    pub(crate) fn new() -> Self {
+        Self {
+            mem: Memory::new(),
+            skip_list_header_offset_lookup: SkipListHeaderOffsetLookup::new(),
+            get_child_offset_lookup: GetChildOffsetLookup::new(),
+            branch_trans_shift_lookup: BranchTransShiftLookup::new(),
+            state_final_tss: HashMap::new(),
+        }
+    }
+}
+
 pub(crate) trait FunctionTable {
     type Data;
+    // lower bound on the log height of the table
+    const MIN_N_VARS: usize;
+    fn n_vars(count: usize) -> usize {
+        std::cmp::max(
+            count.next_power_of_two().trailing_zeros() as usize,
+            Self::MIN_N_VARS,
+        )
+    }
+    fn committed_layout() -> Result<CommittedLayout, anyhow::Error>
+    where
+        Self: Sized,
+    {
+        let table_height = TableHeight::new::<Self>(0);
+        CommittedLayout::measure(table_height.n_vars, |builder, channel_ids| {
+            Self::new().build(builder, channel_ids, table_height)
+        })
+    }
     fn new() -> Self;
+    fn row_count(&self) -> usize;
     fn allocate(&mut self, count: usize) -> usize;
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data);
     fn build(
@@ -367,10 +1620,8 @@ pub fn get_statement_and_account_proofs(
     let addr_val_pairs = account_proofs
         .iter()
         .map(|account_proof| {
-            let leaf_node = account_proof.nodes.last().expect("shoudn't be empty");
-            let mut decoded = rlp_decode_node(&mut leaf_node.as_ref());
-            debug_assert_eq!(decoded.len(), 2);
-            let leaf_val_bytes = decoded.pop().expect("msg");
+            let key = keccak256(account_proof.address).0;
+            let leaf_val_bytes = rlp_decode_value(&key, &account_proof.nodes);
             // this is the true 'item', without the rlp encoding
             AddrValPair {
                 address: account_proof.address.to_vec(),
@@ -382,29 +1633,725 @@ pub fn get_statement_and_account_proofs(
     let statement = Statement {
         root_hash,
         addr_val_pairs,
+        mode: StatementMode::Accounts,
     };
 
     (statement, account_proofs)
 }
 
+// the statement of an update, from the proofs of the same accounts under the pre state root and
+// under the post state root
+#[instrument(name = "get_update_statement_and_account_proofs", fields(alloy_account_proof_count = pre_alloy_account_proofs.len()), skip_all, level = "debug")]
+pub fn get_update_statement_and_account_proofs(
+    pre_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    post_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+) -> Result<(Statement, Vec<AccountProof>, Vec<AccountProof>), anyhow::Error> {
+    update_statement_and_account_proofs(pre_alloy_account_proofs, post_alloy_account_proofs, true)
+}
+
+// without checked, the updates are only checked to be of a kind the prover supports, so that
+// the circuit has to catch the ones changing more than the values on its own
+pub(crate) fn update_statement_and_account_proofs(
+    pre_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    post_alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    checked: bool,
+) -> Result<(Statement, Vec<AccountProof>, Vec<AccountProof>), anyhow::Error> {
+    let (mut statement, pre_account_proofs) =
+        get_statement_and_account_proofs(pre_alloy_account_proofs);
+    let (post_statement, post_account_proofs) =
+        get_statement_and_account_proofs(post_alloy_account_proofs);
+    if !statement.addresses().eq(post_statement.addresses()) {
+        return Err(anyhow!(
+            "The pre-state and post-state proofs are not for the same addresses"
+        ));
+    }
+    let distinct_addresses = statement.addresses().collect::<HashSet<_>>().len();
+    if distinct_addresses != pre_account_proofs.len() {
+        return Err(anyhow!("Every address can only be updated once"));
+    }
+    let references = path_references(&pre_account_proofs, &post_account_proofs);
+    let reference = |node: &[u8]| rlp_encode_bytestring(keccak256(node).as_slice());
+    // the post walk pairs the nodes of the post state trie with the ones of the pre state trie,
+    // which an account inserted into or deleted from an empty child of a branch keeps, besides
+    // its leaf; an account inserted where its path parts from the path of a leaf or an
+    // extension splits the node, and deleting it collapses the nodes back, which the walks go
+    // through virtual nodes for
+    let mut changes = vec![];
+    // the nodes of every split or collapse, by account
+    let mut reshaped_nodes = vec![];
+    for (i, (pre_account_proof, post_account_proof)) in
+        izip!(&pre_account_proofs, &post_account_proofs).enumerate()
+    {
+        let address = pre_account_proof.address;
+        let key = keccak256(address).0;
+        let (pre_end, post_end) = (
+            path_end(&key, &pre_account_proof.nodes),
+            path_end(&key, &post_account_proof.nodes),
+        );
+        let change = match (&pre_end, &post_end) {
+            (PathEnd::Value(_), PathEnd::Value(_)) => ValueChange::Update,
+            (PathEnd::EmptyChild | PathEnd::Absent, PathEnd::Value(_)) => ValueChange::Insert,
+            (PathEnd::Value(_), PathEnd::EmptyChild | PathEnd::Absent) => ValueChange::Delete,
+            _ => return Err(anyhow!("Account {address} is absent from both tries")),
+        };
+        // the post walk hashes every node it pairs, which a node inlined into its parent is not
+        if passes_inlined_node(&key, &pre_account_proof.nodes)
+            || passes_inlined_node(&key, &post_account_proof.nodes)
+        {
+            return Err(anyhow!(
+                "The path of account {address} goes through an inlined node, which is not supported"
+            ));
+        }
+        // the proofs of a split or a collapse are checked to fork the way the prover lays out
+        // the virtual nodes, whether checked or not
+        let reshape = match (change, &pre_end, &post_end) {
+            (ValueChange::Insert, PathEnd::Absent, _) => Some((
+                reshape_geometry(&key, &pre_account_proof.nodes, &post_account_proof.nodes)
+                    .ok_or_else(|| {
+                        anyhow!("Inserting account {address} splits a trie node in a way that is not supported")
+                    })?,
+                &pre_account_proof.nodes,
+                &post_account_proof.nodes,
+            )),
+            (ValueChange::Delete, _, PathEnd::Absent) => Some((
+                reshape_geometry(&key, &post_account_proof.nodes, &pre_account_proof.nodes)
+                    .ok_or_else(|| {
+                        anyhow!("Deleting account {address} collapses a trie node in a way that is not supported")
+                    })?,
+                &post_account_proof.nodes,
+                &pre_account_proof.nodes,
+            )),
+            _ => None,
+        };
+        if let Some((geometry, flat_nodes, forked_nodes)) = reshape {
+            let index = geometry.index;
+            // the node parted from, and the extension and the branch in its place
+            let nodes = [&flat_nodes[index]]
+                .into_iter()
+                .chain(&forked_nodes[index..forked_nodes.len() - 1])
+                .cloned()
+                .collect::<Vec<_>>();
+            reshaped_nodes.push((i, nodes));
+            // the nodes above are paired as usual, the reference to the node parted from being
+            // the only item granted to differ besides the references to the next nodes
+            let (pre_nodes, post_nodes) = (
+                &pre_account_proof.nodes[..index],
+                &post_account_proof.nodes[..index],
+            );
+            let reshaped_child = (
+                reference(&pre_account_proof.nodes[index]),
+                reference(&post_account_proof.nodes[index]),
+            );
+            if checked
+                && !differs_in_windows(pre_nodes, post_nodes, &references, Some(&reshaped_child))
+            {
+                return Err(anyhow!(
+                    "The update changes more than the value of account {address}"
+                ));
+            }
+            changes.push(change);
+            continue;
+        }
+        if !checked {
+            changes.push(change);
+            continue;
+        }
+        // the leaf of an inserted or deleted account is the only node of its path left unpaired,
+        // and the child of the last paired node holding it the only item granted to differ
+        let (pre_nodes, post_nodes, leaf, leaf_child) = match change {
+            ValueChange::Update => (
+                pre_account_proof.nodes.as_slice(),
+                post_account_proof.nodes.as_slice(),
+                None,
+                None,
+            ),
+            ValueChange::Insert => {
+                let (leaf, post_nodes) = post_account_proof.nodes.split_last().unwrap();
+                let leaf_child = (vec![0x80], reference(leaf));
+                (
+                    pre_account_proof.nodes.as_slice(),
+                    post_nodes,
+                    Some(leaf),
+                    Some(leaf_child),
+                )
+            }
+            ValueChange::Delete => {
+                let (leaf, pre_nodes) = pre_account_proof.nodes.split_last().unwrap();
+                let leaf_child = (reference(leaf), vec![0x80]);
+                (
+                    pre_nodes,
+                    post_account_proof.nodes.as_slice(),
+                    Some(leaf),
+                    Some(leaf_child),
+                )
+            }
+        };
+        let node_kinds =
+            |nodes: &[Vec<u8>]| nodes.iter().map(|node| node_kind(node)).collect::<Vec<_>>();
+        if node_kinds(pre_nodes) != node_kinds(post_nodes)
+            || leaf.is_some_and(|leaf| !is_leaf(leaf))
+        {
+            return Err(anyhow!(
+                "The update changes the shape of the trie along the path of account {address}"
+            ));
+        }
+        if !differs_in_windows(pre_nodes, post_nodes, &references, leaf_child.as_ref()) {
+            return Err(anyhow!(
+                "The update changes more than the value of account {address}"
+            ));
+        }
+        changes.push(change);
+    }
+    // the nodes a split or a collapse leaves or takes away are only walked by the sibling key
+    for (i, nodes) in reshaped_nodes {
+        let on_other_path = izip!(&pre_account_proofs, &post_account_proofs)
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .any(|(_, (pre_account_proof, post_account_proof))| {
+                pre_account_proof
+                    .nodes
+                    .iter()
+                    .chain(&post_account_proof.nodes)
+                    .any(|node| nodes.contains(node))
+            });
+        if on_other_path {
+            let address = pre_account_proofs[i].address;
+            return Err(anyhow!(
+                "The trie node account {address} splits or collapses is on the path of another account, which is not supported"
+            ));
+        }
+    }
+
+    let mut values = post_statement
+        .addr_val_pairs
+        .into_iter()
+        .map(|addr_val_pair| addr_val_pair.value)
+        .collect::<Vec<_>>();
+    // the value of an account absent from a trie is left empty
+    for (addr_val_pair, value, change) in
+        izip!(&mut statement.addr_val_pairs, &mut values, &changes)
+    {
+        match change {
+            ValueChange::Update => (),
+            ValueChange::Insert => addr_val_pair.value.clear(),
+            ValueChange::Delete => value.clear(),
+        }
+    }
+    statement.mode = StatementMode::Update(PostState {
+        root_hash: post_statement.root_hash,
+        values,
+        changes,
+    });
+    Ok((statement, pre_account_proofs, post_account_proofs))
+}
+
+// the statement of predicates, from the proofs of the accounts they are on
+#[instrument(name = "get_predicate_statement_and_account_proofs", fields(predicate_count = predicates.len()), skip_all, level = "debug")]
+pub fn get_predicate_statement_and_account_proofs(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    predicates: Vec<AccountPredicate>,
+) -> Result<(Statement, Vec<AccountProof>), anyhow::Error> {
+    if predicates.is_empty() {
+        return Err(anyhow!("Expected at least one predicate"));
+    }
+    let mut alloy_account_proofs = alloy_account_proofs
+        .into_iter()
+        .map(|proof| (proof.address, proof))
+        .collect::<HashMap<_, _>>();
+    // every account has a single predicate, which the account_fields row of the account is
+    // consumed by
+    let alloy_account_proofs = predicates
+        .iter()
+        .map(|predicate| {
+            let address = predicate.address();
+            alloy_account_proofs.remove(&address).ok_or_else(|| {
+                anyhow!("No proof for account {address}, or more than one predicate on it")
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let (mut statement, account_proofs) = get_statement_and_account_proofs(alloy_account_proofs);
+    for (addr_val_pair, predicate) in izip!(&mut statement.addr_val_pairs, &predicates) {
+        let fields = AccountFields::decode(&addr_val_pair.address, &addr_val_pair.value)?;
+        if !predicate.holds(&fields) {
+            return Err(anyhow!("{predicate:?} does not hold"));
+        }
+        addr_val_pair.value.clear();
+    }
+    statement.mode = StatementMode::Predicates(predicates);
+    Ok((statement, account_proofs))
+}
+
+// the statement disclosing the accounts of the given addresses, and the keys of the hidden
+// accounts, whose proofs are sorted by key after the proofs of the disclosed ones
+#[instrument(name = "get_hidden_statement_and_account_proofs", fields(alloy_account_proof_count = alloy_account_proofs.len()), skip_all, level = "debug")]
+pub fn get_hidden_statement_and_account_proofs(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    disclosed: &[Address],
+    salt: &Hash,
+) -> Result<(Statement, Vec<AccountProof>, Vec<Hash>), anyhow::Error> {
+    let mut alloy_account_proofs = alloy_account_proofs
+        .into_iter()
+        .map(|proof| (proof.address, proof))
+        .collect::<HashMap<_, _>>();
+    let mut ordered_proofs = disclosed
+        .iter()
+        .map(|address| {
+            alloy_account_proofs.remove(address).ok_or_else(|| {
+                anyhow!("No proof for account {address}, or it is disclosed more than once")
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut hidden_proofs = alloy_account_proofs
+        .into_values()
+        .map(|proof| (keccak256(proof.address).0, proof))
+        .collect::<Vec<_>>();
+    hidden_proofs.sort_by_key(|(key, _)| *key);
+    let (hidden_keys, hidden_proofs): (Vec<_>, Vec<_>) = hidden_proofs.into_iter().unzip();
+    // the commitment is absorbed by absorb_block, which counts the blocks left in a byte
+    let (preimage_ptr, commitment_ptr) =
+        MPT::commitment_mem_offsets(disclosed.len(), disclosed.len() + hidden_keys.len());
+    if (commitment_ptr - preimage_ptr) as usize / HASH_R > u8::MAX as usize {
+        return Err(anyhow!("Too many hidden accounts: {}", hidden_keys.len()));
+    }
+    ordered_proofs.extend(hidden_proofs);
+
+    let (mut statement, account_proofs) = get_statement_and_account_proofs(ordered_proofs);
+    statement.addr_val_pairs.truncate(disclosed.len());
+    statement.mode = StatementMode::Hidden(HiddenAccounts {
+        commitment: keys_commitment(&hidden_keys, salt),
+        count: hidden_keys.len(),
+    });
+    Ok((statement, account_proofs, hidden_keys))
+}
+
+// the statement of the sum of the balances of the accounts of the proofs
+#[instrument(name = "get_balance_sum_statement_and_account_proofs", fields(alloy_account_proof_count = alloy_account_proofs.len()), skip_all, level = "debug")]
+pub fn get_balance_sum_statement_and_account_proofs(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+) -> Result<(Statement, Vec<AccountProof>), anyhow::Error> {
+    let (mut statement, account_proofs) = get_statement_and_account_proofs(alloy_account_proofs);
+    let mut balance_sum = U256::ZERO;
+    for addr_val_pair in &mut statement.addr_val_pairs {
+        let fields = AccountFields::decode(&addr_val_pair.address, &addr_val_pair.value)?;
+        balance_sum = balance_sum
+            .checked_add(fields.balance)
+            .ok_or_else(|| anyhow!("The sum of the balances overflows"))?;
+        addr_val_pair.value.clear();
+    }
+    statement.mode = StatementMode::BalanceSum(balance_sum);
+    Ok((statement, account_proofs))
+}
+
+// the statement of the accounts of the proofs and of the code of some of them, which must
+// hash to their code hash
+#[instrument(name = "get_bytecode_statement_and_account_proofs", fields(code_count = codes.len()), skip_all, level = "debug")]
+pub fn get_bytecode_statement_and_account_proofs(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    codes: Vec<(Address, Vec<u8>)>,
+) -> Result<(Statement, Vec<AccountProof>), anyhow::Error> {
+    if codes.is_empty() {
+        return Err(anyhow!("Expected at least one code"));
+    }
+    let (mut statement, account_proofs) = get_statement_and_account_proofs(alloy_account_proofs);
+    let codes = codes
+        .into_iter()
+        .map(|(address, code)| {
+            let account = statement
+                .addresses()
+                .position(|proven| proven == address.as_slice())
+                .ok_or_else(|| anyhow!("No proof for account {address}"))?;
+            let fields = AccountFields::decode(
+                address.as_slice(),
+                &statement.addr_val_pairs[account].value,
+            )?;
+            if keccak256(&code).0 != fields.code_hash {
+                return Err(anyhow!(
+                    "The code of account {address} does not match its code hash"
+                ));
+            }
+            // the code is absorbed by absorb_block, which counts the blocks left in a byte
+            if code.len() / HASH_R + 1 > u8::MAX as usize {
+                return Err(anyhow!(
+                    "The code of account {address} is too long: {} bytes",
+                    code.len()
+                ));
+            }
+            Ok(Bytecode { account, code })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    statement.mode = StatementMode::Bytecode(codes);
+    Ok((statement, account_proofs))
+}
+
 // rlp stuff
-fn rlp_decode_node(node: &mut &[u8]) -> Vec<Vec<u8>> {
-    let bytes_list = Vec::<Bytes>::decode(node).unwrap();
-    let bytes_list = bytes_list.iter().map(|bytes| bytes.to_vec()).collect();
-    bytes_list
-}
-
-fn rlp_encode_long_bytestring(bytestring: &[u8]) -> Vec<u8> {
-    let length = bytestring.len();
-    // convert the usize to bytes
-    let bytes = length.to_le_bytes();
-    // trim leading zeros
-    let ell_prime = bytes.iter().rev().skip_while(|&&b| b == 0).count() as u8;
-
-    let mut rlp_bytes = vec![ell_prime + 183];
-    (0..ell_prime).for_each(|i| {
-        rlp_bytes.push(bytes[i as usize]);
+// the encoded items of an rlp list, which are lists themselves for children inlined for being
+// shorter than 32 bytes
+fn rlp_items(node: &[u8]) -> Vec<&[u8]> {
+    let mut payload = node;
+    let header = alloy::rlp::Header::decode(&mut payload).unwrap();
+    assert!(header.list);
+    payload = &payload[..header.payload_length];
+    let mut items = vec![];
+    while !payload.is_empty() {
+        let mut rest = payload;
+        // single bytes below 0x80 are their own payload, so the header consumes nothing
+        let item = alloy::rlp::Header::decode(&mut rest).unwrap();
+        let (encoded_item, remaining) =
+            payload.split_at(payload.len() - rest.len() + item.payload_length);
+        items.push(encoded_item);
+        payload = remaining;
+    }
+    items
+}
+
+// where the nodes of a proof lead a key, following the nodes inlined into them
+#[derive(Debug, PartialEq, Eq)]
+enum PathEnd<'a> {
+    // the encoded value, of a leaf or of a branch the key is exhausted at
+    Value(&'a [u8]),
+    // an empty child of the last node of the proof, a branch listed in it
+    EmptyChild,
+    // any other proof of absence: a path the key diverges from, an empty child of an inlined
+    // branch, or an empty value of a branch the key is exhausted at
+    Absent,
+}
+
+fn path_end<'a>(key: &Hash, nodes: &'a [Vec<u8>]) -> PathEnd<'a> {
+    walk_path(key, nodes).0
+}
+
+// whether the path of key goes through a node inlined into its parent
+fn passes_inlined_node(key: &Hash, nodes: &[Vec<u8>]) -> bool {
+    walk_path(key, nodes).1
+}
+
+fn walk_path<'a>(key: &Hash, nodes: &'a [Vec<u8>]) -> (PathEnd<'a>, bool) {
+    let nibbles = key
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xf])
+        .collect::<Vec<_>>();
+    let mut path = nibbles.as_slice();
+    let mut nodes = nodes.iter().map(Vec::as_slice);
+    let Some(mut node) = nodes.next() else {
+        return (PathEnd::Absent, false);
+    };
+    let mut inlined = false;
+    let mut passes_inlined = false;
+    let end = loop {
+        let items = rlp_items(node);
+        let child = match node_kind(node) {
+            NodeKind::Branch => match path.split_first() {
+                // the value of a branch the key is exhausted at
+                None if items[16] == [0x80] => break PathEnd::Absent,
+                None => break PathEnd::Value(items[16]),
+                Some((&nibble, rest)) => {
+                    path = rest;
+                    items[nibble as usize]
+                }
+            },
+            NodeKind::ExtLeaf => {
+                let (node_path, leaf) = hex_prefix_path(items[0]);
+                let Some(rest) = path.strip_prefix(node_path.as_slice()) else {
+                    break PathEnd::Absent;
+                };
+                path = rest;
+                if leaf {
+                    break match path.is_empty() {
+                        true => PathEnd::Value(items[1]),
+                        false => PathEnd::Absent,
+                    };
+                }
+                items[1]
+            }
+        };
+        (node, inlined) = match child[0] {
+            0x80 if !inlined && nodes.next().is_none() => break PathEnd::EmptyChild,
+            0x80 => break PathEnd::Absent,
+            // inlined children are not listed in the proof
+            0xc0..=0xff => (child, true),
+            _ => match nodes.next() {
+                Some(node) => (node, false),
+                None => break PathEnd::Absent,
+            },
+        };
+        passes_inlined |= inlined;
+    };
+    (end, passes_inlined)
+}
+
+// the encoded value the nodes of a proof lead key to, or None if they prove it absent
+fn proven_value<'a>(key: &Hash, nodes: &'a [Vec<u8>]) -> Option<&'a [u8]> {
+    match path_end(key, nodes) {
+        PathEnd::Value(value) => Some(value),
+        PathEnd::EmptyChild | PathEnd::Absent => None,
+    }
+}
+
+// the nibbles of the hex prefix encoded path of an extension or a leaf, and whether it is the
+// path of a leaf, which the flag nibble tells apart along with odd lengths
+fn hex_prefix_path(mut encoded_path: &[u8]) -> (Vec<u8>, bool) {
+    let compact_path = Bytes::decode(&mut encoded_path).unwrap();
+    let flag = compact_path[0] >> 4;
+    let mut node_path = match flag & 1 {
+        1 => vec![compact_path[0] & 0xf],
+        _ => vec![],
+    };
+    node_path.extend(
+        compact_path[1..]
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0xf]),
+    );
+    (node_path, flag & 2 == 2)
+}
+
+// the hex prefix encoding of the nibbles of the path of an extension or a leaf
+fn hex_prefix_encode(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let odd = nibbles.len() % 2 == 1;
+    let flag = 2 * u8::from(leaf) + u8::from(odd);
+    let (first, rest) = match odd {
+        true => (flag << 4 | nibbles[0], &nibbles[1..]),
+        false => (flag << 4, nibbles),
+    };
+    [first]
+        .into_iter()
+        .chain(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]))
+        .collect()
+}
+
+// an extension or a leaf with the given path and last item, already encoded
+fn short_node(nibbles: &[u8], leaf: bool, item: &[u8]) -> Vec<u8> {
+    let path = rlp_encode_bytestring(&hex_prefix_encode(nibbles, leaf));
+    let mut node = vec![];
+    alloy::rlp::Header {
+        list: true,
+        payload_length: path.len() + item.len(),
+    }
+    .encode(&mut node);
+    node.extend(path);
+    node.extend(item);
+    node
+}
+
+// where the path of a key parts from the path of the last node of the proof of the trie without
+// it, the flat proof, and the nodes the proof of the trie with it, the forked proof, holds in its
+// place: an extension over the nibbles both paths share, if any, and a branch holding the leaf of
+// the key and, at the next nibble of the path parted from, the rest of the node
+#[derive(Debug)]
+struct ReshapeGeometry {
+    // the index of the node parted from in the flat proof
+    index: usize,
+    // the nibbles of the key walked before it
+    depth: usize,
+    path: Vec<u8>,
+    // the nibbles of the path the key shares
+    common: usize,
+    // the node left with the rest of the path, or None if the branch holds the child of an
+    // extension parted from at its last nibble
+    sibling: Option<Vec<u8>>,
+}
+
+// the geometry of a split or a collapse, or None if the proofs do not fork the way the prover
+// lays out the virtual nodes for, every node but the leaf of the key being listed
+fn reshape_geometry(
+    key: &Hash,
+    flat_nodes: &[Vec<u8>],
+    forked_nodes: &[Vec<u8>],
+) -> Option<ReshapeGeometry> {
+    let reference = |node: &[u8]| rlp_encode_bytestring(keccak256(node).as_slice());
+    let nibbles = key
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xf])
+        .collect::<Vec<_>>();
+    let (flat_node, upper_nodes) = flat_nodes.split_last()?;
+    let mut depth = 0;
+    for (i, node) in upper_nodes.iter().enumerate() {
+        let items = rlp_items(node);
+        let child = match node_kind(node) {
+            NodeKind::Branch => {
+                let nibble = *nibbles.get(depth)?;
+                depth += 1;
+                items[nibble as usize]
+            }
+            NodeKind::ExtLeaf => {
+                let (path, leaf) = hex_prefix_path(items[0]);
+                if leaf || !nibbles[depth..].starts_with(&path) {
+                    return None;
+                }
+                depth += path.len();
+                items[1]
+            }
+        };
+        if child != reference(&flat_nodes[i + 1]) {
+            return None;
+        }
+    }
+    if node_kind(flat_node) != NodeKind::ExtLeaf {
+        return None;
+    }
+    let flat_items = rlp_items(flat_node);
+    let (path, leaf) = hex_prefix_path(flat_items[0]);
+    let common = izip!(&path, &nibbles[depth..])
+        .take_while(|(nibble, key_nibble)| nibble == key_nibble)
+        .count();
+    // the child of an extension is compared to the one the branch holds, as a reference
+    if common == path.len() || (!leaf && flat_items[1].len() != HASH_REF_LEN as usize) {
+        return None;
+    }
+    let index = upper_nodes.len();
+    let node_kinds =
+        |nodes: &[Vec<u8>]| nodes.iter().map(|node| node_kind(node)).collect::<Vec<_>>();
+    if forked_nodes.len() != index + usize::from(common > 0) + 2
+        || node_kinds(upper_nodes) != node_kinds(&forked_nodes[..index])
+    {
+        return None;
+    }
+    let [branch, key_leaf] = &forked_nodes[forked_nodes.len() - 2..] else {
+        unreachable!()
+    };
+    let (key_nibble, path_nibble) = (*nibbles.get(depth + common)?, path[common]);
+    if common > 0 {
+        let extension = &forked_nodes[index];
+        if node_kind(extension) != NodeKind::ExtLeaf {
+            return None;
+        }
+        let items = rlp_items(extension);
+        if hex_prefix_path(items[0]) != (path[..common].to_vec(), false)
+            || items[1] != reference(branch)
+        {
+            return None;
+        }
+    }
+    if node_kind(branch) != NodeKind::Branch
+        || !is_leaf(key_leaf)
+        || hex_prefix_path(rlp_items(key_leaf)[0]).0 != nibbles[depth + common + 1..]
+    {
+        return None;
+    }
+    let sibling = (leaf || path.len() > common + 1)
+        .then(|| short_node(&path[common + 1..], leaf, flat_items[1]));
+    let sibling_child = match &sibling {
+        Some(sibling) => reference(sibling),
+        None => flat_items[1].to_vec(),
+    };
+    let branch_items = rlp_items(branch);
+    let forks = branch_items.iter().enumerate().all(|(j, item)| match j {
+        _ if j == key_nibble as usize => *item == reference(key_leaf),
+        _ if j == path_nibble as usize => *item == sibling_child,
+        _ => *item == [0x80],
     });
-    rlp_bytes.extend(bytestring);
+    forks.then_some(ReshapeGeometry {
+        index,
+        depth,
+        path,
+        common,
+        sibling,
+    })
+}
+
+// whether the node is a leaf, rather than a branch or an extension
+fn is_leaf(node: &[u8]) -> bool {
+    node_kind(node) == NodeKind::ExtLeaf && hex_prefix_path(rlp_items(node)[0]).1
+}
+
+// the pairs of references to the pre and post nodes at the same depth of the path of an account,
+// which node_diff grants as windows to every node holding them
+fn path_references(
+    pre_account_proofs: &[AccountProof],
+    post_account_proofs: &[AccountProof],
+) -> HashSet<(Vec<u8>, Vec<u8>)> {
+    let reference = |node: &[u8]| rlp_encode_bytestring(keccak256(node).as_slice());
+    izip!(pre_account_proofs, post_account_proofs)
+        .flat_map(|(pre_account_proof, post_account_proof)| {
+            izip!(
+                &pre_account_proof.nodes[1..],
+                &post_account_proof.nodes[1..]
+            )
+            .map(|(pre_node, post_node)| (reference(pre_node), reference(post_node)))
+        })
+        .collect()
+}
+
+// whether the post nodes differ from the pre nodes only in the windows node_diff grants: the
+// references to the next nodes of any updated path, and the value the last node ends at, or for
+// an inserted or deleted account the child of the last node holding its leaf
+fn differs_in_windows(
+    pre_nodes: &[Vec<u8>],
+    post_nodes: &[Vec<u8>],
+    references: &HashSet<(Vec<u8>, Vec<u8>)>,
+    leaf_child: Option<&(Vec<u8>, Vec<u8>)>,
+) -> bool {
+    pre_nodes.len() == post_nodes.len()
+        && izip!(pre_nodes, post_nodes)
+            .enumerate()
+            .all(|(i, (pre_node, post_node))| {
+                let pre_items = rlp_items(pre_node);
+                let post_items = rlp_items(post_node);
+                pre_items.len() == post_items.len()
+                    && izip!(&pre_items, &post_items).enumerate().all(
+                        |(j, (pre_item, post_item))| {
+                            pre_item == post_item
+                                || references.contains(&(pre_item.to_vec(), post_item.to_vec()))
+                                || (i + 1 == pre_nodes.len()
+                                    && match leaf_child {
+                                        None => j + 1 == pre_items.len(),
+                                        Some((pre_child, post_child)) => {
+                                            **pre_item == pre_child[..]
+                                                && **post_item == post_child[..]
+                                        }
+                                    })
+                        },
+                    )
+            })
+}
+
+pub(crate) fn node_kind(node: &[u8]) -> NodeKind {
+    match rlp_items(node).len() {
+        2 => NodeKind::ExtLeaf,
+        17 => NodeKind::Branch,
+        _ => unreachable!(),
+    }
+}
+
+// the value a key ends at is the last item, of a leaf or of a branch the key is exhausted at,
+// which is inlined into the last node of the proof when it is shorter than 32 bytes
+fn rlp_decode_value(key: &Hash, nodes: &[Vec<u8>]) -> Vec<u8> {
+    let mut value_item = match proven_value(key, nodes) {
+        Some(value_item) => value_item,
+        None => {
+            let last_node = nodes.last().expect("shoudn't be empty");
+            *rlp_items(last_node).last().expect("nodes are not empty")
+        }
+    };
+    Bytes::decode(&mut value_item).unwrap().to_vec()
+}
+
+// the payloads of the nonce, balance, storage root and code hash of an account value, laid out
+// the way account_fields decodes it: a list with a single length byte, two strings of up to 55
+// bytes and two hashes
+fn account_field_ranges(value: &[u8]) -> Result<[Range<usize>; 4], anyhow::Error> {
+    let not_an_account = || anyhow!("Value is not an account");
+    // the string header of the value is 0xb8 for these lengths
+    if !(56..256).contains(&value.len()) || value[0] != 0xf8 || value[1] as usize + 2 != value.len()
+    {
+        return Err(not_an_account());
+    }
+    let item = |item_ptr: usize| match value.get(item_ptr).copied() {
+        Some(0..=0x7f) => Ok(item_ptr..item_ptr + 1),
+        Some(header @ 0x80..=0xb7) => Ok(item_ptr + 1..item_ptr + 1 + (header - 0x80) as usize),
+        _ => Err(not_an_account()),
+    };
+    let nonce = item(2)?;
+    let balance = item(nonce.end)?;
+    let storage_root = item(balance.end)?;
+    let code_hash = item(storage_root.end)?;
+    if storage_root.len() != 32 || code_hash.len() != 32 || code_hash.end != value.len() {
+        return Err(not_an_account());
+    }
+    Ok([nonce, balance, storage_root, code_hash])
+}
+
+fn rlp_encode_bytestring(bytestring: &[u8]) -> Vec<u8> {
+    let mut rlp_bytes = vec![];
+    alloy::rlp::Encodable::encode(bytestring, &mut rlp_bytes);
     rlp_bytes
 }
@@ -2,30 +2,250 @@
 
 use super::*;
 
+type TransFunc = fn(&MPT, &mut Vec<Row>, State) -> State;
+
+enum TraceStep {
+    // the transition was walked by an earlier key, which leaves nothing to append
+    Skipped(State),
+    // the rows of the node and of the transition out of it follow each other in the rows of the
+    // walk, with the timestamps of their states set when they are appended
+    Walked {
+        position: u32,
+        new_state: State,
+        node_rows: usize,
+        trans_rows: usize,
+    },
+    // the rows of node_delete, which the post walk of a deleted key ends with
+    Deleted {
+        node_rows: usize,
+    },
+    // the rows of the walk of the sibling key of a split or a collapse, which follow the walk of
+    // the key from a fork of the node at position, or of the node parted from if None, with the
+    // timestamps of its states all 1
+    Sibling {
+        position: Option<u32>,
+        node_rows: usize,
+        trans_rows: usize,
+    },
+}
+
+impl TraceStep {
+    fn new_state(&self) -> Option<State> {
+        match self {
+            TraceStep::Skipped(new_state) | TraceStep::Walked { new_state, .. } => Some(*new_state),
+            TraceStep::Deleted { .. } | TraceStep::Sibling { .. } => None,
+        }
+    }
+}
+
+// a step of the walk of a key, before the walks are merged in the order of the keys; the rows of
+// the hashing of the node and of the transition out of it come with the step that records them
+enum SubtreeStep {
+    Skipped(State),
+    // the node is hashed by the first key to reach it, hash_rows being None for the others,
+    // which fork it instead once merged
+    Walked {
+        transition: (u32, u8),
+        new_state: State,
+        hash_rows: Option<Vec<Row>>,
+        trans_rows: Vec<Row>,
+    },
+}
+
+// the walk of a key over the subtree it shares with other keys
+struct KeyWalk {
+    key: usize,
+    state: State,
+    steps: Vec<SubtreeStep>,
+}
+
+// the steps of the walk of the key, without the ones of its sibling key
+fn key_steps(steps: &[TraceStep]) -> &[TraceStep] {
+    let sibling_steps = steps
+        .iter()
+        .rev()
+        .take_while(|step| matches!(step, TraceStep::Sibling { .. }))
+        .count();
+    &steps[..steps.len() - sibling_steps]
+}
+
+// the state a walk ends at
+fn end_state(steps: &[TraceStep]) -> State {
+    steps
+        .iter()
+        .rev()
+        .find_map(TraceStep::new_state)
+        .expect("walks start at the root")
+}
+
+impl Row {
+    fn set_ts(&mut self, ts: B32) {
+        match self {
+            Row::HashTrans(HashTransData { state, .. })
+            | Row::BranchTrans(BranchTransData { state, .. })
+            | Row::ExtLeafTrans(ExtLeafTransAdvice { state, .. })
+            | Row::ForkState(ForkStateData { state, .. })
+            | Row::BranchValueTrans(BranchValueTransData { state, .. })
+            | Row::NodeDiff(NodeDiffData { state, .. })
+            | Row::NodeInsert(NodeSlotData { state, .. })
+            | Row::NodeDelete(NodeSlotData { state, .. })
+            | Row::NodeSplit(NodeSplitData { state, .. })
+            | Row::NodeCollapse(NodeSplitData { state, .. }) => state.ts = ts,
+            _ => (),
+        }
+    }
+
+    // node_diff pulls the mid state of the pre node besides its own, and node_insert and
+    // node_delete the state of the pre walk at the child holding the leaf
+    fn set_old_ts(&mut self, ts: B32) {
+        match self {
+            Row::NodeDiff(NodeDiffData { old_state, .. })
+            | Row::NodeInsert(NodeSlotData { old_state, .. })
+            | Row::NodeDelete(NodeSlotData { old_state, .. }) => old_state.ts = ts,
+            _ => (),
+        }
+    }
+}
+
 impl MPT {
     #[instrument(name = "process_account_proofs", fields(account_proof_count = account_proofs.len()), skip_all, level = "debug")]
     pub fn process_account_proofs(&mut self, account_proofs: Vec<AccountProof>) -> Advice {
+        self.session.begin_block();
         self.initialize_prover(account_proofs);
-        let leaf_rlp_ptr_len_pairs = self.get_leaf_rlp_ptr_len_pairs();
+        let leaf_rlp_ptr_len_pairs =
+            self.get_leaf_rlp_ptr_len_pairs(self.proof_data.as_ref().unwrap());
 
-        let mut hashes_visited = HashSet::new();
         let mut nodes_visited = HashMap::new();
 
-        self.process_phase(true, &mut hashes_visited, &mut nodes_visited);
+        let (trace, rows) = self.trace(&mut nodes_visited);
+        let commitment_rows = self.hash_commitment();
+        let code_rows = self.hash_codes();
+        self.count_rows(&commitment_rows);
+        self.count_rows(&code_rows);
         let table_heights = self.allocate_for_function_tables();
-        hashes_visited.clear();
         self.reset_function_table_counters();
         self.simulate_verifier_mem_reading(&leaf_rlp_ptr_len_pairs);
-        self.process_phase(false, &mut hashes_visited, &mut nodes_visited);
+        self.replay(trace, &mut rows.into_iter(), &mut nodes_visited);
+        commitment_rows
+            .into_iter()
+            .for_each(|row| self.append_row(row));
+        // the values of hidden accounts are not decoded
+        let field_ranges =
+            self.decode_account_fields(&leaf_rlp_ptr_len_pairs[..self.disclosed_count()]);
+        self.append_predicates(&leaf_rlp_ptr_len_pairs, &field_ranges);
+        self.append_balance_sum(&leaf_rlp_ptr_len_pairs, &field_ranges);
+        code_rows.into_iter().for_each(|row| self.append_row(row));
         self.populate_lookup_tables();
         let ptr_ts_pairs = self.get_ptr_ts_pairs(&leaf_rlp_ptr_len_pairs);
+        self.session.end_block();
 
         Advice {
             ptr_ts_pairs,
+            post_ptr_ts_pairs: vec![],
             table_heights,
         }
     }
 
+    // like process_account_proofs, followed by a walk of the post state trie whose nodes are
+    // hashed by node_diff rows pairing them with the nodes of the pre state trie
+    #[instrument(name = "process_update_proofs", fields(account_proof_count = pre_account_proofs.len()), skip_all, level = "debug")]
+    pub fn process_update_proofs(
+        &mut self,
+        pre_account_proofs: Vec<AccountProof>,
+        post_account_proofs: Vec<AccountProof>,
+    ) -> Advice {
+        assert!(self.is_update());
+        self.session.begin_block();
+        self.initialize_update_prover(pre_account_proofs, post_account_proofs);
+        let mut leaf_rlp_ptr_len_pairs =
+            self.get_leaf_rlp_ptr_len_pairs(self.proof_data.as_ref().unwrap());
+        let mut post_leaf_rlp_ptr_len_pairs =
+            self.get_leaf_rlp_ptr_len_pairs(self.post_proof_data.as_ref().unwrap());
+
+        let mut nodes_visited = HashMap::new();
+        let mut post_nodes_visited = HashMap::new();
+
+        let (trace, rows) = self.trace(&mut nodes_visited);
+        self.point_at_empty_children(&trace, &mut leaf_rlp_ptr_len_pairs, ValueChange::Insert);
+        // the verifier grants every updated post value to differ from the pre value of the same
+        // key
+        self.diff_windows.value = izip!(&post_leaf_rlp_ptr_len_pairs, &leaf_rlp_ptr_len_pairs)
+            .enumerate()
+            .filter(|&(i, _)| self.statement.value_change(i) == ValueChange::Update)
+            .map(|(_, (&(post_leaf_rlp_ptr, _), &(leaf_rlp_ptr, _)))| {
+                (post_leaf_rlp_ptr, leaf_rlp_ptr)
+            })
+            .collect();
+        self.swap_walk_tables();
+        let (post_trace, post_rows) =
+            self.trace_post_walk(&trace, &mut nodes_visited, &mut post_nodes_visited);
+        self.swap_walk_tables();
+        self.point_at_empty_children(
+            &post_trace,
+            &mut post_leaf_rlp_ptr_len_pairs,
+            ValueChange::Delete,
+        );
+        let table_heights = self.allocate_for_function_tables();
+        self.reset_function_table_counters();
+        self.simulate_verifier_mem_reading(&leaf_rlp_ptr_len_pairs);
+        self.simulate_verifier_post_mem_reading(
+            &leaf_rlp_ptr_len_pairs,
+            &post_leaf_rlp_ptr_len_pairs,
+        );
+        self.replay(trace, &mut rows.into_iter(), &mut nodes_visited);
+        // inserted accounts have no pre value to decode
+        let present_leaf_rlp_ptr_len_pairs = leaf_rlp_ptr_len_pairs
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| self.statement.in_pre_state(i))
+            .map(|(_, &pair)| pair)
+            .collect::<Vec<_>>();
+        self.decode_account_fields(&present_leaf_rlp_ptr_len_pairs);
+        // a value the update leaves in place ends both walks at the same pointer
+        let final_tss = std::mem::take(&mut self.state.state_final_tss);
+        let pre_final_tss = leaf_rlp_ptr_len_pairs
+            .iter()
+            .map(|(leaf_rlp_ptr, _)| final_tss[leaf_rlp_ptr])
+            .collect::<Vec<_>>();
+        self.swap_walk_tables();
+        self.replay_post_walk(
+            post_trace,
+            &mut post_rows.into_iter(),
+            &mut nodes_visited,
+            &mut post_nodes_visited,
+            &pre_final_tss,
+        );
+        self.swap_walk_tables();
+        self.populate_lookup_tables();
+        let post_ptr_ts_pairs = self.get_ptr_ts_pairs(&post_leaf_rlp_ptr_len_pairs);
+        self.state.state_final_tss = final_tss;
+        let ptr_ts_pairs = self.get_ptr_ts_pairs(&leaf_rlp_ptr_len_pairs);
+        self.session.end_block();
+
+        Advice {
+            ptr_ts_pairs,
+            post_ptr_ts_pairs,
+            table_heights,
+        }
+    }
+
+    // the table heights process_account_proofs would allocate, without appending any rows
+    #[instrument(name = "count_table_heights", fields(account_proof_count = account_proofs.len()), skip_all, level = "debug")]
+    pub(crate) fn count_table_heights(
+        &mut self,
+        account_proofs: Vec<AccountProof>,
+    ) -> TableHeights {
+        // the heights only depend on the lengths of the preimages, not on their digests
+        self.session = ProverSession::counting();
+        self.initialize_prover(account_proofs);
+        self.trace(&mut HashMap::new());
+        let commitment_rows = self.hash_commitment();
+        let code_rows = self.hash_codes();
+        self.count_rows(&commitment_rows);
+        self.count_rows(&code_rows);
+        self.table_heights()
+    }
+
     // create proof_data, and insert everything (root hash, keys, and all nodes) into memory
     #[instrument(name = "initialize_prover", skip_all level = "debug")]
     fn initialize_prover(&mut self, account_proofs: Vec<AccountProof>) {
@@ -36,52 +256,273 @@ impl MPT {
         self.state.mem.append(&root_hash_rlp);
         // append keys to mem
         self.keys.iter().for_each(|key| self.state.mem.append(key));
+        // append the salt, its padding and the commitment after the hidden keys
+        if let Some(hidden) = self.statement.hidden() {
+            let salt = self.salt.expect("the prover knows the salt");
+            self.state.mem.append(&salt);
+            let preimage_length = (hidden.count + 1) * 32;
+            self.state
+                .mem
+                .append(&MPT::get_padding_bytes(preimage_length));
+            let mut commitment_rlp = vec![0xa0];
+            commitment_rlp.extend(hidden.commitment);
+            self.state.mem.append(&commitment_rlp);
+        }
+        // append the codes and their padding
+        for bytecode in self.statement.codes().unwrap_or_default() {
+            self.state.mem.append(&bytecode.code);
+            self.state
+                .mem
+                .append(&MPT::get_padding_bytes(bytecode.code.len()));
+        }
         // append all nodes to mem and get proof_data
         self.proof_data = Some(self.setup_proof_data(account_proofs));
         // pad mem
         self.state.mem.pad();
     }
 
+    // like initialize_prover, with the post state root and the templates of the virtual branches
+    // following the keys, the nodes of both tries deduplicated together, and the virtual nodes
+    // of every split and collapse last
+    #[instrument(name = "initialize_update_prover", skip_all level = "debug")]
+    fn initialize_update_prover(
+        &mut self,
+        pre_account_proofs: Vec<AccountProof>,
+        post_account_proofs: Vec<AccountProof>,
+    ) {
+        let key_count = self.keys.len();
+        assert_eq!(pre_account_proofs.len(), key_count);
+        assert_eq!(post_account_proofs.len(), key_count);
+        let mut root_hash_rlp = vec![0xa0];
+        root_hash_rlp.extend(self.statement.root_hash);
+        self.state.mem.append(&root_hash_rlp);
+        self.keys.iter().for_each(|key| self.state.mem.append(key));
+        let mut post_root_hash_rlp = vec![0xa0];
+        post_root_hash_rlp.extend(self.statement.post_state().unwrap().root_hash);
+        self.state.mem.append(&post_root_hash_rlp);
+        let templates = MPT::template_mem_offsets(&self.statement);
+        for _ in &templates {
+            self.state.mem.append(&EMPTY_BRANCH);
+            self.state
+                .mem
+                .append(&MPT::get_padding_bytes(EMPTY_BRANCH.len()));
+        }
+        // the proofs of the tries without and with the key of every split or collapse
+        let geometries = templates
+            .into_iter()
+            .filter_map(|(i, template_ptr)| {
+                let (flat_nodes, forked_nodes) = match self.statement.in_pre_state(i) {
+                    false => (&pre_account_proofs[i].nodes, &post_account_proofs[i].nodes),
+                    true => (&post_account_proofs[i].nodes, &pre_account_proofs[i].nodes),
+                };
+                (path_end(&self.keys[i], flat_nodes) == PathEnd::Absent).then(|| {
+                    let geometry = reshape_geometry(&self.keys[i], flat_nodes, forked_nodes)
+                        .expect("checked by update_statement_and_account_proofs");
+                    (i, template_ptr, geometry)
+                })
+            })
+            .collect::<Vec<_>>();
+        // the post state proofs are listed after the pre state ones
+        let mut proof_data = self.setup_proof_data(
+            pre_account_proofs
+                .into_iter()
+                .chain(post_account_proofs)
+                .collect(),
+        );
+        let mut post_proof_data = (0..key_count)
+            .map(|i| (i, proof_data.remove(&(key_count + i)).unwrap()))
+            .collect::<ProofData>();
+        for (i, template_ptr, geometry) in geometries {
+            let (flat_node_info_list, forked_node_info_list) = match self.statement.in_pre_state(i)
+            {
+                false => (proof_data.get_mut(&i).unwrap(), &post_proof_data[&i]),
+                true => (post_proof_data.get_mut(&i).unwrap(), &proof_data[&i]),
+            };
+            let reshape = self.lay_out_reshape(
+                i,
+                template_ptr,
+                geometry,
+                flat_node_info_list,
+                forked_node_info_list,
+            );
+            self.reshapes.insert(i, reshape);
+        }
+        self.proof_data = Some(proof_data);
+        self.post_proof_data = Some(post_proof_data);
+        self.state.mem.pad();
+    }
+
+    // appends the virtual nodes of a split or a collapse, whose proof of the trie without the key
+    // lists them in place of the node parted from: an extension over the nibbles the paths
+    // share, if any, and the template of an empty branch, followed by the reference to the top
+    // one, the sibling key, and the sibling node
+    fn lay_out_reshape(
+        &mut self,
+        i: usize,
+        template_ptr: u32,
+        geometry: ReshapeGeometry,
+        flat_node_info_list: &mut Vec<NodeInfo>,
+        forked_node_info_list: &[NodeInfo],
+    ) -> Reshape {
+        let ReshapeGeometry {
+            index,
+            depth,
+            path,
+            common,
+            sibling,
+        } = geometry;
+        let reference = |node: &[u8]| rlp_encode_bytestring(keccak256(node).as_slice());
+        let empty_branch: &[u8] = &EMPTY_BRANCH;
+        let nib_ptr = |start_ptr: u32, nibble: usize| NibPtr {
+            byte: start_ptr + nibble as u32 / 2,
+            parity: nibble % 2 == 1,
+        };
+        // the pointer to an item of the node at node_ptr
+        let item_ptr = |mpt: &MPT, node_ptr: u32, item: usize| {
+            let offset = MPT::skip_list_header_offset_lookup(mpt.state.mem[node_ptr]);
+            (0..item).fold(node_ptr + offset as u32, |ptr, _| mpt.skip_item(ptr))
+        };
+
+        let mut virtual_nodes = vec![];
+        let extension = (common > 0).then(|| {
+            let extension = short_node(&path[..common], false, &reference(empty_branch));
+            let extension_ptr = self.state.mem.cursor();
+            self.state.mem.append(&extension);
+            self.state
+                .mem
+                .append(&MPT::get_padding_bytes(extension.len()));
+            virtual_nodes.push((extension, extension_ptr));
+            extension_ptr
+        });
+        let sibling_ptr = sibling.as_ref().map(|sibling| {
+            let sibling_ptr = self.state.mem.cursor();
+            self.state.mem.append(sibling);
+            self.state
+                .mem
+                .append(&MPT::get_padding_bytes(sibling.len()));
+            sibling_ptr
+        });
+        let virtual_ref_ptr = self.state.mem.cursor();
+        let top_node = match virtual_nodes.first() {
+            Some((extension, _)) => extension.as_slice(),
+            None => empty_branch,
+        };
+        self.state.mem.append(&reference(top_node));
+        // the key with the path parted from in place of its nibbles
+        let key = &self.keys[i];
+        let nibbles = key
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0xf])
+            .take(depth)
+            .chain(path.iter().copied())
+            .chain(
+                key.iter()
+                    .flat_map(|byte| [byte >> 4, byte & 0xf])
+                    .skip(depth + path.len()),
+            )
+            .collect::<Vec<_>>();
+        let sibling_key = nibbles
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect::<Vec<_>>();
+        let sibling_start_ptr = self.state.mem.cursor();
+        self.state.mem.append(&sibling_key);
+
+        let mut registered = vec![(empty_branch, template_ptr)];
+        registered.extend(
+            virtual_nodes
+                .iter()
+                .map(|(node, ptr)| (node.as_slice(), *ptr)),
+        );
+        if let (Some(sibling), Some(sibling_ptr)) = (&sibling, sibling_ptr) {
+            registered.push((sibling.as_slice(), sibling_ptr));
+        }
+        self.session.register_nodes(&registered);
+
+        let flat_ptr = flat_node_info_list[index].position;
+        let branch_ptr = forked_node_info_list[forked_node_info_list.len() - 2].position;
+        flat_node_info_list.truncate(index);
+        if let Some(extension_ptr) = extension {
+            flat_node_info_list.push(NodeInfo {
+                kind: NodeKind::ExtLeaf,
+                position: extension_ptr,
+            });
+        }
+        flat_node_info_list.push(NodeInfo {
+            kind: NodeKind::Branch,
+            position: template_ptr,
+        });
+
+        let path_nibble = path[common] as usize;
+        let forked_child_ptr = item_ptr(self, branch_ptr, path_nibble);
+        let flat_tail = item_ptr(self, flat_ptr, 1)..self.skip_list(flat_ptr);
+        let forked_tail = match sibling_ptr {
+            Some(sibling_ptr) => item_ptr(self, sibling_ptr, 1)..self.skip_list(sibling_ptr),
+            None => forked_child_ptr..forked_child_ptr + HASH_REF_LEN,
+        };
+        Reshape {
+            index,
+            flat_ptr,
+            flat_key_ptr: nib_ptr(self.initial_state(i).start_ptr, depth),
+            virtual_ref_ptr,
+            template_ptr,
+            branch_ptr,
+            sibling_start_ptr,
+            sibling_key_ptr: nib_ptr(sibling_start_ptr, depth + path.len()),
+            sibling_ptr,
+            flat_tail,
+            forked_tail,
+            virtual_child_ptr: item_ptr(self, template_ptr, path_nibble),
+            forked_child_ptr,
+        }
+    }
+
     // generate proof_data and append all rlp-encoded nodes into mem
     #[instrument(skip_all, name = "setup_proof_data", level = "debug")]
     fn setup_proof_data(&mut self, account_proofs: Vec<AccountProof>) -> ProofData {
         // a map from nodes to their positions in mem
-        let mut nodes_visited: HashMap<Vec<u8>, u32> = HashMap::new();
-        let mut proof_data = HashMap::new();
+        let mut nodes_visited: HashMap<&[u8], u32> = HashMap::new();
+        let mut new_nodes = vec![];
+        let positions: Vec<Vec<u32>> = account_proofs
+            .iter()
+            .map(|account_proof| {
+                account_proof
+                    .nodes
+                    .iter()
+                    .map(|node_bytes| {
+                        let entry = nodes_visited.entry(node_bytes.as_slice());
+                        // if node visited, return the position; if not get next position by cursor and insert into visited_nodes
+                        *entry.or_insert_with(|| {
+                            let position = self.state.mem.cursor();
+                            self.state.mem.append(node_bytes);
+                            let padding = MPT::get_padding_bytes(node_bytes.len());
+                            self.state.mem.append(&padding);
+                            new_nodes.push((node_bytes.as_slice(), position));
+                            position
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        let node_kinds = self.session.register_nodes(&new_nodes);
 
-        for (i, account_proof) in account_proofs.into_iter().enumerate() {
-            let node_info_list: Vec<NodeInfo> = account_proof
-                .nodes
-                .into_iter()
-                .map(|node_bytes| {
-                    let entry = nodes_visited.entry(node_bytes.clone());
-                    // if node visited, return the position; if not get next position by cursor and insert into visited_nodes
-                    let position = *entry.or_insert_with(|| {
-                        let position = self.state.mem.cursor();
-                        self.state.mem.append(&node_bytes);
-                        let padding = MPT::get_padding_bytes(node_bytes.len());
-                        self.state.mem.append(&padding);
-                        position
-                    });
-                    let node_kind = match rlp_decode_node(&mut node_bytes.as_slice()).len() {
-                        2 => NodeKind::ExtLeaf,
-                        17 => NodeKind::Branch,
-                        _ => unreachable!(),
-                    };
-                    NodeInfo {
-                        bytes: node_bytes,
-                        kind: node_kind,
+        positions
+            .into_iter()
+            .enumerate()
+            .map(|(i, positions)| {
+                let node_info_list = positions
+                    .into_iter()
+                    .map(|position| NodeInfo {
+                        kind: node_kinds[&position],
                         position,
-                    }
-                })
-                .collect();
-
-            proof_data.insert(i, node_info_list);
-        }
-        proof_data
+                    })
+                    .collect();
+                (i, node_info_list)
+            })
+            .collect()
     }
 
-    fn get_padding_bytes(bytes_lenght: usize) -> Vec<u8> {
+    pub(crate) fn get_padding_bytes(bytes_lenght: usize) -> Vec<u8> {
         let total_blocks = bytes_lenght / HASH_R + 1;
         let padding_width = total_blocks * HASH_R - bytes_lenght;
         let mut padding: Vec<u8> = vec![0; padding_width];
@@ -97,211 +538,1148 @@ impl MPT {
         padding
     }
 
-    fn first_phase_trans(
+    // a key exhausted at a branch ends at the branch's value instead of one of its children
+    fn trans_func(kind: NodeKind, state: &State) -> TransFunc {
+        match kind {
+            NodeKind::Branch if state.key_ptr == NibPtr::new(state.start_ptr + 32, false) => {
+                MPT::branch_value_trans
+            }
+            NodeKind::Branch => MPT::branch_trans,
+            NodeKind::ExtLeaf => MPT::ext_leaf_trans,
+        }
+    }
+
+    // the child the transition out of a node goes to: the nibble of the key at a branch, or
+    // the value of a branch the key is exhausted at; other nodes have a single transition
+    fn child_index(&self, kind: NodeKind, state: &State) -> u8 {
+        match kind {
+            NodeKind::Branch if state.key_ptr == NibPtr::new(state.start_ptr + 32, false) => {
+                VALUE_INDEX as u8
+            }
+            NodeKind::Branch => {
+                let key_val = self.state.mem[state.key_ptr.byte];
+                match state.key_ptr.parity {
+                    true => key_val & 0x0f,
+                    false => key_val >> 4,
+                }
+            }
+            NodeKind::ExtLeaf => 0,
+        }
+    }
+
+    fn initial_state(&self, i: usize) -> State {
+        let root_mem_offset = 0;
+        let keys_mem_offset = root_mem_offset + 1 + 32;
+        let start_ptr = keys_mem_offset + i as u32 * 32;
+        State {
+            start_ptr,
+            key_ptr: NibPtr {
+                byte: start_ptr,
+                parity: false,
+            },
+            rlp_ptr: root_mem_offset,
+            ts: B32::ONE,
+        }
+    }
+
+    fn post_initial_state(&self, i: usize) -> State {
+        State {
+            rlp_ptr: MPT::post_root_mem_offset(self.keys.len()),
+            ..self.initial_state(i)
+        }
+    }
+
+    // walks every key once, recording the rows that will be appended for every node and the
+    // transition out of it, unless an earlier key walked the transition already; the first key
+    // to reach a node hashes it while later keys fork it. The keys reaching a node are walked
+    // through it together, and split by the child their walks go on to, whose subtrees share no
+    // node and are walked in parallel; every node is then hashed and every transition walked
+    // once, by the first key in order to reach it, so merging the walks in the order of the keys
+    // gives the rows of a sequential walk
+    #[instrument(skip_all, name = "trace", level = "debug")]
+    fn trace(
         &mut self,
-        trans_func: fn(&mut MPT, Action, State) -> State,
-        node_info: &NodeInfo,
-        state: State,
-        hashes_visited: &mut HashSet<u32>,
         nodes_visited: &mut HashMap<u32, State>,
-    ) -> State {
-        let mid_state = self.hash_trans(Action::Ignore, node_info.position, state);
-        let new_state = trans_func(self, Action::Ignore, mid_state);
-        if hashes_visited.contains(&new_state.rlp_ptr) {
-            return new_state;
+    ) -> (Vec<Vec<TraceStep>>, Vec<Row>) {
+        let walks = self.walk_keys();
+        self.merge_walks(walks, nodes_visited)
+    }
+
+    // the walks of every key, in the order of the keys
+    fn walk_keys(&self) -> Vec<KeyWalk> {
+        if self.keys.is_empty() {
+            return vec![];
+        }
+        let walks = (0..self.keys.len())
+            .map(|i| KeyWalk {
+                key: i,
+                state: self.initial_state(i),
+                steps: vec![],
+            })
+            .collect();
+        let mut walks = self.walk_subtree(walks, 0);
+        walks.sort_by_key(|walk| walk.key);
+        walks
+    }
+
+    // walks keys that reach a node no other key reaches, listed at the given index in their
+    // proofs unless inlined, until their walks part
+    fn walk_subtree(&self, mut walks: Vec<KeyWalk>, mut listed: usize) -> Vec<KeyWalk> {
+        let proof_data = self.proof_data.as_ref().unwrap();
+        loop {
+            let first = &walks[0];
+            let listed_nodes = &proof_data[&first.key];
+            // inlined nodes are not listed in the proof, the walk continues into them
+            let inlined = self.is_inlined(first.state.rlp_ptr);
+            let (position, kind) = match inlined {
+                true => {
+                    let post_node_ptr = self.skip_list(first.state.rlp_ptr);
+                    let kind = node_kind(&self.state.mem[first.state.rlp_ptr..post_node_ptr]);
+                    (first.state.rlp_ptr, kind)
+                }
+                false => match listed_nodes.get(listed) {
+                    Some(node_info) => {
+                        listed += 1;
+                        (node_info.position, node_info.kind)
+                    }
+                    None => return walks,
+                },
+            };
+
+            // an inlined node is pulled right from the state its parent pushed, and the leaf of
+            // a deleted key from the one node_delete pushes
+            let mut hash_rows = vec![];
+            let deleted_leaf =
+                !self.statement.in_post_state(first.key) && listed == listed_nodes.len();
+            if !inlined && !deleted_leaf {
+                self.hash_node(&mut hash_rows, first.key, listed - 1, position, first.state);
+            }
+
+            // the keys going on to the same child, in order
+            let mut children: Vec<(u8, Vec<KeyWalk>)> = vec![];
+            for walk in walks {
+                // hash_trans only moves rlp_ptr to the preimage
+                let mid_state = State {
+                    rlp_ptr: position,
+                    ..walk.state
+                };
+                let child = self.child_index(kind, &mid_state);
+                match children.iter_mut().find(|(index, _)| *index == child) {
+                    Some((_, keys)) => keys.push(walk),
+                    None => children.push((child, vec![walk])),
+                }
+            }
+            if children.len() == 1 {
+                let (child, keys) = children.pop().unwrap();
+                walks = self.walk_transition((position, child), kind, keys, Some(hash_rows));
+                continue;
+            }
+
+            // the first key, which hashed the node, goes on to the first child
+            let mut hash_rows = Some(hash_rows);
+            let children = children
+                .into_iter()
+                .map(|(child, keys)| (child, keys, hash_rows.take()))
+                .collect::<Vec<_>>();
+            return children
+                .into_par_iter()
+                .map(|(child, keys, hash_rows)| {
+                    let walks = self.walk_transition((position, child), kind, keys, hash_rows);
+                    self.walk_subtree(walks, listed)
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flatten()
+                .collect();
         }
-        hashes_visited.insert(new_state.rlp_ptr);
+    }
 
-        if let Some(saved_mid_state) = nodes_visited.get_mut(&mid_state.rlp_ptr) {
-            self.fork_state(Action::Count, *saved_mid_state, state.start_ptr);
-            saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
-        } else {
-            self.hash_trans(Action::Count, node_info.position, state);
-            nodes_visited.insert(mid_state.rlp_ptr, mid_state);
+    // the first of the keys walks the transition, which the others skip
+    fn walk_transition(
+        &self,
+        transition: (u32, u8),
+        kind: NodeKind,
+        mut walks: Vec<KeyWalk>,
+        hash_rows: Option<Vec<Row>>,
+    ) -> Vec<KeyWalk> {
+        let first = &mut walks[0];
+        let mid_state = State {
+            rlp_ptr: transition.0,
+            ..first.state
+        };
+        let mut trans_rows = vec![];
+        let trans_func = MPT::trans_func(kind, &mid_state);
+        let new_state = trans_func(self, &mut trans_rows, mid_state);
+        first.steps.push(SubtreeStep::Walked {
+            transition,
+            new_state,
+            hash_rows,
+            trans_rows,
+        });
+        first.state = new_state;
+        for walk in &mut walks[1..] {
+            walk.state = walk.state.rebase(&new_state);
+            walk.steps.push(SubtreeStep::Skipped(walk.state));
         }
-        trans_func(self, Action::Count, mid_state)
+        walks
     }
 
-    fn second_phase_trans(
+    // the trace of walks of every key in order, forking the nodes an earlier key hashed
+    fn merge_walks(
         &mut self,
-        trans_func: fn(&mut MPT, Action, State) -> State,
-        node_info: &NodeInfo,
-        state: State,
-        hashes_visited: &mut HashSet<u32>,
+        walks: Vec<KeyWalk>,
         nodes_visited: &mut HashMap<u32, State>,
-    ) -> State {
-        // identical to the first block in the first phase, maybe extract out to remove duplication
-        let mut mid_state = self.hash_trans(Action::Ignore, node_info.position, state);
-        let mut new_state = trans_func(self, Action::Ignore, mid_state);
-        if hashes_visited.contains(&new_state.rlp_ptr) {
-            return new_state;
+    ) -> (Vec<Vec<TraceStep>>, Vec<Row>) {
+        let mut trace = Vec::with_capacity(walks.len());
+        let mut rows = vec![];
+        for walk in walks {
+            let mut state = self.initial_state(walk.key);
+            let mut steps = vec![];
+            for step in walk.steps {
+                let (position, new_state, hash_rows, trans_rows) = match step {
+                    SubtreeStep::Skipped(new_state) => {
+                        steps.push(TraceStep::Skipped(new_state));
+                        state = new_state;
+                        continue;
+                    }
+                    SubtreeStep::Walked {
+                        transition: (position, _),
+                        new_state,
+                        hash_rows,
+                        trans_rows,
+                    } => (position, new_state, hash_rows, trans_rows),
+                };
+                let step_start = rows.len();
+                match nodes_visited.get_mut(&position) {
+                    Some(saved_mid_state) => {
+                        debug_assert!(hash_rows.is_none());
+                        self.fork_state(&mut rows, *saved_mid_state, state.start_ptr);
+                        saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+                    }
+                    None => {
+                        rows.extend(hash_rows.expect("the first key to reach a node hashes it"));
+                        let mid_state = State {
+                            rlp_ptr: position,
+                            ..state
+                        };
+                        nodes_visited.insert(position, mid_state);
+                    }
+                }
+                let node_rows = rows.len() - step_start;
+                let trans_rows_len = trans_rows.len();
+                rows.extend(trans_rows);
+                steps.push(TraceStep::Walked {
+                    position,
+                    new_state,
+                    node_rows,
+                    trans_rows: trans_rows_len,
+                });
+                state = new_state;
+            }
+            match self.statement.in_pre_state(walk.key) {
+                true => {
+                    assert_eq!(state.key_ptr.byte, state.start_ptr + 32);
+                    assert!(!state.key_ptr.parity);
+                }
+                // the walk of an inserted key ends at the empty child node_insert reads
+                false => assert_eq!(self.state.mem[state.rlp_ptr], 0x80),
+            }
+            // the sibling key walks the node a split parts from and the virtual branch, and the
+            // branch a collapse takes away
+            if let Some(reshape) = self.reshapes.get(&walk.key) {
+                match self.statement.value_change(walk.key) {
+                    ValueChange::Insert => self.trace_flat_siblings(
+                        &mut rows,
+                        &mut steps,
+                        walk.key,
+                        reshape,
+                        nodes_visited,
+                    ),
+                    _ => self.trace_forked_sibling(
+                        &mut rows,
+                        &mut steps,
+                        reshape,
+                        nodes_visited,
+                        ValueChange::Delete,
+                    ),
+                }
+            }
+            trace.push(steps);
         }
-        hashes_visited.insert(new_state.rlp_ptr);
 
-        let saved_mid_state = nodes_visited.get_mut(&mid_state.rlp_ptr).unwrap();
-        if !State::all_but_ts_eq(saved_mid_state, &mid_state) {
-            self.fork_state(Action::Append, *saved_mid_state, state.start_ptr);
-            saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
-        } else {
-            self.hash_trans(Action::Append, node_info.position, state);
-            let mid_state_ts = mid_state.ts;
-            mid_state.ts *= saved_mid_state.ts;
-            saved_mid_state.ts = mid_state_ts;
+        self.count_rows(&rows);
+        (trace, rows)
+    }
+
+    // the node the walk of a key parts from in a split is hashed by node_split, which moves the
+    // walk to the virtual nodes in its place, listed at the same index
+    fn hash_node(
+        &self,
+        rows: &mut Vec<Row>,
+        key: usize,
+        index: usize,
+        position: u32,
+        state: State,
+    ) {
+        let state = match self.reshapes.get(&key) {
+            Some(reshape)
+                if reshape.index == index
+                    && self.statement.value_change(key) == ValueChange::Insert =>
+            {
+                self.node_split(rows, reshape, state)
+            }
+            _ => state,
+        };
+        self.hash_trans(rows, position, state);
+    }
+
+    // the state of the sibling key at the node the state of the key is at
+    fn sibling_state(reshape: &Reshape, state: &State) -> State {
+        State {
+            start_ptr: reshape.sibling_start_ptr,
+            ts: B32::ONE,
+            ..*state
+        }
+        .rebase(state)
+    }
+
+    // the sibling key forks the node parted from, whose mid state node_split or node_collapse
+    // pushes, and walks it to its last item, then forks the virtual branch to the empty child
+    // paired with the one holding the rest of the node
+    fn trace_flat_siblings(
+        &self,
+        rows: &mut Vec<Row>,
+        steps: &mut Vec<TraceStep>,
+        key: usize,
+        reshape: &Reshape,
+        nodes_visited: &mut HashMap<u32, State>,
+    ) {
+        let flat_state = State {
+            start_ptr: self.initial_state(key).start_ptr,
+            key_ptr: reshape.flat_key_ptr,
+            rlp_ptr: reshape.flat_ptr,
+            ts: B32::ONE,
+        };
+        let step_start = rows.len();
+        self.fork_state(rows, flat_state, reshape.sibling_start_ptr);
+        let node_rows = rows.len() - step_start;
+        let state = self.ext_leaf_trans(rows, MPT::sibling_state(reshape, &flat_state));
+        assert_eq!(state.key_ptr, reshape.sibling_key_ptr);
+        assert_eq!(state.rlp_ptr, reshape.flat_tail.start);
+        steps.push(TraceStep::Sibling {
+            position: None,
+            node_rows,
+            trans_rows: rows.len() - step_start - node_rows,
+        });
+
+        let step_start = rows.len();
+        let saved_mid_state = nodes_visited.get_mut(&reshape.template_ptr).unwrap();
+        self.fork_state(rows, *saved_mid_state, reshape.sibling_start_ptr);
+        saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+        let node_rows = rows.len() - step_start;
+        let state = self.branch_trans(rows, MPT::sibling_state(reshape, saved_mid_state));
+        assert_eq!(state.rlp_ptr, reshape.virtual_child_ptr);
+        steps.push(TraceStep::Sibling {
+            position: Some(reshape.template_ptr),
+            node_rows,
+            trans_rows: rows.len() - step_start - node_rows,
+        });
+    }
+
+    // the sibling key forks the forked branch to the child holding the rest of the node, the
+    // sibling node hashed by node_insert or node_delete and walked to its last item, or the
+    // child of the extension granted by node_graft or node_prune
+    fn trace_forked_sibling(
+        &self,
+        rows: &mut Vec<Row>,
+        steps: &mut Vec<TraceStep>,
+        reshape: &Reshape,
+        nodes_visited: &mut HashMap<u32, State>,
+        change: ValueChange,
+    ) {
+        let step_start = rows.len();
+        let saved_mid_state = nodes_visited.get_mut(&reshape.branch_ptr).unwrap();
+        self.fork_state(rows, *saved_mid_state, reshape.sibling_start_ptr);
+        saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+        let node_rows = rows.len() - step_start;
+        let state = self.branch_trans(rows, MPT::sibling_state(reshape, saved_mid_state));
+        assert_eq!(state.rlp_ptr, reshape.forked_child_ptr);
+        // at the empty child of the virtual branch in the other walk
+        let virtual_state = State {
+            rlp_ptr: reshape.virtual_child_ptr,
+            ..state
+        };
+        match (reshape.sibling_ptr, change) {
+            (Some(sibling_ptr), _) => {
+                let mid_state = match change {
+                    ValueChange::Insert => {
+                        self.node_insert(rows, virtual_state, state, sibling_ptr)
+                    }
+                    _ => {
+                        self.node_delete(rows, state, virtual_state, sibling_ptr);
+                        State {
+                            rlp_ptr: sibling_ptr,
+                            ..state
+                        }
+                    }
+                };
+                let state = self.ext_leaf_trans(rows, mid_state);
+                assert_eq!(state.key_ptr, reshape.sibling_key_ptr);
+                assert_eq!(state.rlp_ptr, reshape.forked_tail.start);
+            }
+            (None, ValueChange::Insert) => self.node_graft(rows, reshape, state),
+            (None, _) => self.node_prune(rows, reshape, state),
         }
-        new_state = trans_func(self, Action::Append, mid_state);
+        steps.push(TraceStep::Sibling {
+            position: Some(reshape.branch_ptr),
+            node_rows,
+            trans_rows: rows.len() - step_start - node_rows,
+        });
+    }
+
+    // walks the keys one after the other, skipping the transitions earlier keys walked and
+    // hashing the nodes none of them visited, which trace must not tell apart from its walks
+    fn walk_keys_in_order(&self) -> Vec<KeyWalk> {
+        let proof_data = self.proof_data.as_ref().unwrap();
+        // the state every transition walked so far leads to, by node and child
+        let mut walked = HashMap::new();
+        let mut nodes_visited = HashSet::new();
+        (0..self.keys.len())
+            .map(|i| {
+                let mut state = self.initial_state(i);
+                let mut steps = vec![];
+                let mut listed_nodes = proof_data[&i].iter();
+                let deleted = !self.statement.in_post_state(i);
+                loop {
+                    let (position, kind) = match self.is_inlined(state.rlp_ptr) {
+                        true => {
+                            let post_node_ptr = self.skip_list(state.rlp_ptr);
+                            let kind = node_kind(&self.state.mem[state.rlp_ptr..post_node_ptr]);
+                            (state.rlp_ptr, kind)
+                        }
+                        false => match listed_nodes.next() {
+                            Some(node_info) => (node_info.position, node_info.kind),
+                            None => break,
+                        },
+                    };
+                    let mid_state = State {
+                        rlp_ptr: position,
+                        ..state
+                    };
+                    let transition = (position, self.child_index(kind, &mid_state));
+                    if let Some(walked_state) = walked.get(&transition) {
+                        state = state.rebase(walked_state);
+                        steps.push(SubtreeStep::Skipped(state));
+                        continue;
+                    }
+
+                    let hash_rows = nodes_visited.insert(position).then(|| {
+                        let mut hash_rows = vec![];
+                        let deleted_leaf = deleted && listed_nodes.len() == 0;
+                        if !self.is_inlined(state.rlp_ptr) && !deleted_leaf {
+                            let index = proof_data[&i].len() - listed_nodes.len() - 1;
+                            self.hash_node(&mut hash_rows, i, index, position, state);
+                        }
+                        hash_rows
+                    });
+                    let mut trans_rows = vec![];
+                    let trans_func = MPT::trans_func(kind, &mid_state);
+                    let new_state = trans_func(self, &mut trans_rows, mid_state);
+                    walked.insert(transition, new_state);
+                    steps.push(SubtreeStep::Walked {
+                        transition,
+                        new_state,
+                        hash_rows,
+                        trans_rows,
+                    });
+                    state = new_state;
+                }
+                KeyWalk {
+                    key: i,
+                    state,
+                    steps,
+                }
+            })
+            .collect()
+    }
 
-        new_state
+    // the rows the walks of the keys append, with their timestamps, walked in parallel like
+    // trace or one key after the other
+    pub(crate) fn traced_rows(
+        &mut self,
+        account_proofs: Vec<AccountProof>,
+        in_order: bool,
+    ) -> Vec<Row> {
+        self.initialize_prover(account_proofs);
+        let mut nodes_visited = HashMap::new();
+        let walks = match in_order {
+            true => self.walk_keys_in_order(),
+            false => self.walk_keys(),
+        };
+        let (trace, rows) = self.merge_walks(walks, &mut nodes_visited);
+        self.traced_rows = Some(vec![]);
+        self.replay(trace, &mut rows.into_iter(), &mut nodes_visited);
+        self.traced_rows.take().unwrap()
     }
 
-    fn process_phase(
+    // appends the recorded rows of every walked transition; once traced, nodes_visited holds
+    // for every node the mid state of its first visit, with the timestamp advanced once per fork
+    #[instrument(skip_all, name = "replay", level = "debug")]
+    fn replay(
         &mut self,
-        first_phase: bool,
-        hashes_visited: &mut HashSet<u32>,
+        trace: Vec<Vec<TraceStep>>,
+        rows: &mut impl Iterator<Item = Row>,
         nodes_visited: &mut HashMap<u32, State>,
     ) {
-        let proof_data = self.proof_data.take().unwrap();
+        for (i, steps) in trace.into_iter().enumerate() {
+            let mut state = self.initial_state(i);
+            let mut node = 0;
+            for step in steps {
+                // the rows of inlined nodes are attributed to the node they are inlined into, and
+                // the ones of the sibling key to the last node of the key
+                let sibling = matches!(step, TraceStep::Sibling { .. });
+                if !self.is_inlined(state.rlp_ptr) && !sibling {
+                    self.record_row_origin(RowOrigin { account: i, node });
+                    node += 1;
+                }
+                state = match step {
+                    // the next node is always forked, so the timestamp is not used
+                    TraceStep::Skipped(new_state) => new_state,
+                    TraceStep::Deleted { .. } => unreachable!("only post walks delete leaves"),
+                    TraceStep::Sibling {
+                        position,
+                        node_rows,
+                        trans_rows,
+                    } => {
+                        self.replay_sibling(rows, nodes_visited, position, node_rows, trans_rows);
+                        state
+                    }
+                    TraceStep::Walked {
+                        position,
+                        new_state,
+                        node_rows,
+                        trans_rows,
+                    } => {
+                        let mut mid_state = State {
+                            rlp_ptr: position,
+                            ..state
+                        };
+                        let saved_mid_state = nodes_visited.get_mut(&position).unwrap();
+                        let node_ts = match State::all_but_ts_eq(saved_mid_state, &mid_state) {
+                            false => {
+                                let fork_ts = saved_mid_state.ts;
+                                saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+                                fork_ts
+                            }
+                            true => {
+                                let mid_state_ts = mid_state.ts;
+                                mid_state.ts *= saved_mid_state.ts;
+                                saved_mid_state.ts = mid_state_ts;
+                                state.ts
+                            }
+                        };
+                        self.append_rows(rows.by_ref().take(node_rows), node_ts);
+                        self.append_rows(rows.by_ref().take(trans_rows), mid_state.ts);
+                        // transitions carry the timestamp of the state over
+                        State {
+                            ts: mid_state.ts,
+                            ..new_state
+                        }
+                    }
+                };
+            }
+            self.state.state_final_tss.insert(state.rlp_ptr, state.ts);
+        }
+    }
 
-        let root_mem_offset = 0;
-        let keys_mem_offset = root_mem_offset + 1 + 32;
+    // walks every key through the post state trie like trace, with node_diff in place of
+    // hash_trans; the nodes of both tries are paired by their index in the proofs, as updates
+    // keep the shape of the trie, besides the leaf of an inserted key, hashed by node_insert,
+    // the leaf of a deleted key, hashed by node_delete once the walk ends, and the nodes a key
+    // splits or collapses, listed in place of virtual ones
+    #[instrument(skip_all, name = "trace_post_walk", level = "debug")]
+    fn trace_post_walk(
+        &mut self,
+        pre_trace: &[Vec<TraceStep>],
+        nodes_visited: &mut HashMap<u32, State>,
+        post_nodes_visited: &mut HashMap<u32, State>,
+    ) -> (Vec<Vec<TraceStep>>, Vec<Row>) {
+        let proof_data = self.proof_data.take().unwrap();
+        let post_proof_data = self.post_proof_data.take().unwrap();
+        let mut walked = HashMap::new();
+        let mut trace = Vec::with_capacity(self.keys.len());
+        let mut rows = vec![];
+        // payloads are compared once the windows of every node are known
+        let mut diffs = vec![];
+        // the last items the walks of the sibling keys end at, old and new
+        let mut tails = vec![];
 
         for i in 0..self.keys.len() {
-            let start_ptr = keys_mem_offset + i as u32 * 32;
-            let key_ptr = NibPtr {
-                byte: start_ptr,
-                parity: false,
-            };
-            let mut state = State {
-                start_ptr,
-                key_ptr,
-                rlp_ptr: root_mem_offset,
-                ts: B32::ONE,
-            };
-
-            for node_info in proof_data.get(&i).unwrap() {
-                let phase = match first_phase {
-                    true => MPT::first_phase_trans,
-                    false => MPT::second_phase_trans,
+            let old_node_info_list = proof_data.get(&i).unwrap();
+            let node_info_list = post_proof_data.get(&i).unwrap();
+            let inserted = !self.statement.in_pre_state(i);
+            let deleted = !self.statement.in_post_state(i);
+            assert_eq!(
+                old_node_info_list.len() + usize::from(inserted),
+                node_info_list.len() + usize::from(deleted),
+                "updates keep the shape of the trie"
+            );
+            let mut state = self.post_initial_state(i);
+            let mut steps = vec![];
+            for (j, node_info) in node_info_list.iter().enumerate() {
+                // get_update_statement_and_account_proofs rejects paths through inlined nodes
+                debug_assert!(!self.is_inlined(state.rlp_ptr));
+                let mid_state = State {
+                    rlp_ptr: node_info.position,
+                    ..state
                 };
-                match node_info.kind {
-                    NodeKind::Branch => {
-                        state = (phase)(
-                            self,
-                            MPT::branch_trans,
-                            node_info,
-                            state,
-                            hashes_visited,
-                            nodes_visited,
+                let transition = (
+                    node_info.position,
+                    self.child_index(node_info.kind, &mid_state),
+                );
+                if let Some(walked_state) = walked.get(&transition) {
+                    let new_state = state.rebase(walked_state);
+                    steps.push(TraceStep::Skipped(new_state));
+                    state = new_state;
+                    continue;
+                }
+
+                let step_start = rows.len();
+                if let Some(saved_mid_state) = post_nodes_visited.get_mut(&node_info.position) {
+                    self.fork_state(&mut rows, *saved_mid_state, state.start_ptr);
+                    saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+                } else {
+                    match old_node_info_list.get(j) {
+                        // node_diff reads the mid state of the pre node like a fork
+                        Some(old_node_info) => {
+                            let old_mid_state = nodes_visited
+                                .get_mut(&old_node_info.position)
+                                .expect("updates keep the shape of the trie");
+                            // the node a collapse parts the key from is hashed by
+                            // node_collapse, the virtual node in its place by node_diff
+                            let diff_state = match self.reshapes.get(&i) {
+                                Some(reshape) if deleted && reshape.index == j => {
+                                    self.node_collapse(&mut rows, reshape, state)
+                                }
+                                _ => state,
+                            };
+                            diffs.push(self.node_diff(
+                                &mut rows,
+                                *old_mid_state,
+                                diff_state,
+                                node_info.position,
+                            ));
+                            old_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+                            // the reference to the node may differ from the one to the pre node
+                            self.diff_windows.hash.insert(state.rlp_ptr);
+                        }
+                        // the leaf of an inserted key, paired with the empty child the pre walk
+                        // ends at
+                        None => {
+                            let old_state = end_state(&pre_trace[i]);
+                            self.node_insert(&mut rows, old_state, state, node_info.position);
+                            // the child the leaf is inserted into differs from the empty one
+                            // of the pre node
+                            self.diff_windows
+                                .slot
+                                .insert(state.rlp_ptr, old_state.rlp_ptr);
+                        }
+                    }
+                    post_nodes_visited.insert(node_info.position, mid_state);
+                }
+                let node_rows = rows.len() - step_start;
+                let trans_func = MPT::trans_func(node_info.kind, &mid_state);
+                let new_state = trans_func(self, &mut rows, mid_state);
+                walked.insert(transition, new_state);
+                steps.push(TraceStep::Walked {
+                    position: node_info.position,
+                    new_state,
+                    node_rows,
+                    trans_rows: rows.len() - step_start - node_rows,
+                });
+                state = new_state;
+            }
+            match deleted {
+                false => {
+                    assert_eq!(state.key_ptr.byte, state.start_ptr + 32);
+                    assert!(!state.key_ptr.parity);
+                }
+                // the walk of a deleted key ends at the empty child, the leaf the pre walk
+                // reached from the same child of the pre node is hashed into it
+                true => {
+                    let pre_steps = key_steps(&pre_trace[i]);
+                    let old_state = end_state(&pre_steps[..pre_steps.len() - 1]);
+                    let leaf_ptr = old_node_info_list.last().unwrap().position;
+                    let step_start = rows.len();
+                    self.node_delete(&mut rows, old_state, state, leaf_ptr);
+                    // the child the leaf is deleted from differs from the empty one of the
+                    // post node
+                    self.diff_windows
+                        .slot
+                        .insert(state.rlp_ptr, old_state.rlp_ptr);
+                    steps.push(TraceStep::Deleted {
+                        node_rows: rows.len() - step_start,
+                    });
+                }
+            }
+            // the sibling key walks the branch a split forks, and the node a collapse parts
+            // from and the virtual branch; the child holding the rest of the node is paired
+            // with the empty one of the virtual branch
+            if let Some(reshape) = self.reshapes.get(&i) {
+                match deleted {
+                    false => {
+                        self.trace_forked_sibling(
+                            &mut rows,
+                            &mut steps,
+                            reshape,
+                            post_nodes_visited,
+                            ValueChange::Insert,
                         );
+                        tails.push((reshape.flat_tail.clone(), reshape.forked_tail.clone()));
                     }
-                    NodeKind::ExtLeaf => {
-                        state = (phase)(
-                            self,
-                            MPT::ext_leaf_trans,
-                            node_info,
-                            state,
-                            hashes_visited,
-                            nodes_visited,
+                    true => {
+                        self.trace_flat_siblings(
+                            &mut rows,
+                            &mut steps,
+                            i,
+                            reshape,
+                            post_nodes_visited,
                         );
+                        tails.push((reshape.forked_tail.clone(), reshape.flat_tail.clone()));
                     }
                 }
             }
-            if !first_phase {
-                self.state.state_final_tss.insert(state.rlp_ptr, state.ts);
-            }
-            assert_eq!(state.key_ptr.byte, start_ptr + 32);
-            assert!(!state.key_ptr.parity);
+            trace.push(steps);
+        }
+        // the children holding the rest of the nodes, paired once every walk is traced
+        for (&i, reshape) in &self.reshapes {
+            let (child_ptr, old_child_ptr) = match self.statement.in_post_state(i) {
+                true => (reshape.forked_child_ptr, reshape.virtual_child_ptr),
+                false => (reshape.virtual_child_ptr, reshape.forked_child_ptr),
+            };
+            self.diff_windows.slot.insert(child_ptr, old_child_ptr);
         }
+        // the rows comparing the payloads follow the ones of the walk
+        diffs
+            .into_iter()
+            .for_each(|ptrs| self.diff_payload(&mut rows, ptrs));
+        tails
+            .into_iter()
+            .for_each(|(old, new)| self.diff_bytes(&mut rows, old, new));
 
+        self.count_rows(&rows);
         self.proof_data = Some(proof_data);
+        self.post_proof_data = Some(post_proof_data);
+        (trace, rows)
+    }
+
+    // appends the rows of the post state walk, after the pre state walk was replayed, and then
+    // the rows comparing the payloads; rows are not traced back to account proofs
+    #[instrument(skip_all, name = "replay_post_walk", level = "debug")]
+    fn replay_post_walk(
+        &mut self,
+        trace: Vec<Vec<TraceStep>>,
+        rows: &mut impl Iterator<Item = Row>,
+        nodes_visited: &mut HashMap<u32, State>,
+        post_nodes_visited: &mut HashMap<u32, State>,
+        pre_final_tss: &[B32],
+    ) {
+        let proof_data = self.proof_data.take().unwrap();
+        for (i, steps) in trace.into_iter().enumerate() {
+            let old_node_info_list = proof_data.get(&i).unwrap();
+            let mut state = self.post_initial_state(i);
+            for (j, step) in steps.into_iter().enumerate() {
+                state = match step {
+                    TraceStep::Skipped(new_state) => new_state,
+                    TraceStep::Sibling {
+                        position,
+                        node_rows,
+                        trans_rows,
+                    } => {
+                        self.replay_sibling(
+                            rows,
+                            post_nodes_visited,
+                            position,
+                            node_rows,
+                            trans_rows,
+                        );
+                        state
+                    }
+                    // node_delete pulls the state the pre walk hashed the deleted leaf at, which
+                    // the first visit of the leaf left in nodes_visited
+                    TraceStep::Deleted { node_rows } => {
+                        let leaf_ptr = old_node_info_list.last().unwrap().position;
+                        let old_ts = nodes_visited[&leaf_ptr].ts;
+                        let node_rows = rows.by_ref().take(node_rows).map(|mut row| {
+                            row.set_old_ts(old_ts);
+                            row
+                        });
+                        self.append_rows(node_rows, state.ts);
+                        state
+                    }
+                    TraceStep::Walked {
+                        position,
+                        new_state,
+                        node_rows,
+                        trans_rows,
+                    } => {
+                        let mut mid_state = State {
+                            rlp_ptr: position,
+                            ..state
+                        };
+                        let saved_mid_state = post_nodes_visited.get_mut(&position).unwrap();
+                        if !State::all_but_ts_eq(saved_mid_state, &mid_state) {
+                            let fork_ts = saved_mid_state.ts;
+                            saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+                            self.append_rows(rows.by_ref().take(node_rows), fork_ts);
+                        } else {
+                            let old_ts = match old_node_info_list.get(j) {
+                                Some(old_node_info) => {
+                                    let old_mid_state =
+                                        nodes_visited.get_mut(&old_node_info.position).unwrap();
+                                    let old_ts = old_mid_state.ts;
+                                    old_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+                                    old_ts
+                                }
+                                // node_insert pulls the final state of the pre walk
+                                None => pre_final_tss[i],
+                            };
+                            let node_rows = rows.by_ref().take(node_rows).map(|mut row| {
+                                row.set_old_ts(old_ts);
+                                row
+                            });
+                            self.append_rows(node_rows, state.ts);
+                            let mid_state_ts = mid_state.ts;
+                            mid_state.ts *= saved_mid_state.ts;
+                            saved_mid_state.ts = mid_state_ts;
+                        }
+                        self.append_rows(rows.by_ref().take(trans_rows), mid_state.ts);
+                        State {
+                            ts: mid_state.ts,
+                            ..new_state
+                        }
+                    }
+                };
+            }
+            self.state.state_final_tss.insert(state.rlp_ptr, state.ts);
+        }
+        rows.for_each(|row| self.append_row(row));
+        self.proof_data = Some(proof_data);
+    }
+
+    // appends the rows of a step of a sibling key, forking the node at position from its saved
+    // mid state, or the node parted from at a timestamp of 1
+    fn replay_sibling(
+        &mut self,
+        rows: &mut impl Iterator<Item = Row>,
+        nodes_visited: &mut HashMap<u32, State>,
+        position: Option<u32>,
+        node_rows: usize,
+        trans_rows: usize,
+    ) {
+        let fork_ts = match position {
+            Some(position) => {
+                let saved_mid_state = nodes_visited.get_mut(&position).unwrap();
+                let fork_ts = saved_mid_state.ts;
+                saved_mid_state.ts *= B32::MULTIPLICATIVE_GENERATOR;
+                fork_ts
+            }
+            None => B32::ONE,
+        };
+        self.append_rows(rows.by_ref().take(node_rows), fork_ts);
+        let trans_rows = rows.by_ref().take(trans_rows).map(|mut row| {
+            row.set_old_ts(B32::ONE);
+            row
+        });
+        self.append_rows(trans_rows, B32::ONE);
+    }
+
+    // appends recorded rows, ts being the timestamp of the state carried by the hashing, fork
+    // or transition row among them
+    fn append_rows(&mut self, rows: impl Iterator<Item = Row>, ts: B32) {
+        for mut row in rows {
+            row.set_ts(ts);
+            match &mut self.traced_rows {
+                Some(traced_rows) => traced_rows.push(row),
+                None => self.append_row(row),
+            }
+        }
+    }
+
+    fn append_row(&mut self, row: Row) {
+        let mpt_state = &mut self.state;
+        match row {
+            Row::SkipListHeader(data) => self.skip_list_header_table.append(mpt_state, data),
+            Row::KeccakF(_) | Row::AbsorbBlockBase(_) | Row::AbsorbBlockRecursive(_) => {
+                self.sponge.append_row(mpt_state, row)
+            }
+            Row::HashTrans(data) => self.hash_trans_table.append(mpt_state, data),
+            Row::GetChildBase(data) => self.get_child_base_table.append(mpt_state, data),
+            Row::GetChildRecursive(data) => self.get_child_recursive_table.append(mpt_state, data),
+            Row::BranchTrans(data) => self.branch_trans_table.append(mpt_state, data),
+            Row::ExtLeafTrans(data) => self.ext_leaf_trans_table.append(mpt_state, data),
+            Row::CheckNibBase(data) => self.check_nib_base_table.append(mpt_state, data),
+            Row::CheckNibRecursive(data) => self.check_nib_recursive_table.append(mpt_state, data),
+            Row::ForkState(data) => self.fork_state_table.append(mpt_state, data),
+            Row::BranchValueTrans(data) => self.branch_value_trans_table.append(mpt_state, data),
+            Row::NodeDiff(data) => self.node_diff_table.append(mpt_state, data),
+            Row::NodeDiffByte(data) => self.node_diff_byte_table.append(mpt_state, data),
+            Row::NodeDiffHash(ptrs) => self.node_diff_hash_table.append(mpt_state, ptrs),
+            Row::NodeDiffValue(ptrs) => self.node_diff_value_table.append(mpt_state, ptrs),
+            Row::NodeDiffSlot(data) => self.node_diff_slot_table.append(mpt_state, data),
+            Row::NodeInsert(data) => self.node_insert_table.append(mpt_state, data),
+            Row::NodeDelete(data) => self.node_delete_table.append(mpt_state, data),
+            Row::NodeSplit(data) => self.node_split_table.append(mpt_state, data),
+            Row::NodeCollapse(data) => self.node_collapse_table.append(mpt_state, data),
+            Row::NodeGraft(data) => self.node_graft_table.append(mpt_state, data),
+            Row::NodePrune(data) => self.node_prune_table.append(mpt_state, data),
+            Row::ListEnd(data) => self.list_end_table.append(mpt_state, data),
+            Row::Bytecode(data) => self.bytecode_table.append(mpt_state, data),
+        }
+    }
+
+    // adds the rows to the table heights
+    fn count_rows(&mut self, rows: &[Row]) {
+        for row in rows {
+            let count = match row {
+                Row::SkipListHeader(_) => &mut self.skip_list_header_count,
+                Row::KeccakF(_) => &mut self.sponge.keccak_f_count,
+                Row::AbsorbBlockBase(_) => &mut self.sponge.absorb_block_base_count,
+                Row::AbsorbBlockRecursive(_) => &mut self.sponge.absorb_block_recursive_count,
+                Row::HashTrans(_) => &mut self.hash_trans_count,
+                Row::GetChildBase(_) => &mut self.get_child_base_count,
+                Row::GetChildRecursive(_) => &mut self.get_child_recursive_count,
+                Row::BranchTrans(_) => &mut self.branch_trans_count,
+                Row::ExtLeafTrans(_) => &mut self.ext_leaf_trans_count,
+                Row::CheckNibBase(_) => &mut self.check_nib_base_count,
+                Row::CheckNibRecursive(_) => &mut self.check_nib_recursive_count,
+                Row::ForkState(_) => &mut self.fork_state_count,
+                Row::BranchValueTrans(_) => &mut self.branch_value_trans_count,
+                Row::NodeDiff(_) => &mut self.node_diff_count,
+                Row::NodeDiffByte(_) => &mut self.node_diff_byte_count,
+                Row::NodeDiffHash(_) => &mut self.node_diff_hash_count,
+                Row::NodeDiffValue(_) => &mut self.node_diff_value_count,
+                Row::NodeDiffSlot(_) => &mut self.node_diff_slot_count,
+                Row::NodeInsert(_) => &mut self.node_insert_count,
+                Row::NodeDelete(_) => &mut self.node_delete_count,
+                Row::NodeSplit(_) => &mut self.node_split_count,
+                Row::NodeCollapse(_) => &mut self.node_collapse_count,
+                Row::NodeGraft(_) => &mut self.node_graft_count,
+                Row::NodePrune(_) => &mut self.node_prune_count,
+                Row::ListEnd(_) => &mut self.list_end_count,
+                // the bytecode table has a row per code of the statement
+                Row::Bytecode(_) => continue,
+            };
+            *count += 1;
+        }
+    }
+
+    fn set_function_table_counters(&mut self, counters: [usize; 13]) {
+        [
+            self.skip_list_header_count,
+            self.sponge.keccak_f_count,
+            self.sponge.absorb_block_base_count,
+            self.sponge.absorb_block_recursive_count,
+            self.hash_trans_count,
+            self.get_child_base_count,
+            self.get_child_recursive_count,
+            self.branch_trans_count,
+            self.ext_leaf_trans_count,
+            self.check_nib_base_count,
+            self.check_nib_recursive_count,
+            self.fork_state_count,
+            self.branch_value_trans_count,
+        ] = counters;
     }
 
     fn reset_function_table_counters(&mut self) {
-        self.skip_list_header_count = 0;
-        self.keccak_f_count = 0;
-        self.absorb_block_base_count = 0;
-        self.absorb_block_recursive_count = 0;
-        self.hash_trans_count = 0;
-        self.get_child_base_count = 0;
-        self.get_child_recursive_count = 0;
-        self.branch_trans_count = 0;
-        self.ext_leaf_trans_count = 0;
-        self.check_nib_base_count = 0;
-        self.check_nib_recursive_count = 0;
-        self.fork_state_count = 0;
+        self.set_function_table_counters([0; 13]);
+        self.post_walk.branch_trans_count = 0;
+        self.post_walk.ext_leaf_trans_count = 0;
+        self.post_walk.fork_state_count = 0;
+        self.post_walk.branch_value_trans_count = 0;
+        self.node_diff_count = 0;
+        self.node_diff_byte_count = 0;
+        self.node_diff_hash_count = 0;
+        self.node_diff_value_count = 0;
+        self.node_diff_slot_count = 0;
+        self.node_insert_count = 0;
+        self.node_delete_count = 0;
+        self.node_split_count = 0;
+        self.node_collapse_count = 0;
+        self.node_graft_count = 0;
+        self.node_prune_count = 0;
+        self.list_end_count = 0;
     }
 
     fn allocate_for_function_tables(&mut self) -> TableHeights {
+        self.skip_list_header_table
+            .allocate(self.skip_list_header_count);
+        self.sponge.allocate();
+        self.hash_trans_table.allocate(self.hash_trans_count);
+        self.get_child_base_table
+            .allocate(self.get_child_base_count);
+        self.get_child_recursive_table
+            .allocate(self.get_child_recursive_count);
+        self.branch_trans_table.allocate(self.branch_trans_count);
+        self.ext_leaf_trans_table
+            .allocate(self.ext_leaf_trans_count);
+        self.check_nib_base_table
+            .allocate(self.check_nib_base_count);
+        self.check_nib_recursive_table
+            .allocate(self.check_nib_recursive_count);
+        self.fork_state_table.allocate(self.fork_state_count);
+        self.branch_value_trans_table
+            .allocate(self.branch_value_trans_count);
+        self.account_fields_table.allocate(self.decoded_count());
+        if self.is_update() {
+            let post_walk = &mut self.post_walk;
+            post_walk
+                .branch_trans_table
+                .allocate(post_walk.branch_trans_count);
+            post_walk
+                .ext_leaf_trans_table
+                .allocate(post_walk.ext_leaf_trans_count);
+            post_walk
+                .fork_state_table
+                .allocate(post_walk.fork_state_count);
+            post_walk
+                .branch_value_trans_table
+                .allocate(post_walk.branch_value_trans_count);
+            self.node_diff_table.allocate(self.node_diff_count);
+            self.node_diff_byte_table
+                .allocate(self.node_diff_byte_count);
+            self.node_diff_hash_table
+                .allocate(self.node_diff_hash_count);
+            self.node_diff_value_table
+                .allocate(self.node_diff_value_count);
+            self.node_diff_slot_table
+                .allocate(self.node_diff_slot_count);
+            self.node_insert_table.allocate(self.node_insert_count);
+            self.node_delete_table.allocate(self.node_delete_count);
+            self.node_split_table.allocate(self.node_split_count);
+            self.node_collapse_table.allocate(self.node_collapse_count);
+            self.node_graft_table.allocate(self.node_graft_count);
+            self.node_prune_table.allocate(self.node_prune_count);
+            self.list_end_table.allocate(self.list_end_count);
+        }
+        if let Some(predicates) = self.statement.predicates() {
+            let counts = PredicateRowCounts::new(predicates);
+            self.balance_at_least_table
+                .allocate(counts.balance_at_least);
+            self.is_contract_table.allocate(counts.is_contract);
+            self.code_hash_in_table.allocate(counts.code_hash_in);
+            self.code_hash_set_sink_table
+                .allocate(counts.code_hash_set_sink);
+        }
+        if let Some(count) = self.account_fields_sink_count() {
+            self.account_fields_sink_table.allocate(count);
+        }
+        if self.has_balance_sum() {
+            self.balance_sum_table.allocate(self.keys.len());
+        }
+        if let Some(codes) = self.statement.codes() {
+            self.bytecode_table.allocate(codes.len());
+        }
+        self.table_heights()
+    }
+
+    fn table_heights(&self) -> TableHeights {
+        let [keccak_f, absorb_block_base_table, absorb_block_recursive_table] =
+            self.sponge.table_heights();
         TableHeights {
-            skip_list_header: TableHeight {
-                n_vars: self
-                    .skip_list_header_table
-                    .allocate(self.skip_list_header_count),
-                count: self.skip_list_header_count,
-            },
-            keccak_f: TableHeight {
-                n_vars: self.keccak_f_table.allocate(self.keccak_f_count),
-                count: self.keccak_f_count,
-            },
-            absorb_block_base_table: TableHeight {
-                n_vars: self
-                    .absorb_block_base_table
-                    .allocate(self.absorb_block_base_count),
-                count: self.absorb_block_base_count,
-            },
-            absorb_block_recursive_table: TableHeight {
-                n_vars: self
-                    .absorb_block_recursive_table
-                    .allocate(self.absorb_block_recursive_count),
-                count: self.absorb_block_recursive_count,
-            },
-            hash_trans_table: TableHeight {
-                n_vars: self.hash_trans_table.allocate(self.hash_trans_count),
-                count: self.hash_trans_count,
-            },
-            get_child_base_table: TableHeight {
-                n_vars: self
-                    .get_child_base_table
-                    .allocate(self.get_child_base_count),
-                count: self.get_child_base_count,
-            },
-            get_child_recursive_table: TableHeight {
-                n_vars: self
-                    .get_child_recursive_table
-                    .allocate(self.get_child_recursive_count),
-                count: self.get_child_recursive_count,
-            },
-            branch_trans_table: TableHeight {
-                n_vars: self.branch_trans_table.allocate(self.branch_trans_count),
-                count: self.branch_trans_count,
-            },
-            ext_leaf_trans_table: TableHeight {
-                n_vars: self
-                    .ext_leaf_trans_table
-                    .allocate(self.ext_leaf_trans_count),
-                count: self.ext_leaf_trans_count,
-            },
-            check_nib_base_table: TableHeight {
-                n_vars: self
-                    .check_nib_base_table
-                    .allocate(self.check_nib_base_count),
-                count: self.check_nib_base_count,
-            },
-            check_nib_recursive_table: TableHeight {
-                n_vars: self
-                    .check_nib_recursive_table
-                    .allocate(self.check_nib_recursive_count),
-                count: self.check_nib_recursive_count,
-            },
-            fork_state_table: TableHeight {
-                n_vars: self.fork_state_table.allocate(self.fork_state_count),
-                count: self.fork_state_count,
-            },
+            skip_list_header: TableHeight::new::<SkipListHeaderTable>(self.skip_list_header_count),
+            keccak_f,
+            absorb_block_base_table,
+            absorb_block_recursive_table,
+            hash_trans_table: TableHeight::new::<HashTransTable>(self.hash_trans_count),
+            get_child_base_table: TableHeight::new::<GetChildBaseTable>(self.get_child_base_count),
+            get_child_recursive_table: TableHeight::new::<GetChildRecursiveTable>(
+                self.get_child_recursive_count,
+            ),
+            branch_trans_table: TableHeight::new::<BranchTransTable>(self.branch_trans_count),
+            ext_leaf_trans_table: TableHeight::new::<ExtLeafTransTable>(self.ext_leaf_trans_count),
+            check_nib_base_table: TableHeight::new::<CheckNibBaseTable>(self.check_nib_base_count),
+            check_nib_recursive_table: TableHeight::new::<CheckNibRecursiveTable>(
+                self.check_nib_recursive_count,
+            ),
+            fork_state_table: TableHeight::new::<ForkStateTable>(self.fork_state_count),
+            branch_value_trans_table: TableHeight::new::<BranchValueTransTable>(
+                self.branch_value_trans_count,
+            ),
+            account_fields_table: TableHeight::new::<AccountFieldsTable>(self.decoded_count()),
             populate_mem: self.state.mem.len().trailing_zeros() as usize,
+            update: self.is_update().then(|| self.update_table_heights()),
+            predicates: self
+                .statement
+                .predicates
+                .as_deref()
+                .map(Self::predicate_table_heights),
+            account_fields_sink_table: self
+                .account_fields_sink_count()
+                .map(TableHeight::new::<AccountFieldsSinkTable>),
+            balance_sum_table: self
+                .has_balance_sum()
+                .then(|| TableHeight::new::<BalanceSumTable>(self.keys.len())),
+            bytecode_table: self
+                .statement
+                .codes()
+                .map(|codes| TableHeight::new::<BytecodeTable>(codes.len())),
         }
     }
 
-    fn get_leaf_rlp_ptr_len_pairs(&mut self) -> Vec<(u32, u32)> {
-        let proof_data = self.proof_data.take().unwrap();
+    // a predicate or the balance sum consumes a single field of every withheld value
+    fn account_fields_sink_count(&self) -> Option<usize> {
+        self.statement
+            .withholds_values()
+            .then(|| 3 * self.keys.len())
+    }
+
+    fn predicate_table_heights(predicates: &[AccountPredicate]) -> PredicateTableHeights {
+        let counts = PredicateRowCounts::new(predicates);
+        PredicateTableHeights {
+            balance_at_least_table: TableHeight::new::<BalanceAtLeastTable>(
+                counts.balance_at_least,
+            ),
+            is_contract_table: TableHeight::new::<IsContractTable>(counts.is_contract),
+            code_hash_in_table: TableHeight::new::<CodeHashInTable>(counts.code_hash_in),
+            code_hash_set_sink_table: TableHeight::new::<CodeHashSetSinkTable>(
+                counts.code_hash_set_sink,
+            ),
+        }
+    }
+
+    fn update_table_heights(&self) -> UpdateTableHeights {
+        let post_walk = &self.post_walk;
+        UpdateTableHeights {
+            post_branch_trans_table: TableHeight::new::<BranchTransTable>(
+                post_walk.branch_trans_count,
+            ),
+            post_ext_leaf_trans_table: TableHeight::new::<ExtLeafTransTable>(
+                post_walk.ext_leaf_trans_count,
+            ),
+            post_fork_state_table: TableHeight::new::<ForkStateTable>(post_walk.fork_state_count),
+            post_branch_value_trans_table: TableHeight::new::<BranchValueTransTable>(
+                post_walk.branch_value_trans_count,
+            ),
+            node_diff_table: TableHeight::new::<NodeDiffTable>(self.node_diff_count),
+            node_diff_byte_table: TableHeight::new::<NodeDiffByteTable>(self.node_diff_byte_count),
+            node_diff_hash_table: TableHeight::new::<NodeDiffHashTable>(self.node_diff_hash_count),
+            node_diff_value_table: TableHeight::new::<NodeDiffValueTable>(
+                self.node_diff_value_count,
+            ),
+            node_diff_slot_table: TableHeight::new::<NodeDiffSlotTable>(self.node_diff_slot_count),
+            node_insert_table: TableHeight::new::<NodeInsertTable>(self.node_insert_count),
+            node_delete_table: TableHeight::new::<NodeDeleteTable>(self.node_delete_count),
+            node_split_table: TableHeight::new::<NodeSplitTable>(self.node_split_count),
+            node_collapse_table: TableHeight::new::<NodeCollapseTable>(self.node_collapse_count),
+            node_graft_table: TableHeight::new::<NodeGraftTable>(self.node_graft_count),
+            node_prune_table: TableHeight::new::<NodePruneTable>(self.node_prune_count),
+            list_end_table: TableHeight::new::<ListEndTable>(self.list_end_count),
+        }
+    }
+
+    fn get_leaf_rlp_ptr_len_pairs(&self, proof_data: &ProofData) -> Vec<(u32, u32)> {
         let leaf_rlp_ptr_len_pairs = (0..self.keys.len())
             .map(|i| {
                 let node_info_list = proof_data.get(&i).unwrap();
@@ -313,20 +1691,18 @@ impl MPT {
                     let offset = MPT::skip_list_header_offset_lookup(prefix_val);
                     list_ptr + offset as u32
                 };
-                let rlp_ptr = self.skip_short_byte_string(post_leaf_header_ptr);
-
-                let length = {
-                    let mut decoded = rlp_decode_node(&mut node_info.bytes.as_ref());
-                    assert_eq!(decoded.len(), 2);
-                    let leaf_val_bytes = decoded.pop().expect("checked length to be 2");
-                    let val = rlp_encode_long_bytestring(&leaf_val_bytes);
-                    val.len() as u32
+                // the value follows the path of a leaf, or the 16 children of a branch
+                let preceding_items = match node_info.kind {
+                    NodeKind::ExtLeaf => 1,
+                    NodeKind::Branch => 16,
                 };
+                let rlp_ptr = (0..preceding_items)
+                    .fold(post_leaf_header_ptr, |item_ptr, _| self.skip_item(item_ptr));
+                let length = self.skip_item(rlp_ptr) - rlp_ptr;
 
                 (rlp_ptr, length)
             })
             .collect::<Vec<_>>();
-        self.proof_data = Some(proof_data);
         leaf_rlp_ptr_len_pairs
     }
 
@@ -337,22 +1713,178 @@ impl MPT {
         (0..32).for_each(|index| {
             self.state.mem.process_timestamp(1 + index);
         });
-        // all keys
-        (0..self.keys.len() * 32).for_each(|index| {
+        // all keys but the hidden ones
+        let disclosed_count = self.disclosed_count();
+        (0..disclosed_count * 32).for_each(|index| {
             self.state.mem.process_timestamp(1 + 32 + index as u32);
         });
+        // the commitment to the hidden keys
+        if self.has_hidden_accounts() {
+            let (_, commitment_ptr) = MPT::commitment_mem_offsets(disclosed_count, self.keys.len());
+            (0..32).for_each(|index| {
+                self.state.mem.process_timestamp(commitment_ptr + 1 + index);
+            });
+        }
+        // the codes and their padding
+        let code_lengths = self
+            .statement
+            .codes()
+            .unwrap_or_default()
+            .iter()
+            .map(|bytecode| bytecode.code.len())
+            .collect::<Vec<_>>();
+        for (code_ptr, code_length) in izip!(MPT::code_mem_offsets(&self.statement), code_lengths) {
+            let padded_length = (code_length / HASH_R + 1) * HASH_R;
+            (0..padded_length as u32).for_each(|index| {
+                self.state.mem.process_timestamp(code_ptr + index);
+            });
+        }
         // the leaf nodes
         let proof_data = self.proof_data.take().unwrap();
         debug_assert_eq!(leaf_rlp_ptr_len_pairs.len(), self.keys.len());
-        leaf_rlp_ptr_len_pairs
-            .iter()
-            .for_each(|(leaf_rlp_ptr, length)| {
+        match &self.statement.mode {
+            // the values of a balance sum are withheld
+            StatementMode::BalanceSum(_) => (),
+            // the values are withheld, but for the payloads of the nonces the predicates fix
+            StatementMode::Predicates(predicates) => {
+                for (predicate, (leaf_rlp_ptr, _)) in izip!(predicates, leaf_rlp_ptr_len_pairs) {
+                    if let AccountPredicate::NonceEquals(_, nonce) = predicate {
+                        let (offset, payload) = nonce_payload(*nonce);
+                        (0..payload.len() as u32).for_each(|index| {
+                            self.state
+                                .mem
+                                .process_timestamp(leaf_rlp_ptr + offset + index);
+                        });
+                    }
+                }
+            }
+            // inserted accounts have no pre value
+            _ => leaf_rlp_ptr_len_pairs[..disclosed_count]
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| self.statement.in_pre_state(i))
+                .for_each(|(_, (leaf_rlp_ptr, length))| {
+                    (0..*length).for_each(|offset| {
+                        let index = leaf_rlp_ptr + offset;
+                        self.state.mem.process_timestamp(index);
+                    });
+                }),
+        }
+        self.proof_data = Some(proof_data);
+    }
+
+    // the post state root, the templates, and the post values other than the ones left in place
+    // or deleted
+    fn simulate_verifier_post_mem_reading(
+        &mut self,
+        leaf_rlp_ptr_len_pairs: &[(u32, u32)],
+        post_leaf_rlp_ptr_len_pairs: &[(u32, u32)],
+    ) {
+        let post_root_mem_offset = MPT::post_root_mem_offset(self.keys.len());
+        (0..1 + 32).for_each(|index| {
+            self.state
+                .mem
+                .process_timestamp(post_root_mem_offset + index);
+        });
+        // the templates of the virtual branches
+        for (_, template_ptr) in MPT::template_mem_offsets(&self.statement) {
+            (0..EMPTY_BRANCH.len() as u32).for_each(|index| {
+                self.state.mem.process_timestamp(template_ptr + index);
+            });
+        }
+        izip!(leaf_rlp_ptr_len_pairs, post_leaf_rlp_ptr_len_pairs)
+            .enumerate()
+            .filter(|(i, ((leaf_rlp_ptr, _), (post_leaf_rlp_ptr, _)))| {
+                leaf_rlp_ptr != post_leaf_rlp_ptr && self.statement.in_post_state(*i)
+            })
+            .for_each(|(_, (_, (post_leaf_rlp_ptr, length)))| {
                 (0..*length).for_each(|offset| {
-                    let index = leaf_rlp_ptr + offset;
-                    self.state.mem.process_timestamp(index);
+                    self.state.mem.process_timestamp(post_leaf_rlp_ptr + offset);
                 });
             });
-        self.proof_data = Some(proof_data);
+    }
+
+    // the keys of the hidden accounts follow the disclosed ones
+    fn disclosed_count(&self) -> usize {
+        self.statement.addr_val_pairs.len()
+    }
+
+    // the values account_fields decodes, which accounts an update inserts have none of
+    fn decoded_count(&self) -> usize {
+        (0..self.disclosed_count())
+            .filter(|&i| self.statement.in_pre_state(i))
+            .count()
+    }
+
+    // the walk of a key absent from a trie ends at the empty child of a branch, whose final
+    // state node_insert or node_delete pulls in place of the verifier
+    fn point_at_empty_children(
+        &self,
+        trace: &[Vec<TraceStep>],
+        leaf_rlp_ptr_len_pairs: &mut [(u32, u32)],
+        absent: ValueChange,
+    ) {
+        for (i, steps) in trace.iter().enumerate() {
+            if self.statement.value_change(i) == absent {
+                leaf_rlp_ptr_len_pairs[i] = (end_state(steps).rlp_ptr, 1);
+            }
+        }
+    }
+
+    // the rows absorbing the hidden keys and the salt into the commitment the verifier reads
+    fn hash_commitment(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        if self.has_hidden_accounts() {
+            let (preimage_ptr, commitment_ptr) =
+                MPT::commitment_mem_offsets(self.disclosed_count(), self.keys.len());
+            let total_blocks = ((commitment_ptr - preimage_ptr) / HASH_R as u32) as u8;
+            self.absorb_block(
+                &mut rows,
+                [0; HASH_B],
+                preimage_ptr,
+                commitment_ptr,
+                total_blocks,
+            );
+        }
+        rows
+    }
+
+    // the rows absorbing every code into the code hash of its account, which ends the value
+    fn hash_codes(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        let leaf_rlp_ptr_len_pairs =
+            self.get_leaf_rlp_ptr_len_pairs(self.proof_data.as_ref().unwrap());
+        let codes = self
+            .statement
+            .codes()
+            .unwrap_or_default()
+            .iter()
+            .map(|bytecode| (bytecode.account, bytecode.code.len()))
+            .collect::<Vec<_>>();
+        for (code_ptr, (account, code_length)) in
+            izip!(MPT::code_mem_offsets(&self.statement), codes)
+        {
+            let (value_ptr, length) = leaf_rlp_ptr_len_pairs[account];
+            self.bytecode(
+                &mut rows,
+                code_ptr,
+                code_length,
+                value_ptr,
+                value_ptr + length,
+            );
+        }
+        rows
+    }
+
+    // a row per account value, after the verifier and the walks read it
+    fn decode_account_fields(
+        &mut self,
+        leaf_rlp_ptr_len_pairs: &[(u32, u32)],
+    ) -> Vec<[Range<u32>; 4]> {
+        leaf_rlp_ptr_len_pairs
+            .iter()
+            .map(|&(leaf_rlp_ptr, _)| self.account_fields(leaf_rlp_ptr))
+            .collect()
     }
 
     fn get_ptr_ts_pairs(&mut self, leaf_rlp_ptr_len_pairs: &[(u32, u32)]) -> Vec<PtrTsPair> {
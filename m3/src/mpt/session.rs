@@ -0,0 +1,187 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+// entries not used for this many blocks are evicted
+const DEFAULT_MAX_IDLE_BLOCKS: u64 = 4;
+
+struct CachedNode {
+    kind: NodeKind,
+    // the keccak_f rows of the node's sponge, one per absorbed block
+    permutations: Vec<Permutation>,
+    // the digest the sponge squeezes, which hash_trans checks the reference to the node against
+    hash: [u8; 32],
+    last_used: u64,
+}
+
+impl CachedNode {
+    fn new(node_bytes: &[u8], count_only: bool) -> Self {
+        let kind = node_kind(node_bytes);
+        if count_only {
+            return Self {
+                kind,
+                permutations: vec![],
+                hash: [0; 32],
+                last_used: 0,
+            };
+        }
+        let mut preimage = node_bytes.to_vec();
+        preimage.extend(MPT::get_padding_bytes(node_bytes.len()));
+
+        let mut hash_state = [0; HASH_B];
+        let permutations = preimage
+            .chunks(HASH_R)
+            .map(|block_vals| {
+                let mut perm_input = hash_state;
+                for (state, val) in perm_input.iter_mut().zip(block_vals) {
+                    *state ^= val;
+                }
+                let permutation = Permutation::new(perm_input);
+                hash_state = permutation.output();
+                permutation
+            })
+            .collect();
+        Self {
+            kind,
+            permutations,
+            hash: hash_state[..32].try_into().unwrap(),
+            last_used: 0,
+        }
+    }
+}
+
+/// Work carried over between consecutive calls to [`prove_in_session`], typically one per block.
+///
+/// Nodes are keyed by their contents, so the upper trie nodes shared by consecutive blocks are
+/// decoded, permuted and hashed only once. Memory and the tables are still laid out per block, since
+/// node positions depend on the whole batch of account proofs.
+pub struct ProverSession {
+    block: u64,
+    max_idle_blocks: u64,
+    nodes: HashMap<Vec<u8>, CachedNode>,
+    // the rows of the nodes registered for this block, keyed by the position in mem of the
+    // block they absorb
+    permutations: HashMap<u32, Permutation>,
+    // the digests of the nodes registered for this block, keyed by their position in mem
+    hashes: HashMap<u32, [u8; 32]>,
+    // nodes of the last block found in the cache, and dropped from it once the block ended
+    hits: usize,
+    evicted: usize,
+    // a session counting rows decodes the nodes without permuting or hashing them, and fills
+    // the keccak_f rows with permutations it does not compute
+    count_only: bool,
+}
+
+impl Default for ProverSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProverSession {
+    pub fn new() -> Self {
+        Self::with_max_idle_blocks(DEFAULT_MAX_IDLE_BLOCKS)
+    }
+
+    /// Nodes absent from `max_idle_blocks` consecutive blocks are dropped from the cache.
+    pub fn with_max_idle_blocks(max_idle_blocks: u64) -> Self {
+        Self {
+            block: 0,
+            max_idle_blocks,
+            nodes: HashMap::new(),
+            permutations: HashMap::new(),
+            hashes: HashMap::new(),
+            hits: 0,
+            evicted: 0,
+            count_only: false,
+        }
+    }
+
+    // a session for a single batch whose rows are counted but never appended
+    pub(crate) fn counting() -> Self {
+        Self {
+            count_only: true,
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn count_only(&self) -> bool {
+        self.count_only
+    }
+
+    /// Number of nodes currently cached.
+    pub fn cached_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of nodes of the last block that were already cached.
+    pub fn cache_hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Number of nodes dropped from the cache at the end of the last block.
+    pub fn evicted_nodes(&self) -> usize {
+        self.evicted
+    }
+
+    pub(crate) fn begin_block(&mut self) {
+        self.block += 1;
+        self.permutations.clear();
+        self.hashes.clear();
+        self.hits = 0;
+        self.evicted = 0;
+    }
+
+    pub(crate) fn end_block(&mut self) {
+        let (block, max_idle_blocks) = (self.block, self.max_idle_blocks);
+        let cached_nodes = self.nodes.len();
+        self.nodes
+            .retain(|_, node| node.last_used + max_idle_blocks > block);
+        self.evicted = cached_nodes - self.nodes.len();
+        self.permutations.clear();
+        self.hashes.clear();
+    }
+
+    // decoding and permuting are independent per node, so they run in parallel ahead of the
+    // walk; nodes are given once each with their position in mem
+    pub(crate) fn register_nodes(&mut self, nodes: &[(&[u8], u32)]) -> HashMap<u32, NodeKind> {
+        let (cached_nodes, count_only) = (&self.nodes, self.count_only);
+        let new_nodes = nodes
+            .par_iter()
+            .filter(|(node_bytes, _)| !cached_nodes.contains_key(*node_bytes))
+            .map(|(node_bytes, _)| (node_bytes.to_vec(), CachedNode::new(node_bytes, count_only)))
+            .collect::<Vec<_>>();
+        self.hits += nodes.len() - new_nodes.len();
+        self.nodes.extend(new_nodes);
+
+        nodes
+            .iter()
+            .map(|&(node_bytes, position)| {
+                let node = self.nodes.get_mut(node_bytes).expect("inserted above");
+                node.last_used = self.block;
+                for (block, permutation) in node.permutations.iter().enumerate() {
+                    self.permutations
+                        .insert(position + (block * HASH_R) as u32, *permutation);
+                }
+                self.hashes.insert(position, node.hash);
+                (position, node.kind)
+            })
+            .collect()
+    }
+
+    // blocks of nodes not registered for this block are permuted on the spot
+    pub(crate) fn permutation(&self, preimage_ptr: u32, perm_input: [u8; HASH_B]) -> Permutation {
+        match self.permutations.get(&preimage_ptr) {
+            Some(permutation) => *permutation,
+            None => Permutation::new(perm_input),
+        }
+    }
+
+    // the digest of the node at preimage_ptr, hashed on the spot if it was not registered
+    pub(crate) fn node_hash(&self, preimage_ptr: u32, preimage: &[u8]) -> [u8; 32] {
+        match self.hashes.get(&preimage_ptr) {
+            Some(hash) => *hash,
+            None => alloy::primitives::keccak256(preimage).0,
+        }
+    }
+}
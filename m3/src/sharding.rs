@@ -0,0 +1,196 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+fn nibble(key: &[u8; 32], index: usize) -> u8 {
+    key[index / 2] >> (4 * (1 - index % 2)) & 0xf
+}
+
+fn same_prefix(left: &[u8; 32], right: &[u8; 32], nibbles: usize) -> bool {
+    (0..nibbles).all(|index| nibble(left, index) == nibble(right, index))
+}
+
+// groups the proofs by the shortest key prefix that keeps every group within the limit and
+// packs consecutive groups into shards, so that the subtrees below that prefix are never split
+fn shard_by_prefix(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    max_accounts_per_shard: usize,
+) -> Result<Vec<Vec<EIP1186AccountProofResponse>>, anyhow::Error> {
+    let mut keyed_proofs = alloy_account_proofs
+        .into_iter()
+        .map(|proof| (keccak256(proof.address).0, proof))
+        .collect::<Vec<_>>();
+    keyed_proofs.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    let groups = |nibbles: usize| {
+        keyed_proofs.chunk_by(move |(left, _), (right, _)| same_prefix(left, right, nibbles))
+    };
+    let prefix_len = (0..=64)
+        .find(|&nibbles| groups(nibbles).all(|group| group.len() <= max_accounts_per_shard))
+        .ok_or_else(|| anyhow!("Account proofs of the same address cannot be sharded"))?;
+    let group_lens = groups(prefix_len).map(<[_]>::len).collect::<Vec<_>>();
+
+    let mut proofs = keyed_proofs.into_iter().map(|(_, proof)| proof);
+    let mut shards: Vec<Vec<_>> = vec![];
+    for group_len in group_lens {
+        let group = proofs.by_ref().take(group_len);
+        match shards.last_mut() {
+            Some(shard) if shard.len() + group_len <= max_accounts_per_shard => shard.extend(group),
+            _ => shards.push(group.collect()),
+        }
+    }
+    Ok(shards)
+}
+
+/// Splits the account proofs along trie prefixes into shards of at most
+/// `max_accounts_per_shard` accounts, proves every shard, and returns the proofs as a
+/// serialized [`MPTProofBundle`].
+pub fn prove_sharded(
+    alloy_account_proofs: Vec<EIP1186AccountProofResponse>,
+    max_accounts_per_shard: usize,
+    config: &ProverConfig,
+    options: &ProveOptions,
+    backend: &impl ComputationBackend,
+) -> Result<(Vec<u8>, Vec<MPTProofInfo>), anyhow::Error> {
+    if max_accounts_per_shard == 0 {
+        return Err(anyhow!("Shards must hold at least one account"));
+    }
+    let shards = shard_by_prefix(alloy_account_proofs, max_accounts_per_shard)?;
+    let _scope =
+        tracing::debug_span!("binius_mp3::prove_sharded", n_shards = shards.len()).entered();
+
+    // the prover is parallel already, so shards are proved one after the other and share the
+    // upper nodes through the session
+    let mut session = ProverSession::new();
+    let mut bundle = MPTProofBundle::new();
+    let mut infos = Vec::with_capacity(shards.len());
+    for shard in shards {
+        let addresses = shard.iter().map(|proof| proof.address).collect::<Vec<_>>();
+        let (proof, info) = prove_in_session(&mut session, shard, config, options, backend)?;
+        bundle.push(&proof, addresses)?;
+        infos.push(info);
+    }
+
+    Ok((bundle.to_bytes()?, infos))
+}
+
+// verifies one proof of the bundle against the expected root, and checks that it is a proof of
+// accounts proving exactly the addresses indexed for it
+fn verify_bundled_proof(
+    bundle: &MPTProofBundle,
+    proof_index: usize,
+    root_hash: &[u8; 32],
+    policy: &VerifierPolicy,
+) -> Result<Vec<AccountFields>, anyhow::Error> {
+    let proof = bundle
+        .proof(proof_index)
+        .ok_or_else(|| anyhow!("Bundle has no proof {proof_index}"))?;
+    let (statement, accounts) = verify_statement(proof.to_vec(), policy)?;
+    if statement.root_hash() != root_hash {
+        return Err(anyhow!(
+            "Proof {proof_index} proves accounts under a different state root"
+        ));
+    }
+    // an update, predicates or hidden accounts would pass for the accounts of their addresses
+    let accounts = statement
+        .into_plain_account_fields(accounts)
+        .map_err(|err| anyhow!("Proof {proof_index}: {err}"))?;
+
+    let mut proven_addresses = accounts
+        .iter()
+        .map(|account| account.address.clone())
+        .collect::<Vec<_>>();
+    let mut indexed_addresses = bundle
+        .addresses(proof_index)
+        .map(|address| address.to_vec())
+        .collect::<Vec<_>>();
+    proven_addresses.sort();
+    indexed_addresses.sort();
+    if proven_addresses != indexed_addresses {
+        return Err(anyhow!(
+            "Proof {proof_index} proves {} accounts, which do not match the {} addresses indexed for it",
+            proven_addresses.len(),
+            indexed_addresses.len()
+        ));
+    }
+    Ok(accounts)
+}
+
+// returns the accounts proven by the given proofs by address
+fn verify_bundled_proofs(
+    bundle: &MPTProofBundle,
+    proof_indices: impl IntoIterator<Item = usize>,
+    root_hash: &[u8; 32],
+    policy: &VerifierPolicy,
+) -> Result<BTreeMap<Vec<u8>, AccountFields>, anyhow::Error> {
+    let proven_accounts = BTreeMap::new();
+    for proof_index in proof_indices {
+        proven_accounts.extend(
+            verify_bundled_proof(bundle, proof_index, root_hash, policy)?
+                .into_iter()
+                .map(|account| (account.address.clone(), account)),
+        );
+    }
+    Ok(proven_accounts)
+}
+
+/// Verifies every proof of a bundle produced by [`prove_sharded`] under `policy`, checks that
+/// all proofs are for `root_hash` and that together they prove exactly `addresses`, and returns
+/// the fields of the accounts in the order of `addresses`, like [`verify_accounts`].
+#[instrument("binius_mp3::verify_sharded", skip_all, level = "debug")]
+pub fn verify_sharded(
+    bundle: Vec<u8>,
+    root_hash: &[u8; 32],
+    addresses: &[Address],
+    policy: &VerifierPolicy,
+) -> Result<Vec<AccountFields>, anyhow::Error> {
+    let bundle = MPTProofBundle::from_bytes(bundle)?;
+    let proven_accounts = verify_bundled_proofs(&bundle, 0..bundle.len(), root_hash, policy)?;
+    let expected_addresses = addresses
+        .iter()
+        .map(|address| address.to_vec())
+        .collect::<BTreeSet<_>>();
+    if !proven_accounts.keys().eq(&expected_addresses) {
+        return Err(anyhow!(
+            "Bundle proves {} accounts, which do not match the {} expected addresses",
+            proven_accounts.len(),
+            expected_addresses.len()
+        ));
+    }
+    Ok(addresses
+        .iter()
+        .map(|address| proven_accounts[address.as_slice()].clone())
+        .collect())
+}
+
+/// Same as [`verify_sharded`], but only verifies the proofs covering the given addresses,
+/// which may prove further accounts of the same shards, and only returns the fields of the
+/// accounts of `addresses`.
+#[instrument("binius_mp3::verify_sharded_accounts", skip_all, level = "debug")]
+pub fn verify_sharded_accounts(
+    bundle: Vec<u8>,
+    root_hash: &[u8; 32],
+    addresses: &[Address],
+    policy: &VerifierPolicy,
+) -> Result<Vec<AccountFields>, anyhow::Error> {
+    let bundle = MPTProofBundle::from_bytes(bundle)?;
+    let proof_indices = addresses
+        .iter()
+        .map(|address| {
+            bundle
+                .proof_index(address)
+                .ok_or_else(|| anyhow!("Bundle has no proof for address {address}"))
+        })
+        .collect::<Result<BTreeSet<_>, _>>()?;
+    let proven_accounts = verify_bundled_proofs(&bundle, proof_indices, root_hash, policy)?;
+    addresses
+        .iter()
+        .map(|address| {
+            proven_accounts
+                .get(address.as_slice())
+                .cloned()
+                .ok_or_else(|| anyhow!("The proof indexed for address {address} does not prove it"))
+        })
+        .collect()
+}
@@ -4,29 +4,184 @@ use super::*;
 
 use std::array;
 
-impl MPT {
+// The sponge the trie nodes and other preimages in mem are hashed with. absorb_block rows
+// absorb a preimage block by block into the keccak state, each block through a keccak_f row,
+// and the base row reads the digest the state ends in after the byte at hash_ptr.
+// The channels the sponge tables flush, so that a circuit hashing preimages in mem without the
+// trie tables allocates no others.
+#[derive(Clone, Copy)]
+pub(crate) struct SpongeChannels {
+    pub(crate) mem: ChannelId,
+    pub(crate) keccak_f: ChannelId,
+    pub(crate) absorb_block: ChannelId,
+}
+
+impl From<&ChannelIds> for SpongeChannels {
+    fn from(channel_ids: &ChannelIds) -> Self {
+        Self {
+            mem: channel_ids.mem,
+            keccak_f: channel_ids.keccak_f,
+            absorb_block: channel_ids.absorb_block,
+        }
+    }
+}
+
+pub(crate) struct KeccakSponge {
+    pub(crate) keccak_f_table: KeccakFTable,
+    pub(crate) keccak_f_count: usize,
+    pub(crate) absorb_block_base_table: AbsorbBlockBaseTable,
+    pub(crate) absorb_block_base_count: usize,
+    pub(crate) absorb_block_recursive_table: AbsorbBlockRecursiveTable,
+    pub(crate) absorb_block_recursive_count: usize,
+}
+
+impl KeccakSponge {
+    pub(crate) fn new() -> Self {
+        Self {
+            keccak_f_table: KeccakFTable::new(),
+            keccak_f_count: 0,
+            absorb_block_base_table: AbsorbBlockBaseTable::new(),
+            absorb_block_base_count: 0,
+            absorb_block_recursive_table: AbsorbBlockRecursiveTable::new(),
+            absorb_block_recursive_count: 0,
+        }
+    }
+
+    // rows the sponge takes to absorb a preimage of len bytes
+    pub(crate) fn count_preimage(&mut self, len: usize) {
+        let blocks = len / HASH_R + 1;
+        self.keccak_f_count += blocks;
+        self.absorb_block_base_count += 1;
+        self.absorb_block_recursive_count += blocks;
+    }
+
+    pub(crate) fn allocate(&mut self) {
+        self.keccak_f_table.allocate(self.keccak_f_count);
+        self.absorb_block_base_table
+            .allocate(self.absorb_block_base_count);
+        self.absorb_block_recursive_table
+            .allocate(self.absorb_block_recursive_count);
+    }
+
+    // appends a row absorb_block recorded
+    pub(crate) fn append_row(&mut self, mpt_state: &mut MPTState, row: Row) {
+        match row {
+            Row::KeccakF(data) => self.keccak_f_table.append(mpt_state, data),
+            Row::AbsorbBlockBase(data) => self.absorb_block_base_table.append(mpt_state, data),
+            Row::AbsorbBlockRecursive(data) => {
+                self.absorb_block_recursive_table.append(mpt_state, *data)
+            }
+            _ => unreachable!("the sponge only records keccak_f and absorb_block rows"),
+        }
+    }
+
+    // the heights of the keccak_f, absorb_block base and recursive tables
+    pub(crate) fn table_heights(&self) -> [TableHeight; 3] {
+        [
+            TableHeight::new::<KeccakFTable>(self.keccak_f_count),
+            TableHeight::new::<AbsorbBlockBaseTable>(self.absorb_block_base_count),
+            TableHeight::new::<AbsorbBlockRecursiveTable>(self.absorb_block_recursive_count),
+        ]
+    }
+
+    pub(crate) fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channels: SpongeChannels,
+        [keccak_f, absorb_block_base, absorb_block_recursive]: [TableHeight; 3],
+    ) -> Result<(), anyhow::Error> {
+        self.keccak_f_table
+            .build_with(builder, channels, keccak_f)?;
+        self.absorb_block_base_table
+            .build_with(builder, channels, absorb_block_base)?;
+        self.absorb_block_recursive_table
+            .build_with(builder, channels, absorb_block_recursive)?;
+        Ok(())
+    }
+
+    // permutation gives the keccak_f row of a block absorbed into a state, given the position
+    // of the block in mem and the permutation input, so that callers can cache the rows
     pub(crate) fn absorb_block(
-        &mut self,
-        action: Action,
+        mpt_state: &MPTState,
+        rows: &mut Vec<Row>,
         pre_hash_state: [u8; HASH_B],
         preimage_ptr: u32,
         hash_ptr: u32,
         blocks_left: u8,
+        permutation: &impl Fn(u32, [u8; HASH_B]) -> Permutation,
     ) {
         match blocks_left == 0 {
-            true => self.absorb_block_base(action, pre_hash_state, preimage_ptr, hash_ptr),
-            false => self.absorb_block_recursive(
-                action,
+            true => {
+                Self::absorb_block_base(mpt_state, rows, pre_hash_state, preimage_ptr, hash_ptr)
+            }
+            false => Self::absorb_block_recursive(
+                mpt_state,
+                rows,
                 pre_hash_state,
                 preimage_ptr,
                 hash_ptr,
                 blocks_left,
+                permutation,
             ),
         }
     }
+
+    // the rows absorb_block records, in the same order but without their states, for rows
+    // that are counted but never appended
+    pub(crate) fn count_absorb_block(
+        rows: &mut Vec<Row>,
+        preimage_ptr: u32,
+        hash_ptr: u32,
+        blocks_left: u8,
+    ) {
+        let block_ptr = |block: u8| preimage_ptr + block as u32 * HASH_R as u32;
+        (0..blocks_left).for_each(|_| Self::count_keccak_f(rows));
+        rows.push(Row::AbsorbBlockBase(AbsorbBlockBaseData {
+            pre_hash_state: [0; HASH_B],
+            preimage_ptr: block_ptr(blocks_left),
+            hash_ptr,
+        }));
+        for block in (0..blocks_left).rev() {
+            rows.push(Row::AbsorbBlockRecursive(Box::new(
+                AbsorbBlockRecursiveData {
+                    pre_hash_state: [0; HASH_B],
+                    post_hash_state: [0; HASH_B],
+                    preimage_block_vals: [0; HASH_R],
+                    preimage_ptr: block_ptr(block),
+                    hash_ptr,
+                },
+            )));
+        }
+    }
+}
+
+impl MPT {
+    pub(crate) fn absorb_block(
+        &self,
+        rows: &mut Vec<Row>,
+        pre_hash_state: [u8; HASH_B],
+        preimage_ptr: u32,
+        hash_ptr: u32,
+        blocks_left: u8,
+    ) {
+        if self.session.count_only() {
+            KeccakSponge::count_absorb_block(rows, preimage_ptr, hash_ptr, blocks_left);
+            return;
+        }
+        KeccakSponge::absorb_block(
+            &self.state,
+            rows,
+            pre_hash_state,
+            preimage_ptr,
+            hash_ptr,
+            blocks_left,
+            &|preimage_ptr, perm_input| self.session.permutation(preimage_ptr, perm_input),
+        );
+    }
 }
 
 // BASE
+#[derive(PartialEq)]
 pub(crate) struct AbsorbBlockBaseData {
     pre_hash_state: [u8; HASH_B],
     preimage_ptr: u32,
@@ -49,6 +204,7 @@ pub(crate) struct AbsorbBlockBaseTable {
 }
 impl FunctionTable for AbsorbBlockBaseTable {
     type Data = AbsorbBlockBaseData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 2; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -58,13 +214,13 @@ impl FunctionTable for AbsorbBlockBaseTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.row_majors = Vec::with_capacity(count);
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 2, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -81,16 +237,27 @@ impl FunctionTable for AbsorbBlockBaseTable {
 
         self.index += 1;
     }
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        self.build_with(builder, channel_ids.into(), table_height)
+    }
+}
+
+impl AbsorbBlockBaseTable {
     #[instrument(
         name = "absorb_block_base",
         fields(n_vars = n_vars)
         skip_all,
         level = "debug"
     )]
-    fn build(
+    pub(crate) fn build_with(
         self,
         builder: &mut ConstraintSystemBuilder<U, B128>,
-        channel_ids: &ChannelIds,
+        channels: SpongeChannels,
         TableHeight { n_vars, count }: TableHeight,
     ) -> Result<(), anyhow::Error> {
         builder.push_namespace("absorb_block_base");
@@ -207,7 +374,7 @@ impl FunctionTable for AbsorbBlockBaseTable {
 
             let mut all_blocks = vec![block0];
             all_blocks.extend(other_blocks);
-            builder.receive(channel_ids.absorb_block, count, all_blocks);
+            builder.receive(channels.absorb_block, count, all_blocks);
         }
         // memory
         {
@@ -224,7 +391,7 @@ impl FunctionTable for AbsorbBlockBaseTable {
                         n_vars,
                         [addr_pair, val_pair, (read_tss[j], basis(32, 2))],
                     )?;
-                    builder.receive(channel_ids.mem, count, [read_block]);
+                    builder.receive(channels.mem, count, [read_block]);
 
                     // to mem push (hash_ptr * g * g^j, pre_hash_state[j], read_tss[j] * g)
                     let write_block = builder.add_linear_combination(
@@ -236,7 +403,7 @@ impl FunctionTable for AbsorbBlockBaseTable {
                             (read_tss[j], basis(32, 2) * B32::MULTIPLICATIVE_GENERATOR),
                         ],
                     )?;
-                    builder.send(channel_ids.mem, count, [write_block]);
+                    builder.send(channels.mem, count, [write_block]);
 
                     if let Some(witness) = builder.witness() {
                         (
@@ -282,34 +449,29 @@ impl FunctionTable for AbsorbBlockBaseTable {
     }
 }
 
-impl MPT {
+impl KeccakSponge {
     fn absorb_block_base(
-        &mut self,
-        action: Action,
+        mpt_state: &MPTState,
+        rows: &mut Vec<Row>,
         pre_hash_state: [u8; HASH_B],
         preimage_ptr: u32,
         hash_ptr: u32,
     ) {
         assert_eq!(pre_hash_state.len(), HASH_B); // dev
         for (j, &state) in pre_hash_state.iter().enumerate().take(32) {
-            assert_eq!(state, self.state.mem[hash_ptr + 1 + j as u32])
-        }
-        match action {
-            Action::Append => self.absorb_block_base_table.append(
-                &mut self.state,
-                AbsorbBlockBaseData {
-                    pre_hash_state,
-                    preimage_ptr,
-                    hash_ptr,
-                },
-            ),
-            Action::Count => self.absorb_block_base_count += 1,
-            Action::Ignore => (),
+            assert_eq!(state, mpt_state.mem[hash_ptr + 1 + j as u32])
         }
+        let data = AbsorbBlockBaseData {
+            pre_hash_state,
+            preimage_ptr,
+            hash_ptr,
+        };
+        rows.push(Row::AbsorbBlockBase(data));
     }
 }
 
 // RECURSIVE
+#[derive(PartialEq)]
 pub(crate) struct AbsorbBlockRecursiveData {
     pre_hash_state: [u8; HASH_B],
     post_hash_state: [u8; HASH_B],
@@ -336,6 +498,7 @@ pub(crate) struct AbsorbBlockRecursiveTable {
 }
 impl FunctionTable for AbsorbBlockRecursiveTable {
     type Data = AbsorbBlockRecursiveData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -345,13 +508,13 @@ impl FunctionTable for AbsorbBlockRecursiveTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.row_majors = Vec::with_capacity(count);
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -372,16 +535,27 @@ impl FunctionTable for AbsorbBlockRecursiveTable {
 
         self.index += 1;
     }
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        self.build_with(builder, channel_ids.into(), table_height)
+    }
+}
+
+impl AbsorbBlockRecursiveTable {
     #[instrument(
         name = "absorb_block_recursive",
         fields(n_vars = n_vars)
         skip_all,
         level = "debug"
     )]
-    fn build(
+    pub(crate) fn build_with(
         self,
         builder: &mut ConstraintSystemBuilder<U, B128>,
-        channel_ids: &ChannelIds,
+        channels: SpongeChannels,
         TableHeight { n_vars, count }: TableHeight,
     ) -> Result<(), anyhow::Error> {
         builder.push_namespace("absorb_block_recursive");
@@ -525,7 +699,7 @@ impl FunctionTable for AbsorbBlockRecursiveTable {
 
             let mut all_blocks = vec![block0];
             all_blocks.extend(other_blocks);
-            builder.receive(channel_ids.absorb_block, count, all_blocks);
+            builder.receive(channels.absorb_block, count, all_blocks);
         }
         // memory
         {
@@ -543,7 +717,7 @@ impl FunctionTable for AbsorbBlockRecursiveTable {
                         n_vars,
                         [addr_pair, val_pair, (read_tss[j], basis(32, 2))],
                     )?;
-                    builder.receive(channel_ids.mem, count, [read_block]);
+                    builder.receive(channels.mem, count, [read_block]);
 
                     // to mem pull (preimage_ptr * g^j, preimage_block_vals[j], read_tss[j] * g)
                     let write_block = builder.add_linear_combination(
@@ -555,7 +729,7 @@ impl FunctionTable for AbsorbBlockRecursiveTable {
                             (read_tss[j], basis(32, 2) * B32::MULTIPLICATIVE_GENERATOR),
                         ],
                     )?;
-                    builder.send(channel_ids.mem, count, [write_block]);
+                    builder.send(channels.mem, count, [write_block]);
 
                     if let Some(witness) = builder.witness() {
                         (
@@ -739,7 +913,7 @@ impl FunctionTable for AbsorbBlockRecursiveTable {
             all_blocks.extend(high_blocks);
             assert_eq!(all_blocks.len(), 25);
 
-            builder.send(channel_ids.keccak_f, count, all_blocks);
+            builder.send(channels.keccak_f, count, all_blocks);
         }
         // to absorb_block push (preimage_ptr * g^HASH_R, hash_ptr, post_hash_state)
         {
@@ -808,52 +982,50 @@ impl FunctionTable for AbsorbBlockRecursiveTable {
 
             let mut all_blocks = vec![block0];
             all_blocks.extend(other_blocks);
-            builder.send(channel_ids.absorb_block, count, all_blocks);
+            builder.send(channels.absorb_block, count, all_blocks);
         }
         builder.pop_namespace();
         Ok(())
     }
 }
 
-impl MPT {
+impl KeccakSponge {
     fn absorb_block_recursive(
-        &mut self,
-        action: Action,
+        mpt_state: &MPTState,
+        rows: &mut Vec<Row>,
         pre_hash_state: [u8; HASH_B],
         preimage_ptr: u32,
         hash_ptr: u32,
         blocks_left: u8,
+        permutation: &impl Fn(u32, [u8; HASH_B]) -> Permutation,
     ) {
         assert!(blocks_left > 0); // dev
         let preimage_block_vals =
-            self.state.mem[preimage_ptr..preimage_ptr + HASH_R as u32].to_vec();
+            mpt_state.mem[preimage_ptr..preimage_ptr + HASH_R as u32].to_vec();
         let mut perm_input = pre_hash_state;
         for j in 0..HASH_R {
             perm_input[j] ^= preimage_block_vals[j]
         }
-        let post_hash_state = self.keccak_f(action, perm_input);
-        assert_eq!(post_hash_state, crate::utils::keccak_f(perm_input));
-
-        self.absorb_block(
-            action,
-            post_hash_state,
+        let post_state = Self::keccak_f(rows, perm_input, permutation(preimage_ptr, perm_input));
+        // cached rows are only recomputed in debug builds
+        debug_assert_eq!(post_state, crate::utils::keccak_f(perm_input));
+
+        Self::absorb_block(
+            mpt_state,
+            rows,
+            post_state,
             preimage_ptr + HASH_R as u32,
             hash_ptr,
             blocks_left - 1,
+            permutation,
         );
-        match action {
-            Action::Append => self.absorb_block_recursive_table.append(
-                &mut self.state,
-                AbsorbBlockRecursiveData {
-                    pre_hash_state,
-                    post_hash_state,
-                    preimage_block_vals: preimage_block_vals.try_into().unwrap(),
-                    preimage_ptr,
-                    hash_ptr,
-                },
-            ),
-            Action::Count => self.absorb_block_recursive_count += 1,
-            Action::Ignore => (),
-        }
+        let data = AbsorbBlockRecursiveData {
+            pre_hash_state,
+            post_hash_state: post_state,
+            preimage_block_vals: preimage_block_vals.try_into().unwrap(),
+            preimage_ptr,
+            hash_ptr,
+        };
+        rows.push(Row::AbsorbBlockRecursive(Box::new(data)));
     }
 }
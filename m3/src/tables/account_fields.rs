@@ -0,0 +1,563 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+use std::ops::Range;
+
+// the fields of an account value, in the order of its list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AccountField {
+    Nonce,
+    Balance,
+    StorageRoot,
+    CodeHash,
+}
+
+impl AccountField {
+    pub(crate) const ALL: [AccountField; 4] = [
+        AccountField::Nonce,
+        AccountField::Balance,
+        AccountField::StorageRoot,
+        AccountField::CodeHash,
+    ];
+
+    // the last lane of the tuples of the field on the account_fields channel
+    pub(crate) fn tag(self) -> B32 {
+        B32::new(self as u32)
+    }
+}
+
+pub(crate) struct AccountFieldsData {
+    value_ptr: u32,
+    balance_item_ptr: u32,
+    storage_root_item_ptr: u32,
+}
+
+struct RowMajors {
+    value_ptr: B32,
+    value_prefix_ts: B32,
+    list_prefix_ts: B32,
+    list_len_val: B8,
+    list_len_ts: B32,
+    nonce_header_val: B8,
+    nonce_header_ts: B32,
+    nonce_payload_ptr: B32,
+    balance_item_ptr: B32,
+    balance_header_val: B8,
+    balance_header_ts: B32,
+    balance_payload_ptr: B32,
+    storage_root_item_ptr: B32,
+    storage_root_prefix_ts: B32,
+    code_hash_prefix_ts: B32,
+    g_raised_to_low_four_list_len_bits: B32,
+    g_raised_to_high_four_list_len_bits: B32,
+    g_raised_to_low_three_nonce_header_bits: B32,
+    g_raised_to_high_three_nonce_header_bits: B32,
+    g_raised_to_low_three_balance_header_bits: B32,
+    g_raised_to_high_three_balance_header_bits: B32,
+}
+
+// the payload of a string item, which is the item itself for single bytes below 0x80
+fn payload_ptr(mem: &Memory, item_ptr: u32) -> u32 {
+    match mem[item_ptr] {
+        0..=0x7f => item_ptr,
+        _ => item_ptr + 1,
+    }
+}
+
+// An account value is the string 0xb8, len, followed by the list 0xf8, len, nonce, balance,
+// 0xa0, storage_root, 0xa0, code_hash, as the list is between 56 and 255 bytes long. The nonce
+// and the balance are single bytes below 0x80 or strings of up to 55 bytes. A row per account
+// decodes where the payloads of the four items are and pushes (value_ptr, payload_ptr,
+// payload_end_ptr, field) for each of them on the account_fields channel.
+pub(crate) struct AccountFieldsTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<RowMajors>,
+}
+impl FunctionTable for AccountFieldsTable {
+    type Data = AccountFieldsData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+
+        let AccountFieldsData {
+            value_ptr,
+            balance_item_ptr,
+            storage_root_item_ptr,
+        } = data;
+        let list_len_val = mem[value_ptr + 3];
+        let nonce_header_val = mem[value_ptr + 4];
+        let balance_header_val = mem[balance_item_ptr];
+        let nonce_payload_ptr = payload_ptr(mem, value_ptr + 4);
+        let balance_payload_ptr = payload_ptr(mem, balance_item_ptr);
+        self.row_majors.push(RowMajors {
+            value_ptr: mem.to_mult(value_ptr),
+            value_prefix_ts: mem.process_timestamp(value_ptr),
+            list_prefix_ts: mem.process_timestamp(value_ptr + 2),
+            list_len_val: B8::from_underlier(list_len_val),
+            list_len_ts: mem.process_timestamp(value_ptr + 3),
+            nonce_header_val: B8::from_underlier(nonce_header_val),
+            nonce_header_ts: mem.process_timestamp(value_ptr + 4),
+            nonce_payload_ptr: mem.to_mult(nonce_payload_ptr),
+            balance_item_ptr: mem.to_mult(balance_item_ptr),
+            balance_header_val: B8::from_underlier(balance_header_val),
+            balance_header_ts: mem.process_timestamp(balance_item_ptr),
+            balance_payload_ptr: mem.to_mult(balance_payload_ptr),
+            storage_root_item_ptr: mem.to_mult(storage_root_item_ptr),
+            storage_root_prefix_ts: mem.process_timestamp(storage_root_item_ptr),
+            code_hash_prefix_ts: mem.process_timestamp(storage_root_item_ptr + 33),
+            g_raised_to_low_four_list_len_bits: g_raised_to_bits(
+                list_len_val,
+                0,
+                g_powers(0).take(4),
+            ),
+            g_raised_to_high_four_list_len_bits: g_raised_to_bits(
+                list_len_val,
+                4,
+                g_powers(4).take(4),
+            ),
+            g_raised_to_low_three_nonce_header_bits: g_raised_to_bits(
+                nonce_header_val,
+                0,
+                g_powers(0).take(3),
+            ),
+            g_raised_to_high_three_nonce_header_bits: g_raised_to_bits(
+                nonce_header_val,
+                3,
+                g_powers(3).take(3),
+            ),
+            g_raised_to_low_three_balance_header_bits: g_raised_to_bits(
+                balance_header_val,
+                0,
+                g_powers(0).take(3),
+            ),
+            g_raised_to_high_three_balance_header_bits: g_raised_to_bits(
+                balance_header_val,
+                3,
+                g_powers(3).take(3),
+            ),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "account_fields",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("account_fields");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let value_ptr = builder.add_committed("value_ptr", n_vars, B32::TOWER_LEVEL);
+        let value_prefix_ts = builder.add_committed("value_prefix_ts", n_vars, B32::TOWER_LEVEL);
+        let list_prefix_ts = builder.add_committed("list_prefix_ts", n_vars, B32::TOWER_LEVEL);
+        let list_len_val = builder.add_committed("list_len_val", n_vars, B8::TOWER_LEVEL);
+        let list_len_ts = builder.add_committed("list_len_ts", n_vars, B32::TOWER_LEVEL);
+        let nonce_header_val = builder.add_committed("nonce_header_val", n_vars, B8::TOWER_LEVEL);
+        let nonce_header_ts = builder.add_committed("nonce_header_ts", n_vars, B32::TOWER_LEVEL);
+        let nonce_payload_ptr =
+            builder.add_committed("nonce_payload_ptr", n_vars, B32::TOWER_LEVEL);
+        let balance_item_ptr = builder.add_committed("balance_item_ptr", n_vars, B32::TOWER_LEVEL);
+        let balance_header_val =
+            builder.add_committed("balance_header_val", n_vars, B8::TOWER_LEVEL);
+        let balance_header_ts =
+            builder.add_committed("balance_header_ts", n_vars, B32::TOWER_LEVEL);
+        let balance_payload_ptr =
+            builder.add_committed("balance_payload_ptr", n_vars, B32::TOWER_LEVEL);
+        let storage_root_item_ptr =
+            builder.add_committed("storage_root_item_ptr", n_vars, B32::TOWER_LEVEL);
+        let storage_root_prefix_ts =
+            builder.add_committed("storage_root_prefix_ts", n_vars, B32::TOWER_LEVEL);
+        let code_hash_prefix_ts =
+            builder.add_committed("code_hash_prefix_ts", n_vars, B32::TOWER_LEVEL);
+        let g_raised_to_low_four_list_len_bits = builder.add_committed(
+            "g_raised_to_low_four_list_len_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_high_four_list_len_bits = builder.add_committed(
+            "g_raised_to_high_four_list_len_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_low_three_nonce_header_bits = builder.add_committed(
+            "g_raised_to_low_three_nonce_header_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_high_three_nonce_header_bits = builder.add_committed(
+            "g_raised_to_high_three_nonce_header_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_low_three_balance_header_bits = builder.add_committed(
+            "g_raised_to_low_three_balance_header_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_high_three_balance_header_bits = builder.add_committed(
+            "g_raised_to_high_three_balance_header_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            // padding rows are all zero pointers and bytes
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (value_ptr, B32),
+                    (list_len_val, B8),
+                    (nonce_header_val, B8),
+                    (nonce_payload_ptr, B32),
+                    (balance_item_ptr, B32),
+                    (balance_header_val, B8),
+                    (balance_payload_ptr, B32),
+                    (storage_root_item_ptr, B32),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (value_prefix_ts, B32, B32::one()),
+                    (list_prefix_ts, B32, B32::one()),
+                    (list_len_ts, B32, B32::one()),
+                    (nonce_header_ts, B32, B32::one()),
+                    (balance_header_ts, B32, B32::one()),
+                    (storage_root_prefix_ts, B32, B32::one()),
+                    (code_hash_prefix_ts, B32, B32::one()),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (g_raised_to_low_four_list_len_bits, B32, B32::one()),
+                    (g_raised_to_high_four_list_len_bits, B32, B32::one()),
+                    (g_raised_to_low_three_nonce_header_bits, B32, B32::one()),
+                    (g_raised_to_high_three_nonce_header_bits, B32, B32::one()),
+                    (g_raised_to_low_three_balance_header_bits, B32, B32::one()),
+                    (g_raised_to_high_three_balance_header_bits, B32, B32::one()),
+                ]
+            );
+        }
+
+        let list_len_val_bits =
+            builder.add_committed_multiple::<8>("list_len_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, list_len_val, list_len_val_bits)?;
+        let nonce_header_val_bits =
+            builder.add_committed_multiple::<8>("nonce_header_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, nonce_header_val, nonce_header_val_bits)?;
+        let balance_header_val_bits =
+            builder.add_committed_multiple::<8>("balance_header_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, balance_header_val, balance_header_val_bits)?;
+
+        // mem reads
+        {
+            read_const(
+                builder,
+                "value_prefix",
+                value_ptr,
+                B8::new(0xb8),
+                value_prefix_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_const_at_offset(
+                builder,
+                "list_prefix",
+                value_ptr,
+                2,
+                B8::new(0xf8),
+                list_prefix_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_mem_at_offset(
+                builder,
+                "list_len_val",
+                value_ptr,
+                3,
+                list_len_val,
+                list_len_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_mem_at_offset(
+                builder,
+                "nonce_header_val",
+                value_ptr,
+                4,
+                nonce_header_val,
+                nonce_header_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_mem(
+                builder,
+                "balance_header_val",
+                balance_item_ptr,
+                balance_header_val,
+                balance_header_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_const(
+                builder,
+                "storage_root_prefix",
+                storage_root_item_ptr,
+                B8::new(0xa0),
+                storage_root_prefix_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_const_at_offset(
+                builder,
+                "code_hash_prefix",
+                storage_root_item_ptr,
+                1 + 32,
+                B8::new(0xa0),
+                code_hash_prefix_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        assert_byte_bits(builder, list_len_val, list_len_val_bits);
+        assert_byte_bits(builder, nonce_header_val, nonce_header_val_bits);
+        assert_byte_bits(builder, balance_header_val, balance_header_val_bits);
+        // the nonce and the balance are a single byte below 0x80 or a string of up to 55 bytes,
+        // so b7 == 1 implies b6 == 0 and not all of b5, b4, b3
+        for header_val_bits in [nonce_header_val_bits, balance_header_val_bits] {
+            builder.assert_zero(
+                [header_val_bits[7], header_val_bits[6]],
+                binius_macros::arith_expr!(B1[b7, b6] = b7 * b6).convert_field(),
+            );
+            builder.assert_zero(
+                [
+                    header_val_bits[7],
+                    header_val_bits[5],
+                    header_val_bits[4],
+                    header_val_bits[3],
+                ],
+                binius_macros::arith_expr!(B1[b7, b5, b4, b3] = b7 * b5 * b4 * b3).convert_field(),
+            );
+        }
+        // check the g_raised_to columns against the bits they are raised to
+        {
+            for (bits, first_exponent_bit, column) in [
+                (
+                    &list_len_val_bits[0..4],
+                    0,
+                    g_raised_to_low_four_list_len_bits,
+                ),
+                (
+                    &list_len_val_bits[4..8],
+                    4,
+                    g_raised_to_high_four_list_len_bits,
+                ),
+                (
+                    &nonce_header_val_bits[0..3],
+                    0,
+                    g_raised_to_low_three_nonce_header_bits,
+                ),
+                (
+                    &nonce_header_val_bits[3..6],
+                    3,
+                    g_raised_to_high_three_nonce_header_bits,
+                ),
+                (
+                    &balance_header_val_bits[0..3],
+                    0,
+                    g_raised_to_low_three_balance_header_bits,
+                ),
+                (
+                    &balance_header_val_bits[3..6],
+                    3,
+                    g_raised_to_high_three_balance_header_bits,
+                ),
+            ] {
+                assert_g_raised_to_bits(builder, bits, first_exponent_bit, column);
+            }
+        }
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        // payload_ptr == item_ptr * ((1 - b7) + b7 * g), the header of a string is skipped
+        // next_item_ptr == item_ptr * g * ((1 - b7) + b7 * g^(header_val - 0x80))
+        for (
+            item_ptr,
+            item_offset,
+            header_val_bits,
+            g_raised_to_low,
+            g_raised_to_high,
+            payload,
+            next_item,
+        ) in [
+            (
+                value_ptr,
+                4,
+                nonce_header_val_bits,
+                g_raised_to_low_three_nonce_header_bits,
+                g_raised_to_high_three_nonce_header_bits,
+                nonce_payload_ptr,
+                balance_item_ptr,
+            ),
+            (
+                balance_item_ptr,
+                0,
+                balance_header_val_bits,
+                g_raised_to_low_three_balance_header_bits,
+                g_raised_to_high_three_balance_header_bits,
+                balance_payload_ptr,
+                storage_root_item_ptr,
+            ),
+        ] {
+            let item_ptr_expr = ArithExpr::Var(0) * ArithExpr::Const(g.pow([item_offset]));
+            let b7 = ArithExpr::Var(1);
+            let is_single_byte = ArithExpr::one() - b7.clone();
+            builder.assert_zero(
+                [item_ptr, header_val_bits[7], payload],
+                (ArithExpr::Var(2)
+                    - item_ptr_expr.clone()
+                        * (is_single_byte.clone() + b7.clone() * ArithExpr::Const(g)))
+                .convert_field(),
+            );
+            builder.assert_zero(
+                [
+                    item_ptr,
+                    header_val_bits[7],
+                    next_item,
+                    g_raised_to_low,
+                    g_raised_to_high,
+                ],
+                (ArithExpr::Var(2)
+                    - item_ptr_expr
+                        * ArithExpr::Const(g)
+                        * (is_single_byte + b7 * ArithExpr::Var(3) * ArithExpr::Var(4)))
+                .convert_field(),
+            );
+        }
+        // the code hash ends the list
+        // storage_root_item_ptr * g^66 == value_ptr * g^4 * g^list_len_val
+        builder.assert_zero(
+            [
+                storage_root_item_ptr,
+                value_ptr,
+                g_raised_to_low_four_list_len_bits,
+                g_raised_to_high_four_list_len_bits,
+            ],
+            (ArithExpr::Var(0) * ArithExpr::Const(g.pow([2 * (1 + 32)]))
+                - ArithExpr::Var(1)
+                    * ArithExpr::Const(g.pow([4]))
+                    * ArithExpr::Var(2)
+                    * ArithExpr::Var(3))
+            .convert_field(),
+        );
+        // to account_fields push (value_ptr, payload_ptr, payload_end_ptr, field)
+        for (field, lanes) in izip!(
+            AccountField::ALL,
+            [
+                [
+                    (value_ptr, B32::ONE),
+                    (nonce_payload_ptr, B32::ONE),
+                    (balance_item_ptr, B32::ONE),
+                ],
+                [
+                    (value_ptr, B32::ONE),
+                    (balance_payload_ptr, B32::ONE),
+                    (storage_root_item_ptr, B32::ONE),
+                ],
+                [
+                    (value_ptr, B32::ONE),
+                    (storage_root_item_ptr, g),
+                    (storage_root_item_ptr, g.pow([1 + 32])),
+                ],
+                [
+                    (value_ptr, B32::ONE),
+                    (storage_root_item_ptr, g.pow([1 + 32 + 1])),
+                    (storage_root_item_ptr, g.pow([2 * (1 + 32)])),
+                ],
+            ]
+        ) {
+            let block = ptr_lanes_block_with_offset(
+                builder,
+                format!("{field:?} block"),
+                n_vars,
+                basis(32, 3) * field.tag(),
+                &lanes,
+            )?;
+            builder.send(channel_ids.account_fields, count, [block]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // decodes the account value at value_ptr, which the verifier read as part of the statement
+    // unless it is withheld, and returns where the payloads of the fields are
+    pub(crate) fn account_fields(&mut self, value_ptr: u32) -> [Range<u32>; 4] {
+        assert_eq!(self.state.mem[value_ptr], 0xb8);
+        assert_eq!(self.state.mem[value_ptr + 2], 0xf8);
+        let balance_item_ptr = self.skip_item(value_ptr + 4);
+        let storage_root_item_ptr = self.skip_item(balance_item_ptr);
+        let nonce_payload_ptr = payload_ptr(&self.state.mem, value_ptr + 4);
+        let balance_payload_ptr = payload_ptr(&self.state.mem, balance_item_ptr);
+        self.account_fields_table.append(
+            &mut self.state,
+            AccountFieldsData {
+                value_ptr,
+                balance_item_ptr,
+                storage_root_item_ptr,
+            },
+        );
+        [
+            nonce_payload_ptr..balance_item_ptr,
+            balance_payload_ptr..storage_root_item_ptr,
+            storage_root_item_ptr + 1..storage_root_item_ptr + 1 + 32,
+            storage_root_item_ptr + 1 + 32 + 1..storage_root_item_ptr + 2 * (1 + 32),
+        ]
+    }
+}
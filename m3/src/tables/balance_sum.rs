@@ -0,0 +1,211 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+use std::ops::Range;
+
+// the tuple the sum of the balances is passed along by on partial_balance_sum
+pub(crate) fn partial_balance_sum_tuple(sum: U256) -> [B128; 2] {
+    u256_halves(sum).map(B128::new)
+}
+
+pub(crate) struct BalanceSumData {
+    balance: BalanceData,
+    sum_in: U256,
+}
+
+struct BalanceSumRowMajors {
+    sum_in: U256,
+    sum_out: U256,
+    carries: U256,
+}
+
+// A row per account reads its balance like BalanceAtLeast does and adds it to the sum of the
+// balances of the rows before, with no carry out of the top bit. It pulls (value_ptr,
+// payload_ptr, payload_end_ptr, balance) from account_fields and (value_ptr) from
+// balance_sum, which the verifier pushes, and pulls (sum_in) from and pushes (sum_out) to
+// partial_balance_sum, which the verifier starts at zero and ends at the sum it reads.
+pub(crate) struct BalanceSumTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    balance_row_majors: Vec<BalanceRowMajors>,
+    row_majors: Vec<BalanceSumRowMajors>,
+}
+impl FunctionTable for BalanceSumTable {
+    type Data = BalanceSumData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            balance_row_majors: vec![],
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.balance_row_majors = Vec::with_capacity(count);
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let BalanceSumData { balance, sum_in } = data;
+        let balance_row_majors = BalanceRowMajors::new(mpt_state, balance);
+        let balance = balance_row_majors.balance;
+        // the carry out of every bit of sum_in + balance
+        let mut carries = U256::ZERO;
+        let mut carry = false;
+        for i in 0..256 {
+            let (sum_bit, balance_bit) = (sum_in.bit(i), balance.bit(i));
+            carry = (sum_bit && balance_bit) || (carry && (sum_bit != balance_bit));
+            carries.set_bit(i, carry);
+        }
+        assert!(!carry, "the sum of the balances overflows");
+        self.balance_row_majors.push(balance_row_majors);
+        self.row_majors.push(BalanceSumRowMajors {
+            sum_in,
+            sum_out: sum_in + balance,
+            carries,
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "balance_sum",
+        fields(n_vars = table_height.n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        let TableHeight { n_vars, count } = table_height;
+        builder.push_namespace("balance_sum");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let balance = build_balance(builder, channel_ids, table_height, &self.balance_row_majors)?;
+        let row = |i: usize| self.row_majors.get(i);
+        let sum_in_bits =
+            builder.add_committed_multiple::<256>("sum_in_bits", n_vars, B1::TOWER_LEVEL);
+        populate_bits(builder, &sum_in_bits, |i, k| {
+            row(i).is_some_and(|row| row.sum_in.bit(k))
+        });
+        let sum_out_bits =
+            builder.add_committed_multiple::<256>("sum_out_bits", n_vars, B1::TOWER_LEVEL);
+        populate_bits(builder, &sum_out_bits, |i, k| {
+            row(i).is_some_and(|row| row.sum_out.bit(k))
+        });
+        let carries = builder.add_committed_multiple::<255>("carries", n_vars, B1::TOWER_LEVEL);
+        populate_bits(builder, &carries, |i, k| {
+            row(i).is_some_and(|row| row.carries.bit(k))
+        });
+
+        // sum_in + balance
+        // sum_out_bit == sum_in_bit + balance_bit + carry_in
+        // carry_out == sum_in_bit * balance_bit + carry_in * (sum_in_bit + balance_bit)
+        // with no carry into the least significant bit nor out of the most significant one
+        for i in 0..256 {
+            let mut oracle_ids = balance.bit(i).to_vec();
+            oracle_ids.extend([sum_in_bits[i], sum_out_bits[i]]);
+            let balance_bit = ArithExpr::<B1>::Var(0) * ArithExpr::Var(1);
+            let sum_in_bit = ArithExpr::Var(2);
+            let mut sum_out_expr = sum_in_bit.clone() + balance_bit.clone() - ArithExpr::Var(3);
+            let mut carry_expr = sum_in_bit.clone() * balance_bit.clone();
+            if i > 0 {
+                let carry_in = ArithExpr::Var(oracle_ids.len());
+                oracle_ids.push(carries[i - 1]);
+                sum_out_expr = sum_out_expr + carry_in.clone();
+                carry_expr = carry_expr + carry_in * (sum_in_bit + balance_bit);
+            }
+            if i < 255 {
+                carry_expr = carry_expr - ArithExpr::Var(oracle_ids.len());
+                oracle_ids.push(carries[i]);
+            }
+            builder.assert_zero(oracle_ids.clone(), sum_out_expr.convert_field());
+            builder.assert_zero(oracle_ids, carry_expr.convert_field());
+        }
+        // from balance_sum pull (value_ptr)
+        let value_block = ptr_lanes_block(
+            builder,
+            "value block",
+            n_vars,
+            &[(balance.value_ptr, B32::ONE)],
+        )?;
+        builder.receive(channel_ids.balance_sum, count, [value_block]);
+        // from partial_balance_sum pull (sum_in)
+        let sum_in_blocks = u256_blocks(
+            builder,
+            "sum_in",
+            n_vars,
+            &sum_in_bits,
+            |i| self.row_majors[i].sum_in,
+            count,
+        )?;
+        builder.receive(channel_ids.partial_balance_sum, count, sum_in_blocks);
+        // to partial_balance_sum push (sum_out)
+        let sum_out_blocks = u256_blocks(
+            builder,
+            "sum_out",
+            n_vars,
+            &sum_out_bits,
+            |i| self.row_majors[i].sum_out,
+            count,
+        )?;
+        builder.send(channel_ids.partial_balance_sum, count, sum_out_blocks);
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // a row per account, after account_fields decoded the values, and a sink row per field
+    // other than the balance
+    pub(crate) fn append_balance_sum(
+        &mut self,
+        leaf_rlp_ptr_len_pairs: &[(u32, u32)],
+        field_ranges: &[[Range<u32>; 4]],
+    ) {
+        if self.statement.balance_sum().is_none() {
+            return;
+        }
+        let mut sum = U256::ZERO;
+        for (&(value_ptr, _), fields) in izip!(leaf_rlp_ptr_len_pairs, field_ranges) {
+            let [_, balance, _, _] = fields.clone();
+            let balance_value = U256::from_be_slice(&self.state.mem[balance.clone()]);
+            self.balance_sum_table.append(
+                &mut self.state,
+                BalanceSumData {
+                    balance: BalanceData { value_ptr, balance },
+                    sum_in: sum,
+                },
+            );
+            sum += balance_value;
+            for (field, payload) in izip!(AccountField::ALL, fields.clone()) {
+                if field != AccountField::Balance {
+                    self.account_fields_sink_table.append(
+                        &mut self.state,
+                        account_field_tuple(value_ptr, field, payload),
+                    );
+                }
+            }
+        }
+        debug_assert_eq!(self.statement.balance_sum(), Some(sum));
+    }
+}
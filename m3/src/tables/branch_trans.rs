@@ -2,12 +2,14 @@
 
 use super::*;
 
+#[derive(PartialEq)]
 pub(crate) struct BranchTransData {
-    state: State,
+    pub(crate) state: State,
     first_child_ptr: u32,
     first_child_val: u8,
     key_val: u8,
     new_hash_ptr: u32,
+    new_hash_val: u8,
     new_key_ptr: NibPtr,
 }
 
@@ -28,6 +30,8 @@ struct RowMajors {
     index_nib: B8,
     shift_lookup_ts: B32,
     new_hash_ptr: B32,
+    new_hash_val: B8,
+    new_hash_ts: B32,
     new_key_ptr_byte: B32,
 }
 
@@ -41,6 +45,7 @@ pub(crate) struct BranchTransTable {
 }
 impl FunctionTable for BranchTransTable {
     type Data = BranchTransData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -53,6 +58,9 @@ impl FunctionTable for BranchTransTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.col_majors = ColMajors {
             state_key_ptr_parity: BitVec::new(Some(count)),
@@ -60,10 +68,7 @@ impl FunctionTable for BranchTransTable {
         self.row_majors = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -87,6 +92,8 @@ impl FunctionTable for BranchTransTable {
                 .branch_trans_shift_lookup
                 .process_timestamp(data.state.key_ptr.parity, data.key_val),
             new_hash_ptr: mem.to_mult(data.new_hash_ptr),
+            new_hash_val: B8::from_underlier(data.new_hash_val),
+            new_hash_ts: mem.process_timestamp(data.new_hash_ptr),
             new_key_ptr_byte: mem.to_mult(data.new_key_ptr.byte),
         });
 
@@ -124,6 +131,8 @@ impl FunctionTable for BranchTransTable {
         let index_nib = builder.add_committed("index_nib", n_vars, B8::TOWER_LEVEL);
         let shift_lookup_ts = builder.add_committed("shift_lookup_ts", n_vars, B32::TOWER_LEVEL);
         let new_hash_ptr = builder.add_committed("new_hash_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_hash_val = builder.add_committed("new_hash_val", n_vars, B8::TOWER_LEVEL);
+        let new_hash_ts = builder.add_committed("new_hash_ts", n_vars, B32::TOWER_LEVEL);
         let new_key_ptr_byte = builder.add_committed("new_key_byte_ptr", n_vars, B32::TOWER_LEVEL);
 
         if let Some(witness) = builder.witness() {
@@ -151,6 +160,8 @@ impl FunctionTable for BranchTransTable {
                     (first_child_val, B8, B8::new(128)),
                     (first_child_ts, B32, B32::one()),
                     (key_ts, B32, B32::one()),
+                    (new_hash_val, B8, B8::new(128)),
+                    (new_hash_ts, B32, B32::one()),
                 ]
             );
         }
@@ -186,6 +197,13 @@ impl FunctionTable for BranchTransTable {
                 );
         }
 
+        let first_child_val_bits =
+            builder.add_committed_multiple::<8>("first_child_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, first_child_val, first_child_val_bits)?;
+        let new_hash_val_bits =
+            builder.add_committed_multiple::<8>("new_hash_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, new_hash_val, new_hash_val_bits)?;
+
         // from state pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
         {
             flush_state(
@@ -247,6 +265,16 @@ impl FunctionTable for BranchTransTable {
                 count,
                 channel_ids.mem,
             )?;
+            read_mem(
+                builder,
+                "new_hash_val",
+                new_hash_ptr,
+                new_hash_val,
+                new_hash_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
         }
         // to get_child push (first_child_ptr, index_nib, new_hash_ptr)
         {
@@ -365,12 +393,13 @@ impl FunctionTable for BranchTransTable {
                 );
             }
         }
-        // first_child_val == 0x80 || first_child_val == 0xa0
-        {
-            let composition = (ArithExpr::Var(0) + ArithExpr::Const(B8::new(128)))
-                * (ArithExpr::Var(0) + ArithExpr::Const(B8::new(160)));
-            builder.assert_zero([first_child_val], composition.convert_field());
-        }
+        // the first child is a child reference, as a leaf or extension starts with its path,
+        // which is never empty and never a list
+        assert_byte_bits(builder, first_child_val, first_child_val_bits);
+        assert_child_reference(builder, first_child_val_bits, None);
+        // the child the walk goes on to is hashed unless it is shorter than 32 bytes
+        assert_byte_bits(builder, new_hash_val, new_hash_val_bits);
+        assert_child_reference(builder, new_hash_val_bits, None);
         // new_key_ptr_byte == state_key_ptr_byte * (state_key_ptr_parity ? g : 1)
         {
             let composition = {
@@ -391,17 +420,19 @@ impl FunctionTable for BranchTransTable {
 }
 
 impl MPT {
-    pub(crate) fn branch_trans(&mut self, action: Action, state: State) -> State {
-        let first_child_ptr = self.skip_list_header(action, state.rlp_ptr);
+    pub(crate) fn branch_trans(&self, rows: &mut Vec<Row>, state: State) -> State {
+        let first_child_ptr = self.skip_list_header(rows, state.rlp_ptr);
         let first_child_val = self.state.mem[first_child_ptr];
-        assert!(first_child_val == 0x80 || first_child_val == 0xa0);
+        assert!(MPT::is_child_reference(first_child_val));
         let key_val = self.state.mem[state.key_ptr.byte];
         let shift = match state.key_ptr.parity {
             true => 0,
             false => 4,
         };
         let index_nib = (key_val >> shift) & 0x0f;
-        let new_hash_ptr = self.get_child(action, first_child_ptr, index_nib);
+        let new_hash_ptr = self.get_child(rows, first_child_ptr, index_nib);
+        // checked in-circuit only, a longer child passed off as inlined fails the constraints
+        let new_hash_val = self.state.mem[new_hash_ptr];
         let new_key_ptr = match state.key_ptr.parity {
             true => NibPtr {
                 byte: state.key_ptr.byte + 1,
@@ -418,21 +449,16 @@ impl MPT {
             rlp_ptr: new_hash_ptr,
             ts: state.ts,
         };
-        match action {
-            Action::Append => self.branch_trans_table.append(
-                &mut self.state,
-                BranchTransData {
-                    state,
-                    first_child_ptr,
-                    first_child_val,
-                    key_val,
-                    new_hash_ptr,
-                    new_key_ptr,
-                },
-            ),
-            Action::Count => self.branch_trans_count += 1,
-            Action::Ignore => (),
-        }
+        let data = BranchTransData {
+            state,
+            first_child_ptr,
+            first_child_val,
+            key_val,
+            new_hash_ptr,
+            new_hash_val,
+            new_key_ptr,
+        };
+        rows.push(Row::BranchTrans(data));
         new_state
     }
 }
@@ -6,6 +6,8 @@ pub(crate) struct BranchTransShiftLookupTable {
 }
 
 impl BranchTransShiftLookupTable {
+    pub(crate) const N_VARS: usize = 9;
+
     pub(crate) fn new() -> Self {
         Self {
             final_ts: Vec::with_capacity(256),
@@ -33,7 +35,7 @@ impl BranchTransShiftLookupTable {
         builder: &mut ConstraintSystemBuilder<U, B128>,
         channel_ids: &ChannelIds,
     ) -> Result<(), anyhow::Error> {
-        let n_vars = 9;
+        let n_vars = Self::N_VARS;
         let final_ts = builder.add_committed("final_ts", n_vars, B32::TOWER_LEVEL);
 
         if let Some(witness) = builder.witness() {
@@ -0,0 +1,292 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+// the value of a branch is its 17th item, after the 16 children
+pub(crate) const VALUE_INDEX: usize = 16;
+
+#[derive(PartialEq)]
+pub(crate) struct BranchValueTransData {
+    pub(crate) state: State,
+    first_child_ptr: u32,
+    first_child_val: u8,
+    value_ptr: u32,
+}
+
+struct ColMajors {
+    state_key_ptr_parity: BitVec,
+}
+
+struct RowMajors {
+    state_start_ptr: B32,
+    state_key_ptr_byte: B32,
+    state_rlp_ptr: B32,
+    state_ts: B32,
+    first_child_ptr: B32,
+    first_child_val: B8,
+    first_child_ts: B32,
+    value_ptr: B32,
+}
+
+pub(crate) struct BranchValueTransTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: ColMajors,
+    row_majors: Vec<RowMajors>,
+}
+impl FunctionTable for BranchValueTransTable {
+    type Data = BranchValueTransData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: ColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors = ColMajors {
+            state_key_ptr_parity: BitVec::new(Some(count)),
+        };
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.row_majors.push(RowMajors {
+            state_start_ptr: mem.to_mult(data.state.start_ptr),
+            state_key_ptr_byte: mem.to_mult(data.state.key_ptr.byte),
+            state_rlp_ptr: mem.to_mult(data.state.rlp_ptr),
+            state_ts: data.state.ts,
+            first_child_ptr: mem.to_mult(data.first_child_ptr),
+            first_child_val: B8::from_underlier(data.first_child_val),
+            first_child_ts: mem.process_timestamp(data.first_child_ptr),
+            value_ptr: mem.to_mult(data.value_ptr),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "branch_value_trans",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("branch_value_trans");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let state_start_ptr = builder.add_committed("state_start_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_key_ptr_byte =
+            builder.add_committed("state_key_ptr_byte", n_vars, B32::TOWER_LEVEL);
+        let state_rlp_ptr = builder.add_committed("state_rlp_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_ts = builder.add_committed("state_ts", n_vars, B32::TOWER_LEVEL);
+        let first_child_ptr = builder.add_committed("first_child_ptr", n_vars, B32::TOWER_LEVEL);
+        let first_child_val = builder.add_committed("first_child_val", n_vars, B8::TOWER_LEVEL);
+        let first_child_ts = builder.add_committed("first_child_ts", n_vars, B32::TOWER_LEVEL);
+        let value_ptr = builder.add_committed("value_ptr", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (state_start_ptr, B32),
+                    (state_key_ptr_byte, B32),
+                    (state_rlp_ptr, B32),
+                    (first_child_ptr, B32),
+                    (value_ptr, B32)
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (state_ts, B32, B32::one()),
+                    (first_child_val, B8, B8::new(128)),
+                    (first_child_ts, B32, B32::one()),
+                ]
+            );
+        }
+
+        let state_key_ptr_parity =
+            builder.add_committed("state_key_ptr_parity", n_vars, B1::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            (
+                self.col_majors.state_key_ptr_parity,
+                witness
+                    .new_column::<B1>(state_key_ptr_parity)
+                    .as_mut_slice::<u8>(),
+            )
+                .into_par_iter()
+                .for_each(|(src, dest)| {
+                    *dest = src;
+                });
+        }
+
+        let first_child_val_bits =
+            builder.add_committed_multiple::<8>("first_child_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, first_child_val, first_child_val_bits)?;
+
+        // from state pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Pull,
+                state_start_ptr,
+                state_key_ptr_byte,
+                state_rlp_ptr,
+                state_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.state,
+            )?;
+        }
+        // to skip_list_header push (state_rlp_ptr, first_child_ptr)
+        {
+            let block0 = builder.add_linear_combination(
+                "flush 0, block 0",
+                n_vars,
+                [
+                    (state_rlp_ptr, basis(32, 0)),
+                    (first_child_ptr, basis(32, 1)),
+                ],
+            )?;
+            if let Some(witness) = builder.witness() {
+                (
+                    witness.new_column::<B128>(block0).as_mut_slice::<u128>(),
+                    witness.get::<B32>(state_rlp_ptr)?.as_slice::<u32>(),
+                    witness.get::<B32>(first_child_ptr)?.as_slice::<u32>(),
+                )
+                    .into_par_iter()
+                    .for_each(|(block, state_rlp_ptr, first_child_ptr)| {
+                        *block = (*first_child_ptr as u128) << 32 | (*state_rlp_ptr as u128);
+                    });
+            }
+
+            builder.send(channel_ids.skip_list_header, count, [block0]);
+        }
+        // mem reads
+        {
+            read_mem(
+                builder,
+                "first_child_val",
+                first_child_ptr,
+                first_child_val,
+                first_child_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // to get_child push (first_child_ptr, 16, value_ptr)
+        {
+            let value_index = U8_TO_MULT_MAP[VALUE_INDEX];
+            let block0 = builder.add_linear_combination_with_offset(
+                "flush k, block 0",
+                n_vars,
+                value_index * basis(32, 1),
+                [(first_child_ptr, basis(32, 0)), (value_ptr, basis(32, 2))],
+            )?;
+            if let Some(witness) = builder.witness() {
+                (
+                    witness.new_column::<B128>(block0).as_mut_slice::<u128>(),
+                    witness.get::<B32>(first_child_ptr)?.as_slice::<u32>(),
+                    witness.get::<B32>(value_ptr)?.as_slice::<u32>(),
+                )
+                    .into_par_iter()
+                    .for_each(|(block, first_child_ptr, value_ptr)| {
+                        *block = (*value_ptr as u128) << 64
+                            | (value_index.to_underlier() as u128) << 32
+                            | (*first_child_ptr as u128);
+                    });
+            }
+
+            builder.send(channel_ids.get_child, count, [block0]);
+        }
+        // to state push (state_start_ptr, state_key_ptr_byte, value_ptr, state_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Push,
+                state_start_ptr,
+                state_key_ptr_byte,
+                value_ptr,
+                state_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.state,
+            )?;
+        }
+        // the first child is a child reference, as in branch_trans
+        assert_byte_bits(builder, first_child_val, first_child_val_bits);
+        assert_child_reference(builder, first_child_val_bits, None);
+        // the key is exhausted: state_key_ptr_byte == state_start_ptr * g^32 and state_key_ptr_parity == 0
+        {
+            let g_32 = B32::MULTIPLICATIVE_GENERATOR.pow([32]);
+            builder.assert_zero(
+                [state_key_ptr_byte, state_start_ptr],
+                (ArithExpr::Var(0) - ArithExpr::Var(1) * ArithExpr::Const(g_32)).convert_field(),
+            );
+            builder.assert_zero(
+                [state_key_ptr_parity],
+                binius_macros::arith_expr!(B1[parity] = parity).convert_field(),
+            );
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // the transition out of a branch once the key is exhausted, to its value rather than a child
+    pub(crate) fn branch_value_trans(&self, rows: &mut Vec<Row>, state: State) -> State {
+        assert_eq!(state.key_ptr, NibPtr::new(state.start_ptr + 32, false));
+        let first_child_ptr = self.skip_list_header(rows, state.rlp_ptr);
+        let first_child_val = self.state.mem[first_child_ptr];
+        assert!(MPT::is_child_reference(first_child_val));
+        let value_ptr = self.get_child(rows, first_child_ptr, VALUE_INDEX as u8);
+        let new_state = State {
+            rlp_ptr: value_ptr,
+            ..state
+        };
+        let data = BranchValueTransData {
+            state,
+            first_child_ptr,
+            first_child_val,
+            value_ptr,
+        };
+        rows.push(Row::BranchValueTrans(data));
+        new_state
+    }
+}
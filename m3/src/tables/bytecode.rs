@@ -0,0 +1,189 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+// the tuple the verifier pushes on bytecode for the code of the value at value_ptr
+pub(crate) fn bytecode_tuple(value_ptr: u32, code_ptr: u32) -> B128 {
+    let g = B32::MULTIPLICATIVE_GENERATOR;
+    g.pow([value_ptr as u64]) * basis(32, 0) + g.pow([code_ptr as u64]) * basis(32, 1)
+}
+
+// the code at code_ptr of the value at value_ptr, whose code hash item is at code_hash_item_ptr
+#[derive(PartialEq)]
+pub(crate) struct BytecodeData {
+    value_ptr: u32,
+    code_ptr: u32,
+    code_hash_item_ptr: u32,
+}
+
+struct RowMajors {
+    value_ptr: B32,
+    code_ptr: B32,
+    code_hash_item_ptr: B32,
+}
+
+// A row per code pulls (value_ptr, code_ptr) from bytecode, which the verifier pushes, and
+// pulls (value_ptr, code_hash_ptr, code_hash_end_ptr, code_hash) from account_fields, pushing
+// it back for the table consuming the code hash otherwise. It pushes (code_ptr,
+// code_hash_item_ptr, initial_state) to absorb_block, whose base row reads the hash the code
+// is absorbed into from the payload of the item, the code hash.
+pub(crate) struct BytecodeTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<RowMajors>,
+}
+impl FunctionTable for BytecodeTable {
+    type Data = BytecodeData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mpt_state.mem;
+        self.row_majors.push(RowMajors {
+            value_ptr: mem.to_mult(data.value_ptr),
+            code_ptr: mem.to_mult(data.code_ptr),
+            code_hash_item_ptr: mem.to_mult(data.code_hash_item_ptr),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "bytecode",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("bytecode");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let value_ptr = builder.add_committed("value_ptr", n_vars, B32::TOWER_LEVEL);
+        let code_ptr = builder.add_committed("code_ptr", n_vars, B32::TOWER_LEVEL);
+        let code_hash_item_ptr =
+            builder.add_committed("code_hash_item_ptr", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [(value_ptr, B32), (code_ptr, B32), (code_hash_item_ptr, B32),]
+            );
+        }
+
+        let poly = binius_core::transparent::constant::Constant::new(n_vars, B128::zero());
+        let initial_state = (0..12)
+            .map(|_| builder.add_transparent("initial_state", poly))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(witness) = builder.witness() {
+            initial_state.iter().for_each(|id| {
+                witness.new_column::<B128>(*id);
+            });
+        }
+
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        // from bytecode pull (value_ptr, code_ptr)
+        let bytecode_block = ptr_lanes_block(
+            builder,
+            "bytecode block",
+            n_vars,
+            &[(value_ptr, B32::ONE), (code_ptr, B32::ONE)],
+        )?;
+        builder.receive(channel_ids.bytecode, count, [bytecode_block]);
+        // from account_fields pull (value_ptr, code_hash_item_ptr * g, code_hash_item_ptr * g^33,
+        // code_hash) and push it back
+        let code_hash_block = ptr_lanes_block_with_offset(
+            builder,
+            "code_hash block",
+            n_vars,
+            basis(32, 3) * AccountField::CodeHash.tag(),
+            &[
+                (value_ptr, B32::ONE),
+                (code_hash_item_ptr, g),
+                (code_hash_item_ptr, g.pow([33])),
+            ],
+        )?;
+        builder.receive(channel_ids.account_fields, count, [code_hash_block]);
+        builder.send(channel_ids.account_fields, count, [code_hash_block]);
+        // to absorb_block push (code_ptr, code_hash_item_ptr, initial_state)
+        let block0 = ptr_lanes_block(
+            builder,
+            "block 0, flush 0",
+            n_vars,
+            &[(code_ptr, B32::ONE), (code_hash_item_ptr, B32::ONE)],
+        )?;
+        let mut all_blocks = vec![block0];
+        all_blocks.extend(initial_state);
+        builder.send(channel_ids.absorb_block, count, all_blocks);
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // a row per code, absorbed from code_ptr into the code hash of the value at value_ptr, whose
+    // item ends the value
+    pub(crate) fn bytecode(
+        &self,
+        rows: &mut Vec<Row>,
+        code_ptr: u32,
+        code_length: usize,
+        value_ptr: u32,
+        value_end_ptr: u32,
+    ) {
+        let code_hash_item_ptr = value_end_ptr - 1 - 32;
+        assert_eq!(self.state.mem[code_hash_item_ptr], 0xa0);
+        let total_blocks = (code_length / HASH_R + 1) as u8;
+        if !self.session.count_only() {
+            let hash_input = &self.state.mem[code_ptr..code_ptr + code_length as u32];
+            let hash = alloy::primitives::keccak256(hash_input);
+            for j in 0..32 {
+                assert_eq!(self.state.mem[code_hash_item_ptr + 1 + j], hash[j as usize]);
+            }
+        }
+        // all the above is dev checks and prover computation
+
+        self.absorb_block(
+            rows,
+            [0; HASH_B],
+            code_ptr,
+            code_hash_item_ptr,
+            total_blocks,
+        );
+        rows.push(Row::Bytecode(BytecodeData {
+            value_ptr,
+            code_ptr,
+            code_hash_item_ptr,
+        }));
+    }
+}
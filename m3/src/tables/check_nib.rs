@@ -6,19 +6,20 @@ use std::array;
 
 impl MPT {
     pub(crate) fn check_nib(
-        &mut self,
-        action: Action,
+        &self,
+        rows: &mut Vec<Row>,
         target_ptr: NibPtr,
         key_ptr: NibPtr,
         nib_ptr: NibPtr,
     ) -> NibPtr {
         match nib_ptr == target_ptr {
-            true => self.check_nib_base(action, target_ptr, key_ptr, nib_ptr),
-            false => self.check_nib_recursive(action, target_ptr, key_ptr, nib_ptr),
+            true => self.check_nib_base(rows, target_ptr, key_ptr, nib_ptr),
+            false => self.check_nib_recursive(rows, target_ptr, key_ptr, nib_ptr),
         }
     }
 }
 
+#[derive(PartialEq)]
 pub(crate) struct CheckNibBaseData {
     nib_ptr: NibPtr,
     key_ptr: NibPtr,
@@ -35,6 +36,7 @@ pub(crate) struct CheckNibBaseTable {
 }
 impl FunctionTable for CheckNibBaseTable {
     type Data = CheckNibBaseData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -47,6 +49,9 @@ impl FunctionTable for CheckNibBaseTable {
             key_ptr_parity: BitVec::new(None),
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.nib_ptr_byte = Vec::with_capacity(count);
         self.nib_ptr_parity = BitVec::new(Some(count));
@@ -54,10 +59,7 @@ impl FunctionTable for CheckNibBaseTable {
         self.key_ptr_parity = BitVec::new(Some(count));
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -213,24 +215,20 @@ impl FunctionTable for CheckNibBaseTable {
 
 impl MPT {
     fn check_nib_base(
-        &mut self,
-        action: Action,
+        &self,
+        rows: &mut Vec<Row>,
         target_ptr: NibPtr,
         key_ptr: NibPtr,
         nib_ptr: NibPtr,
     ) -> NibPtr {
         assert_eq!(target_ptr, nib_ptr);
-        match action {
-            Action::Append => self
-                .check_nib_base_table
-                .append(&mut self.state, CheckNibBaseData { nib_ptr, key_ptr }),
-            Action::Count => self.check_nib_base_count += 1,
-            Action::Ignore => (),
-        }
+        let data = CheckNibBaseData { nib_ptr, key_ptr };
+        rows.push(Row::CheckNibBase(data));
         key_ptr
     }
 }
 
+#[derive(PartialEq)]
 pub(crate) struct CheckNibRecursiveData {
     target_ptr: NibPtr,
     nib_val: u8,
@@ -272,6 +270,7 @@ pub(crate) struct CheckNibRecursiveTable {
 }
 impl FunctionTable for CheckNibRecursiveTable {
     type Data = CheckNibRecursiveData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -289,6 +288,9 @@ impl FunctionTable for CheckNibRecursiveTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.col_majors = ColumnMajors {
             target_ptr_parity: BitVec::new(Some(count)),
@@ -301,10 +303,7 @@ impl FunctionTable for CheckNibRecursiveTable {
         self.row_majors = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -809,8 +808,8 @@ impl FunctionTable for CheckNibRecursiveTable {
 
 impl MPT {
     fn check_nib_recursive(
-        &mut self,
-        action: Action,
+        &self,
+        rows: &mut Vec<Row>,
         target_ptr: NibPtr,
         key_ptr: NibPtr,
         nib_ptr: NibPtr,
@@ -835,24 +834,18 @@ impl MPT {
 
         let new_key_ptr = MPT::increment(key_ptr);
         let new_nib_ptr = MPT::increment(nib_ptr);
-        let return_ptr = self.check_nib(action, target_ptr, new_key_ptr, new_nib_ptr);
-        match action {
-            Action::Append => self.check_nib_recursive_table.append(
-                &mut self.state,
-                CheckNibRecursiveData {
-                    target_ptr,
-                    nib_val,
-                    nib_ptr,
-                    new_nib_ptr,
-                    key_val,
-                    key_ptr,
-                    new_key_ptr,
-                    return_ptr,
-                },
-            ),
-            Action::Count => self.check_nib_recursive_count += 1,
-            Action::Ignore => (),
-        }
+        let return_ptr = self.check_nib(rows, target_ptr, new_key_ptr, new_nib_ptr);
+        let data = CheckNibRecursiveData {
+            target_ptr,
+            nib_val,
+            nib_ptr,
+            new_nib_ptr,
+            key_val,
+            key_ptr,
+            new_key_ptr,
+            return_ptr,
+        };
+        rows.push(Row::CheckNibRecursive(data));
         return_ptr
     }
 }
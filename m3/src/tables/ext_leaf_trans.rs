@@ -2,14 +2,16 @@
 
 use super::*;
 
+#[derive(PartialEq)]
 pub(crate) struct ExtLeafTransAdvice {
-    state: State,
+    pub(crate) state: State,
     first_child_ptr: u32,
     first_child_val: u8,
     bytes_ptr: u32,
     bytes_ptr_val: u8,
     nib_ptr: NibPtr,
     post_bytes_ptr: u32,
+    post_bytes_val: u8,
     new_key_ptr: NibPtr,
 }
 
@@ -32,6 +34,8 @@ struct RowMajors {
     bytes_ptr_val: B8,
     bytes_ptr_ts: B32,
     post_bytes_ptr: B32,
+    post_bytes_val: B8,
+    post_bytes_ts: B32,
     new_key_ptr_byte: B32,
     nib_ptr_byte: B32,
     g_raised_to_first_three_first_child_bits: B32,
@@ -48,6 +52,7 @@ pub(crate) struct ExtLeafTransTable {
 
 impl FunctionTable for ExtLeafTransTable {
     type Data = ExtLeafTransAdvice;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -63,6 +68,9 @@ impl FunctionTable for ExtLeafTransTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.row_majors = Vec::with_capacity(count);
 
@@ -72,10 +80,7 @@ impl FunctionTable for ExtLeafTransTable {
         self.col_majors.bytes_ptr_val_bits = std::array::from_fn(|_| BitVec::new(Some(count)));
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -147,6 +152,8 @@ impl FunctionTable for ExtLeafTransTable {
             bytes_ptr: mem.to_mult(data.bytes_ptr),
             bytes_ptr_val: B8::from_underlier(data.bytes_ptr_val),
             bytes_ptr_ts: mem.process_timestamp(data.bytes_ptr),
+            post_bytes_val: B8::from_underlier(data.post_bytes_val),
+            post_bytes_ts: mem.process_timestamp(data.post_bytes_ptr),
             nib_ptr_byte: mem.to_mult(data.nib_ptr.byte),
             // this should hold
             g_raised_to_first_three_first_child_bits,
@@ -180,6 +187,8 @@ impl FunctionTable for ExtLeafTransTable {
         let state_rlp_ptr = builder.add_committed("state_rlp_ptr", n_vars, B32::TOWER_LEVEL);
         let state_ts = builder.add_committed("state_ts", n_vars, B32::TOWER_LEVEL);
         let post_bytes_ptr = builder.add_committed("post_bytes_ptr", n_vars, B32::TOWER_LEVEL);
+        let post_bytes_val = builder.add_committed("post_bytes_val", n_vars, B8::TOWER_LEVEL);
+        let post_bytes_ts = builder.add_committed("post_bytes_ts", n_vars, B32::TOWER_LEVEL);
         let new_key_ptr_byte = builder.add_committed("new_key_ptr_byte", n_vars, B32::TOWER_LEVEL);
         let first_child_ptr = builder.add_committed("first_child_ptr", n_vars, B32::TOWER_LEVEL);
         let first_child_val = builder.add_committed("first_child_val", n_vars, B8::TOWER_LEVEL);
@@ -224,6 +233,8 @@ impl FunctionTable for ExtLeafTransTable {
                     (first_child_ts, B32, B32::one()),
                     (state_ts, B32, B32::one()),
                     (bytes_ptr_ts, B32, B32::one()),
+                    (post_bytes_val, B8, B8::new(128)),
+                    (post_bytes_ts, B32, B32::one()),
                     (g_raised_to_first_three_first_child_bits, B32, B32::one()),
                     (g_raised_to_next_four_first_child_bits, B32, B32::one())
                 ]
@@ -280,6 +291,10 @@ impl FunctionTable for ExtLeafTransTable {
             );
         }
 
+        let post_bytes_val_bits =
+            builder.add_committed_multiple::<8>("post_bytes_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, post_bytes_val, post_bytes_val_bits)?;
+
         // from state pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
         {
             flush_state(
@@ -387,6 +402,20 @@ impl FunctionTable for ExtLeafTransTable {
                 channel_ids.mem,
             )?;
         }
+        // read post_bytes_val from mem
+        {
+            assert_byte_bits(builder, post_bytes_val, post_bytes_val_bits);
+            read_mem(
+                builder,
+                "post_bytes_val",
+                post_bytes_ptr,
+                post_bytes_val,
+                post_bytes_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
         // to check_nib push (post_bytes_ptr, state_key_ptr_byte, nib_ptr_byte, new_key_ptr_byte, false, state_key_ptr_parity, nib_ptr_parity, new_key_ptr_parity)
         {
             let block0 = builder.add_linear_combination(
@@ -518,6 +547,17 @@ impl FunctionTable for ExtLeafTransTable {
 
             builder.assert_not_zero(first_child_val_minus_160);
         }
+        // first_child_val < 0xc0, as a list is the header of a branch child inlined for being
+        // shorter than 32 bytes
+        {
+            builder.assert_zero(
+                [first_child_val_bits[7], first_child_val_bits[6]],
+                binius_macros::arith_expr!(B1[b7, b6] = b7 * b6).convert_field(),
+            );
+        }
+        // the child of an extension is hashed unless it is shorter than 32 bytes, the leaf flag
+        // bytes_ptr_val_bits[5] skips the values of leaves
+        assert_child_reference(builder, post_bytes_val_bits, Some(bytes_ptr_val_bits[5]));
         // bytes_ptr == first_child_ptr * (first_child_val_bits[7] * g + (1 - first_child_val_bits[7]) * 1)
         {
             let arith = {
@@ -626,10 +666,10 @@ impl FunctionTable for ExtLeafTransTable {
 }
 
 impl MPT {
-    pub(crate) fn ext_leaf_trans(&mut self, action: Action, state: State) -> State {
-        let first_child_ptr = self.skip_list_header(action, state.rlp_ptr);
+    pub(crate) fn ext_leaf_trans(&self, rows: &mut Vec<Row>, state: State) -> State {
+        let first_child_ptr = self.skip_list_header(rows, state.rlp_ptr);
         let first_child_val = self.state.mem[first_child_ptr];
-        assert!(first_child_val != 0x80 && first_child_val != 0xa0);
+        assert!(first_child_val != 0x80 && first_child_val != 0xa0 && first_child_val < 0xc0);
         let bytes_ptr = first_child_ptr + ((first_child_val >> 7) & 0x01) as u32;
         let post_bytes_ptr = bytes_ptr
             + match first_child_val & 0x80 == 0x80 {
@@ -637,13 +677,15 @@ impl MPT {
                 false => 1,
             } as u32;
         let bytes_ptr_val = self.state.mem[bytes_ptr];
+        // checked in-circuit only, for extensions
+        let post_bytes_val = self.state.mem[post_bytes_ptr];
         let is_odd = (bytes_ptr_val >> 4) & 0x01;
         let nib_ptr = NibPtr {
             byte: bytes_ptr + 1 - is_odd as u32,
             parity: is_odd == 0x01,
         };
         let new_key_ptr = self.check_nib(
-            action,
+            rows,
             NibPtr {
                 byte: post_bytes_ptr,
                 parity: false,
@@ -657,23 +699,18 @@ impl MPT {
             rlp_ptr: post_bytes_ptr,
             ts: state.ts,
         };
-        match action {
-            Action::Append => self.ext_leaf_trans_table.append(
-                &mut self.state,
-                ExtLeafTransAdvice {
-                    state,
-                    first_child_ptr,
-                    first_child_val,
-                    bytes_ptr,
-                    bytes_ptr_val,
-                    nib_ptr,
-                    post_bytes_ptr,
-                    new_key_ptr,
-                },
-            ),
-            Action::Count => self.ext_leaf_trans_count += 1,
-            Action::Ignore => (),
-        }
+        let data = ExtLeafTransAdvice {
+            state,
+            first_child_ptr,
+            first_child_val,
+            bytes_ptr,
+            bytes_ptr_val,
+            nib_ptr,
+            post_bytes_ptr,
+            post_bytes_val,
+            new_key_ptr,
+        };
+        rows.push(Row::ExtLeafTrans(data));
         new_state
     }
 }
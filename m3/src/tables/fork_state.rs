@@ -2,8 +2,9 @@
 
 use super::*;
 
+#[derive(PartialEq)]
 pub(crate) struct ForkStateData {
-    state: State,
+    pub(crate) state: State,
     new_key_ptr: NibPtr,
     new_start_ptr: u32,
 }
@@ -33,6 +34,7 @@ pub(crate) struct ForkStateTable {
 
 impl FunctionTable for ForkStateTable {
     type Data = ForkStateData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -46,6 +48,9 @@ impl FunctionTable for ForkStateTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.col_majors = ColMajors {
             state_key_ptr_parity: BitVec::new(Some(count)),
@@ -54,10 +59,7 @@ impl FunctionTable for ForkStateTable {
         self.row_majors = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -310,24 +312,18 @@ impl FunctionTable for ForkStateTable {
 }
 
 impl MPT {
-    pub(crate) fn fork_state(&mut self, action: Action, state: State, new_start_ptr: u32) {
+    pub(crate) fn fork_state(&self, rows: &mut Vec<Row>, state: State, new_start_ptr: u32) {
         let new_key_ptr = self.check_nib(
-            action,
+            rows,
             state.key_ptr,
             NibPtr::new(new_start_ptr, false),
             NibPtr::new(state.start_ptr, false),
         );
-        match action {
-            Action::Append => self.fork_state_table.append(
-                &mut self.state,
-                ForkStateData {
-                    state,
-                    new_key_ptr,
-                    new_start_ptr,
-                },
-            ),
-            Action::Count => self.fork_state_count += 1,
-            Action::Ignore => (),
-        }
+        let data = ForkStateData {
+            state,
+            new_key_ptr,
+            new_start_ptr,
+        };
+        rows.push(Row::ForkState(data));
     }
 }
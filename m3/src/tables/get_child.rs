@@ -3,15 +3,16 @@
 use super::*;
 
 impl MPT {
-    pub(crate) fn get_child(&mut self, action: Action, ptr: u32, index: u8) -> u32 {
+    pub(crate) fn get_child(&self, rows: &mut Vec<Row>, ptr: u32, index: u8) -> u32 {
         match index {
-            0 => self.get_child_base(action, ptr),
-            _ => self.get_child_recursive(action, ptr, index),
+            0 => self.get_child_base(rows, ptr),
+            _ => self.get_child_recursive(rows, ptr, index),
         }
     }
 }
 
 // BASE
+#[derive(PartialEq)]
 pub(crate) struct GetChildBaseData {
     ptr: u32,
 }
@@ -24,6 +25,7 @@ pub(crate) struct GetChildBaseTable {
 }
 impl FunctionTable for GetChildBaseTable {
     type Data = GetChildBaseData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -33,14 +35,14 @@ impl FunctionTable for GetChildBaseTable {
             ptr: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.ptr = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -108,19 +110,15 @@ impl FunctionTable for GetChildBaseTable {
 }
 
 impl MPT {
-    pub(crate) fn get_child_base(&mut self, action: Action, ptr: u32) -> u32 {
-        match action {
-            Action::Append => self
-                .get_child_base_table
-                .append(&mut self.state, GetChildBaseData { ptr }),
-            Action::Count => self.get_child_base_count += 1,
-            Action::Ignore => (),
-        }
+    pub(crate) fn get_child_base(&self, rows: &mut Vec<Row>, ptr: u32) -> u32 {
+        let data = GetChildBaseData { ptr };
+        rows.push(Row::GetChildBase(data));
         ptr
     }
 }
 
 // RECURSIVE
+#[derive(PartialEq)]
 pub(crate) struct GetChildRecursiveData {
     ptr: u32,
     val: u8,
@@ -148,6 +146,7 @@ pub(crate) struct GetChildRecursiveTable {
 }
 impl FunctionTable for GetChildRecursiveTable {
     type Data = GetChildRecursiveData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -157,14 +156,14 @@ impl FunctionTable for GetChildRecursiveTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.row_majors = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -392,26 +391,25 @@ impl FunctionTable for GetChildRecursiveTable {
 }
 
 impl MPT {
-    pub(crate) fn get_child_recursive(&mut self, action: Action, ptr: u32, child_index: u8) -> u32 {
+    pub(crate) fn get_child_recursive(
+        &self,
+        rows: &mut Vec<Row>,
+        ptr: u32,
+        child_index: u8,
+    ) -> u32 {
         let val = self.state.mem[ptr];
         let offset = GetChildOffsetLookup::query(val) as u32;
         let new_ptr = ptr + offset;
-        let output = self.get_child(action, new_ptr, child_index - 1);
-        match action {
-            Action::Append => self.get_child_recursive_table.append(
-                &mut self.state,
-                GetChildRecursiveData {
-                    ptr,
-                    val,
-                    offset,
-                    child_index,
-                    new_ptr,
-                    output,
-                },
-            ),
-            Action::Count => self.get_child_recursive_count += 1,
-            Action::Ignore => (),
-        }
+        let output = self.get_child(rows, new_ptr, child_index - 1);
+        let data = GetChildRecursiveData {
+            ptr,
+            val,
+            offset,
+            child_index,
+            new_ptr,
+            output,
+        };
+        rows.push(Row::GetChildRecursive(data));
         output
     }
 }
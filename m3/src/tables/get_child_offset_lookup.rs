@@ -7,6 +7,8 @@ pub(crate) struct GetChildOffsetLookupTable {
 }
 
 impl GetChildOffsetLookupTable {
+    pub(crate) const N_VARS: usize = 8;
+
     pub(crate) fn new() -> Self {
         Self {
             final_ts: Vec::with_capacity(256),
@@ -32,7 +34,7 @@ impl GetChildOffsetLookupTable {
         builder: &mut ConstraintSystemBuilder<U, B128>,
         channel_ids: &ChannelIds,
     ) -> Result<(), anyhow::Error> {
-        let n_vars = 8;
+        let n_vars = Self::N_VARS;
 
         let final_ts = builder.add_committed("final_ts", n_vars, B32::TOWER_LEVEL);
         if let Some(witness) = builder.witness() {
@@ -123,10 +125,13 @@ impl GetChildOffsetLookup {
         self.timestamps[index as usize] *= B32::MULTIPLICATIVE_GENERATOR;
         ts
     }
+    // children are empty, a 32-byte hash, or a node inlined for being shorter than 32 bytes,
+    // and only the value following them can be long
     pub fn query(val: u8) -> u8 {
-        match val & 0x20 == 0x20 {
-            true => 1 + 32,
-            false => 1,
+        match val {
+            0x80..=0xb7 => 1 + val - 0x80,
+            0xc0..=0xf7 => 1 + val - 0xc0,
+            _ => 1,
         }
     }
 }
@@ -1,8 +1,9 @@
 // Copyright 2024 Irreducible Inc.
 
 use super::*;
+#[derive(PartialEq)]
 pub(crate) struct HashTransData {
-    state: State,
+    pub(crate) state: State,
     preimage_ptr: u32,
 }
 struct ColMajors {
@@ -27,6 +28,7 @@ pub(crate) struct HashTransTable {
 
 impl FunctionTable for HashTransTable {
     type Data = HashTransData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -39,15 +41,15 @@ impl FunctionTable for HashTransTable {
             row_majors: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.col_majors.state_key_ptr_parity = BitVec::new(Some(count));
         self.row_majors = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -212,22 +214,24 @@ impl FunctionTable for HashTransTable {
     }
 }
 impl MPT {
-    pub(crate) fn hash_trans(&mut self, action: Action, preimage_ptr: u32, state: State) -> State {
+    pub(crate) fn hash_trans(&self, rows: &mut Vec<Row>, preimage_ptr: u32, state: State) -> State {
         let u8_val = self.state.mem[state.rlp_ptr];
         assert_eq!(u8_val, 0xa0);
         let post_preimage_ptr = self.skip_list(preimage_ptr);
         let preimage_length = post_preimage_ptr - preimage_ptr;
         let total_blocks = (preimage_length / (HASH_R as u32) + 1) as u8;
-        let hash_input = &self.state.mem[preimage_ptr..post_preimage_ptr];
-        let hash = alloy::primitives::keccak256(hash_input);
-        for j in 0..32 {
-            assert_eq!(self.state.mem[state.rlp_ptr + 1 + j], hash[j as usize]);
+        if !self.session.count_only() {
+            let hash_input = &self.state.mem[preimage_ptr..post_preimage_ptr];
+            let hash = self.session.node_hash(preimage_ptr, hash_input);
+            for j in 0..32 {
+                assert_eq!(self.state.mem[state.rlp_ptr + 1 + j], hash[j as usize]);
+            }
         }
         // all the above is dev checks and prover computation
 
         let initial_state: Vec<u8> = vec![0; HASH_B];
         self.absorb_block(
-            action,
+            rows,
             initial_state.try_into().unwrap(),
             preimage_ptr,
             state.rlp_ptr,
@@ -239,17 +243,11 @@ impl MPT {
             rlp_ptr: preimage_ptr,
             ts: state.ts,
         };
-        match action {
-            Action::Append => self.hash_trans_table.append(
-                &mut self.state,
-                HashTransData {
-                    state,
-                    preimage_ptr,
-                },
-            ),
-            Action::Count => self.hash_trans_count += 1,
-            Action::Ignore => (),
-        }
+        let data = HashTransData {
+            state,
+            preimage_ptr,
+        };
+        rows.push(Row::HashTrans(data));
         new_state
     }
 }
@@ -4,10 +4,34 @@ use binius_circuits::keccakf::{keccakf, KeccakfOracles, KeccakfState};
 
 use super::*;
 
+#[derive(Clone, Copy, PartialEq)]
 pub struct KeccakFData {
     perm_input: [u64; HASH_B / 8],
 }
 
+// a keccak_f row with the output of its permutation, which can be reused by any sponge
+// absorbing the same block from the same state
+#[derive(Clone, Copy)]
+pub(crate) struct Permutation {
+    row: KeccakFData,
+    output: [u8; HASH_B],
+}
+
+impl Permutation {
+    pub(crate) fn new(perm_input: [u8; HASH_B]) -> Self {
+        Self {
+            row: KeccakFData {
+                perm_input: to_u64_array(&perm_input),
+            },
+            output: crate::utils::keccak_f(perm_input),
+        }
+    }
+
+    pub(crate) fn output(&self) -> [u8; HASH_B] {
+        self.output
+    }
+}
+
 pub struct KeccakFTable {
     n_vars: usize,
     count: usize,
@@ -17,6 +41,7 @@ pub struct KeccakFTable {
 }
 impl FunctionTable for KeccakFTable {
     type Data = KeccakFData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 2; // - smallest_tower_level
     fn new() -> Self {
         Self {
             n_vars: 0,
@@ -26,30 +51,41 @@ impl FunctionTable for KeccakFTable {
             perm_input: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.perm_input = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 2, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, _mpt_state: &mut MPTState, data: Self::Data) {
         self.perm_input.push(KeccakfState(data.perm_input));
         self.index += 1;
     }
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        self.build_with(builder, channel_ids.into(), table_height)
+    }
+}
+
+impl KeccakFTable {
     #[instrument(
         name = "keccak_f",
         fields(n_vars = n_vars)
         skip_all,
         level = "debug"
     )]
-    fn build(
+    pub(crate) fn build_with(
         self,
         builder: &mut ConstraintSystemBuilder<U, B128>,
-        channel_ids: &ChannelIds,
+        channels: SpongeChannels,
         TableHeight { n_vars, count }: TableHeight,
     ) -> Result<(), anyhow::Error> {
         builder.push_namespace("keccak_f");
@@ -117,7 +153,7 @@ impl FunctionTable for KeccakFTable {
             }
         }
 
-        builder.receive(channel_ids.keccak_f, count, flushed_blocks);
+        builder.receive(channels.keccak_f, count, flushed_blocks);
 
         builder.pop_namespace();
         Ok(())
@@ -131,17 +167,22 @@ fn to_u64_array(bytes: &[u8; 200]) -> [u64; 25] {
     }
     u64_array
 }
-impl MPT {
-    pub(crate) fn keccak_f(&mut self, action: Action, perm_input: [u8; HASH_B]) -> [u8; HASH_B] {
-        let old_perm_output = crate::utils::keccak_f(perm_input);
-        let perm_input = to_u64_array(&perm_input);
-        match action {
-            Action::Append => self
-                .keccak_f_table
-                .append(&mut self.state, KeccakFData { perm_input }),
-            Action::Count => self.keccak_f_count += 1,
-            Action::Ignore => (),
-        }
-        old_perm_output
+impl KeccakSponge {
+    pub(crate) fn keccak_f(
+        rows: &mut Vec<Row>,
+        perm_input: [u8; HASH_B],
+        permutation: Permutation,
+    ) -> [u8; HASH_B] {
+        // a cached row has to permute the same input
+        debug_assert_eq!(permutation.row.perm_input, to_u64_array(&perm_input));
+        rows.push(Row::KeccakF(permutation.row));
+        permutation.output
+    }
+
+    // a keccak_f row that is counted but never appended, so nothing is permuted
+    pub(crate) fn count_keccak_f(rows: &mut Vec<Row>) {
+        rows.push(Row::KeccakF(KeccakFData {
+            perm_input: [0; HASH_B / 8],
+        }));
     }
 }
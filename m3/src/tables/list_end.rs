@@ -0,0 +1,455 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+#[derive(PartialEq)]
+pub(crate) struct ListEndData {
+    list_ptr: u32,
+    prefix_val: u8,
+    len_vals: [u8; 2],
+    post_list_ptr: u32,
+}
+
+struct ColMajors {
+    is_f8: BitVec,
+    is_f9: BitVec,
+}
+
+struct RowMajors {
+    list_ptr: B32,
+    post_list_ptr: B32,
+    prefix_val: B8,
+    prefix_ts: B32,
+    len_val_0: B8,
+    len_ts_0: B32,
+    len_val_1: B8,
+    len_ts_1: B32,
+    g_raised_to_low_three_prefix_bits: B32,
+    g_raised_to_high_three_prefix_bits: B32,
+    g_raised_to_low_four_len_0_bits: B32,
+    g_raised_to_high_four_len_0_bits: B32,
+    g_raised_to_low_four_shifted_len_0_bits: B32,
+    g_raised_to_high_four_shifted_len_0_bits: B32,
+    g_raised_to_low_four_len_1_bits: B32,
+    g_raised_to_high_four_len_1_bits: B32,
+}
+
+// the end of a list, from its header: 0xc0 to 0xf7 holds the payload length itself, 0xf8 and
+// 0xf9 are followed by one or two big-endian length bytes. Nodes are never longer than 2^16
+// bytes. Both length bytes are read for every list, they are in memory even for short lists.
+pub(crate) struct ListEndTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: ColMajors,
+    row_majors: Vec<RowMajors>,
+}
+impl FunctionTable for ListEndTable {
+    type Data = ListEndData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: ColMajors {
+                is_f8: BitVec::new(None),
+                is_f9: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors = ColMajors {
+            is_f8: BitVec::new(Some(count)),
+            is_f9: BitVec::new(Some(count)),
+        };
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+
+        self.col_majors.is_f8.push(data.prefix_val == 0xf8);
+        self.col_majors.is_f9.push(data.prefix_val == 0xf9);
+        let [len_val_0, len_val_1] = data.len_vals;
+        self.row_majors.push(RowMajors {
+            list_ptr: mem.to_mult(data.list_ptr),
+            post_list_ptr: mem.to_mult(data.post_list_ptr),
+            prefix_val: B8::from_underlier(data.prefix_val),
+            prefix_ts: mem.process_timestamp(data.list_ptr),
+            len_val_0: B8::from_underlier(len_val_0),
+            len_ts_0: mem.process_timestamp(data.list_ptr + 1),
+            len_val_1: B8::from_underlier(len_val_1),
+            len_ts_1: mem.process_timestamp(data.list_ptr + 2),
+            g_raised_to_low_three_prefix_bits: g_raised_to_bits(
+                data.prefix_val,
+                0,
+                g_powers(0).take(3),
+            ),
+            g_raised_to_high_three_prefix_bits: g_raised_to_bits(
+                data.prefix_val,
+                3,
+                g_powers(3).take(3),
+            ),
+            g_raised_to_low_four_len_0_bits: g_raised_to_bits(len_val_0, 0, g_powers(0).take(4)),
+            g_raised_to_high_four_len_0_bits: g_raised_to_bits(len_val_0, 4, g_powers(4).take(4)),
+            g_raised_to_low_four_shifted_len_0_bits: g_raised_to_bits(
+                len_val_0,
+                0,
+                g_powers(8).take(4),
+            ),
+            g_raised_to_high_four_shifted_len_0_bits: g_raised_to_bits(
+                len_val_0,
+                4,
+                g_powers(12).take(4),
+            ),
+            g_raised_to_low_four_len_1_bits: g_raised_to_bits(len_val_1, 0, g_powers(0).take(4)),
+            g_raised_to_high_four_len_1_bits: g_raised_to_bits(len_val_1, 4, g_powers(4).take(4)),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "list_end",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("list_end");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let list_ptr = builder.add_committed("list_ptr", n_vars, B32::TOWER_LEVEL);
+        let post_list_ptr = builder.add_committed("post_list_ptr", n_vars, B32::TOWER_LEVEL);
+        let prefix_val = builder.add_committed("prefix_val", n_vars, B8::TOWER_LEVEL);
+        let prefix_ts = builder.add_committed("prefix_ts", n_vars, B32::TOWER_LEVEL);
+        let len_val_0 = builder.add_committed("len_val_0", n_vars, B8::TOWER_LEVEL);
+        let len_ts_0 = builder.add_committed("len_ts_0", n_vars, B32::TOWER_LEVEL);
+        let len_val_1 = builder.add_committed("len_val_1", n_vars, B8::TOWER_LEVEL);
+        let len_ts_1 = builder.add_committed("len_ts_1", n_vars, B32::TOWER_LEVEL);
+        let g_raised_to_low_three_prefix_bits = builder.add_committed(
+            "g_raised_to_low_three_prefix_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_high_three_prefix_bits = builder.add_committed(
+            "g_raised_to_high_three_prefix_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_low_four_len_0_bits =
+            builder.add_committed("g_raised_to_low_four_len_0_bits", n_vars, B32::TOWER_LEVEL);
+        let g_raised_to_high_four_len_0_bits =
+            builder.add_committed("g_raised_to_high_four_len_0_bits", n_vars, B32::TOWER_LEVEL);
+        let g_raised_to_low_four_shifted_len_0_bits = builder.add_committed(
+            "g_raised_to_low_four_shifted_len_0_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_high_four_shifted_len_0_bits = builder.add_committed(
+            "g_raised_to_high_four_shifted_len_0_bits",
+            n_vars,
+            B32::TOWER_LEVEL,
+        );
+        let g_raised_to_low_four_len_1_bits =
+            builder.add_committed("g_raised_to_low_four_len_1_bits", n_vars, B32::TOWER_LEVEL);
+        let g_raised_to_high_four_len_1_bits =
+            builder.add_committed("g_raised_to_high_four_len_1_bits", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (list_ptr, B32),
+                    (post_list_ptr, B32),
+                    (len_val_0, B8),
+                    (len_val_1, B8),
+                ]
+            );
+            // padding rows are the empty list 0xc0 at the zero pointer
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (prefix_val, B8, B8::new(0xc0)),
+                    (prefix_ts, B32, B32::one()),
+                    (len_ts_0, B32, B32::one()),
+                    (len_ts_1, B32, B32::one()),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (g_raised_to_low_three_prefix_bits, B32, B32::one()),
+                    (g_raised_to_high_three_prefix_bits, B32, B32::one()),
+                    (g_raised_to_low_four_len_0_bits, B32, B32::one()),
+                    (g_raised_to_high_four_len_0_bits, B32, B32::one()),
+                    (g_raised_to_low_four_shifted_len_0_bits, B32, B32::one()),
+                    (g_raised_to_high_four_shifted_len_0_bits, B32, B32::one()),
+                    (g_raised_to_low_four_len_1_bits, B32, B32::one()),
+                    (g_raised_to_high_four_len_1_bits, B32, B32::one()),
+                ]
+            );
+        }
+
+        let is_f8 = builder.add_committed("is_f8", n_vars, B1::TOWER_LEVEL);
+        let is_f9 = builder.add_committed("is_f9", n_vars, B1::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            (
+                self.col_majors.is_f8,
+                witness.new_column::<B1>(is_f8).as_mut_slice::<u8>(),
+            )
+                .into_par_iter()
+                .for_each(|(src, dest)| {
+                    *dest = src;
+                });
+            (
+                self.col_majors.is_f9,
+                witness.new_column::<B1>(is_f9).as_mut_slice::<u8>(),
+            )
+                .into_par_iter()
+                .for_each(|(src, dest)| {
+                    *dest = src;
+                });
+        }
+
+        let prefix_val_bits =
+            builder.add_committed_multiple::<8>("prefix_val_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, prefix_val, prefix_val_bits)?;
+        let len_val_0_bits =
+            builder.add_committed_multiple::<8>("len_val_0_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, len_val_0, len_val_0_bits)?;
+        let len_val_1_bits =
+            builder.add_committed_multiple::<8>("len_val_1_bits", n_vars, B1::TOWER_LEVEL);
+        populate_byte_bits(builder, len_val_1, len_val_1_bits)?;
+
+        // from list_end pull (list_ptr, post_list_ptr)
+        {
+            let block0 = builder.add_linear_combination(
+                "flush 0, block 0",
+                n_vars,
+                [(list_ptr, basis(32, 0)), (post_list_ptr, basis(32, 1))],
+            )?;
+            if let Some(witness) = builder.witness() {
+                (
+                    witness.new_column::<B128>(block0).as_mut_slice::<u128>(),
+                    witness.get::<B32>(list_ptr)?.as_slice::<u32>(),
+                    witness.get::<B32>(post_list_ptr)?.as_slice::<u32>(),
+                )
+                    .into_par_iter()
+                    .for_each(|(block, list_ptr, post_list_ptr)| {
+                        *block = (*post_list_ptr as u128) << 32 | (*list_ptr as u128);
+                    });
+            }
+
+            builder.receive(channel_ids.list_end, count, [block0]);
+        }
+        // mem reads
+        {
+            read_mem(
+                builder,
+                "prefix_val",
+                list_ptr,
+                prefix_val,
+                prefix_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_mem_at_offset(
+                builder,
+                "len_val_0",
+                list_ptr,
+                1,
+                len_val_0,
+                len_ts_0,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_mem_at_offset(
+                builder,
+                "len_val_1",
+                list_ptr,
+                2,
+                len_val_1,
+                len_ts_1,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        assert_byte_bits(builder, prefix_val, prefix_val_bits);
+        assert_byte_bits(builder, len_val_0, len_val_0_bits);
+        assert_byte_bits(builder, len_val_1, len_val_1_bits);
+        // prefix_val_bits[7] == 1 and prefix_val_bits[6] == 1, the prefix is a list header
+        {
+            builder.assert_zero(
+                [prefix_val_bits[7]],
+                binius_macros::arith_expr!(B1[b7] = b7 - 1).convert_field(),
+            );
+            builder.assert_zero(
+                [prefix_val_bits[6]],
+                binius_macros::arith_expr!(B1[b6] = b6 - 1).convert_field(),
+            );
+        }
+        // is_f8 == (prefix_val == 0xf8) and is_f9 == (prefix_val == 0xf9), given the two bits above
+        {
+            builder.assert_zero(
+                [
+                    is_f8,
+                    prefix_val_bits[0],
+                    prefix_val_bits[1],
+                    prefix_val_bits[2],
+                    prefix_val_bits[3],
+                    prefix_val_bits[4],
+                    prefix_val_bits[5],
+                ],
+                binius_macros::arith_expr!(
+                    B1[is_f8, b0, b1, b2, b3, b4, b5] =
+                        (1 - b0) * (1 - b1) * (1 - b2) * b3 * b4 * b5 - is_f8
+                )
+                .convert_field(),
+            );
+            builder.assert_zero(
+                [
+                    is_f9,
+                    prefix_val_bits[0],
+                    prefix_val_bits[1],
+                    prefix_val_bits[2],
+                    prefix_val_bits[3],
+                    prefix_val_bits[4],
+                    prefix_val_bits[5],
+                ],
+                binius_macros::arith_expr!(
+                    B1[is_f9, b0, b1, b2, b3, b4, b5] =
+                        b0 * (1 - b1) * (1 - b2) * b3 * b4 * b5 - is_f9
+                )
+                .convert_field(),
+            );
+        }
+        // a short list is below 0xf8, the headers above 0xf9 are longer than any node
+        {
+            builder.assert_zero(
+                [
+                    is_f8,
+                    is_f9,
+                    prefix_val_bits[3],
+                    prefix_val_bits[4],
+                    prefix_val_bits[5],
+                ],
+                binius_macros::arith_expr!(
+                    B1[is_f8, is_f9, b3, b4, b5] = (1 - is_f8 - is_f9) * b3 * b4 * b5
+                )
+                .convert_field(),
+            );
+        }
+        // check the g_raised_to columns against the bits they are raised to
+        {
+            for (bits, first_exponent_bit, column) in [
+                (&prefix_val_bits[0..3], 0, g_raised_to_low_three_prefix_bits),
+                (
+                    &prefix_val_bits[3..6],
+                    3,
+                    g_raised_to_high_three_prefix_bits,
+                ),
+                (&len_val_0_bits[0..4], 0, g_raised_to_low_four_len_0_bits),
+                (&len_val_0_bits[4..8], 4, g_raised_to_high_four_len_0_bits),
+                (
+                    &len_val_0_bits[0..4],
+                    8,
+                    g_raised_to_low_four_shifted_len_0_bits,
+                ),
+                (
+                    &len_val_0_bits[4..8],
+                    12,
+                    g_raised_to_high_four_shifted_len_0_bits,
+                ),
+                (&len_val_1_bits[0..4], 0, g_raised_to_low_four_len_1_bits),
+                (&len_val_1_bits[4..8], 4, g_raised_to_high_four_len_1_bits),
+            ] {
+                assert_g_raised_to_bits(builder, bits, first_exponent_bit, column);
+            }
+        }
+        // post_list_ptr == list_ptr * ((1 - is_f8 - is_f9) * g * g^(prefix_val - 0xc0)
+        //     + is_f8 * g^2 * g^len_val_0 + is_f9 * g^3 * g^(256 * len_val_0 + len_val_1))
+        {
+            let arith = {
+                let post_list_ptr = ArithExpr::Var(0);
+                let list_ptr = ArithExpr::Var(1);
+                let is_f8 = ArithExpr::Var(2);
+                let is_f9 = ArithExpr::Var(3);
+                let short_len = ArithExpr::Var(4) * ArithExpr::Var(5);
+                let long_len_0 = ArithExpr::Var(6) * ArithExpr::Var(7);
+                let long_len_1 =
+                    ArithExpr::Var(8) * ArithExpr::Var(9) * ArithExpr::Var(10) * ArithExpr::Var(11);
+                let g = B32::MULTIPLICATIVE_GENERATOR;
+                let is_short = ArithExpr::one() - is_f8.clone() - is_f9.clone();
+                post_list_ptr
+                    - list_ptr
+                        * (is_short * ArithExpr::Const(g) * short_len
+                            + is_f8 * ArithExpr::Const(g.pow([2])) * long_len_0
+                            + is_f9 * ArithExpr::Const(g.pow([3])) * long_len_1)
+            };
+            builder.assert_zero(
+                [
+                    post_list_ptr,
+                    list_ptr,
+                    is_f8,
+                    is_f9,
+                    g_raised_to_low_three_prefix_bits,
+                    g_raised_to_high_three_prefix_bits,
+                    g_raised_to_low_four_len_0_bits,
+                    g_raised_to_high_four_len_0_bits,
+                    g_raised_to_low_four_shifted_len_0_bits,
+                    g_raised_to_high_four_shifted_len_0_bits,
+                    g_raised_to_low_four_len_1_bits,
+                    g_raised_to_high_four_len_1_bits,
+                ],
+                arith.convert_field(),
+            );
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // the pointer right past the list at list_ptr
+    pub(crate) fn list_end(&self, rows: &mut Vec<Row>, list_ptr: u32) -> u32 {
+        let prefix_val = self.state.mem[list_ptr];
+        assert!((0xc0..=0xf9).contains(&prefix_val));
+        let len_vals = [self.state.mem[list_ptr + 1], self.state.mem[list_ptr + 2]];
+        let post_list_ptr = self.skip_list(list_ptr);
+        let data = ListEndData {
+            list_ptr,
+            prefix_val,
+            len_vals,
+            post_list_ptr,
+        };
+        rows.push(Row::ListEnd(data));
+        post_list_ptr
+    }
+}
@@ -3,8 +3,12 @@
 use super::*;
 
 mod absorb_block;
+mod account_fields;
+mod balance_sum;
 mod branch_trans;
 mod branch_trans_shift_lookup;
+mod branch_value_trans;
+mod bytecode;
 mod check_nib;
 mod ext_leaf_trans;
 mod fork_state;
@@ -12,13 +16,22 @@ mod get_child;
 mod get_child_offset_lookup;
 mod hash_trans;
 mod keccak_f;
+mod list_end;
+mod node_diff;
+mod node_slot;
+mod node_split;
 mod populate_mem;
+mod predicates;
 mod skip_list_header;
 mod skip_list_header_offset_lookup;
 
 pub(crate) use absorb_block::*;
+pub(crate) use account_fields::*;
+pub(crate) use balance_sum::*;
 pub(crate) use branch_trans::*;
 pub(crate) use branch_trans_shift_lookup::*;
+pub(crate) use branch_value_trans::*;
+pub(crate) use bytecode::*;
 pub(crate) use check_nib::*;
 pub(crate) use ext_leaf_trans::*;
 pub(crate) use fork_state::*;
@@ -26,6 +39,11 @@ pub(crate) use get_child::*;
 pub(crate) use get_child_offset_lookup::*;
 pub(crate) use hash_trans::*;
 pub(crate) use keccak_f::*;
+pub(crate) use list_end::*;
+pub(crate) use node_diff::*;
+pub(crate) use node_slot::*;
+pub(crate) use node_split::*;
 pub(crate) use populate_mem::*;
+pub(crate) use predicates::*;
 pub(crate) use skip_list_header::*;
 pub(crate) use skip_list_header_offset_lookup::*;
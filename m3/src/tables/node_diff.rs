@@ -0,0 +1,1130 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+use std::ops::Range;
+
+// the length of a hash reference, 0xa0 followed by the 32-byte hash
+pub(crate) const HASH_REF_LEN: u32 = 1 + 32;
+
+// A node of the post state trie is hashed by a node_diff row rather than by hash_trans. The row
+// pairs it with the node of the pre state trie at the same position of the same key, and starts
+// a token on the node_diff channel that walks the payloads of both nodes in lockstep, one row
+// per byte in node_diff_byte, until it is pulled back at the end of both lists. The only parts
+// of the payloads allowed to differ are windows granted on the node_diff_window channel:
+// references to the nodes the post walk hashes next, granted by the node_diff rows of those
+// nodes, the values the keys end at, granted by the verifier, and the children holding the
+// leaves of inserted and deleted keys, granted by node_insert and node_delete.
+
+// where the comparison of the payloads of a pre node and a post node stands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct DiffPtrs {
+    old_ptr: u32,
+    new_ptr: u32,
+    old_end_ptr: u32,
+    new_end_ptr: u32,
+}
+
+// the windows of the post nodes that may differ from the pre nodes
+#[derive(Debug, Default)]
+pub(crate) struct DiffWindows {
+    // the references the post walk hashes
+    pub(crate) hash: HashSet<u32>,
+    // the values the keys end at in the post state trie, to the ones they end at in the pre state trie
+    pub(crate) value: HashMap<u32, u32>,
+    // the children holding the leaves of inserted or deleted keys in the post nodes, to the
+    // ones in the pre nodes
+    pub(crate) slot: HashMap<u32, u32>,
+}
+
+// HEAD
+
+#[derive(PartialEq)]
+pub(crate) struct NodeDiffData {
+    pub(crate) state: State,
+    new_node_ptr: u32,
+    pub(crate) old_state: State,
+    ptrs: DiffPtrs,
+}
+
+struct ColMajors {
+    state_key_ptr_parity: BitVec,
+}
+
+struct RowMajors {
+    state_start_ptr: B32,
+    state_key_ptr_byte: B32,
+    state_rlp_ptr: B32,
+    state_ts: B32,
+    new_node_ptr: B32,
+    prefix_val_ts: B32,
+    old_node_ptr: B32,
+    old_ts: B32,
+    old_first_child_ptr: B32,
+    new_first_child_ptr: B32,
+    old_end_ptr: B32,
+    new_end_ptr: B32,
+}
+
+pub(crate) struct NodeDiffTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: ColMajors,
+    row_majors: Vec<RowMajors>,
+}
+
+impl FunctionTable for NodeDiffTable {
+    type Data = NodeDiffData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: ColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors.state_key_ptr_parity = BitVec::new(Some(count));
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.row_majors.push(RowMajors {
+            state_start_ptr: mem.to_mult(data.state.start_ptr),
+            state_key_ptr_byte: mem.to_mult(data.state.key_ptr.byte),
+            state_rlp_ptr: mem.to_mult(data.state.rlp_ptr),
+            state_ts: data.state.ts,
+            new_node_ptr: mem.to_mult(data.new_node_ptr),
+            prefix_val_ts: mem.process_timestamp(data.state.rlp_ptr),
+            old_node_ptr: mem.to_mult(data.old_state.rlp_ptr),
+            old_ts: data.old_state.ts,
+            old_first_child_ptr: mem.to_mult(data.ptrs.old_ptr),
+            new_first_child_ptr: mem.to_mult(data.ptrs.new_ptr),
+            old_end_ptr: mem.to_mult(data.ptrs.old_end_ptr),
+            new_end_ptr: mem.to_mult(data.ptrs.new_end_ptr),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_diff",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_diff");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let state_start_ptr = builder.add_committed("state_start_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_key_ptr_byte =
+            builder.add_committed("state_key_ptr_byte", n_vars, B32::TOWER_LEVEL);
+        let state_rlp_ptr = builder.add_committed("state_rlp_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_ts = builder.add_committed("state_ts", n_vars, B32::TOWER_LEVEL);
+        let new_node_ptr = builder.add_committed("new_node_ptr", n_vars, B32::TOWER_LEVEL);
+        let prefix_val_ts = builder.add_committed("prefix_val_ts", n_vars, B32::TOWER_LEVEL);
+        let old_node_ptr = builder.add_committed("old_node_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_ts = builder.add_committed("old_ts", n_vars, B32::TOWER_LEVEL);
+        let old_first_child_ptr =
+            builder.add_committed("old_first_child_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_first_child_ptr =
+            builder.add_committed("new_first_child_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_end_ptr = builder.add_committed("old_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_end_ptr = builder.add_committed("new_end_ptr", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (state_start_ptr, B32),
+                    (state_key_ptr_byte, B32),
+                    (state_rlp_ptr, B32),
+                    (new_node_ptr, B32),
+                    (old_node_ptr, B32),
+                    (old_first_child_ptr, B32),
+                    (new_first_child_ptr, B32),
+                    (old_end_ptr, B32),
+                    (new_end_ptr, B32),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (state_ts, B32, B32::one()),
+                    (prefix_val_ts, B32, B32::one()),
+                    (old_ts, B32, B32::one()),
+                ]
+            );
+        }
+
+        let poly = binius_core::transparent::constant::Constant::new(n_vars, B128::zero());
+        let initial_state = (0..12)
+            .map(|_| builder.add_transparent("initial_state", poly))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(witness) = builder.witness() {
+            initial_state.iter().for_each(|id| {
+                witness.new_column::<B128>(*id);
+            });
+        }
+
+        let state_key_ptr_parity =
+            builder.add_committed("state_key_ptr_parity", n_vars, B1::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            (
+                self.col_majors.state_key_ptr_parity,
+                witness
+                    .new_column::<B1>(state_key_ptr_parity)
+                    .as_mut_slice::<u8>(),
+            )
+                .into_par_iter()
+                .for_each(|(src, dest)| {
+                    *dest = src;
+                });
+        }
+
+        let one = B32::one();
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+
+        // from post_state pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Pull,
+                state_start_ptr,
+                state_key_ptr_byte,
+                state_rlp_ptr,
+                state_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.post_state,
+            )?;
+        }
+        // read 0xa0 from mem
+        {
+            read_const(
+                builder,
+                "0xa0",
+                state_rlp_ptr,
+                B8::new(0xa0),
+                prefix_val_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // to absorb_block push (new_node_ptr, state_rlp_ptr, initial_state)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 0, block 0",
+                n_vars,
+                &[(new_node_ptr, one), (state_rlp_ptr, one)],
+            )?;
+            let mut all_blocks = vec![block0];
+            all_blocks.extend(initial_state);
+
+            builder.send(channel_ids.absorb_block, count, all_blocks);
+        }
+        // to post_state push (state_start_ptr, state_key_ptr_byte, new_node_ptr, state_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Push,
+                state_start_ptr,
+                state_key_ptr_byte,
+                new_node_ptr,
+                state_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.post_state,
+            )?;
+        }
+        // from state pull (state_start_ptr, state_key_ptr_byte, old_node_ptr, old_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Pull,
+                state_start_ptr,
+                state_key_ptr_byte,
+                old_node_ptr,
+                old_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.state,
+            )?;
+        }
+        // check old_ts != 0
+        builder.assert_not_zero(old_ts);
+        // to state push (state_start_ptr, state_key_ptr_byte, old_node_ptr, old_ts * g; state_key_ptr_parity)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 4, block 0",
+                n_vars,
+                &[
+                    (state_start_ptr, one),
+                    (state_key_ptr_byte, one),
+                    (old_node_ptr, one),
+                    (old_ts, g),
+                ],
+            )?;
+            builder.send(channel_ids.state, count, [block0, state_key_ptr_parity]);
+        }
+        // to skip_list_header push (old_node_ptr, old_first_child_ptr) and (new_node_ptr, new_first_child_ptr)
+        {
+            let old_block = ptr_lanes_block(
+                builder,
+                "flush 5, block 0",
+                n_vars,
+                &[(old_node_ptr, one), (old_first_child_ptr, one)],
+            )?;
+            builder.send(channel_ids.skip_list_header, count, [old_block]);
+            let new_block = ptr_lanes_block(
+                builder,
+                "flush 6, block 0",
+                n_vars,
+                &[(new_node_ptr, one), (new_first_child_ptr, one)],
+            )?;
+            builder.send(channel_ids.skip_list_header, count, [new_block]);
+        }
+        // to list_end push (old_node_ptr, old_end_ptr) and (new_node_ptr, new_end_ptr)
+        {
+            let old_block = ptr_lanes_block(
+                builder,
+                "flush 7, block 0",
+                n_vars,
+                &[(old_node_ptr, one), (old_end_ptr, one)],
+            )?;
+            builder.send(channel_ids.list_end, count, [old_block]);
+            let new_block = ptr_lanes_block(
+                builder,
+                "flush 8, block 0",
+                n_vars,
+                &[(new_node_ptr, one), (new_end_ptr, one)],
+            )?;
+            builder.send(channel_ids.list_end, count, [new_block]);
+        }
+        // to node_diff push (old_first_child_ptr, new_first_child_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 9, block 0",
+                n_vars,
+                &[
+                    (old_first_child_ptr, one),
+                    (new_first_child_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff, count, [block0]);
+        }
+        // from node_diff pull (old_end_ptr, new_end_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 10, block 0",
+                n_vars,
+                &[
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.receive(channel_ids.node_diff, count, [block0]);
+        }
+        // to node_diff_window push (state_rlp_ptr, state_rlp_ptr * g^33, 0)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 11, block 0",
+                n_vars,
+                &[
+                    (state_rlp_ptr, one),
+                    (state_rlp_ptr, g.pow([HASH_REF_LEN as u64])),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff_window, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// BYTE
+
+#[derive(PartialEq)]
+pub(crate) struct NodeDiffByteData {
+    ptrs: DiffPtrs,
+    val: u8,
+}
+
+struct ByteRowMajors {
+    old_ptr: B32,
+    new_ptr: B32,
+    old_end_ptr: B32,
+    new_end_ptr: B32,
+    val: B8,
+    old_ts: B32,
+    new_ts: B32,
+}
+
+pub(crate) struct NodeDiffByteTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<ByteRowMajors>,
+}
+
+impl FunctionTable for NodeDiffByteTable {
+    type Data = NodeDiffByteData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+        // the old byte is read first, for when both nodes are the same
+        let old_ts = mem.process_timestamp(data.ptrs.old_ptr);
+        let new_ts = mem.process_timestamp(data.ptrs.new_ptr);
+        self.row_majors.push(ByteRowMajors {
+            old_ptr: mem.to_mult(data.ptrs.old_ptr),
+            new_ptr: mem.to_mult(data.ptrs.new_ptr),
+            old_end_ptr: mem.to_mult(data.ptrs.old_end_ptr),
+            new_end_ptr: mem.to_mult(data.ptrs.new_end_ptr),
+            val: B8::from_underlier(data.val),
+            old_ts,
+            new_ts,
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_diff_byte",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_diff_byte");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let old_ptr = builder.add_committed("old_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_ptr = builder.add_committed("new_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_end_ptr = builder.add_committed("old_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_end_ptr = builder.add_committed("new_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let val = builder.add_committed("val", n_vars, B8::TOWER_LEVEL);
+        let old_ts = builder.add_committed("old_ts", n_vars, B32::TOWER_LEVEL);
+        let new_ts = builder.add_committed("new_ts", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (old_ptr, B32),
+                    (new_ptr, B32),
+                    (old_end_ptr, B32),
+                    (new_end_ptr, B32),
+                    (val, B8),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [(old_ts, B32, B32::one()), (new_ts, B32, B32::one())]
+            );
+        }
+
+        let one = B32::one();
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+
+        // from node_diff pull (old_ptr, new_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 0, block 0",
+                n_vars,
+                &[
+                    (old_ptr, one),
+                    (new_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.receive(channel_ids.node_diff, count, [block0]);
+        }
+        // mem reads, the same byte from both nodes
+        {
+            read_mem(
+                builder,
+                "old_val",
+                old_ptr,
+                val,
+                old_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_mem(
+                builder,
+                "new_val",
+                new_ptr,
+                val,
+                new_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // to node_diff push (old_ptr * g, new_ptr * g, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 3, block 0",
+                n_vars,
+                &[
+                    (old_ptr, g),
+                    (new_ptr, g),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// HASH
+
+struct HashRowMajors {
+    old_ptr: B32,
+    new_ptr: B32,
+    old_end_ptr: B32,
+    new_end_ptr: B32,
+    old_ts: B32,
+    new_ts: B32,
+}
+
+pub(crate) struct NodeDiffHashTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<HashRowMajors>,
+}
+
+impl FunctionTable for NodeDiffHashTable {
+    type Data = DiffPtrs;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+        let old_ts = mem.process_timestamp(data.old_ptr);
+        let new_ts = mem.process_timestamp(data.new_ptr);
+        self.row_majors.push(HashRowMajors {
+            old_ptr: mem.to_mult(data.old_ptr),
+            new_ptr: mem.to_mult(data.new_ptr),
+            old_end_ptr: mem.to_mult(data.old_end_ptr),
+            new_end_ptr: mem.to_mult(data.new_end_ptr),
+            old_ts,
+            new_ts,
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_diff_hash",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_diff_hash");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let old_ptr = builder.add_committed("old_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_ptr = builder.add_committed("new_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_end_ptr = builder.add_committed("old_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_end_ptr = builder.add_committed("new_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_ts = builder.add_committed("old_ts", n_vars, B32::TOWER_LEVEL);
+        let new_ts = builder.add_committed("new_ts", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (old_ptr, B32),
+                    (new_ptr, B32),
+                    (old_end_ptr, B32),
+                    (new_end_ptr, B32),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [(old_ts, B32, B32::one()), (new_ts, B32, B32::one())]
+            );
+        }
+
+        let one = B32::one();
+        let g_33 = B32::MULTIPLICATIVE_GENERATOR.pow([HASH_REF_LEN as u64]);
+
+        // from node_diff pull (old_ptr, new_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 0, block 0",
+                n_vars,
+                &[
+                    (old_ptr, one),
+                    (new_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.receive(channel_ids.node_diff, count, [block0]);
+        }
+        // read 0xa0 from both nodes, the hashes themselves may differ
+        {
+            read_const(
+                builder,
+                "old 0xa0",
+                old_ptr,
+                B8::new(0xa0),
+                old_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+            read_const(
+                builder,
+                "new 0xa0",
+                new_ptr,
+                B8::new(0xa0),
+                new_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // from node_diff_window pull (new_ptr, new_ptr * g^33, 0)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 3, block 0",
+                n_vars,
+                &[(new_ptr, one), (new_ptr, g_33)],
+            )?;
+            builder.receive(channel_ids.node_diff_window, count, [block0]);
+        }
+        // to node_diff push (old_ptr * g^33, new_ptr * g^33, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 4, block 0",
+                n_vars,
+                &[
+                    (old_ptr, g_33),
+                    (new_ptr, g_33),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// VALUE
+
+struct ValueRowMajors {
+    old_ptr: B32,
+    new_ptr: B32,
+    old_end_ptr: B32,
+    new_end_ptr: B32,
+}
+
+pub(crate) struct NodeDiffValueTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<ValueRowMajors>,
+}
+
+impl FunctionTable for NodeDiffValueTable {
+    type Data = DiffPtrs;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+        self.row_majors.push(ValueRowMajors {
+            old_ptr: mem.to_mult(data.old_ptr),
+            new_ptr: mem.to_mult(data.new_ptr),
+            old_end_ptr: mem.to_mult(data.old_end_ptr),
+            new_end_ptr: mem.to_mult(data.new_end_ptr),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_diff_value",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_diff_value");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let old_ptr = builder.add_committed("old_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_ptr = builder.add_committed("new_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_end_ptr = builder.add_committed("old_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_end_ptr = builder.add_committed("new_end_ptr", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (old_ptr, B32),
+                    (new_ptr, B32),
+                    (old_end_ptr, B32),
+                    (new_end_ptr, B32),
+                ]
+            );
+        }
+
+        let one = B32::one();
+
+        // from node_diff pull (old_ptr, new_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 0, block 0",
+                n_vars,
+                &[
+                    (old_ptr, one),
+                    (new_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.receive(channel_ids.node_diff, count, [block0]);
+        }
+        // from node_diff_window pull (new_ptr, new_end_ptr, old_ptr), the value is the last item
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 1, block 0",
+                n_vars,
+                &[(new_ptr, one), (new_end_ptr, one), (old_ptr, one)],
+            )?;
+            builder.receive(channel_ids.node_diff_window, count, [block0]);
+        }
+        // to node_diff push (old_end_ptr, new_end_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 2, block 0",
+                n_vars,
+                &[
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// SLOT
+
+#[derive(PartialEq)]
+pub(crate) struct NodeDiffSlotData {
+    ptrs: DiffPtrs,
+    old_next_ptr: u32,
+    new_next_ptr: u32,
+}
+
+struct SlotRowMajors {
+    old_ptr: B32,
+    new_ptr: B32,
+    old_end_ptr: B32,
+    new_end_ptr: B32,
+    old_next_ptr: B32,
+    new_next_ptr: B32,
+}
+
+pub(crate) struct NodeDiffSlotTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<SlotRowMajors>,
+}
+
+impl FunctionTable for NodeDiffSlotTable {
+    type Data = NodeDiffSlotData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let mem = &mut mpt_state.mem;
+        self.row_majors.push(SlotRowMajors {
+            old_ptr: mem.to_mult(data.ptrs.old_ptr),
+            new_ptr: mem.to_mult(data.ptrs.new_ptr),
+            old_end_ptr: mem.to_mult(data.ptrs.old_end_ptr),
+            new_end_ptr: mem.to_mult(data.ptrs.new_end_ptr),
+            old_next_ptr: mem.to_mult(data.old_next_ptr),
+            new_next_ptr: mem.to_mult(data.new_next_ptr),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_diff_slot",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_diff_slot");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let old_ptr = builder.add_committed("old_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_ptr = builder.add_committed("new_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_end_ptr = builder.add_committed("old_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_end_ptr = builder.add_committed("new_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_next_ptr = builder.add_committed("old_next_ptr", n_vars, B32::TOWER_LEVEL);
+        let new_next_ptr = builder.add_committed("new_next_ptr", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (old_ptr, B32),
+                    (new_ptr, B32),
+                    (old_end_ptr, B32),
+                    (new_end_ptr, B32),
+                    (old_next_ptr, B32),
+                    (new_next_ptr, B32),
+                ]
+            );
+        }
+
+        let one = B32::one();
+
+        // from node_diff pull (old_ptr, new_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 0, block 0",
+                n_vars,
+                &[
+                    (old_ptr, one),
+                    (new_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.receive(channel_ids.node_diff, count, [block0]);
+        }
+        // from node_diff_window pull (new_ptr, new_next_ptr, old_ptr, old_next_ptr), the child is
+        // empty in one node and holds the reference to the leaf in the other
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 1, block 0",
+                n_vars,
+                &[
+                    (new_ptr, one),
+                    (new_next_ptr, one),
+                    (old_ptr, one),
+                    (old_next_ptr, one),
+                ],
+            )?;
+            builder.receive(channel_ids.node_diff_window, count, [block0]);
+        }
+        // to node_diff push (old_next_ptr, new_next_ptr, old_end_ptr, new_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 2, block 0",
+                n_vars,
+                &[
+                    (old_next_ptr, one),
+                    (new_next_ptr, one),
+                    (old_end_ptr, one),
+                    (new_end_ptr, one),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // the hashing step of the post walk, which also pairs the post node with the pre node the
+    // pre walk reached at the same key position, whose saved mid state is pulled like a fork
+    pub(crate) fn node_diff(
+        &self,
+        rows: &mut Vec<Row>,
+        old_state: State,
+        state: State,
+        new_node_ptr: u32,
+    ) -> DiffPtrs {
+        assert_eq!(old_state.start_ptr, state.start_ptr);
+        assert_eq!(
+            old_state.key_ptr, state.key_ptr,
+            "updates keep the shape of the trie"
+        );
+        assert_eq!(self.state.mem[state.rlp_ptr], 0xa0);
+        let post_node_ptr = self.skip_list(new_node_ptr);
+        let total_blocks = ((post_node_ptr - new_node_ptr) / (HASH_R as u32) + 1) as u8;
+        let hash = keccak256(&self.state.mem[new_node_ptr..post_node_ptr]);
+        assert_eq!(
+            &self.state.mem[state.rlp_ptr + 1..state.rlp_ptr + HASH_REF_LEN],
+            hash.as_slice()
+        );
+        self.absorb_block(rows, [0; HASH_B], new_node_ptr, state.rlp_ptr, total_blocks);
+
+        let ptrs = DiffPtrs {
+            old_ptr: self.skip_list_header(rows, old_state.rlp_ptr),
+            new_ptr: self.skip_list_header(rows, new_node_ptr),
+            old_end_ptr: self.list_end(rows, old_state.rlp_ptr),
+            new_end_ptr: self.list_end(rows, new_node_ptr),
+        };
+        let data = NodeDiffData {
+            state,
+            new_node_ptr,
+            old_state,
+            ptrs,
+        };
+        rows.push(Row::NodeDiff(data));
+        ptrs
+    }
+
+    // compares the payloads from where node_diff left them, once the windows of every post
+    // node are known
+    pub(crate) fn diff_payload(&self, rows: &mut Vec<Row>, mut ptrs: DiffPtrs) {
+        while ptrs.new_ptr != ptrs.new_end_ptr {
+            if self.diff_windows.hash.contains(&ptrs.new_ptr) {
+                assert_eq!(
+                    self.state.mem[ptrs.old_ptr], 0xa0,
+                    "updates keep the shape of the trie"
+                );
+                rows.push(Row::NodeDiffHash(ptrs));
+                ptrs.old_ptr += HASH_REF_LEN;
+                ptrs.new_ptr += HASH_REF_LEN;
+            } else if let Some(&old_child_ptr) = self.diff_windows.slot.get(&ptrs.new_ptr) {
+                assert_eq!(
+                    ptrs.old_ptr, old_child_ptr,
+                    "updates keep the shape of the trie"
+                );
+                let data = NodeDiffSlotData {
+                    ptrs,
+                    old_next_ptr: self.skip_item(ptrs.old_ptr),
+                    new_next_ptr: self.skip_item(ptrs.new_ptr),
+                };
+                ptrs.old_ptr = data.old_next_ptr;
+                ptrs.new_ptr = data.new_next_ptr;
+                rows.push(Row::NodeDiffSlot(data));
+            } else if let Some(&old_value_ptr) = self.diff_windows.value.get(&ptrs.new_ptr) {
+                assert_eq!(
+                    ptrs.old_ptr, old_value_ptr,
+                    "updates keep the shape of the trie"
+                );
+                rows.push(Row::NodeDiffValue(ptrs));
+                ptrs.old_ptr = ptrs.old_end_ptr;
+                ptrs.new_ptr = ptrs.new_end_ptr;
+            } else {
+                // checked in-circuit only, the pre byte is read with the value of the post byte
+                let val = self.state.mem[ptrs.new_ptr];
+                let data = NodeDiffByteData { ptrs, val };
+                rows.push(Row::NodeDiffByte(data));
+                ptrs.old_ptr += 1;
+                ptrs.new_ptr += 1;
+            }
+        }
+        assert_eq!(ptrs.old_ptr, ptrs.old_end_ptr);
+    }
+
+    // compares two runs of bytes of the same length byte by byte, from a token pushed on
+    // node_diff by a table other than node_diff
+    pub(crate) fn diff_bytes(&self, rows: &mut Vec<Row>, old: Range<u32>, new: Range<u32>) {
+        assert_eq!(old.len(), new.len());
+        for (old_ptr, new_ptr) in izip!(old.clone(), new.clone()) {
+            let ptrs = DiffPtrs {
+                old_ptr,
+                new_ptr,
+                old_end_ptr: old.end,
+                new_end_ptr: new.end,
+            };
+            let val = self.state.mem[new_ptr];
+            assert_eq!(self.state.mem[old_ptr], val);
+            rows.push(Row::NodeDiffByte(NodeDiffByteData { ptrs, val }));
+        }
+    }
+}
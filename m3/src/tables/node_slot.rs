@@ -0,0 +1,668 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+// The leaf of a key inserted into an empty child of a branch, or deleted from one, has no node
+// to be paired with in the other trie. node_insert hashes the leaf of an inserted key into the
+// post walk in place of node_diff, and pulls the final state of the pre walk, which ends at the
+// empty child. node_delete hashes the leaf of a deleted key into the pre walk in place of
+// hash_trans, and pulls the final state of the post walk, which ends at the empty child. Both
+// grant the pair of children as a window of the branch on node_diff_window, and pull the end of
+// the leaf from leaf_end, where the verifier pushes the end of the value it reads, so the leaf
+// holds no other key.
+//
+// The branch a key is deleted from is not checked to keep two other children: a branch left
+// with a single child is not in canonical form, so its trie has no real post state root.
+
+#[derive(PartialEq)]
+pub(crate) struct NodeSlotData {
+    // at the child of the post node
+    pub(crate) state: State,
+    // at the child of the pre node
+    pub(crate) old_state: State,
+    leaf_ptr: u32,
+    leaf_end_ptr: u32,
+}
+
+struct ColMajors {
+    state_key_ptr_parity: BitVec,
+}
+
+struct RowMajors {
+    state_start_ptr: B32,
+    state_key_ptr_byte: B32,
+    state_rlp_ptr: B32,
+    state_ts: B32,
+    prefix_val_ts: B32,
+    old_rlp_ptr: B32,
+    old_ts: B32,
+    old_prefix_val_ts: B32,
+    leaf_ptr: B32,
+    leaf_end_ptr: B32,
+}
+
+impl RowMajors {
+    fn new(mpt_state: &mut MPTState, data: &NodeSlotData) -> Self {
+        let mem = &mut mpt_state.mem;
+        Self {
+            state_start_ptr: mem.to_mult(data.state.start_ptr),
+            state_key_ptr_byte: mem.to_mult(data.state.key_ptr.byte),
+            state_rlp_ptr: mem.to_mult(data.state.rlp_ptr),
+            state_ts: data.state.ts,
+            prefix_val_ts: mem.process_timestamp(data.state.rlp_ptr),
+            old_rlp_ptr: mem.to_mult(data.old_state.rlp_ptr),
+            old_ts: data.old_state.ts,
+            old_prefix_val_ts: mem.process_timestamp(data.old_state.rlp_ptr),
+            leaf_ptr: mem.to_mult(data.leaf_ptr),
+            leaf_end_ptr: mem.to_mult(data.leaf_end_ptr),
+        }
+    }
+}
+
+// the columns node_insert and node_delete share
+struct Columns {
+    state_start_ptr: OracleId,
+    state_key_ptr_byte: OracleId,
+    state_rlp_ptr: OracleId,
+    state_ts: OracleId,
+    prefix_val_ts: OracleId,
+    old_rlp_ptr: OracleId,
+    old_ts: OracleId,
+    old_prefix_val_ts: OracleId,
+    leaf_ptr: OracleId,
+    leaf_end_ptr: OracleId,
+    state_key_ptr_parity: OracleId,
+    initial_state: Vec<OracleId>,
+}
+
+impl Columns {
+    fn new(
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        n_vars: usize,
+        col_majors: ColMajors,
+        row_majors: &[RowMajors],
+    ) -> Result<Self, anyhow::Error> {
+        let state_start_ptr = builder.add_committed("state_start_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_key_ptr_byte =
+            builder.add_committed("state_key_ptr_byte", n_vars, B32::TOWER_LEVEL);
+        let state_rlp_ptr = builder.add_committed("state_rlp_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_ts = builder.add_committed("state_ts", n_vars, B32::TOWER_LEVEL);
+        let prefix_val_ts = builder.add_committed("prefix_val_ts", n_vars, B32::TOWER_LEVEL);
+        let old_rlp_ptr = builder.add_committed("old_rlp_ptr", n_vars, B32::TOWER_LEVEL);
+        let old_ts = builder.add_committed("old_ts", n_vars, B32::TOWER_LEVEL);
+        let old_prefix_val_ts =
+            builder.add_committed("old_prefix_val_ts", n_vars, B32::TOWER_LEVEL);
+        let leaf_ptr = builder.add_committed("leaf_ptr", n_vars, B32::TOWER_LEVEL);
+        let leaf_end_ptr = builder.add_committed("leaf_end_ptr", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (state_start_ptr, B32),
+                    (state_key_ptr_byte, B32),
+                    (state_rlp_ptr, B32),
+                    (old_rlp_ptr, B32),
+                    (leaf_ptr, B32),
+                    (leaf_end_ptr, B32),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (state_ts, B32, B32::one()),
+                    (prefix_val_ts, B32, B32::one()),
+                    (old_ts, B32, B32::one()),
+                    (old_prefix_val_ts, B32, B32::one()),
+                ]
+            );
+        }
+
+        let poly = binius_core::transparent::constant::Constant::new(n_vars, B128::zero());
+        let initial_state = (0..12)
+            .map(|_| builder.add_transparent("initial_state", poly))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(witness) = builder.witness() {
+            initial_state.iter().for_each(|id| {
+                witness.new_column::<B128>(*id);
+            });
+        }
+
+        let state_key_ptr_parity =
+            builder.add_committed("state_key_ptr_parity", n_vars, B1::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            (
+                col_majors.state_key_ptr_parity,
+                witness
+                    .new_column::<B1>(state_key_ptr_parity)
+                    .as_mut_slice::<u8>(),
+            )
+                .into_par_iter()
+                .for_each(|(src, dest)| {
+                    *dest = src;
+                });
+        }
+
+        Ok(Self {
+            state_start_ptr,
+            state_key_ptr_byte,
+            state_rlp_ptr,
+            state_ts,
+            prefix_val_ts,
+            old_rlp_ptr,
+            old_ts,
+            old_prefix_val_ts,
+            leaf_ptr,
+            leaf_end_ptr,
+            state_key_ptr_parity,
+            initial_state,
+        })
+    }
+
+    // to absorb_block push (leaf_ptr, child_ptr, initial_state), the child being the one holding
+    // the reference to the leaf
+    fn absorb_leaf(
+        &self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        name: &str,
+        child_ptr: OracleId,
+        n_vars: usize,
+        count: usize,
+        channel_ids: &ChannelIds,
+    ) -> Result<(), anyhow::Error> {
+        let one = B32::one();
+        let block0 = ptr_lanes_block(
+            builder,
+            name,
+            n_vars,
+            &[(self.leaf_ptr, one), (child_ptr, one)],
+        )?;
+        let mut all_blocks = vec![block0];
+        all_blocks.extend(self.initial_state.iter().copied());
+
+        builder.send(channel_ids.absorb_block, count, all_blocks);
+        Ok(())
+    }
+
+    // to list_end push (leaf_ptr, leaf_end_ptr), and from leaf_end pull (state_start_ptr,
+    // leaf_end_ptr)
+    fn check_leaf_end(
+        &self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        names: [&str; 2],
+        n_vars: usize,
+        count: usize,
+        channel_ids: &ChannelIds,
+    ) -> Result<(), anyhow::Error> {
+        let one = B32::one();
+        let [list_end_name, leaf_end_name] = names;
+        let list_end_block = ptr_lanes_block(
+            builder,
+            list_end_name,
+            n_vars,
+            &[(self.leaf_ptr, one), (self.leaf_end_ptr, one)],
+        )?;
+        builder.send(channel_ids.list_end, count, [list_end_block]);
+        let leaf_end_block = ptr_lanes_block(
+            builder,
+            leaf_end_name,
+            n_vars,
+            &[(self.state_start_ptr, one), (self.leaf_end_ptr, one)],
+        )?;
+        builder.receive(channel_ids.leaf_end, count, [leaf_end_block]);
+        Ok(())
+    }
+}
+
+// INSERT
+
+pub(crate) struct NodeInsertTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: ColMajors,
+    row_majors: Vec<RowMajors>,
+}
+
+impl FunctionTable for NodeInsertTable {
+    type Data = NodeSlotData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: ColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors.state_key_ptr_parity = BitVec::new(Some(count));
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.row_majors.push(RowMajors::new(mpt_state, &data));
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_insert",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_insert");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let columns = Columns::new(builder, n_vars, self.col_majors, &self.row_majors)?;
+        let Columns {
+            state_start_ptr,
+            state_key_ptr_byte,
+            state_rlp_ptr,
+            state_ts,
+            prefix_val_ts,
+            old_rlp_ptr,
+            old_ts,
+            old_prefix_val_ts,
+            leaf_ptr,
+            state_key_ptr_parity,
+            ..
+        } = columns;
+
+        let one = B32::one();
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+
+        // from post_state pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Pull,
+                state_start_ptr,
+                state_key_ptr_byte,
+                state_rlp_ptr,
+                state_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.post_state,
+            )?;
+        }
+        // read 0xa0 from mem
+        {
+            read_const(
+                builder,
+                "0xa0",
+                state_rlp_ptr,
+                B8::new(0xa0),
+                prefix_val_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // to absorb_block push (leaf_ptr, state_rlp_ptr, initial_state)
+        columns.absorb_leaf(
+            builder,
+            "flush 2, block 0",
+            state_rlp_ptr,
+            n_vars,
+            count,
+            channel_ids,
+        )?;
+        // to post_state push (state_start_ptr, state_key_ptr_byte, leaf_ptr, state_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Push,
+                state_start_ptr,
+                state_key_ptr_byte,
+                leaf_ptr,
+                state_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.post_state,
+            )?;
+        }
+        // from state pull (state_start_ptr, state_key_ptr_byte, old_rlp_ptr, old_ts; state_key_ptr_parity)
+        // the final state of the pre walk
+        {
+            flush_state(
+                builder,
+                FlushDirection::Pull,
+                state_start_ptr,
+                state_key_ptr_byte,
+                old_rlp_ptr,
+                old_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.state,
+            )?;
+        }
+        // read 0x80 from mem, the empty child
+        {
+            read_const(
+                builder,
+                "0x80",
+                old_rlp_ptr,
+                B8::new(0x80),
+                old_prefix_val_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // to list_end push (leaf_ptr, leaf_end_ptr), from leaf_end pull (state_start_ptr, leaf_end_ptr)
+        columns.check_leaf_end(
+            builder,
+            ["flush 6, block 0", "flush 7, block 0"],
+            n_vars,
+            count,
+            channel_ids,
+        )?;
+        // to node_diff_window push (state_rlp_ptr, state_rlp_ptr * g^33, old_rlp_ptr, old_rlp_ptr * g)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 8, block 0",
+                n_vars,
+                &[
+                    (state_rlp_ptr, one),
+                    (state_rlp_ptr, g.pow([HASH_REF_LEN as u64])),
+                    (old_rlp_ptr, one),
+                    (old_rlp_ptr, g),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff_window, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// DELETE
+
+pub(crate) struct NodeDeleteTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: ColMajors,
+    row_majors: Vec<RowMajors>,
+}
+
+impl FunctionTable for NodeDeleteTable {
+    type Data = NodeSlotData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: ColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors.state_key_ptr_parity = BitVec::new(Some(count));
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.row_majors.push(RowMajors::new(mpt_state, &data));
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_delete",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_delete");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let columns = Columns::new(builder, n_vars, self.col_majors, &self.row_majors)?;
+        let Columns {
+            state_start_ptr,
+            state_key_ptr_byte,
+            state_rlp_ptr,
+            state_ts,
+            prefix_val_ts,
+            old_rlp_ptr,
+            old_ts,
+            old_prefix_val_ts,
+            leaf_ptr,
+            state_key_ptr_parity,
+            ..
+        } = columns;
+
+        let one = B32::one();
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+
+        // from state pull (state_start_ptr, state_key_ptr_byte, old_rlp_ptr, old_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Pull,
+                state_start_ptr,
+                state_key_ptr_byte,
+                old_rlp_ptr,
+                old_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.state,
+            )?;
+        }
+        // read 0xa0 from mem
+        {
+            read_const(
+                builder,
+                "0xa0",
+                old_rlp_ptr,
+                B8::new(0xa0),
+                old_prefix_val_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // to absorb_block push (leaf_ptr, old_rlp_ptr, initial_state)
+        columns.absorb_leaf(
+            builder,
+            "flush 2, block 0",
+            old_rlp_ptr,
+            n_vars,
+            count,
+            channel_ids,
+        )?;
+        // to state push (state_start_ptr, state_key_ptr_byte, leaf_ptr, old_ts; state_key_ptr_parity)
+        {
+            flush_state(
+                builder,
+                FlushDirection::Push,
+                state_start_ptr,
+                state_key_ptr_byte,
+                leaf_ptr,
+                old_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.state,
+            )?;
+        }
+        // to list_end push (leaf_ptr, leaf_end_ptr), from leaf_end pull (state_start_ptr, leaf_end_ptr)
+        columns.check_leaf_end(
+            builder,
+            ["flush 4, block 0", "flush 5, block 0"],
+            n_vars,
+            count,
+            channel_ids,
+        )?;
+        // from post_state pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
+        // the final state of the post walk
+        {
+            flush_state(
+                builder,
+                FlushDirection::Pull,
+                state_start_ptr,
+                state_key_ptr_byte,
+                state_rlp_ptr,
+                state_ts,
+                state_key_ptr_parity,
+                n_vars,
+                count,
+                channel_ids.post_state,
+            )?;
+        }
+        // read 0x80 from mem, the empty child
+        {
+            read_const(
+                builder,
+                "0x80",
+                state_rlp_ptr,
+                B8::new(0x80),
+                prefix_val_ts,
+                n_vars,
+                count,
+                channel_ids.mem,
+            )?;
+        }
+        // to node_diff_window push (state_rlp_ptr, state_rlp_ptr * g, old_rlp_ptr, old_rlp_ptr * g^33)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 8, block 0",
+                n_vars,
+                &[
+                    (state_rlp_ptr, one),
+                    (state_rlp_ptr, g),
+                    (old_rlp_ptr, one),
+                    (old_rlp_ptr, g.pow([HASH_REF_LEN as u64])),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff_window, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // the hashing step of the post walk into the leaf of an inserted key, which the child at
+    // state.rlp_ptr of the post node refers to, while the pre walk ended at old_state.rlp_ptr,
+    // the same child of the pre node left empty
+    pub(crate) fn node_insert(
+        &self,
+        rows: &mut Vec<Row>,
+        old_state: State,
+        state: State,
+        leaf_ptr: u32,
+    ) -> State {
+        assert_eq!(self.state.mem[old_state.rlp_ptr], 0x80);
+        let data = self.node_slot(rows, old_state, state, leaf_ptr, state.rlp_ptr);
+        rows.push(Row::NodeInsert(data));
+        State {
+            rlp_ptr: leaf_ptr,
+            ..state
+        }
+    }
+
+    // the hashing step of the pre walk into the leaf of a deleted key, which the child at
+    // old_state.rlp_ptr of the pre node refers to, taken once the post walk ended at
+    // state.rlp_ptr, the same child of the post node left empty
+    pub(crate) fn node_delete(
+        &self,
+        rows: &mut Vec<Row>,
+        old_state: State,
+        state: State,
+        leaf_ptr: u32,
+    ) {
+        assert_eq!(self.state.mem[state.rlp_ptr], 0x80);
+        let data = self.node_slot(rows, old_state, state, leaf_ptr, old_state.rlp_ptr);
+        rows.push(Row::NodeDelete(data));
+    }
+
+    // absorbs the leaf into the reference at child_ptr
+    fn node_slot(
+        &self,
+        rows: &mut Vec<Row>,
+        old_state: State,
+        state: State,
+        leaf_ptr: u32,
+        child_ptr: u32,
+    ) -> NodeSlotData {
+        assert_eq!(old_state.start_ptr, state.start_ptr);
+        assert_eq!(old_state.key_ptr, state.key_ptr);
+        assert_eq!(self.state.mem[child_ptr], 0xa0);
+        let post_leaf_ptr = self.skip_list(leaf_ptr);
+        let total_blocks = ((post_leaf_ptr - leaf_ptr) / (HASH_R as u32) + 1) as u8;
+        let hash = keccak256(&self.state.mem[leaf_ptr..post_leaf_ptr]);
+        assert_eq!(
+            &self.state.mem[child_ptr + 1..child_ptr + HASH_REF_LEN],
+            hash.as_slice()
+        );
+        self.absorb_block(rows, [0; HASH_B], leaf_ptr, child_ptr, total_blocks);
+        let leaf_end_ptr = self.list_end(rows, leaf_ptr);
+        NodeSlotData {
+            state,
+            old_state,
+            leaf_ptr,
+            leaf_end_ptr,
+        }
+    }
+}
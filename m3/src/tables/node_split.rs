@@ -0,0 +1,1097 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+use std::ops::Range;
+
+// A key inserted into the path of a leaf or an extension it parts from splits the node: the post
+// state trie holds a branch with the leaf of the key and the rest of the node, behind an
+// extension over the nibbles both paths share. A key deleted from such a branch collapses it back.
+// The walk through the trie without the key, the flat walk, cannot be paired node by node with
+// the walk through the trie with it, the forked walk, so it goes through virtual nodes instead:
+// an extension over the shared nibbles, prepared by the prover, and an empty branch, a template
+// the verifier reads. node_split hashes the flat node into its reference in the pre walk, and
+// moves the walk to a virtual reference to the virtual nodes, which are then paired with the
+// forked ones by node_diff like any other, the key ending at an empty child of the template.
+// node_collapse does the same in the post walk.
+//
+// The rest of the flat node is tied to the other child of the forked branch by a sibling key,
+// which the prover lays out with the nibbles of the flat path past the shared ones. Its walk is
+// forked from the flat node, which it walks to the end of the path, and from the forked branch,
+// through the sibling node left with the rest of the path, hashed by node_insert or node_delete,
+// or straight to the child of an extension the branch ends, granted by node_graft or
+// node_prune. The virtual branch is walked along to the empty child paired with the sibling.
+// Both walks are pulled back at the same key position, and the last items they end at are
+// compared byte by byte. The sibling node ends where the verifier would read a value, so
+// node_split pushes the end of its last item to leaf_end in place of the verifier.
+//
+// The nodes a split leaves, or a collapse takes away, are not checked to be canonical: the kinds
+// of the sibling node and of the forked extension are not checked, nor the forked branch to hold
+// no other child, so such a trie has no real post state root.
+
+// where a key parts from the path of a node of the other trie, and the virtual nodes in its place
+#[derive(Debug)]
+pub(crate) struct Reshape {
+    // the index of the virtual node in place of the flat one in the proof of the key, which is the
+    // index of the forked node in the proof of the other trie
+    pub(crate) index: usize,
+    pub(crate) flat_ptr: u32,
+    // the key pointer of the key at the flat node
+    pub(crate) flat_key_ptr: NibPtr,
+    pub(crate) virtual_ref_ptr: u32,
+    pub(crate) template_ptr: u32,
+    pub(crate) branch_ptr: u32,
+    pub(crate) sibling_start_ptr: u32,
+    // where both walks of the sibling key end
+    pub(crate) sibling_key_ptr: NibPtr,
+    // the sibling node, or None if the child of the flat extension is grafted onto the branch
+    pub(crate) sibling_ptr: Option<u32>,
+    // the last items the walks of the sibling key end at
+    pub(crate) flat_tail: Range<u32>,
+    pub(crate) forked_tail: Range<u32>,
+    // the children of the template and of the forked branch the sibling key goes on to
+    pub(crate) virtual_child_ptr: u32,
+    pub(crate) forked_child_ptr: u32,
+}
+
+// SPLIT AND COLLAPSE
+
+#[derive(PartialEq)]
+pub(crate) struct NodeSplitData {
+    // at the reference to the flat node
+    pub(crate) state: State,
+    virtual_ref_ptr: u32,
+    flat_ptr: u32,
+    flat_end_ptr: u32,
+    sibling_start_ptr: u32,
+    sibling_key_ptr: NibPtr,
+    flat_tail_ptr: u32,
+    forked_tail_ptr: u32,
+    forked_end_ptr: u32,
+    forked_ts: B32,
+}
+
+struct ColMajors {
+    state_key_ptr_parity: BitVec,
+    sibling_key_ptr_parity: BitVec,
+}
+
+struct RowMajors {
+    state_start_ptr: B32,
+    state_key_ptr_byte: B32,
+    state_rlp_ptr: B32,
+    state_ts: B32,
+    prefix_val_ts: B32,
+    virtual_ref_ptr: B32,
+    flat_ptr: B32,
+    flat_end_ptr: B32,
+    sibling_start_ptr: B32,
+    sibling_key_ptr_byte: B32,
+    flat_tail_ptr: B32,
+    forked_tail_ptr: B32,
+    forked_end_ptr: B32,
+    forked_ts: B32,
+}
+
+impl RowMajors {
+    fn new(mpt_state: &mut MPTState, data: &NodeSplitData) -> Self {
+        let mem = &mut mpt_state.mem;
+        Self {
+            state_start_ptr: mem.to_mult(data.state.start_ptr),
+            state_key_ptr_byte: mem.to_mult(data.state.key_ptr.byte),
+            state_rlp_ptr: mem.to_mult(data.state.rlp_ptr),
+            state_ts: data.state.ts,
+            prefix_val_ts: mem.process_timestamp(data.state.rlp_ptr),
+            virtual_ref_ptr: mem.to_mult(data.virtual_ref_ptr),
+            flat_ptr: mem.to_mult(data.flat_ptr),
+            flat_end_ptr: mem.to_mult(data.flat_end_ptr),
+            sibling_start_ptr: mem.to_mult(data.sibling_start_ptr),
+            sibling_key_ptr_byte: mem.to_mult(data.sibling_key_ptr.byte),
+            flat_tail_ptr: mem.to_mult(data.flat_tail_ptr),
+            forked_tail_ptr: mem.to_mult(data.forked_tail_ptr),
+            forked_end_ptr: mem.to_mult(data.forked_end_ptr),
+            forked_ts: data.forked_ts,
+        }
+    }
+}
+
+// the columns node_split and node_collapse share
+struct Columns {
+    state_start_ptr: OracleId,
+    state_key_ptr_byte: OracleId,
+    state_rlp_ptr: OracleId,
+    state_ts: OracleId,
+    prefix_val_ts: OracleId,
+    virtual_ref_ptr: OracleId,
+    flat_ptr: OracleId,
+    flat_end_ptr: OracleId,
+    sibling_start_ptr: OracleId,
+    sibling_key_ptr_byte: OracleId,
+    flat_tail_ptr: OracleId,
+    forked_tail_ptr: OracleId,
+    forked_end_ptr: OracleId,
+    forked_ts: OracleId,
+    state_key_ptr_parity: OracleId,
+    sibling_key_ptr_parity: OracleId,
+    initial_state: Vec<OracleId>,
+}
+
+impl Columns {
+    fn new(
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        n_vars: usize,
+        col_majors: ColMajors,
+        row_majors: &[RowMajors],
+    ) -> Result<Self, anyhow::Error> {
+        let state_start_ptr = builder.add_committed("state_start_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_key_ptr_byte =
+            builder.add_committed("state_key_ptr_byte", n_vars, B32::TOWER_LEVEL);
+        let state_rlp_ptr = builder.add_committed("state_rlp_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_ts = builder.add_committed("state_ts", n_vars, B32::TOWER_LEVEL);
+        let prefix_val_ts = builder.add_committed("prefix_val_ts", n_vars, B32::TOWER_LEVEL);
+        let virtual_ref_ptr = builder.add_committed("virtual_ref_ptr", n_vars, B32::TOWER_LEVEL);
+        let flat_ptr = builder.add_committed("flat_ptr", n_vars, B32::TOWER_LEVEL);
+        let flat_end_ptr = builder.add_committed("flat_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let sibling_start_ptr =
+            builder.add_committed("sibling_start_ptr", n_vars, B32::TOWER_LEVEL);
+        let sibling_key_ptr_byte =
+            builder.add_committed("sibling_key_ptr_byte", n_vars, B32::TOWER_LEVEL);
+        let flat_tail_ptr = builder.add_committed("flat_tail_ptr", n_vars, B32::TOWER_LEVEL);
+        let forked_tail_ptr = builder.add_committed("forked_tail_ptr", n_vars, B32::TOWER_LEVEL);
+        let forked_end_ptr = builder.add_committed("forked_end_ptr", n_vars, B32::TOWER_LEVEL);
+        let forked_ts = builder.add_committed("forked_ts", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (state_start_ptr, B32),
+                    (state_key_ptr_byte, B32),
+                    (state_rlp_ptr, B32),
+                    (virtual_ref_ptr, B32),
+                    (flat_ptr, B32),
+                    (flat_end_ptr, B32),
+                    (sibling_start_ptr, B32),
+                    (sibling_key_ptr_byte, B32),
+                    (flat_tail_ptr, B32),
+                    (forked_tail_ptr, B32),
+                    (forked_end_ptr, B32),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [
+                    (state_ts, B32, B32::one()),
+                    (prefix_val_ts, B32, B32::one()),
+                    (forked_ts, B32, B32::one()),
+                ]
+            );
+        }
+
+        let poly = binius_core::transparent::constant::Constant::new(n_vars, B128::zero());
+        let initial_state = (0..12)
+            .map(|_| builder.add_transparent("initial_state", poly))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(witness) = builder.witness() {
+            initial_state.iter().for_each(|id| {
+                witness.new_column::<B128>(*id);
+            });
+        }
+
+        let state_key_ptr_parity =
+            builder.add_committed("state_key_ptr_parity", n_vars, B1::TOWER_LEVEL);
+        let sibling_key_ptr_parity =
+            builder.add_committed("sibling_key_ptr_parity", n_vars, B1::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            for (src, parity) in [
+                (col_majors.state_key_ptr_parity, state_key_ptr_parity),
+                (col_majors.sibling_key_ptr_parity, sibling_key_ptr_parity),
+            ] {
+                (src, witness.new_column::<B1>(parity).as_mut_slice::<u8>())
+                    .into_par_iter()
+                    .for_each(|(src, dest)| {
+                        *dest = src;
+                    });
+            }
+        }
+
+        Ok(Self {
+            state_start_ptr,
+            state_key_ptr_byte,
+            state_rlp_ptr,
+            state_ts,
+            prefix_val_ts,
+            virtual_ref_ptr,
+            flat_ptr,
+            flat_end_ptr,
+            sibling_start_ptr,
+            sibling_key_ptr_byte,
+            flat_tail_ptr,
+            forked_tail_ptr,
+            forked_end_ptr,
+            forked_ts,
+            state_key_ptr_parity,
+            sibling_key_ptr_parity,
+            initial_state,
+        })
+    }
+
+    // the flushes node_split and node_collapse share, the flat walk being the pre walk of a split
+    // and the post walk of a collapse; the last items are compared with the ones of the pre trie
+    // as the old ones
+    #[allow(clippy::too_many_arguments)]
+    fn flush(
+        &self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        n_vars: usize,
+        count: usize,
+        channel_ids: &ChannelIds,
+        flat_channel: ChannelId,
+        forked_channel: ChannelId,
+    ) -> Result<(), anyhow::Error> {
+        let one = B32::one();
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+
+        // from flat pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
+        flush_state(
+            builder,
+            FlushDirection::Pull,
+            self.state_start_ptr,
+            self.state_key_ptr_byte,
+            self.state_rlp_ptr,
+            self.state_ts,
+            self.state_key_ptr_parity,
+            n_vars,
+            count,
+            flat_channel,
+        )?;
+        // read 0xa0 from mem
+        read_const(
+            builder,
+            "0xa0",
+            self.state_rlp_ptr,
+            B8::new(0xa0),
+            self.prefix_val_ts,
+            n_vars,
+            count,
+            channel_ids.mem,
+        )?;
+        // to absorb_block push (flat_ptr, state_rlp_ptr, initial_state)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 2, block 0",
+                n_vars,
+                &[(self.flat_ptr, one), (self.state_rlp_ptr, one)],
+            )?;
+            let mut all_blocks = vec![block0];
+            all_blocks.extend(self.initial_state.iter().copied());
+
+            builder.send(channel_ids.absorb_block, count, all_blocks);
+        }
+        // to flat push (state_start_ptr, state_key_ptr_byte, virtual_ref_ptr, state_ts; state_key_ptr_parity)
+        flush_state(
+            builder,
+            FlushDirection::Push,
+            self.state_start_ptr,
+            self.state_key_ptr_byte,
+            self.virtual_ref_ptr,
+            self.state_ts,
+            self.state_key_ptr_parity,
+            n_vars,
+            count,
+            flat_channel,
+        )?;
+        // to flat push (state_start_ptr, state_key_ptr_byte, flat_ptr, 1; state_key_ptr_parity),
+        // the mid state of the flat node the sibling key forks, and pull it back forked once
+        {
+            let lanes = [
+                (self.state_start_ptr, one),
+                (self.state_key_ptr_byte, one),
+                (self.flat_ptr, one),
+            ];
+            let block0 = ptr_lanes_block_with_offset(
+                builder,
+                "flush 4, block 0",
+                n_vars,
+                basis(32, 3),
+                &lanes,
+            )?;
+            builder.send(flat_channel, count, [block0, self.state_key_ptr_parity]);
+            let block0 = ptr_lanes_block_with_offset(
+                builder,
+                "flush 5, block 0",
+                n_vars,
+                basis(32, 3) * g,
+                &lanes,
+            )?;
+            builder.receive(flat_channel, count, [block0, self.state_key_ptr_parity]);
+        }
+        // to list_end push (flat_ptr, flat_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 6, block 0",
+                n_vars,
+                &[(self.flat_ptr, one), (self.flat_end_ptr, one)],
+            )?;
+            builder.send(channel_ids.list_end, count, [block0]);
+        }
+        // from flat pull (sibling_start_ptr, sibling_key_ptr_byte, flat_tail_ptr, 1; sibling_key_ptr_parity)
+        {
+            let block0 = ptr_lanes_block_with_offset(
+                builder,
+                "flush 7, block 0",
+                n_vars,
+                basis(32, 3),
+                &[
+                    (self.sibling_start_ptr, one),
+                    (self.sibling_key_ptr_byte, one),
+                    (self.flat_tail_ptr, one),
+                ],
+            )?;
+            builder.receive(flat_channel, count, [block0, self.sibling_key_ptr_parity]);
+        }
+        // from forked pull (sibling_start_ptr, sibling_key_ptr_byte, forked_tail_ptr, forked_ts; sibling_key_ptr_parity)
+        flush_state(
+            builder,
+            FlushDirection::Pull,
+            self.sibling_start_ptr,
+            self.sibling_key_ptr_byte,
+            self.forked_tail_ptr,
+            self.forked_ts,
+            self.sibling_key_ptr_parity,
+            n_vars,
+            count,
+            forked_channel,
+        )?;
+        // to leaf_end push (sibling_start_ptr, forked_end_ptr)
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 9, block 0",
+                n_vars,
+                &[(self.sibling_start_ptr, one), (self.forked_end_ptr, one)],
+            )?;
+            builder.send(channel_ids.leaf_end, count, [block0]);
+        }
+        Ok(())
+    }
+
+    // to node_diff push (old_tail_ptr, new_tail_ptr, old_end_ptr, new_end_ptr), and pull it back
+    // at the end of both items
+    fn compare_tails(
+        &self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        n_vars: usize,
+        count: usize,
+        channel_ids: &ChannelIds,
+        old: [OracleId; 2],
+        new: [OracleId; 2],
+    ) -> Result<(), anyhow::Error> {
+        let one = B32::one();
+        let [old_tail_ptr, old_end_ptr] = old;
+        let [new_tail_ptr, new_end_ptr] = new;
+        let block0 = ptr_lanes_block(
+            builder,
+            "flush 10, block 0",
+            n_vars,
+            &[
+                (old_tail_ptr, one),
+                (new_tail_ptr, one),
+                (old_end_ptr, one),
+                (new_end_ptr, one),
+            ],
+        )?;
+        builder.send(channel_ids.node_diff, count, [block0]);
+        let block0 = ptr_lanes_block(
+            builder,
+            "flush 11, block 0",
+            n_vars,
+            &[
+                (old_end_ptr, one),
+                (new_end_ptr, one),
+                (old_end_ptr, one),
+                (new_end_ptr, one),
+            ],
+        )?;
+        builder.receive(channel_ids.node_diff, count, [block0]);
+        Ok(())
+    }
+}
+
+// SPLIT
+
+pub(crate) struct NodeSplitTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: ColMajors,
+    row_majors: Vec<RowMajors>,
+}
+
+impl FunctionTable for NodeSplitTable {
+    type Data = NodeSplitData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: ColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+                sibling_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors = ColMajors {
+            state_key_ptr_parity: BitVec::new(Some(count)),
+            sibling_key_ptr_parity: BitVec::new(Some(count)),
+        };
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.col_majors
+            .sibling_key_ptr_parity
+            .push(data.sibling_key_ptr.parity);
+        self.row_majors.push(RowMajors::new(mpt_state, &data));
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_split",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_split");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let columns = Columns::new(builder, n_vars, self.col_majors, &self.row_majors)?;
+        // the flat walk is the pre walk
+        columns.flush(
+            builder,
+            n_vars,
+            count,
+            channel_ids,
+            channel_ids.state,
+            channel_ids.post_state,
+        )?;
+        // the last item of the flat node is the old one
+        columns.compare_tails(
+            builder,
+            n_vars,
+            count,
+            channel_ids,
+            [columns.flat_tail_ptr, columns.flat_end_ptr],
+            [columns.forked_tail_ptr, columns.forked_end_ptr],
+        )?;
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// COLLAPSE
+
+pub(crate) struct NodeCollapseTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: ColMajors,
+    row_majors: Vec<RowMajors>,
+}
+
+impl FunctionTable for NodeCollapseTable {
+    type Data = NodeSplitData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: ColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+                sibling_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors = ColMajors {
+            state_key_ptr_parity: BitVec::new(Some(count)),
+            sibling_key_ptr_parity: BitVec::new(Some(count)),
+        };
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.col_majors
+            .sibling_key_ptr_parity
+            .push(data.sibling_key_ptr.parity);
+        self.row_majors.push(RowMajors::new(mpt_state, &data));
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_collapse",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_collapse");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let columns = Columns::new(builder, n_vars, self.col_majors, &self.row_majors)?;
+        // the flat walk is the post walk
+        columns.flush(
+            builder,
+            n_vars,
+            count,
+            channel_ids,
+            channel_ids.post_state,
+            channel_ids.state,
+        )?;
+        // the last item of the sibling node, or the child of the forked branch, is the old one
+        columns.compare_tails(
+            builder,
+            n_vars,
+            count,
+            channel_ids,
+            [columns.forked_tail_ptr, columns.forked_end_ptr],
+            [columns.flat_tail_ptr, columns.flat_end_ptr],
+        )?;
+
+        let one = B32::one();
+        let g_33 = B32::MULTIPLICATIVE_GENERATOR.pow([HASH_REF_LEN as u64]);
+        // from node_diff_window pull (virtual_ref_ptr, virtual_ref_ptr * g^33), the window
+        // node_diff grants for the virtual reference, and to node_diff_window push
+        // (state_rlp_ptr, state_rlp_ptr * g^33) for the reference to the flat node in its place
+        {
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 12, block 0",
+                n_vars,
+                &[
+                    (columns.virtual_ref_ptr, one),
+                    (columns.virtual_ref_ptr, g_33),
+                ],
+            )?;
+            builder.receive(channel_ids.node_diff_window, count, [block0]);
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 13, block 0",
+                n_vars,
+                &[(columns.state_rlp_ptr, one), (columns.state_rlp_ptr, g_33)],
+            )?;
+            builder.send(channel_ids.node_diff_window, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// GRAFT AND PRUNE
+
+#[derive(PartialEq)]
+pub(crate) struct NodeGraftData {
+    // at the child of the forked branch, with a timestamp of 1
+    state: State,
+    virtual_child_ptr: u32,
+}
+
+struct GraftColMajors {
+    state_key_ptr_parity: BitVec,
+}
+
+struct GraftRowMajors {
+    state_start_ptr: B32,
+    state_key_ptr_byte: B32,
+    state_rlp_ptr: B32,
+    virtual_child_ptr: B32,
+    virtual_prefix_val_ts: B32,
+}
+
+// the columns node_graft and node_prune share
+struct GraftColumns {
+    state_start_ptr: OracleId,
+    state_key_ptr_byte: OracleId,
+    state_rlp_ptr: OracleId,
+    virtual_child_ptr: OracleId,
+    virtual_prefix_val_ts: OracleId,
+    state_key_ptr_parity: OracleId,
+}
+
+impl GraftColumns {
+    fn new(
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        n_vars: usize,
+        col_majors: GraftColMajors,
+        row_majors: &[GraftRowMajors],
+    ) -> Result<Self, anyhow::Error> {
+        let state_start_ptr = builder.add_committed("state_start_ptr", n_vars, B32::TOWER_LEVEL);
+        let state_key_ptr_byte =
+            builder.add_committed("state_key_ptr_byte", n_vars, B32::TOWER_LEVEL);
+        let state_rlp_ptr = builder.add_committed("state_rlp_ptr", n_vars, B32::TOWER_LEVEL);
+        let virtual_child_ptr =
+            builder.add_committed("virtual_child_ptr", n_vars, B32::TOWER_LEVEL);
+        let virtual_prefix_val_ts =
+            builder.add_committed("virtual_prefix_val_ts", n_vars, B32::TOWER_LEVEL);
+
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = row_majors;
+            populate_committed_polys!(
+                par_data_iter,
+                witness,
+                [
+                    (state_start_ptr, B32),
+                    (state_key_ptr_byte, B32),
+                    (state_rlp_ptr, B32),
+                    (virtual_child_ptr, B32),
+                ]
+            );
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [(virtual_prefix_val_ts, B32, B32::one())]
+            );
+        }
+
+        let state_key_ptr_parity =
+            builder.add_committed("state_key_ptr_parity", n_vars, B1::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            (
+                col_majors.state_key_ptr_parity,
+                witness
+                    .new_column::<B1>(state_key_ptr_parity)
+                    .as_mut_slice::<u8>(),
+            )
+                .into_par_iter()
+                .for_each(|(src, dest)| {
+                    *dest = src;
+                });
+        }
+
+        Ok(Self {
+            state_start_ptr,
+            state_key_ptr_byte,
+            state_rlp_ptr,
+            virtual_child_ptr,
+            virtual_prefix_val_ts,
+            state_key_ptr_parity,
+        })
+    }
+
+    // from forked pull (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, 1; state_key_ptr_parity)
+    // and push it back with a timestamp of g for node_split or node_collapse, from virtual pull
+    // (state_start_ptr, state_key_ptr_byte, virtual_child_ptr, 1; state_key_ptr_parity) and read
+    // 0x80 there, and from leaf_end pull (state_start_ptr, state_rlp_ptr * g^33), the child
+    // ending where node_split or node_collapse compares it to the last item of the flat node
+    fn flush(
+        &self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        n_vars: usize,
+        count: usize,
+        channel_ids: &ChannelIds,
+        forked_channel: ChannelId,
+        virtual_channel: ChannelId,
+    ) -> Result<(), anyhow::Error> {
+        let one = B32::one();
+        let g = B32::MULTIPLICATIVE_GENERATOR;
+        let lanes = [
+            (self.state_start_ptr, one),
+            (self.state_key_ptr_byte, one),
+            (self.state_rlp_ptr, one),
+        ];
+        let block0 =
+            ptr_lanes_block_with_offset(builder, "flush 0, block 0", n_vars, basis(32, 3), &lanes)?;
+        builder.receive(forked_channel, count, [block0, self.state_key_ptr_parity]);
+        let block0 = ptr_lanes_block_with_offset(
+            builder,
+            "flush 1, block 0",
+            n_vars,
+            basis(32, 3) * g,
+            &lanes,
+        )?;
+        builder.send(forked_channel, count, [block0, self.state_key_ptr_parity]);
+        let block0 = ptr_lanes_block_with_offset(
+            builder,
+            "flush 2, block 0",
+            n_vars,
+            basis(32, 3),
+            &[
+                (self.state_start_ptr, one),
+                (self.state_key_ptr_byte, one),
+                (self.virtual_child_ptr, one),
+            ],
+        )?;
+        builder.receive(virtual_channel, count, [block0, self.state_key_ptr_parity]);
+        read_const(
+            builder,
+            "0x80",
+            self.virtual_child_ptr,
+            B8::new(0x80),
+            self.virtual_prefix_val_ts,
+            n_vars,
+            count,
+            channel_ids.mem,
+        )?;
+        let block0 = ptr_lanes_block(
+            builder,
+            "flush 4, block 0",
+            n_vars,
+            &[
+                (self.state_start_ptr, one),
+                (self.state_rlp_ptr, g.pow([HASH_REF_LEN as u64])),
+            ],
+        )?;
+        builder.receive(channel_ids.leaf_end, count, [block0]);
+        Ok(())
+    }
+}
+
+// GRAFT
+
+pub(crate) struct NodeGraftTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: GraftColMajors,
+    row_majors: Vec<GraftRowMajors>,
+}
+
+impl FunctionTable for NodeGraftTable {
+    type Data = NodeGraftData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: GraftColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors.state_key_ptr_parity = BitVec::new(Some(count));
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.row_majors.push(GraftRowMajors::new(mpt_state, &data));
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_graft",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_graft");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let columns = GraftColumns::new(builder, n_vars, self.col_majors, &self.row_majors)?;
+        // the forked walk is the post walk
+        columns.flush(
+            builder,
+            n_vars,
+            count,
+            channel_ids,
+            channel_ids.post_state,
+            channel_ids.state,
+        )?;
+        // to node_diff_window push (state_rlp_ptr, state_rlp_ptr * g^33, virtual_child_ptr, virtual_child_ptr * g)
+        {
+            let one = B32::one();
+            let g = B32::MULTIPLICATIVE_GENERATOR;
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 5, block 0",
+                n_vars,
+                &[
+                    (columns.state_rlp_ptr, one),
+                    (columns.state_rlp_ptr, g.pow([HASH_REF_LEN as u64])),
+                    (columns.virtual_child_ptr, one),
+                    (columns.virtual_child_ptr, g),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff_window, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// PRUNE
+
+pub(crate) struct NodePruneTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    col_majors: GraftColMajors,
+    row_majors: Vec<GraftRowMajors>,
+}
+
+impl FunctionTable for NodePruneTable {
+    type Data = NodeGraftData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            col_majors: GraftColMajors {
+                state_key_ptr_parity: BitVec::new(None),
+            },
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.col_majors.state_key_ptr_parity = BitVec::new(Some(count));
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        self.col_majors
+            .state_key_ptr_parity
+            .push(data.state.key_ptr.parity);
+        self.row_majors.push(GraftRowMajors::new(mpt_state, &data));
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "node_prune",
+        fields(n_vars = n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        TableHeight { n_vars, count }: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("node_prune");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let columns = GraftColumns::new(builder, n_vars, self.col_majors, &self.row_majors)?;
+        // the forked walk is the pre walk
+        columns.flush(
+            builder,
+            n_vars,
+            count,
+            channel_ids,
+            channel_ids.state,
+            channel_ids.post_state,
+        )?;
+        // to node_diff_window push (virtual_child_ptr, virtual_child_ptr * g, state_rlp_ptr, state_rlp_ptr * g^33)
+        {
+            let one = B32::one();
+            let g = B32::MULTIPLICATIVE_GENERATOR;
+            let block0 = ptr_lanes_block(
+                builder,
+                "flush 5, block 0",
+                n_vars,
+                &[
+                    (columns.virtual_child_ptr, one),
+                    (columns.virtual_child_ptr, g),
+                    (columns.state_rlp_ptr, one),
+                    (columns.state_rlp_ptr, g.pow([HASH_REF_LEN as u64])),
+                ],
+            )?;
+            builder.send(channel_ids.node_diff_window, count, [block0]);
+        }
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl GraftRowMajors {
+    fn new(mpt_state: &mut MPTState, data: &NodeGraftData) -> Self {
+        let mem = &mut mpt_state.mem;
+        Self {
+            state_start_ptr: mem.to_mult(data.state.start_ptr),
+            state_key_ptr_byte: mem.to_mult(data.state.key_ptr.byte),
+            state_rlp_ptr: mem.to_mult(data.state.rlp_ptr),
+            virtual_child_ptr: mem.to_mult(data.virtual_child_ptr),
+            virtual_prefix_val_ts: mem.process_timestamp(data.virtual_child_ptr),
+        }
+    }
+}
+
+impl MPT {
+    // the hashing step of the pre walk into the flat node an inserted key parts from, which moves
+    // the walk to the virtual reference
+    pub(crate) fn node_split(&self, rows: &mut Vec<Row>, reshape: &Reshape, state: State) -> State {
+        let data = self.node_reshape(rows, reshape, state);
+        rows.push(Row::NodeSplit(data));
+        State {
+            rlp_ptr: reshape.virtual_ref_ptr,
+            ..state
+        }
+    }
+
+    // the hashing step of the post walk into the flat node a deleted key parted from, which moves
+    // the walk to the virtual reference
+    pub(crate) fn node_collapse(
+        &self,
+        rows: &mut Vec<Row>,
+        reshape: &Reshape,
+        state: State,
+    ) -> State {
+        let data = self.node_reshape(rows, reshape, state);
+        rows.push(Row::NodeCollapse(data));
+        State {
+            rlp_ptr: reshape.virtual_ref_ptr,
+            ..state
+        }
+    }
+
+    // absorbs the flat node into the reference at state.rlp_ptr
+    fn node_reshape(&self, rows: &mut Vec<Row>, reshape: &Reshape, state: State) -> NodeSplitData {
+        assert_eq!(state.key_ptr, reshape.flat_key_ptr);
+        assert_eq!(self.state.mem[state.rlp_ptr], 0xa0);
+        let flat_ptr = reshape.flat_ptr;
+        let post_flat_ptr = self.skip_list(flat_ptr);
+        let total_blocks = ((post_flat_ptr - flat_ptr) / (HASH_R as u32) + 1) as u8;
+        let hash = keccak256(&self.state.mem[flat_ptr..post_flat_ptr]);
+        assert_eq!(
+            &self.state.mem[state.rlp_ptr + 1..state.rlp_ptr + HASH_REF_LEN],
+            hash.as_slice()
+        );
+        self.absorb_block(rows, [0; HASH_B], flat_ptr, state.rlp_ptr, total_blocks);
+        let flat_end_ptr = self.list_end(rows, flat_ptr);
+        assert_eq!(flat_end_ptr, reshape.flat_tail.end);
+        // the sibling key grafted onto the branch is forked once more by node_graft or
+        // node_prune
+        let forked_ts = match reshape.sibling_ptr {
+            Some(_) => B32::ONE,
+            None => B32::MULTIPLICATIVE_GENERATOR,
+        };
+        NodeSplitData {
+            state,
+            virtual_ref_ptr: reshape.virtual_ref_ptr,
+            flat_ptr,
+            flat_end_ptr,
+            sibling_start_ptr: reshape.sibling_start_ptr,
+            sibling_key_ptr: reshape.sibling_key_ptr,
+            flat_tail_ptr: reshape.flat_tail.start,
+            forked_tail_ptr: reshape.forked_tail.start,
+            forked_end_ptr: reshape.forked_tail.end,
+            forked_ts,
+        }
+    }
+
+    // the step of the post walk of the sibling key from the child of the forked branch, which
+    // holds the child of the flat extension, paired with the empty child of the template
+    pub(crate) fn node_graft(&self, rows: &mut Vec<Row>, reshape: &Reshape, state: State) {
+        let data = self.node_graft_data(reshape, state);
+        rows.push(Row::NodeGraft(data));
+    }
+
+    // the step of the pre walk of the sibling key from the child of the forked branch, which
+    // holds the child of the flat extension, paired with the empty child of the template
+    pub(crate) fn node_prune(&self, rows: &mut Vec<Row>, reshape: &Reshape, state: State) {
+        let data = self.node_graft_data(reshape, state);
+        rows.push(Row::NodePrune(data));
+    }
+
+    fn node_graft_data(&self, reshape: &Reshape, state: State) -> NodeGraftData {
+        assert_eq!(state.start_ptr, reshape.sibling_start_ptr);
+        assert_eq!(state.key_ptr, reshape.sibling_key_ptr);
+        assert_eq!(state.rlp_ptr, reshape.forked_child_ptr);
+        assert_eq!(state.ts, B32::ONE);
+        assert_eq!(self.state.mem[reshape.virtual_child_ptr], 0x80);
+        NodeGraftData {
+            state,
+            virtual_child_ptr: reshape.virtual_child_ptr,
+        }
+    }
+}
@@ -17,7 +17,7 @@ impl PopulateMemTable {
     pub(crate) fn build(
         self,
         builder: &mut ConstraintSystemBuilder<U, B128>,
-        channel_ids: &ChannelIds,
+        mem: ChannelId,
         mpt_state: &mut MPTState,
         n_vars: usize,
     ) -> Result<(), anyhow::Error> {
@@ -40,15 +40,17 @@ impl PopulateMemTable {
         let val = builder.add_committed("val", n_vars, B8::TOWER_LEVEL);
         let ts = builder.add_committed("ts", n_vars, B32::TOWER_LEVEL);
         if let Some(witness) = builder.witness() {
-            izip!(
+            // the final timestamps, nothing reads memory after this
+            (
                 witness.new_column::<B8>(val).as_mut_slice::<B8>(),
                 witness.new_column::<B32>(ts).as_mut_slice::<B32>(),
             )
-            .enumerate()
-            .for_each(|(addr, (val, ts))| {
-                *val = B8::from_underlier(mpt_state.mem[addr as u32]);
-                *ts = mpt_state.mem.process_timestamp(addr as u32);
-            });
+                .into_par_iter()
+                .enumerate()
+                .for_each(|(addr, (val, ts))| {
+                    *val = B8::from_underlier(mpt_state.mem[addr as u32]);
+                    *ts = mpt_state.mem.timestamp(addr as u32);
+                });
         }
 
         // to mem push (addr, val, 1)
@@ -70,7 +72,7 @@ impl PopulateMemTable {
                         *block = (1 << 64) | (*val as u128) << 32 | (*addr as u128);
                     });
             }
-            builder.flush(FlushDirection::Push, channel_ids.mem, 1 << n_vars, [block0]);
+            builder.flush(FlushDirection::Push, mem, 1 << n_vars, [block0]);
         }
         // from mem pull (addr, val, ts)
         {
@@ -95,7 +97,7 @@ impl PopulateMemTable {
                         *block = (*ts as u128) << 64 | (*val as u128) << 32 | (*addr as u128);
                     });
             }
-            builder.flush(FlushDirection::Pull, channel_ids.mem, 1 << n_vars, [block0]);
+            builder.flush(FlushDirection::Pull, mem, 1 << n_vars, [block0]);
         }
 
         builder.pop_namespace();
@@ -0,0 +1,1122 @@
+// Copyright 2024 Irreducible Inc.
+
+use super::*;
+
+use std::array;
+use std::ops::Range;
+
+// the tuple account_fields pushes for the field of the value at value_ptr with the given payload
+pub(crate) fn account_field_tuple(
+    value_ptr: u32,
+    field: AccountField,
+    payload: Range<u32>,
+) -> B128 {
+    let g = B32::MULTIPLICATIVE_GENERATOR;
+    g.pow([value_ptr as u64]) * basis(32, 0)
+        + g.pow([payload.start as u64]) * basis(32, 1)
+        + g.pow([payload.end as u64]) * basis(32, 2)
+        + field.tag() * basis(32, 3)
+}
+
+// the tuple the verifier pushes on code_hash_set for every code hash of the set of a CodeHashIn
+pub(crate) fn code_hash_set_tuple(set_id: u32, code_hash: &[u8; 32]) -> [B128; 3] {
+    let [lo, hi] = code_hash_halves(code_hash);
+    [
+        B32::new(set_id) * basis(32, 0),
+        B128::new(lo),
+        B128::new(hi),
+    ]
+}
+
+// the little endian numbers of the first and the last 16 bytes of a code hash
+fn code_hash_halves(code_hash: &[u8; 32]) -> [u128; 2] {
+    array::from_fn(|half| u128::from_le_bytes(array::from_fn(|byte| code_hash[16 * half + byte])))
+}
+
+// the low and the high 128 bits of a 256-bit number
+pub(crate) fn u256_halves(value: U256) -> [u128; 2] {
+    let limbs = value.as_limbs();
+    [
+        (limbs[1] as u128) << 64 | limbs[0] as u128,
+        (limbs[3] as u128) << 64 | limbs[2] as u128,
+    ]
+}
+
+// the offset from the value pointer the canonical encoding of a nonce puts its payload at, and
+// the payload, which is empty for a zero nonce
+pub(crate) fn nonce_payload(nonce: u64) -> (u32, Vec<u8>) {
+    let payload = nonce.to_be_bytes()[nonce.leading_zeros() as usize / 8..].to_vec();
+    match nonce {
+        1..=0x7f => (4, payload),
+        _ => (5, payload),
+    }
+}
+
+// fills bit columns, bit(row, i) being the bit of the i-th column in row
+pub(crate) fn populate_bits(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    columns: &[OracleId],
+    bit: impl Fn(usize, usize) -> bool + Sync,
+) {
+    if let Some(witness) = builder.witness() {
+        for (i, &id) in columns.iter().enumerate() {
+            witness
+                .new_column::<B1>(id)
+                .as_mut_slice::<u8>()
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(chunk, bits)| {
+                    // fill bits from LSB to MSB
+                    *bits = (0..8).fold(0, |acc, k| acc | (bit(8 * chunk + k, i) as u8) << k);
+                });
+        }
+    }
+}
+
+// the rows of the predicate tables of a statement
+pub(crate) struct PredicateRowCounts {
+    pub(crate) balance_at_least: usize,
+    pub(crate) is_contract: usize,
+    pub(crate) code_hash_in: usize,
+    pub(crate) code_hash_set_sink: usize,
+}
+
+impl PredicateRowCounts {
+    pub(crate) fn new(predicates: &[AccountPredicate]) -> Self {
+        let mut counts = Self {
+            balance_at_least: 0,
+            is_contract: 0,
+            code_hash_in: 0,
+            code_hash_set_sink: 0,
+        };
+        for predicate in predicates {
+            match predicate {
+                AccountPredicate::BalanceAtLeast(..) => counts.balance_at_least += 1,
+                AccountPredicate::NonceEquals(..) => (),
+                AccountPredicate::CodeHashIn(_, code_hashes) => {
+                    counts.code_hash_in += 1;
+                    counts.code_hash_set_sink += code_hashes.len().saturating_sub(1);
+                }
+                AccountPredicate::IsContract(_) => counts.is_contract += 1,
+            }
+        }
+        counts
+    }
+}
+
+// BALANCE
+// the balance of the value at value_ptr, whose payload is balance
+pub(crate) struct BalanceData {
+    pub(crate) value_ptr: u32,
+    pub(crate) balance: Range<u32>,
+}
+
+pub(crate) struct BalanceRowMajors {
+    value_ptr: B32,
+    payload_ptr: B32,
+    window_ptr: B32,
+    len: u8,
+    g_raised_to_low_three_len_bits: B32,
+    g_raised_to_high_three_len_bits: B32,
+    window_vals: [B8; 32],
+    window_tss: [B32; 32],
+    pub(crate) balance: U256,
+}
+
+impl BalanceRowMajors {
+    pub(crate) fn new(mpt_state: &mut MPTState, data: BalanceData) -> Self {
+        let mem = &mut mpt_state.mem;
+
+        let BalanceData { value_ptr, balance } = data;
+        let len = (balance.end - balance.start) as u8;
+        let payload_ptr = balance.start;
+        let window_ptr = balance.end - 32;
+        Self {
+            value_ptr: mem.to_mult(value_ptr),
+            payload_ptr: mem.to_mult(payload_ptr),
+            window_ptr: mem.to_mult(window_ptr),
+            len,
+            g_raised_to_low_three_len_bits: g_raised_to_bits(len, 0, g_powers(0).take(3)),
+            g_raised_to_high_three_len_bits: g_raised_to_bits(len, 3, g_powers(3).take(3)),
+            window_vals: array::from_fn(|j| B8::from_underlier(mem[window_ptr + j as u32])),
+            window_tss: array::from_fn(|j| mem.process_timestamp(window_ptr + j as u32)),
+            balance: U256::try_from_be_slice(&mem[balance]).expect("balance is 32 bytes at most"),
+        }
+    }
+}
+
+// the columns of the balance read by every row
+pub(crate) struct BalanceColumns {
+    pub(crate) value_ptr: OracleId,
+    masks: [OracleId; 32],
+    window_val_bits: [[OracleId; 8]; 32],
+}
+
+impl BalanceColumns {
+    // the i-th bit of the balance is the masked bit i % 8 of the window byte 31 - i / 8, the
+    // product of the two columns
+    pub(crate) fn bit(&self, i: usize) -> [OracleId; 2] {
+        let j = 31 - i / 8;
+        [self.masks[j], self.window_val_bits[j][i % 8]]
+    }
+}
+
+// commits the columns of the 32 bytes ending where the balance of every row ends, masking the
+// ones before its payload, and pulls (value_ptr, payload_ptr, payload_end_ptr, balance) from
+// account_fields
+pub(crate) fn build_balance(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    channel_ids: &ChannelIds,
+    TableHeight { n_vars, count }: TableHeight,
+    row_majors: &[BalanceRowMajors],
+) -> Result<BalanceColumns, anyhow::Error> {
+    let value_ptr = builder.add_committed("value_ptr", n_vars, B32::TOWER_LEVEL);
+    let payload_ptr = builder.add_committed("payload_ptr", n_vars, B32::TOWER_LEVEL);
+    let window_ptr = builder.add_committed("window_ptr", n_vars, B32::TOWER_LEVEL);
+    let g_raised_to_low_three_len_bits =
+        builder.add_committed("g_raised_to_low_three_len_bits", n_vars, B32::TOWER_LEVEL);
+    let g_raised_to_high_three_len_bits =
+        builder.add_committed("g_raised_to_high_three_len_bits", n_vars, B32::TOWER_LEVEL);
+    let window_vals = builder.add_committed_multiple::<32>("window_vals", n_vars, B8::TOWER_LEVEL);
+    let window_tss = builder.add_committed_multiple::<32>("window_tss", n_vars, B32::TOWER_LEVEL);
+
+    if let Some(witness) = builder.witness() {
+        let par_data_iter = row_majors;
+        // padding rows are all zero pointers and bytes, of an empty balance
+        populate_committed_polys!(
+            par_data_iter,
+            witness,
+            [(value_ptr, B32), (payload_ptr, B32), (window_ptr, B32),]
+        );
+        populate_committed_polys_with_default!(
+            par_data_iter,
+            witness,
+            [
+                (g_raised_to_low_three_len_bits, B32, B32::one()),
+                (g_raised_to_high_three_len_bits, B32, B32::one()),
+            ]
+        );
+
+        let mut window_vals_columns = window_vals.map(|id| witness.new_column::<B8>(id));
+        let window_vals_slices = window_vals_columns
+            .each_mut()
+            .map(|col| col.as_mut_slice::<B8>());
+        let mut window_tss_columns =
+            window_tss.map(|id| witness.new_column_with_default::<B32>(id, B32::one()));
+        let window_tss_slices = window_tss_columns
+            .each_mut()
+            .map(|col| col.as_mut_slice::<B32>());
+        for (i, row_majors) in row_majors.iter().enumerate() {
+            for (j, value) in row_majors.window_vals.iter().enumerate() {
+                window_vals_slices[j][i] = *value;
+            }
+            for (j, value) in row_majors.window_tss.iter().enumerate() {
+                window_tss_slices[j][i] = *value;
+            }
+        }
+    }
+
+    let row = |i: usize| row_majors.get(i);
+    let len_bits = builder.add_committed_multiple::<6>("len_bits", n_vars, B1::TOWER_LEVEL);
+    populate_bits(builder, &len_bits, |i, k| {
+        row(i).is_some_and(|row| (row.len >> k) & 1 == 1)
+    });
+    let window_val_bits: [[OracleId; 8]; 32] = array::from_fn(|j| {
+        builder.add_committed_multiple::<8>(format!("window_val_bits_{j}"), n_vars, B1::TOWER_LEVEL)
+    });
+    for (&window_val, &bits) in izip!(&window_vals, &window_val_bits) {
+        populate_byte_bits(builder, window_val, bits)?;
+    }
+    let masks = builder.add_committed_multiple::<32>("masks", n_vars, B1::TOWER_LEVEL);
+    populate_bits(builder, &masks, |i, j| {
+        row(i).is_some_and(|row| row.len as usize > 31 - j)
+    });
+
+    // mem reads
+    for (j, (&window_val, &window_ts)) in izip!(&window_vals, &window_tss).enumerate() {
+        read_mem_at_offset(
+            builder,
+            format!("window_val_{j}"),
+            window_ptr,
+            j as u32,
+            window_val,
+            window_ts,
+            n_vars,
+            count,
+            channel_ids.mem,
+        )?;
+    }
+    for (&window_val, &bits) in izip!(&window_vals, &window_val_bits) {
+        assert_byte_bits(builder, window_val, bits);
+    }
+    // the balance is 32 bytes at most
+    for &len_bit in &len_bits[0..5] {
+        builder.assert_zero(
+            [len_bits[5], len_bit],
+            binius_macros::arith_expr!(B1[l5, l] = l5 * l).convert_field(),
+        );
+    }
+    assert_g_raised_to_bits(builder, &len_bits[0..3], 0, g_raised_to_low_three_len_bits);
+    assert_g_raised_to_bits(builder, &len_bits[3..6], 3, g_raised_to_high_three_len_bits);
+    let g = B32::MULTIPLICATIVE_GENERATOR;
+    // the window ends where the payload does
+    // window_ptr * g^32 == payload_ptr * g^len
+    builder.assert_zero(
+        [
+            window_ptr,
+            payload_ptr,
+            g_raised_to_low_three_len_bits,
+            g_raised_to_high_three_len_bits,
+        ],
+        (ArithExpr::Var(0) * ArithExpr::Const(g.pow([32]))
+            - ArithExpr::Var(1) * ArithExpr::Var(2) * ArithExpr::Var(3))
+        .convert_field(),
+    );
+    // masks[j] == (len >= 32 - j), whether the j-th byte of the window is in the payload,
+    // comparing the bits of len with the bits of 32 - j from the least significant one up
+    for (j, &mask) in masks.iter().enumerate() {
+        let threshold = 32 - j;
+        let at_least = (0..6).fold(ArithExpr::<B1>::one(), |acc, k| {
+            let len_bit = ArithExpr::Var(k);
+            match (threshold >> k) & 1 {
+                1 => len_bit * acc,
+                _ => len_bit.clone() + (ArithExpr::one() - len_bit) * acc,
+            }
+        });
+        let mut oracle_ids = len_bits.to_vec();
+        oracle_ids.push(mask);
+        builder.assert_zero(oracle_ids, (ArithExpr::Var(6) - at_least).convert_field());
+    }
+    // from account_fields pull (value_ptr, payload_ptr, payload_end_ptr, balance)
+    let balance_block = ptr_lanes_block_with_offset(
+        builder,
+        "balance block",
+        n_vars,
+        basis(32, 3) * AccountField::Balance.tag(),
+        &[
+            (value_ptr, B32::ONE),
+            (payload_ptr, B32::ONE),
+            (window_ptr, g.pow([32])),
+        ],
+    )?;
+    builder.receive(channel_ids.account_fields, count, [balance_block]);
+
+    Ok(BalanceColumns {
+        value_ptr,
+        masks,
+        window_val_bits,
+    })
+}
+
+// the halves of a 256-bit number of every row, from its bits
+pub(crate) fn u256_blocks(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    name: &str,
+    n_vars: usize,
+    bits: &[OracleId; 256],
+    value: impl Fn(usize) -> U256 + Sync,
+    count: usize,
+) -> Result<[OracleId; 2], anyhow::Error> {
+    let mut blocks = vec![];
+    for (half, bits) in bits.chunks(128).enumerate() {
+        let block = builder.add_linear_combination(
+            format!("{name} block {half}"),
+            n_vars,
+            bits.iter()
+                .enumerate()
+                .map(|(i, &bit)| (bit, basis(1, i)))
+                .collect::<Vec<_>>(),
+        )?;
+        if let Some(witness) = builder.witness() {
+            witness
+                .new_column::<B128>(block)
+                .as_mut_slice::<u128>()
+                .par_iter_mut()
+                .take(count)
+                .enumerate()
+                .for_each(|(i, block)| {
+                    *block = u256_halves(value(i))[half];
+                });
+        }
+        blocks.push(block);
+    }
+    Ok([blocks[0], blocks[1]])
+}
+
+// BALANCE AT LEAST
+pub(crate) struct BalanceAtLeastData {
+    balance: BalanceData,
+    amount: U256,
+}
+
+struct BalanceAtLeastRowMajors {
+    amount: U256,
+    borrows: U256,
+}
+
+// A row per BalanceAtLeast predicate reads the 32 bytes ending where the balance ends, masks
+// the ones before its payload, and subtracts the amount from the 256-bit number they make,
+// with no borrow out of the top bit. It pulls (value_ptr, payload_ptr, payload_end_ptr,
+// balance) from account_fields and (value_ptr, amount) from balance_at_least, which the
+// verifier pushes.
+pub(crate) struct BalanceAtLeastTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    balance_row_majors: Vec<BalanceRowMajors>,
+    row_majors: Vec<BalanceAtLeastRowMajors>,
+}
+impl FunctionTable for BalanceAtLeastTable {
+    type Data = BalanceAtLeastData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            balance_row_majors: vec![],
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.balance_row_majors = Vec::with_capacity(count);
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let BalanceAtLeastData { balance, amount } = data;
+        let balance_row_majors = BalanceRowMajors::new(mpt_state, balance);
+        let balance = balance_row_majors.balance;
+        // the borrow out of every bit of balance - amount
+        let mut borrows = U256::ZERO;
+        let mut borrow = false;
+        for i in 0..256 {
+            let (balance_bit, amount_bit) = (balance.bit(i), amount.bit(i));
+            borrow = (amount_bit && !balance_bit) || (amount_bit == balance_bit && borrow);
+            borrows.set_bit(i, borrow);
+        }
+        assert!(!borrow, "balance is below the amount");
+        self.balance_row_majors.push(balance_row_majors);
+        self.row_majors
+            .push(BalanceAtLeastRowMajors { amount, borrows });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "balance_at_least",
+        fields(n_vars = table_height.n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        let TableHeight { n_vars, count } = table_height;
+        builder.push_namespace("balance_at_least");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let balance = build_balance(builder, channel_ids, table_height, &self.balance_row_majors)?;
+        let row = |i: usize| self.row_majors.get(i);
+        let amount_bits =
+            builder.add_committed_multiple::<256>("amount_bits", n_vars, B1::TOWER_LEVEL);
+        populate_bits(builder, &amount_bits, |i, k| {
+            row(i).is_some_and(|row| row.amount.bit(k))
+        });
+        let borrows = builder.add_committed_multiple::<255>("borrows", n_vars, B1::TOWER_LEVEL);
+        populate_bits(builder, &borrows, |i, k| {
+            row(i).is_some_and(|row| row.borrows.bit(k))
+        });
+
+        // balance - amount
+        // borrow_out == amount_bit * (1 - balance_bit)
+        //     + borrow_in * (1 - amount_bit - balance_bit)
+        // with no borrow into the least significant bit nor out of the most significant one
+        for i in 0..256 {
+            let mut oracle_ids = balance.bit(i).to_vec();
+            oracle_ids.push(amount_bits[i]);
+            let balance_bit = ArithExpr::<B1>::Var(0) * ArithExpr::Var(1);
+            let amount_bit = ArithExpr::Var(2);
+            let mut borrow_expr = amount_bit.clone() * (ArithExpr::one() - balance_bit.clone());
+            if i > 0 {
+                borrow_expr = borrow_expr
+                    + ArithExpr::Var(oracle_ids.len())
+                        * (ArithExpr::one() - amount_bit - balance_bit);
+                oracle_ids.push(borrows[i - 1]);
+            }
+            if i < 255 {
+                borrow_expr = borrow_expr - ArithExpr::Var(oracle_ids.len());
+                oracle_ids.push(borrows[i]);
+            }
+            builder.assert_zero(oracle_ids, borrow_expr.convert_field());
+        }
+        // from balance_at_least pull (value_ptr, amount_lo, amount_hi)
+        let value_block = ptr_lanes_block(
+            builder,
+            "value block",
+            n_vars,
+            &[(balance.value_ptr, B32::ONE)],
+        )?;
+        let [amount_lo, amount_hi] = u256_blocks(
+            builder,
+            "amount",
+            n_vars,
+            &amount_bits,
+            |i| self.row_majors[i].amount,
+            count,
+        )?;
+        builder.receive(
+            channel_ids.balance_at_least,
+            count,
+            [value_block, amount_lo, amount_hi],
+        );
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// CODE HASH
+// the code hash of the value at value_ptr, whose payload is at code_hash_ptr
+pub(crate) struct CodeHashData {
+    value_ptr: u32,
+    code_hash_ptr: u32,
+}
+
+struct CodeHashRowMajors {
+    value_ptr: B32,
+    code_hash_ptr: B32,
+    code_hash: [u8; 32],
+    code_hash_tss: [B32; 32],
+}
+
+impl CodeHashRowMajors {
+    fn new(mpt_state: &mut MPTState, data: CodeHashData) -> Self {
+        let mem = &mut mpt_state.mem;
+
+        let CodeHashData {
+            value_ptr,
+            code_hash_ptr,
+        } = data;
+        Self {
+            value_ptr: mem.to_mult(value_ptr),
+            code_hash_ptr: mem.to_mult(code_hash_ptr),
+            code_hash: array::from_fn(|j| mem[code_hash_ptr + j as u32]),
+            code_hash_tss: array::from_fn(|j| mem.process_timestamp(code_hash_ptr + j as u32)),
+        }
+    }
+}
+
+// commits the columns of the code hash read by every row, and pulls (value_ptr, code_hash_ptr,
+// code_hash_ptr * g^32, code_hash) from account_fields
+fn build_code_hash(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    channel_ids: &ChannelIds,
+    TableHeight { n_vars, count }: TableHeight,
+    row_majors: &[CodeHashRowMajors],
+) -> Result<(OracleId, [OracleId; 32]), anyhow::Error> {
+    let value_ptr = builder.add_committed("value_ptr", n_vars, B32::TOWER_LEVEL);
+    let code_hash_ptr = builder.add_committed("code_hash_ptr", n_vars, B32::TOWER_LEVEL);
+    let code_hash = builder.add_committed_multiple::<32>("code_hash", n_vars, B8::TOWER_LEVEL);
+    let code_hash_tss =
+        builder.add_committed_multiple::<32>("code_hash_tss", n_vars, B32::TOWER_LEVEL);
+
+    if let Some(witness) = builder.witness() {
+        let par_data_iter = row_majors;
+        populate_committed_polys!(
+            par_data_iter,
+            witness,
+            [(value_ptr, B32), (code_hash_ptr, B32),]
+        );
+
+        let mut code_hash_columns = code_hash.map(|id| witness.new_column::<B8>(id));
+        let code_hash_slices = code_hash_columns
+            .each_mut()
+            .map(|col| col.as_mut_slice::<u8>());
+        let mut code_hash_tss_columns =
+            code_hash_tss.map(|id| witness.new_column_with_default::<B32>(id, B32::one()));
+        let code_hash_tss_slices = code_hash_tss_columns
+            .each_mut()
+            .map(|col| col.as_mut_slice::<B32>());
+        for (i, row_majors) in row_majors.iter().enumerate() {
+            for (j, value) in row_majors.code_hash.iter().enumerate() {
+                code_hash_slices[j][i] = *value;
+            }
+            for (j, value) in row_majors.code_hash_tss.iter().enumerate() {
+                code_hash_tss_slices[j][i] = *value;
+            }
+        }
+    }
+
+    // mem reads
+    for (j, (&code_hash_val, &code_hash_ts)) in izip!(&code_hash, &code_hash_tss).enumerate() {
+        read_mem_at_offset(
+            builder,
+            format!("code_hash_{j}"),
+            code_hash_ptr,
+            j as u32,
+            code_hash_val,
+            code_hash_ts,
+            n_vars,
+            count,
+            channel_ids.mem,
+        )?;
+    }
+    // from account_fields pull (value_ptr, code_hash_ptr, code_hash_ptr * g^32, code_hash)
+    let code_hash_block = ptr_lanes_block_with_offset(
+        builder,
+        "code_hash block",
+        n_vars,
+        basis(32, 3) * AccountField::CodeHash.tag(),
+        &[
+            (value_ptr, B32::ONE),
+            (code_hash_ptr, B32::ONE),
+            (code_hash_ptr, B32::MULTIPLICATIVE_GENERATOR.pow([32])),
+        ],
+    )?;
+    builder.receive(channel_ids.account_fields, count, [code_hash_block]);
+
+    Ok((value_ptr, code_hash))
+}
+
+// a half of the code hash of every row as a little endian number, plus offset
+fn code_hash_half_block(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    name: impl ToString,
+    n_vars: usize,
+    offset: u128,
+    code_hash: &[OracleId; 32],
+    half: usize,
+    row_majors: &[CodeHashRowMajors],
+) -> Result<OracleId, anyhow::Error> {
+    let block = builder.add_linear_combination_with_offset(
+        name,
+        n_vars,
+        B128::new(offset),
+        code_hash[16 * half..16 * (half + 1)]
+            .iter()
+            .enumerate()
+            .map(|(j, &code_hash_val)| (code_hash_val, basis(8, j)))
+            .collect::<Vec<_>>(),
+    )?;
+    if let Some(witness) = builder.witness() {
+        witness
+            .new_column_with_default::<B128>(block, B128::new(offset))
+            .as_mut_slice::<u128>()
+            .par_iter_mut()
+            .zip(row_majors)
+            .for_each(|(block, row_majors)| {
+                *block = code_hash_halves(&row_majors.code_hash)[half] ^ offset;
+            });
+    }
+    Ok(block)
+}
+
+// IS CONTRACT
+struct IsContractRowMajors {
+    selector: bool,
+    diff: B128,
+}
+
+// A row per IsContract predicate reads the code hash of the account and checks it differs from
+// the empty code hash in the half selector picks. It pulls (value_ptr) from is_contract, which
+// the verifier pushes.
+pub(crate) struct IsContractTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    code_hash_row_majors: Vec<CodeHashRowMajors>,
+    row_majors: Vec<IsContractRowMajors>,
+}
+impl FunctionTable for IsContractTable {
+    type Data = CodeHashData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 0,
+            code_hash_row_majors: vec![],
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.code_hash_row_majors = Vec::with_capacity(count);
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        let code_hash_row_majors = CodeHashRowMajors::new(mpt_state, data);
+        let [lo, hi] = code_hash_halves(&code_hash_row_majors.code_hash);
+        let [empty_lo, empty_hi] = code_hash_halves(&EMPTY_CODE_HASH);
+        let selector = lo == empty_lo;
+        let diff = match selector {
+            false => lo ^ empty_lo,
+            true => hi ^ empty_hi,
+        };
+        assert_ne!(diff, 0, "the code hash is empty");
+        self.code_hash_row_majors.push(code_hash_row_majors);
+        self.row_majors.push(IsContractRowMajors {
+            selector,
+            diff: B128::new(diff),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "is_contract",
+        fields(n_vars = table_height.n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        let TableHeight { n_vars, count } = table_height;
+        builder.push_namespace("is_contract");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let (value_ptr, code_hash) = build_code_hash(
+            builder,
+            channel_ids,
+            table_height,
+            &self.code_hash_row_majors,
+        )?;
+        let [empty_lo, empty_hi] = code_hash_halves(&EMPTY_CODE_HASH);
+        let lo_diff = code_hash_half_block(
+            builder,
+            "lo_diff",
+            n_vars,
+            empty_lo,
+            &code_hash,
+            0,
+            &self.code_hash_row_majors,
+        )?;
+        let hi_diff = code_hash_half_block(
+            builder,
+            "hi_diff",
+            n_vars,
+            empty_hi,
+            &code_hash,
+            1,
+            &self.code_hash_row_majors,
+        )?;
+        let selector = builder.add_committed("selector", n_vars, B1::TOWER_LEVEL);
+        populate_bits(builder, &[selector], |i, _| {
+            self.row_majors.get(i).is_some_and(|row| row.selector)
+        });
+        let diff = builder.add_committed("diff", n_vars, B128::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            // padding rows have a zero code hash, whose low half differs
+            populate_committed_polys_with_default!(
+                par_data_iter,
+                witness,
+                [(diff, B128, B128::new(empty_lo))]
+            );
+        }
+
+        // diff == (1 - selector) * lo_diff + selector * hi_diff != 0, so the code hash differs
+        // from the empty code hash in at least one half
+        builder.assert_zero(
+            [diff, selector, lo_diff, hi_diff],
+            (ArithExpr::<B128>::Var(0)
+                - (ArithExpr::one() - ArithExpr::Var(1)) * ArithExpr::Var(2)
+                - ArithExpr::Var(1) * ArithExpr::Var(3))
+            .convert_field(),
+        );
+        builder.assert_not_zero(diff);
+        // from is_contract pull (value_ptr)
+        let value_block =
+            ptr_lanes_block(builder, "value block", n_vars, &[(value_ptr, B32::ONE)])?;
+        builder.receive(channel_ids.is_contract, count, [value_block]);
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// CODE HASH IN
+pub(crate) struct CodeHashInData {
+    code_hash: CodeHashData,
+    set_id: u32,
+}
+
+struct CodeHashInRowMajors {
+    set_id: B32,
+}
+
+// A row per CodeHashIn predicate reads the code hash of the account and pulls it from the
+// code hashes of the set of the predicate. It pulls (value_ptr, set_id) from code_hash_in and
+// (set_id, code_hash) from code_hash_set, which the verifier pushes for every code hash of the
+// set.
+pub(crate) struct CodeHashInTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    code_hash_row_majors: Vec<CodeHashRowMajors>,
+    row_majors: Vec<CodeHashInRowMajors>,
+}
+impl FunctionTable for CodeHashInTable {
+    type Data = CodeHashInData;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 3,
+            code_hash_row_majors: vec![],
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.code_hash_row_majors = Vec::with_capacity(count);
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
+        self.code_hash_row_majors
+            .push(CodeHashRowMajors::new(mpt_state, data.code_hash));
+        self.row_majors.push(CodeHashInRowMajors {
+            set_id: B32::new(data.set_id),
+        });
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "code_hash_in",
+        fields(n_vars = table_height.n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        let TableHeight { n_vars, count } = table_height;
+        builder.push_namespace("code_hash_in");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, n_vars);
+            assert_eq!(self.count, count);
+        }
+
+        let (value_ptr, code_hash) = build_code_hash(
+            builder,
+            channel_ids,
+            table_height,
+            &self.code_hash_row_majors,
+        )?;
+        let lo_block = code_hash_half_block(
+            builder,
+            "lo block",
+            n_vars,
+            0,
+            &code_hash,
+            0,
+            &self.code_hash_row_majors,
+        )?;
+        let hi_block = code_hash_half_block(
+            builder,
+            "hi block",
+            n_vars,
+            0,
+            &code_hash,
+            1,
+            &self.code_hash_row_majors,
+        )?;
+        let set_id = builder.add_committed("set_id", n_vars, B32::TOWER_LEVEL);
+        if let Some(witness) = builder.witness() {
+            let par_data_iter = &self.row_majors;
+            populate_committed_polys!(par_data_iter, witness, [(set_id, B32)]);
+        }
+
+        // from code_hash_in pull (value_ptr, set_id)
+        let request_block = ptr_lanes_block(
+            builder,
+            "request block",
+            n_vars,
+            &[(value_ptr, B32::ONE), (set_id, B32::ONE)],
+        )?;
+        builder.receive(channel_ids.code_hash_in, count, [request_block]);
+        // from code_hash_set pull (set_id, code_hash)
+        let set_id_block = ptr_lanes_block(builder, "set_id block", n_vars, &[(set_id, B32::ONE)])?;
+        builder.receive(
+            channel_ids.code_hash_set,
+            count,
+            [set_id_block, lo_block, hi_block],
+        );
+
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// SINKS
+// pulls the tuple of every row, to absorb the tuples pushed for none of the other tables to
+// pull, and cannot absorb a tuple nobody pushed
+fn build_sink<const N: usize>(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    channel_id: ChannelId,
+    TableHeight { n_vars, count }: TableHeight,
+    row_majors: &[[B128; N]],
+) -> Result<(), anyhow::Error> {
+    let tuple = builder.add_committed_multiple::<N>("tuple", n_vars, B128::TOWER_LEVEL);
+    if let Some(witness) = builder.witness() {
+        for (lane, &id) in tuple.iter().enumerate() {
+            witness
+                .new_column::<B128>(id)
+                .as_mut_slice::<B128>()
+                .par_iter_mut()
+                .zip(row_majors)
+                .for_each(|(entry, row_majors)| *entry = row_majors[lane]);
+        }
+    }
+    builder.receive(channel_id, count, tuple);
+    Ok(())
+}
+
+// A row per account field of a withheld value that neither a predicate nor the balance sum
+// consumes pulls its tuple from account_fields.
+pub(crate) struct AccountFieldsSinkTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<[B128; 1]>,
+}
+impl FunctionTable for AccountFieldsSinkTable {
+    type Data = B128;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 7,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, _mpt_state: &mut MPTState, data: Self::Data) {
+        self.row_majors.push([data]);
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "account_fields_sink",
+        fields(n_vars = table_height.n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("account_fields_sink");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, table_height.n_vars);
+            assert_eq!(self.count, table_height.count);
+        }
+        build_sink(
+            builder,
+            channel_ids.account_fields,
+            table_height,
+            &self.row_majors,
+        )?;
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+// A row per code hash of a CodeHashIn set other than the one the account has pulls
+// (set_id, code_hash) from code_hash_set.
+pub(crate) struct CodeHashSetSinkTable {
+    n_vars: usize,
+    count: usize,
+    index: usize,
+    _smallest_tower_level: usize,
+    row_majors: Vec<[B128; 3]>,
+}
+impl FunctionTable for CodeHashSetSinkTable {
+    type Data = [B128; 3];
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
+    fn new() -> Self {
+        Self {
+            n_vars: 0,
+            count: 0,
+            index: 0,
+            _smallest_tower_level: 7,
+            row_majors: vec![],
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.index
+    }
+    fn allocate(&mut self, count: usize) -> usize {
+        self.row_majors = Vec::with_capacity(count);
+
+        self.count = count;
+        self.n_vars = Self::n_vars(count);
+        self.n_vars
+    }
+    fn append(&mut self, _mpt_state: &mut MPTState, data: Self::Data) {
+        self.row_majors.push(data);
+
+        self.index += 1;
+    }
+    #[instrument(
+        name = "code_hash_set_sink",
+        fields(n_vars = table_height.n_vars)
+        skip_all,
+        level = "debug"
+    )]
+    fn build(
+        self,
+        builder: &mut ConstraintSystemBuilder<U, B128>,
+        channel_ids: &ChannelIds,
+        table_height: TableHeight,
+    ) -> Result<(), anyhow::Error> {
+        builder.push_namespace("code_hash_set_sink");
+        assert_eq!(self.index, self.count);
+        if builder.witness().is_some() {
+            assert_eq!(self.n_vars, table_height.n_vars);
+            assert_eq!(self.count, table_height.count);
+        }
+        build_sink(
+            builder,
+            channel_ids.code_hash_set,
+            table_height,
+            &self.row_majors,
+        )?;
+        builder.pop_namespace();
+        Ok(())
+    }
+}
+
+impl MPT {
+    // a row per predicate, after account_fields decoded the values, and a sink row per field
+    // and per code hash of a set the predicates leave over
+    pub(crate) fn append_predicates(
+        &mut self,
+        leaf_rlp_ptr_len_pairs: &[(u32, u32)],
+        field_ranges: &[[Range<u32>; 4]],
+    ) {
+        let predicates = match self.statement.predicates() {
+            Some(predicates) => predicates.to_vec(),
+            None => return,
+        };
+        for (set_id, (predicate, &(value_ptr, _), fields)) in
+            izip!(predicates, leaf_rlp_ptr_len_pairs, field_ranges).enumerate()
+        {
+            let set_id = set_id as u32;
+            let [_, balance, _, code_hash] = fields.clone();
+            let code_hash_data = CodeHashData {
+                value_ptr,
+                code_hash_ptr: code_hash.start,
+            };
+            let consumed_field = match predicate {
+                AccountPredicate::BalanceAtLeast(_, amount) => {
+                    self.balance_at_least_table.append(
+                        &mut self.state,
+                        BalanceAtLeastData {
+                            balance: BalanceData { value_ptr, balance },
+                            amount,
+                        },
+                    );
+                    AccountField::Balance
+                }
+                // the verifier pulls the nonce itself
+                AccountPredicate::NonceEquals(..) => AccountField::Nonce,
+                AccountPredicate::CodeHashIn(_, code_hashes) => {
+                    let position = code_hashes
+                        .iter()
+                        .position(|set_code_hash| {
+                            self.state.mem[code_hash.clone()] == *set_code_hash
+                        })
+                        .expect("the code hash is in the set");
+                    self.code_hash_in_table.append(
+                        &mut self.state,
+                        CodeHashInData {
+                            code_hash: code_hash_data,
+                            set_id,
+                        },
+                    );
+                    for (_, set_code_hash) in code_hashes
+                        .iter()
+                        .enumerate()
+                        .filter(|&(i, _)| i != position)
+                    {
+                        self.code_hash_set_sink_table
+                            .append(&mut self.state, code_hash_set_tuple(set_id, set_code_hash));
+                    }
+                    AccountField::CodeHash
+                }
+                AccountPredicate::IsContract(_) => {
+                    self.is_contract_table
+                        .append(&mut self.state, code_hash_data);
+                    AccountField::CodeHash
+                }
+            };
+            for (field, payload) in izip!(AccountField::ALL, fields.clone()) {
+                if field != consumed_field {
+                    self.account_fields_sink_table.append(
+                        &mut self.state,
+                        account_field_tuple(value_ptr, field, payload),
+                    );
+                }
+            }
+        }
+    }
+}
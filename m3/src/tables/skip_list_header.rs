@@ -2,6 +2,7 @@
 
 use super::*;
 
+#[derive(PartialEq)]
 pub(crate) struct SkipListHeaderAdvice {
     list_ptr: u32,
     prefix_val: u8,
@@ -28,6 +29,7 @@ pub(crate) struct SkipListHeaderTable {
 
 impl FunctionTable for SkipListHeaderTable {
     type Data = SkipListHeaderAdvice;
+    const MIN_N_VARS: usize = U::LOG_BITS + 3; // - smallest_tower_level
     fn new() -> Self {
         Self {
             count: 0,
@@ -37,14 +39,14 @@ impl FunctionTable for SkipListHeaderTable {
             data: vec![],
         }
     }
+    fn row_count(&self) -> usize {
+        self.index
+    }
     fn allocate(&mut self, count: usize) -> usize {
         self.data = Vec::with_capacity(count);
 
         self.count = count;
-        self.n_vars = std::cmp::max(
-            self.count.next_power_of_two().trailing_zeros() as usize,
-            U::LOG_BITS + 3, // - self.smallest_tower_level,
-        );
+        self.n_vars = Self::n_vars(count);
         self.n_vars
     }
     fn append(&mut self, mpt_state: &mut MPTState, data: Self::Data) {
@@ -232,27 +234,17 @@ impl FunctionTable for SkipListHeaderTable {
     }
 }
 impl MPT {
-    pub(crate) fn skip_list_header(&mut self, action: Action, list_ptr: u32) -> u32 {
+    pub(crate) fn skip_list_header(&self, rows: &mut Vec<Row>, list_ptr: u32) -> u32 {
         let prefix_val = self.state.mem[list_ptr];
         let offset = MPT::skip_list_header_offset_lookup(prefix_val) as u32;
         let first_child_ptr = list_ptr + offset;
-        match action {
-            Action::Append => {
-                self.skip_list_header_table.append(
-                    &mut self.state,
-                    SkipListHeaderAdvice {
-                        list_ptr,
-                        prefix_val,
-                        offset,
-                        first_child_ptr,
-                    },
-                );
-            }
-            Action::Count => {
-                self.skip_list_header_count += 1;
-            }
-            Action::Ignore => (),
-        }
+        let data = SkipListHeaderAdvice {
+            list_ptr,
+            prefix_val,
+            offset,
+            first_child_ptr,
+        };
+        rows.push(Row::SkipListHeader(data));
         first_child_ptr
     }
 }
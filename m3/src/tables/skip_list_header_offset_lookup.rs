@@ -7,6 +7,8 @@ pub(crate) struct SkipListHeaderOffsetLookupTable {
 }
 
 impl SkipListHeaderOffsetLookupTable {
+    pub(crate) const N_VARS: usize = 6;
+
     pub(crate) fn new() -> Self {
         Self {
             final_ts: Vec::with_capacity(256),
@@ -32,7 +34,7 @@ impl SkipListHeaderOffsetLookupTable {
         builder: &mut ConstraintSystemBuilder<U, B128>,
         channel_ids: &ChannelIds,
     ) -> Result<(), anyhow::Error> {
-        let n_vars = 6;
+        let n_vars = Self::N_VARS;
 
         let final_ts = builder.add_committed("final_ts", n_vars, B32::TOWER_LEVEL);
         if let Some(witness) = builder.witness() {
@@ -4,18 +4,39 @@ use core::iter::Iterator;
 
 use super::*;
 
+// generator powers and timestamps are kept in blocks of this many addresses
+const MEM_BLOCK_BITS: usize = 10;
+const MEM_BLOCK_LEN: usize = 1 << MEM_BLOCK_BITS;
+
 // memory
+//
+// The generator powers take MEM_BLOCK_LEN entries and one per block, instead of four bytes per
+// address. The timestamps still take four bytes per address of every block read at all, which
+// is every block of the trie nodes, since the sponge reads every byte it hashes; only the blocks
+// of padding and of unread bytes are left unallocated.
 pub struct Memory {
     mem: Vec<u8>,
-    timestamps: Vec<B32>,
-    powers: Vec<B32>,
+    // per block, the timestamps once any of its addresses is read, all ones before that
+    timestamps: Vec<Option<Box<[B32; MEM_BLOCK_LEN]>>>,
+    // g^i and g^(i * MEM_BLOCK_LEN), so any power takes a single multiplication
+    low_powers: Vec<B32>,
+    high_powers: Vec<B32>,
 }
 impl Memory {
     pub fn new() -> Self {
+        let mut power = B32::ONE;
+        let low_powers = (0..MEM_BLOCK_LEN)
+            .map(|_| {
+                let low_power = power;
+                power *= B32::MULTIPLICATIVE_GENERATOR;
+                low_power
+            })
+            .collect();
         Self {
             mem: Vec::new(),
             timestamps: Vec::new(),
-            powers: Vec::new(),
+            low_powers,
+            high_powers: Vec::new(),
         }
     }
     pub fn len(&self) -> usize {
@@ -25,28 +46,41 @@ impl Memory {
         self.len() as u32
     }
     pub fn append(&mut self, data: &[u8]) {
-        // extend powers
-        let mut next_power = match self.len() {
-            0 => B32::ONE,
-            _ => self.powers[self.len() - 1] * B32::MULTIPLICATIVE_GENERATOR,
-        };
-        for _ in 0..data.len() {
-            self.powers.push(next_power);
-            next_power *= B32::MULTIPLICATIVE_GENERATOR;
-        }
         // extend mem
         self.mem.extend_from_slice(data);
-        // extend counts
-        self.timestamps.extend(vec![B32::ONE; data.len()]);
+        // extend powers, one per block
+        let block_power = self.low_powers[MEM_BLOCK_LEN - 1] * B32::MULTIPLICATIVE_GENERATOR;
+        while self.high_powers.len() << MEM_BLOCK_BITS < self.len() {
+            let next_power = match self.high_powers.last() {
+                Some(&last_power) => last_power * block_power,
+                None => B32::ONE,
+            };
+            self.high_powers.push(next_power);
+        }
+        // blocks of timestamps are only allocated when read
+        self.timestamps.resize_with(self.high_powers.len(), || None);
     }
     fn get_power(&self, index: u32) -> B32 {
-        self.powers[index as usize]
+        let index = index as usize;
+        self.high_powers[index >> MEM_BLOCK_BITS] * self.low_powers[index & (MEM_BLOCK_LEN - 1)]
     }
     pub fn process_timestamp(&mut self, index: u32) -> B32 {
-        let ts = self.timestamps[index as usize];
-        self.timestamps[index as usize] *= B32::MULTIPLICATIVE_GENERATOR;
+        assert!((index as usize) < self.len());
+        let index = index as usize;
+        let block = self.timestamps[index >> MEM_BLOCK_BITS]
+            .get_or_insert_with(|| Box::new([B32::ONE; MEM_BLOCK_LEN]));
+        let ts = block[index & (MEM_BLOCK_LEN - 1)];
+        block[index & (MEM_BLOCK_LEN - 1)] *= B32::MULTIPLICATIVE_GENERATOR;
         ts
     }
+    // the timestamp the next read would get, without reading
+    pub fn timestamp(&self, index: u32) -> B32 {
+        assert!((index as usize) < self.len());
+        let index = index as usize;
+        self.timestamps[index >> MEM_BLOCK_BITS]
+            .as_ref()
+            .map_or(B32::ONE, |block| block[index & (MEM_BLOCK_LEN - 1)])
+    }
     pub fn to_mult(&self, index: u32) -> B32 {
         assert!((index as usize) < self.len());
         self.get_power(index)
@@ -560,6 +594,282 @@ where
     Ok(())
 }
 
+// reads the byte at ptr * g^offset, for bytes at a fixed distance from a committed pointer
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn read_mem_at_offset(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    name: impl ToString,
+    ptr: OracleId,
+    offset: u32,
+    val: OracleId,
+    ts: OracleId,
+    n_vars: usize,
+    count: usize,
+    mem_channel_id: usize,
+) -> Result<(), anyhow::Error> {
+    let g_offset = B32::MULTIPLICATIVE_GENERATOR.pow([offset as u64]);
+    let vals = builder
+        .witness()
+        .map(|witness| {
+            Ok::<_, anyhow::Error>(
+                (
+                    witness.get::<B32>(ptr)?.as_slice::<B32>(),
+                    witness.get::<B8>(val)?.as_slice::<u8>(),
+                )
+                    .into_par_iter()
+                    .map(move |(ptr, val)| {
+                        (*val as u128) << 32 | (*ptr * g_offset).to_underlier() as u128
+                    }),
+            )
+        })
+        .transpose()?;
+
+    one_block_rom(
+        builder,
+        name,
+        ts,
+        n_vars,
+        count,
+        [(ptr, basis(32, 0) * g_offset), (val, basis(32, 1))],
+        None,
+        vals,
+        mem_channel_id,
+    )?;
+
+    Ok(())
+}
+
+// reads the constant byte at ptr * g^offset
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn read_const_at_offset(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    name: impl ToString,
+    ptr: OracleId,
+    offset: u32,
+    val: B8,
+    ts: OracleId,
+    n_vars: usize,
+    count: usize,
+    mem_channel_id: usize,
+) -> Result<(), anyhow::Error> {
+    let g_offset = B32::MULTIPLICATIVE_GENERATOR.pow([offset as u64]);
+    let vals = builder
+        .witness()
+        .map(|witness| {
+            Ok::<_, anyhow::Error>(
+                witness
+                    .get::<B32>(ptr)?
+                    .as_slice::<B32>()
+                    .into_par_iter()
+                    .map(move |ptr| {
+                        (val.to_underlier() as u128) << 32
+                            | (*ptr * g_offset).to_underlier() as u128
+                    }),
+            )
+        })
+        .transpose()?;
+
+    one_block_rom(
+        builder,
+        name,
+        ts,
+        n_vars,
+        count,
+        [(ptr, basis(32, 0) * g_offset)],
+        Some(val * basis(32, 1)),
+        vals,
+        mem_channel_id,
+    )?;
+
+    Ok(())
+}
+
+// the bits are derived from the committed byte, padding rows included
+pub(crate) fn populate_byte_bits(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    byte: OracleId,
+    bits: [OracleId; 8],
+) -> Result<(), anyhow::Error> {
+    if let Some(witness) = builder.witness() {
+        let byte = witness.get::<B8>(byte)?;
+        let bytes = byte.as_slice::<u8>();
+        for (i, id) in bits.into_iter().enumerate() {
+            (
+                witness.new_column::<B1>(id).as_mut_slice::<u8>(),
+                bytes.par_chunks(8),
+            )
+                .into_par_iter()
+                .for_each(|(bits, bytes)| {
+                    // fill bits from LSB to MSB
+                    *bits = bytes
+                        .iter()
+                        .enumerate()
+                        .fold(0, |acc, (k, val)| acc | ((val >> i) & 1) << k);
+                });
+        }
+    }
+    Ok(())
+}
+
+// byte == sum of bits[i] * 2^i
+pub(crate) fn assert_byte_bits(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    byte: OracleId,
+    bits: [OracleId; 8],
+) {
+    builder.assert_zero(
+        [
+            byte, bits[0], bits[1], bits[2], bits[3], bits[4], bits[5], bits[6], bits[7],
+        ],
+        binius_macros::arith_expr!(
+            B8[val, b0, b1, b2, b3, b4, b5, b6, b7] =
+                b0 * 1 + b1 * 2 + b2 * 4 + b3 * 8 + b4 * 16 + b5 * 32 + b6 * 64 + b7 * 128
+                    - val
+        )
+        .convert_field(),
+    );
+}
+
+// the byte of bits is the header of a child reference: 0x80 for an empty child, 0xa0 for a
+// hash, or 0xc0 <= byte < 0xdf for a child inlined for being shorter than 32 bytes. Rows where
+// skipped is set are not checked.
+pub(crate) fn assert_child_reference(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    bits: [OracleId; 8],
+    skipped: Option<OracleId>,
+) {
+    let [b0, b1, b2, b3, b4, b5, b6, b7] = bits;
+    // every check is a product of bits, each of them set or unset
+    let mut products = vec![
+        // bits[7] == 1
+        vec![(b7, false)],
+        // bits[6] == 1 => bits[5] == 0
+        vec![(b6, true), (b5, true)],
+        // bits[6] == 1 => bits[0..5] != 0x1f
+        vec![
+            (b6, true),
+            (b0, true),
+            (b1, true),
+            (b2, true),
+            (b3, true),
+            (b4, true),
+        ],
+    ];
+    // bits[6] == 0 => bits[0..5] == 0
+    products.extend([b0, b1, b2, b3, b4].map(|bit| vec![(b6, false), (bit, true)]));
+    for mut factors in products {
+        factors.extend(skipped.map(|skipped| (skipped, false)));
+        let composition = factors.iter().enumerate().fold(
+            ArithExpr::<B1>::one(),
+            |acc, (i, &(_, set))| match set {
+                true => acc * ArithExpr::Var(i),
+                false => acc * (ArithExpr::one() - ArithExpr::Var(i)),
+            },
+        );
+        builder.assert_zero(
+            factors.iter().map(|&(bit, _)| bit).collect::<Vec<_>>(),
+            composition.convert_field(),
+        );
+    }
+}
+
+// g raised to the bits of val from first_bit on, the i-th of them weighted by g_powers[i]
+pub(crate) fn g_raised_to_bits(
+    val: u8,
+    first_bit: usize,
+    g_powers: impl Iterator<Item = B32>,
+) -> B32 {
+    g_powers.enumerate().fold(B32::ONE, |acc, (i, g_power)| {
+        match (val >> (first_bit + i)) & 1 {
+            1 => acc * g_power,
+            _ => acc,
+        }
+    })
+}
+
+// g^(2^i) from g^(2^first_exponent_bit) on
+pub(crate) fn g_powers(first_exponent_bit: usize) -> impl Iterator<Item = B32> {
+    std::iter::successors(Some(B32::MULTIPLICATIVE_GENERATOR), |&prev| {
+        Some(prev * prev)
+    })
+    .skip(first_exponent_bit)
+}
+
+// column == g raised to the bits, the i-th of them weighted by g^(2^(first_exponent_bit + i))
+pub(crate) fn assert_g_raised_to_bits(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    bits: &[OracleId],
+    first_exponent_bit: usize,
+    column: OracleId,
+) {
+    let g_raised_to_bits = g_powers(first_exponent_bit)
+        .take(bits.len())
+        .enumerate()
+        .fold(ArithExpr::one(), |acc, (i, g_power)| {
+            acc * (ArithExpr::Var(i) * ArithExpr::Const(g_power)
+                + (ArithExpr::one() - ArithExpr::Var(i)))
+        });
+    let mut oracle_ids = bits.to_vec();
+    oracle_ids.push(column);
+    builder.assert_zero(
+        oracle_ids,
+        (g_raised_to_bits - ArithExpr::Var(bits.len())).convert_field(),
+    );
+}
+
+// a block of 32-bit lanes, each a pointer column times a constant power of g
+pub(crate) fn ptr_lanes_block(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    name: impl ToString,
+    n_vars: usize,
+    lanes: &[(OracleId, B32)],
+) -> Result<OracleId, anyhow::Error> {
+    ptr_lanes_block_with_offset(builder, name, n_vars, B128::ZERO, lanes)
+}
+
+// like ptr_lanes_block, with constants in the lanes left after the pointer lanes
+pub(crate) fn ptr_lanes_block_with_offset(
+    builder: &mut ConstraintSystemBuilder<U, B128>,
+    name: impl ToString,
+    n_vars: usize,
+    offset: B128,
+    lanes: &[(OracleId, B32)],
+) -> Result<OracleId, anyhow::Error> {
+    let mut terms: Vec<(OracleId, B128)> = vec![];
+    for (lane, &(ptr, factor)) in lanes.iter().enumerate() {
+        let coefficient = basis(32, lane) * factor;
+        match terms.iter_mut().find(|(id, _)| *id == ptr) {
+            Some((_, acc)) => *acc += coefficient,
+            None => terms.push((ptr, coefficient)),
+        }
+    }
+    let block = builder.add_linear_combination_with_offset(name, n_vars, offset, terms)?;
+    if let Some(witness) = builder.witness() {
+        let entries = lanes
+            .iter()
+            .map(|&(ptr, _)| witness.get::<B32>(ptr))
+            .collect::<Result<Vec<_>, _>>()?;
+        let columns = entries
+            .iter()
+            .map(|entry| entry.as_slice::<B32>())
+            .collect::<Vec<_>>();
+        witness
+            .new_column::<B128>(block)
+            .as_mut_slice::<u128>()
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(row, block)| {
+                *block = izip!(lanes, &columns).enumerate().fold(
+                    offset.to_underlier(),
+                    |acc, (lane, ((_, factor), column))| {
+                        acc | ((column[row] * *factor).to_underlier() as u128) << (32 * lane)
+                    },
+                );
+            });
+    }
+    Ok(block)
+}
+
 // (state_start_ptr, state_key_ptr_byte, state_rlp_ptr, state_ts; state_key_ptr_parity)
 #[allow(clippy::too_many_arguments)]
 pub fn flush_state(
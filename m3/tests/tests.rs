@@ -1,13 +1,23 @@
 // Copyright 2024 Irreducible Inc.
 
 use alloy::{
+    primitives::{keccak256, Bytes, U256},
     providers::{Provider, ProviderBuilder},
     rpc::types::EIP1186AccountProofResponse,
 };
+use binius_circuits::builder::ConstraintSystemBuilder;
+use binius_field::{arch::OptimalUnderlier, BinaryField128b};
 use binius_hal::make_portable_backend;
+use binius_mp3::{
+    AccountPredicate, ChannelTuple, Fault, HashFunction, Keccak256, Keccak256Channels,
+    MPTProofBundle, MPTProofInfo, ProveOptions, ProverConfig, ProverSession, RowLocation,
+    RowOrigin, StatementCompression, TableType, VerifierPolicy,
+};
+use std::collections::HashSet;
 use std::fs::File;
 use std::future::IntoFuture;
 use std::path::PathBuf;
+use tiny_keccak::{Hasher, Keccak};
 use tracing_profile::init_tracing;
 
 #[test]
@@ -15,10 +25,1331 @@ fn test_prove_verify_cpu_76_testnet() {
     let account_proofs: Vec<EIP1186AccountProofResponse> =
         bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
     let _guard = init_tracing().expect("failed to initialize tracing");
-    let (proof, _) = binius_mp3::prove(account_proofs, &make_portable_backend()).unwrap();
+    let (proof, _) = binius_mp3::prove(
+        account_proofs,
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    binius_mp3::verify(proof).unwrap();
+}
+
+#[test]
+fn test_prove_verify_cpu_76_testnet_witness_checks() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let options = ProveOptions::audited();
+    let (proof, _) = binius_mp3::prove(
+        account_proofs,
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    binius_mp3::verify(proof).unwrap();
+}
+
+#[test]
+fn test_prove_verify_cpu_76_testnet_compressed_statement() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let config = ProverConfig {
+        statement_compression: StatementCompression::KnownHashes,
+        ..Default::default()
+    };
+    let (proof, _) = binius_mp3::prove(
+        account_proofs.clone(),
+        &config,
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let (uncompressed_proof, _) = binius_mp3::prove(
+        account_proofs,
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert!(proof.len() < uncompressed_proof.len());
     binius_mp3::verify(proof).unwrap();
 }
 
+#[test]
+fn test_compressed_statement_keeps_its_mode() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let config = ProverConfig {
+        statement_compression: StatementCompression::KnownHashes,
+        ..Default::default()
+    };
+    // an update and a hidden statement compressed together with their values
+    let (proof, _) = binius_mp3::prove_update(
+        account_proofs.clone(),
+        account_proofs.clone(),
+        &config,
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(update.pre_root, update.post_root);
+    assert_eq!(update.updates.len(), account_proofs.len());
+
+    let disclosed = [account_proofs[0].address];
+    let (proof, _) = binius_mp3::prove_hidden(
+        account_proofs.clone(),
+        &disclosed,
+        [1; 32],
+        &config,
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let proven = binius_mp3::verify_hidden(proof).unwrap();
+    assert_eq!(proven.accounts.len(), 1);
+    assert_eq!(proven.hidden_count, account_proofs.len() - 1);
+}
+
+#[test]
+fn test_prove_verify_cpu_76_testnet_security_parameters() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let config = ProverConfig {
+        log_inv_rate: 1,
+        security_bits: 128,
+        ..Default::default()
+    };
+    let (proof, _) = binius_mp3::prove(
+        account_proofs,
+        &config,
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    binius_mp3::verify(proof).unwrap();
+}
+
+#[test]
+fn test_verify_with_policy_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let account_count = account_proofs.len();
+    let config = ProverConfig {
+        log_inv_rate: 1,
+        security_bits: 128,
+        ..Default::default()
+    };
+    let (proof, _) = binius_mp3::prove(
+        account_proofs,
+        &config,
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+
+    let stricter_policy = VerifierPolicy {
+        min_security_bits: 129,
+        ..Default::default()
+    };
+    assert!(binius_mp3::verify_with_policy(proof.clone(), &stricter_policy).is_err());
+    let size_policy = VerifierPolicy {
+        max_proof_size: Some(proof.len() - 1),
+        ..Default::default()
+    };
+    assert!(binius_mp3::verify_with_policy(proof.clone(), &size_policy).is_err());
+    let keccak_only_policy = VerifierPolicy {
+        allowed_hashes: vec![HashFunction::Keccak256],
+        ..Default::default()
+    };
+    assert!(binius_mp3::verify_with_policy(proof.clone(), &keccak_only_policy).is_err());
+    let accounts_policy = VerifierPolicy {
+        max_accounts: Some(account_count - 1),
+        ..Default::default()
+    };
+    assert!(binius_mp3::verify_with_policy(proof.clone(), &accounts_policy).is_err());
+    // the account count is read from the header, and has to match the statement
+    let mut forged_count = proof.clone();
+    forged_count[binius_mp3::PROOF_ACCOUNT_COUNT_OFFSET] ^= 1;
+    assert!(binius_mp3::verify(forged_count).is_err());
+
+    let policy = VerifierPolicy {
+        min_security_bits: 128,
+        max_accounts: Some(account_count),
+        ..Default::default()
+    };
+    binius_mp3::verify_with_policy(proof, &policy).unwrap();
+}
+
+#[test]
+fn test_prove_verify_cpu_76_testnet_hash_functions() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    for hash in [HashFunction::Keccak256, HashFunction::Sha256] {
+        let config = ProverConfig {
+            hash,
+            ..Default::default()
+        };
+        let (proof, _) = binius_mp3::prove(
+            account_proofs.clone(),
+            &config,
+            &ProveOptions::default(),
+            &make_portable_backend(),
+        )
+        .unwrap();
+        binius_mp3::verify(proof).unwrap();
+    }
+}
+
+#[test]
+fn test_estimate_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let estimate = binius_mp3::estimate(account_proofs.clone(), &ProverConfig::default()).unwrap();
+    let (proof, info) = binius_mp3::prove(
+        account_proofs,
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_eq!(estimate.table_stats, info.table_stats);
+    assert_table_stats_match(&info);
+    assert_eq!(estimate.committed_bits, info.committed_bits);
+    assert!(estimate.committed_bits > 0);
+
+    // the prover holds at least the committed columns and their codeword, and at most a 128-bit
+    // derived value per committed cell besides
+    let log_inv_rate = ProverConfig::default().log_inv_rate;
+    let committed_bytes = estimate.committed_bits / 8;
+    let committed_cells = estimate
+        .table_stats
+        .iter()
+        .map(|table_stats| table_stats.committed_columns << table_stats.n_vars)
+        .sum::<usize>();
+    let codeword_bytes = committed_bytes * ((1 << log_inv_rate) + 1);
+    assert!(estimate.peak_memory_bytes >= codeword_bytes);
+    assert!(estimate.peak_memory_bytes <= codeword_bytes + committed_cells * 16);
+
+    // within a quarter of the actual proof
+    assert!(estimate.proof_size_bytes.abs_diff(proof.len()) * 4 <= proof.len());
+}
+
+#[test]
+fn test_trace_hashes_every_node_once() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let nodes = account_proofs
+        .iter()
+        .flat_map(|account_proof| &account_proof.account_proof)
+        .collect::<HashSet<_>>();
+    // the keys go through several children of the root, whose subtrees are walked in parallel
+    let first_nibbles = account_proofs
+        .iter()
+        .map(|account_proof| keccak256(account_proof.address)[0] >> 4)
+        .collect::<HashSet<_>>();
+    assert!(first_nibbles.len() > 1);
+    let estimate = binius_mp3::estimate(account_proofs, &ProverConfig::default()).unwrap();
+    let hash_trans = estimate
+        .table_stats
+        .iter()
+        .find(|table_stats| table_stats.table_type == TableType::HashTransTable)
+        .unwrap();
+    assert_eq!(hash_trans.count, nodes.len());
+}
+
+#[test]
+fn test_parallel_trace_matches_sequential_walk() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // the walks part at the root and below it, so they are split across threads more than once
+    let first_bytes = account_proofs
+        .iter()
+        .map(|account_proof| keccak256(account_proof.address)[0])
+        .collect::<Vec<_>>();
+    let first_nibbles = first_bytes
+        .iter()
+        .map(|byte| byte >> 4)
+        .collect::<HashSet<_>>();
+    assert!(first_nibbles.len() > 1);
+    assert!(first_nibbles.len() < first_bytes.iter().collect::<HashSet<_>>().len());
+
+    let parallel = binius_mp3::traced_rows(account_proofs.clone(), false);
+    let sequential = binius_mp3::traced_rows(account_proofs, true);
+    assert!(sequential.row_count() > 0);
+    assert_eq!(parallel.row_count(), sequential.row_count());
+    assert!(parallel == sequential);
+}
+
+#[test]
+fn test_prove_verify_cpu_76_testnet_session() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let (first_half, second_half) = account_proofs.split_at(account_proofs.len() / 2);
+    let prove = |session: &mut ProverSession, batch: &[EIP1186AccountProofResponse]| {
+        binius_mp3::prove_in_session(
+            session,
+            batch.to_vec(),
+            &ProverConfig::default(),
+            &ProveOptions::default(),
+            &make_portable_backend(),
+        )
+        .unwrap()
+        .0
+    };
+    let mut session = ProverSession::with_max_idle_blocks(2);
+
+    prove(&mut session, first_half);
+    let first_nodes = session.cached_nodes();
+    assert!(first_nodes > 0);
+    assert_eq!(session.evicted_nodes(), 0);
+
+    // the second block holds every node of the first one, all of them found in the cache
+    let proof = prove(&mut session, &account_proofs);
+    assert_eq!(session.cache_hits(), first_nodes);
+    assert_eq!(session.evicted_nodes(), 0);
+    let (cold_proof, _) = binius_mp3::prove(
+        account_proofs.clone(),
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_eq!(proof, cold_proof);
+    binius_mp3::verify(proof).unwrap();
+
+    // the nodes only the first half uses idle for a block, and are dropped after two
+    let all_nodes = session.cached_nodes();
+    prove(&mut session, second_half);
+    assert!(session.cache_hits() > 0);
+    assert_eq!(session.evicted_nodes(), 0);
+    assert_eq!(session.cached_nodes(), all_nodes);
+    let proof = prove(&mut session, second_half);
+    assert!(session.evicted_nodes() > 0);
+    assert_eq!(session.cached_nodes(), all_nodes - session.evicted_nodes());
+    binius_mp3::verify(proof).unwrap();
+}
+
+#[test]
+fn test_prove_verify_cpu_76_testnet_sharded() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let (bundle, infos) = binius_mp3::prove_sharded(
+        account_proofs.clone(),
+        32,
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert!(infos.len() >= 3);
+
+    let parsed = MPTProofBundle::from_bytes(bundle.clone()).unwrap();
+    assert_eq!(parsed.len(), infos.len());
+    assert_eq!(parsed.to_bytes().unwrap(), bundle);
+    let address = *parsed.addresses(1).next().unwrap();
+    assert_eq!(parsed.proof_index(&address), Some(1));
+    binius_mp3::verify(parsed.proof(1).unwrap().to_vec()).unwrap();
+
+    let root_hash = keccak256(&account_proofs[0].account_proof[0]).0;
+    let addresses = account_proofs
+        .iter()
+        .map(|proof| proof.address)
+        .collect::<Vec<_>>();
+    let policy = VerifierPolicy::default();
+    let accounts =
+        binius_mp3::verify_sharded_accounts(bundle.clone(), &root_hash, &[address], &policy)
+            .unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].address, address.to_vec());
+    let accounts =
+        binius_mp3::verify_sharded(bundle.clone(), &root_hash, &addresses, &policy).unwrap();
+    assert_eq!(accounts.len(), account_proofs.len());
+    for (account, account_proof) in accounts.iter().zip(&account_proofs) {
+        assert_eq!(account.address, account_proof.address.to_vec());
+        assert_eq!(account.nonce, account_proof.nonce);
+        assert_eq!(account.balance, account_proof.balance);
+        assert_eq!(account.code_hash, account_proof.code_hash.0);
+    }
+
+    let mut other_root_hash = root_hash;
+    other_root_hash[0] ^= 1;
+    assert!(binius_mp3::verify_sharded_accounts(
+        bundle.clone(),
+        &other_root_hash,
+        &[address],
+        &policy
+    )
+    .is_err());
+    assert!(
+        binius_mp3::verify_sharded(bundle.clone(), &other_root_hash, &addresses, &policy).is_err()
+    );
+    assert!(
+        binius_mp3::verify_sharded(bundle.clone(), &root_hash, &addresses[1..], &policy).is_err()
+    );
+
+    // every proof of the bundle is held to the policy
+    let stricter_policy = VerifierPolicy {
+        min_security_bits: 129,
+        ..Default::default()
+    };
+    assert!(binius_mp3::verify_sharded_accounts(
+        bundle.clone(),
+        &root_hash,
+        &[address],
+        &stricter_policy
+    )
+    .is_err());
+    assert!(binius_mp3::verify_sharded(bundle, &root_hash, &addresses, &stricter_policy).is_err());
+}
+
+#[test]
+fn test_verify_sharded_rejects_update_shard() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // an update proves the account under the pre state root too, but not its value alone
+    let (pre_account_proof, post_account_proof) = with_leaf_removed(&account_proofs);
+    let (proof, _) = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof],
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let mut bundle = MPTProofBundle::new();
+    bundle
+        .push(&proof, vec![pre_account_proof.address])
+        .unwrap();
+    let bundle = bundle.to_bytes().unwrap();
+
+    let root_hash = keccak256(&pre_account_proof.account_proof[0]).0;
+    let addresses = [pre_account_proof.address];
+    let policy = VerifierPolicy::default();
+    assert!(binius_mp3::verify_sharded(bundle.clone(), &root_hash, &addresses, &policy).is_err());
+    assert!(binius_mp3::verify_sharded_accounts(bundle, &root_hash, &addresses, &policy).is_err());
+}
+
+#[test]
+fn test_prove_verify_accounts_cpu_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let options = ProveOptions::audited();
+    let (proof, _) = binius_mp3::prove(
+        account_proofs.clone(),
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let accounts = binius_mp3::verify_accounts(proof).unwrap();
+    assert_eq!(accounts.len(), account_proofs.len());
+    for (account, account_proof) in accounts.iter().zip(&account_proofs) {
+        assert_eq!(account.address, account_proof.address.to_vec());
+        assert_eq!(account.nonce, account_proof.nonce);
+        assert_eq!(account.balance, account_proof.balance);
+        assert_eq!(account.storage_root, account_proof.storage_hash.0);
+        assert_eq!(account.code_hash, account_proof.code_hash.0);
+    }
+}
+
+#[test]
+fn test_prove_verify_predicates_cpu_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let empty_code_hash = keccak256(b"");
+    // every kind of predicate, on the fields the proofs report
+    let predicates = account_proofs
+        .iter()
+        .enumerate()
+        .map(|(i, account_proof)| {
+            let address = account_proof.address;
+            match i % 4 {
+                0 => AccountPredicate::BalanceAtLeast(address, account_proof.balance),
+                1 => AccountPredicate::NonceEquals(address, account_proof.nonce),
+                2 => AccountPredicate::CodeHashIn(
+                    address,
+                    vec![[0xff; 32], account_proof.code_hash.0, empty_code_hash.0],
+                ),
+                _ if account_proof.code_hash != empty_code_hash => {
+                    AccountPredicate::IsContract(address)
+                }
+                _ => AccountPredicate::BalanceAtLeast(address, U256::ZERO),
+            }
+        })
+        .collect::<Vec<_>>();
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_predicates(
+        account_proofs,
+        predicates.clone(),
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let proven = binius_mp3::verify_predicates(proof.clone()).unwrap();
+    assert_eq!(proven.predicates, predicates);
+    // the values are withheld
+    assert!(binius_mp3::verify_accounts(proof).is_err());
+}
+
+#[test]
+fn test_prove_predicates_rejects_false_predicate() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let account_proof = &account_proofs[0];
+    let predicates = vec![AccountPredicate::BalanceAtLeast(
+        account_proof.address,
+        account_proof.balance + U256::from(1),
+    )];
+    let result = binius_mp3::prove_predicates(
+        account_proofs[..1].to_vec(),
+        predicates,
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_prove_verify_balance_sum_cpu_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let total = account_proofs
+        .iter()
+        .map(|account_proof| account_proof.balance)
+        .fold(U256::ZERO, |sum, balance| sum + balance);
+    let addresses = account_proofs
+        .iter()
+        .map(|account_proof| account_proof.address.to_vec())
+        .collect::<Vec<_>>();
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_balance_sum(
+        account_proofs,
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let proven = binius_mp3::verify_balance_sum(proof.clone()).unwrap();
+    assert_eq!(proven.total, total);
+    assert_eq!(proven.addresses, addresses);
+    // the values are withheld
+    assert!(binius_mp3::verify_accounts(proof).is_err());
+}
+
+#[test]
+fn test_prove_verify_bytecode_cpu_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let account_count = account_proofs.len();
+    // the accounts without code run the empty code
+    let empty_code_hash = keccak256(b"");
+    let codes = account_proofs
+        .iter()
+        .filter(|account_proof| account_proof.code_hash == empty_code_hash)
+        .map(|account_proof| (account_proof.address, vec![]))
+        .collect::<Vec<_>>();
+    assert!(!codes.is_empty());
+    // code that does not hash to the code hash of its account is rejected
+    let result = binius_mp3::prove_bytecode(
+        account_proofs.clone(),
+        vec![(codes[0].0, vec![0x00])],
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    );
+    assert!(result.is_err());
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_bytecode(
+        account_proofs,
+        codes.clone(),
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let proven = binius_mp3::verify_bytecode(proof).unwrap();
+    assert_eq!(proven.accounts.len(), account_count);
+    assert_eq!(
+        proven
+            .codes
+            .iter()
+            .map(|account_code| (
+                account_code.address.as_slice(),
+                account_code.code.as_slice()
+            ))
+            .collect::<Vec<_>>(),
+        codes
+            .iter()
+            .map(|(address, code)| (address.as_slice(), code.as_slice()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_prove_verify_bytecode_several_blocks() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // code absorbed in six blocks of 136 bytes, whose hash becomes the code hash of an account
+    // of a trie with a new root
+    let code = (0..700).map(|i| (i * 7 + 3) as u8).collect::<Vec<u8>>();
+    let account_proof = with_fields(&account_proofs[0], |account_proof| {
+        account_proof.code_hash = keccak256(&code)
+    });
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_bytecode(
+        vec![account_proof.clone()],
+        vec![(account_proof.address, code.clone())],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let proven = binius_mp3::verify_bytecode(proof).unwrap();
+    assert_eq!(proven.codes.len(), 1);
+    assert_eq!(proven.codes[0].address, account_proof.address.to_vec());
+    assert_eq!(proven.codes[0].code, code);
+    // a byte off in a later block, the code no longer hashes to the code hash
+    let mut other_code = code;
+    other_code[500] ^= 1;
+    let result = binius_mp3::prove_bytecode(
+        vec![account_proof.clone()],
+        vec![(account_proof.address, other_code)],
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_prove_verify_hidden_cpu_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // disclose every third account, in reverse order
+    let disclosed = account_proofs
+        .iter()
+        .step_by(3)
+        .rev()
+        .map(|account_proof| account_proof.address)
+        .collect::<Vec<_>>();
+    let hidden = account_proofs
+        .iter()
+        .map(|account_proof| account_proof.address)
+        .filter(|address| !disclosed.contains(address))
+        .collect::<Vec<_>>();
+    let salt = [0x5a; 32];
+    let options = ProveOptions::audited();
+    let (proof, _) = binius_mp3::prove_hidden(
+        account_proofs,
+        &disclosed,
+        salt,
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let proven = binius_mp3::verify_hidden(proof.clone()).unwrap();
+    assert_eq!(
+        proven
+            .accounts
+            .iter()
+            .map(|account| account.address.as_slice())
+            .collect::<Vec<_>>(),
+        disclosed
+            .iter()
+            .map(|address| address.as_slice())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(proven.hidden_count, hidden.len());
+    assert_eq!(
+        proven.commitment,
+        binius_mp3::hidden_accounts_commitment(&hidden, &salt)
+    );
+    // the commitment opens to the hidden addresses only with the salt
+    assert_ne!(
+        proven.commitment,
+        binius_mp3::hidden_accounts_commitment(&hidden, &[0; 32])
+    );
+    assert!(binius_mp3::verify_accounts(proof).is_err());
+}
+
+#[test]
+fn test_prove_verify_update_cpu_76_testnet_unchanged() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let account_count = account_proofs.len();
+    let options = ProveOptions::audited();
+    // the same proofs under both roots prove an update that changes nothing
+    let (proof, info) = binius_mp3::prove_update(
+        account_proofs.clone(),
+        account_proofs,
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(update.pre_root, update.post_root);
+    assert_eq!(update.updates.len(), account_count);
+    assert!(update
+        .updates
+        .iter()
+        .all(|account_update| account_update.old_value == account_update.new_value));
+}
+
+#[test]
+fn test_prove_update_rejects_absent_account() {
+    let mut account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    account_proofs.truncate(2);
+    // the proof of the first account does not lead to the key of the second one
+    account_proofs[0].address = account_proofs[1].address;
+    let result = binius_mp3::prove_update(
+        account_proofs[..1].to_vec(),
+        account_proofs[..1].to_vec(),
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_prove_verify_inlined_siblings() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // the children of the branch above the leaf, before the one on the path, become leaves
+    // shorter than 32 bytes inlined into it, which get_child skips
+    let (mut account_proof, leaf_index, child_index) = leaf_with_siblings_before(&account_proofs);
+    let parent_index = leaf_index - 1;
+    let mut items = rlp_items(&account_proof.account_proof[parent_index]);
+    for (i, item) in items[..child_index].iter_mut().enumerate() {
+        *item = rlp_list(&[
+            alloy::rlp::encode([0x20u8].as_slice()),
+            alloy::rlp::encode(vec![0xff; i + 1]),
+        ]);
+        assert!(item.len() < 32);
+    }
+    replace_node(
+        &mut account_proof.account_proof,
+        parent_index,
+        rlp_list(&items),
+    );
+
+    let options = ProveOptions::audited();
+    let (proof, _) = binius_mp3::prove(
+        vec![account_proof.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let accounts = binius_mp3::verify_accounts(proof).unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].address, account_proof.address.to_vec());
+    assert_eq!(accounts[0].nonce, account_proof.nonce);
+    assert_eq!(accounts[0].balance, account_proof.balance);
+}
+
+#[test]
+fn test_prove_rejects_hashed_child_passed_off_as_inlined() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let (account_proof, _) = with_hashed_child_inlined(&account_proofs);
+
+    let options = ProveOptions::audited();
+    let err = binius_mp3::prove(
+        vec![account_proof],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap_err()
+    .to_string();
+    // the check of the reference to the child the walk goes on to, in the row of the parent
+    assert!(
+        err.lines()
+            .any(|line| line.starts_with("branch_trans[") && line.contains("new_hash_val_bits")),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_prove_verify_value_in_branch() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // an extension of every nibble of the key leads to a branch without children holding the
+    // value of the leaf, which branch_value_trans reads once the key is exhausted
+    let mut account_proof = account_proofs[0].clone();
+    let leaf = account_proof.account_proof.last().unwrap();
+    let mut items = vec![vec![0x80]; 16];
+    items.push(rlp_items(leaf).pop().unwrap());
+    let branch = rlp_list(&items);
+    let mut path = vec![0x00];
+    path.extend_from_slice(keccak256(account_proof.address).as_slice());
+    let extension = rlp_list(&[
+        alloy::rlp::encode(path),
+        alloy::rlp::encode(keccak256(&branch)),
+    ]);
+    account_proof.account_proof = vec![extension.into(), branch.into()];
+
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove(
+        vec![account_proof.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let branch_value_trans = info
+        .table_stats
+        .iter()
+        .find(|table_stats| table_stats.table_type == TableType::BranchValueTrans)
+        .unwrap();
+    assert_eq!(branch_value_trans.count, 1);
+    let accounts = binius_mp3::verify_accounts(proof).unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].nonce, account_proof.nonce);
+    assert_eq!(accounts[0].balance, account_proof.balance);
+    assert_eq!(accounts[0].code_hash, account_proof.code_hash.0);
+}
+
+#[test]
+fn test_prove_verify_update_changed_balance() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let pre_account_proof = account_proofs
+        .iter()
+        .find(|account_proof| account_proof.balance > U256::from(1))
+        .unwrap();
+    let post_account_proof = with_fields(pre_account_proof, |account_proof| {
+        account_proof.balance ^= U256::from(1)
+    });
+    let options = ProveOptions::audited();
+    let (proof, _) = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(
+        update.pre_root,
+        keccak256(&pre_account_proof.account_proof[0]).0
+    );
+    assert_eq!(
+        update.post_root,
+        keccak256(&post_account_proof.account_proof[0]).0
+    );
+    assert_ne!(update.pre_root, update.post_root);
+    assert_eq!(update.updates.len(), 1);
+    assert_eq!(
+        update.updates[0].address,
+        pre_account_proof.address.to_vec()
+    );
+    assert_eq!(
+        update.updates[0].new_value,
+        Some(account_value(&post_account_proof))
+    );
+    assert_ne!(update.updates[0].old_value, update.updates[0].new_value);
+}
+
+#[test]
+fn test_prove_verify_update_two_accounts_sharing_a_branch() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let mut pre_account_proofs = account_proofs
+        .iter()
+        .filter(|account_proof| account_proof.balance > U256::from(1));
+    let pre_account_proof_a = pre_account_proofs.next().unwrap();
+    let pre_account_proof_b = pre_account_proofs
+        .find(|account_proof| account_proof.address != pre_account_proof_a.address)
+        .unwrap();
+    // both paths start at the root, where both references change
+    let (post_account_proof_a, post_account_proof_b) =
+        with_fields_of_both(pre_account_proof_a, pre_account_proof_b, |account_proof| {
+            account_proof.balance ^= U256::from(1)
+        });
+    assert_eq!(
+        post_account_proof_a.account_proof[0],
+        post_account_proof_b.account_proof[0]
+    );
+    let options = ProveOptions::audited();
+    let (proof, _) = binius_mp3::prove_update(
+        vec![pre_account_proof_a.clone(), pre_account_proof_b.clone()],
+        vec![post_account_proof_a.clone(), post_account_proof_b.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(
+        update.post_root,
+        keccak256(&post_account_proof_a.account_proof[0]).0
+    );
+    assert_eq!(update.updates.len(), 2);
+    for (update, post_account_proof) in update
+        .updates
+        .iter()
+        .zip([&post_account_proof_a, &post_account_proof_b])
+    {
+        assert_eq!(update.address, post_account_proof.address.to_vec());
+        assert_eq!(update.new_value, Some(account_value(post_account_proof)));
+        assert_ne!(update.old_value, update.new_value);
+    }
+}
+
+#[test]
+fn test_verify_update_rejects_tampered_new_value() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let pre_account_proof = account_proofs
+        .iter()
+        .find(|account_proof| account_proof.balance > U256::from(1))
+        .unwrap();
+    let post_account_proof = with_fields(pre_account_proof, |account_proof| {
+        account_proof.balance ^= U256::from(1)
+    });
+    let (proof, _) = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof.clone()],
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
+    // the statement claims the value is left in place under the same post root
+    let new_value = account_value(&post_account_proof);
+    let old_value = account_value(pre_account_proof);
+    let position = proof
+        .windows(new_value.len())
+        .position(|window| window == new_value)
+        .unwrap();
+    let mut tampered_proof = proof.clone();
+    tampered_proof[position..position + new_value.len()].copy_from_slice(&old_value);
+    binius_mp3::verify_update(proof).unwrap();
+    assert!(binius_mp3::verify_update(tampered_proof).is_err());
+}
+
+#[test]
+fn test_prove_update_rejects_diff_outside_window() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let pre_account_proof = account_proofs
+        .iter()
+        .find(|account_proof| account_proof.balance > U256::from(1))
+        .unwrap();
+    let mut post_account_proof = with_fields(pre_account_proof, |account_proof| {
+        account_proof.balance ^= U256::from(1)
+    });
+    // a sibling of the leaf changes along with the value, which no window grants
+    let nodes = &mut post_account_proof.account_proof;
+    let parent_index = nodes.len() - 2;
+    let leaf_reference = alloy::rlp::encode(keccak256(&nodes[parent_index + 1]));
+    let mut items = rlp_items(&nodes[parent_index]);
+    let sibling = items
+        .iter_mut()
+        .find(|item| item[0] == 0xa0 && **item != leaf_reference)
+        .unwrap();
+    sibling[1] ^= 1;
+    replace_node(nodes, parent_index, rlp_list(&items));
+
+    let result = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof],
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_diagnose_unchecked_update_catches_diff_outside_window() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let pre_account_proof = account_proofs[0].clone();
+    // a sibling of the path changes in the root, which no window grants
+    let mut post_account_proof = pre_account_proof.clone();
+    let nodes = &mut post_account_proof.account_proof;
+    let child_reference = alloy::rlp::encode(keccak256(&nodes[1]));
+    let mut items = rlp_items(&nodes[0]);
+    let sibling = items
+        .iter_mut()
+        .find(|item| item[0] == 0xa0 && **item != child_reference)
+        .unwrap();
+    sibling[1] ^= 1;
+    replace_node(nodes, 0, rlp_list(&items));
+
+    let result = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof.clone()],
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    );
+    assert!(result.is_err());
+
+    // without the host check, node_diff_byte reads the pre byte with the value of the post
+    // byte, which the memory does not hold
+    let diagnosis =
+        binius_mp3::diagnose_unchecked_update(vec![pre_account_proof], vec![post_account_proof])
+            .unwrap();
+    assert!(diagnosis.validation_error.is_some());
+    assert!(
+        diagnosis
+            .channel_imbalances
+            .iter()
+            .any(|tuple| tuple.channel == "mem"
+                && tuple
+                    .sources
+                    .iter()
+                    .flatten()
+                    .any(|source| source.namespace == "node_diff_byte")),
+        "{diagnosis:?}"
+    );
+}
+
+#[test]
+fn test_prove_verify_update_inserted_account() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // the leaf of the account goes into an empty child of a branch that keeps its other children
+    let (post_account_proof, pre_account_proof) = with_leaf_removed(&account_proofs);
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let node_insert = info
+        .table_stats
+        .iter()
+        .find(|table_stats| table_stats.table_type == TableType::NodeInsert)
+        .unwrap();
+    assert_eq!(node_insert.count, 1);
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(
+        update.pre_root,
+        keccak256(&pre_account_proof.account_proof[0]).0
+    );
+    assert_eq!(
+        update.post_root,
+        keccak256(&post_account_proof.account_proof[0]).0
+    );
+    assert_eq!(update.updates.len(), 1);
+    assert_eq!(update.updates[0].old_value, None);
+    assert_eq!(
+        update.updates[0].new_value,
+        Some(account_value(&post_account_proof))
+    );
+}
+
+#[test]
+fn test_prove_verify_update_deleted_account() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // the leaf of the account leaves an empty child in a branch that keeps two other children
+    let (pre_account_proof, post_account_proof) = with_leaf_removed(&account_proofs);
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let node_delete = info
+        .table_stats
+        .iter()
+        .find(|table_stats| table_stats.table_type == TableType::NodeDelete)
+        .unwrap();
+    assert_eq!(node_delete.count, 1);
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(
+        update.post_root,
+        keccak256(&post_account_proof.account_proof[0]).0
+    );
+    assert_eq!(update.updates.len(), 1);
+    assert_eq!(
+        update.updates[0].old_value,
+        Some(account_value(&pre_account_proof))
+    );
+    assert_eq!(update.updates[0].new_value, None);
+}
+
+#[test]
+fn test_prove_verify_update_leaf_split() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // the path of the account parts from the leaf it walks to, which is split into an extension
+    // over the nibbles both paths share and a branch holding both leaves
+    let (pre_account_proof, post_account_proof) = with_leaf_split(&account_proofs);
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let count = |table_type| {
+        info.table_stats
+            .iter()
+            .find(|table_stats| table_stats.table_type == table_type)
+            .unwrap()
+            .count
+    };
+    assert_eq!(count(TableType::NodeSplit), 1);
+    // the leaf of the account and the leaf split off below the new branch
+    assert_eq!(count(TableType::NodeInsert), 2);
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(
+        update.pre_root,
+        keccak256(&pre_account_proof.account_proof[0]).0
+    );
+    assert_eq!(
+        update.post_root,
+        keccak256(&post_account_proof.account_proof[0]).0
+    );
+    assert_eq!(update.updates.len(), 1);
+    assert_eq!(update.updates[0].old_value, None);
+    assert_eq!(
+        update.updates[0].new_value,
+        Some(account_value(&post_account_proof))
+    );
+}
+
+#[test]
+fn test_prove_verify_update_branch_collapse() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // deleting the leaf of the account leaves its branch a single child, so the extension above
+    // it and the branch collapse into the leaf of that child
+    let (post_account_proof, pre_account_proof) = with_leaf_split(&account_proofs);
+    let options = ProveOptions::audited();
+    let (proof, info) = binius_mp3::prove_update(
+        vec![pre_account_proof.clone()],
+        vec![post_account_proof.clone()],
+        &ProverConfig::default(),
+        &options,
+        &make_portable_backend(),
+    )
+    .unwrap();
+    assert_table_stats_match(&info);
+    let count = |table_type| {
+        info.table_stats
+            .iter()
+            .find(|table_stats| table_stats.table_type == table_type)
+            .unwrap()
+            .count
+    };
+    assert_eq!(count(TableType::NodeCollapse), 1);
+    // the leaf of the account and the leaf folded into the collapsed node
+    assert_eq!(count(TableType::NodeDelete), 2);
+    let update = binius_mp3::verify_update(proof).unwrap();
+    assert_eq!(
+        update.pre_root,
+        keccak256(&pre_account_proof.account_proof[0]).0
+    );
+    assert_eq!(
+        update.post_root,
+        keccak256(&post_account_proof.account_proof[0]).0
+    );
+    assert_eq!(update.updates.len(), 1);
+    assert_eq!(
+        update.updates[0].old_value,
+        Some(account_value(&pre_account_proof))
+    );
+    assert_eq!(update.updates[0].new_value, None);
+}
+
+#[test]
+fn test_prove_update_rejects_inlined_node() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    // an extension of all but the last two nibbles of the key leads to a branch inlined into
+    // it, whose children are leaves of a single nibble inlined into the branch
+    let with_value = |value: u8| {
+        let mut account_proof = account_proofs[0].clone();
+        let key = keccak256(account_proof.address);
+        let leaf = |value: u8| {
+            rlp_list(&[
+                alloy::rlp::encode([0x30 | (key[31] & 0xf)].as_slice()),
+                alloy::rlp::encode([value].as_slice()),
+            ])
+        };
+        let mut items = vec![vec![0x80]; 17];
+        items[(key[31] >> 4) as usize] = leaf(value);
+        items[((key[31] >> 4) ^ 1) as usize] = leaf(0x7f);
+        let branch = rlp_list(&items);
+        assert!(branch.len() < 32);
+        let mut path = vec![0x00];
+        path.extend_from_slice(&key[..31]);
+        account_proof.account_proof = vec![rlp_list(&[alloy::rlp::encode(path), branch]).into()];
+        account_proof
+    };
+    let err = binius_mp3::prove_update(
+        vec![with_value(1)],
+        vec![with_value(2)],
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("goes through an inlined node"), "{err}");
+}
+
+#[test]
+#[ignore = "Evaluates every constraint row by row, which is slow"]
+fn test_diagnose_76_testnet() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let diagnosis = binius_mp3::diagnose(account_proofs).unwrap();
+    assert!(diagnosis.is_ok(), "{diagnosis:?}");
+}
+
+#[test]
+fn test_diagnose_locates_failing_constraint() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let (account_proof, parent_index) = with_hashed_child_inlined(&account_proofs);
+    // one row of branch_trans per branch on the path, the parent of the leaf is the last one
+    let row = account_proof.account_proof[..parent_index]
+        .iter()
+        .filter(|node| rlp_items(node).len() == 17)
+        .count();
+
+    let diagnosis = binius_mp3::diagnose(vec![account_proof.clone()]).unwrap();
+    assert!(diagnosis.validation_error.is_some());
+    assert!(diagnosis.zero_oracles.is_empty(), "{diagnosis:?}");
+    assert!(diagnosis.channel_imbalances.is_empty(), "{diagnosis:?}");
+    assert!(!diagnosis.constraint_failures.is_empty());
+    for failure in &diagnosis.constraint_failures {
+        assert_eq!(failure.location.namespace, "branch_trans");
+        assert_eq!(failure.location.row, row);
+        assert_eq!(
+            failure.location.origin,
+            Some(RowOrigin {
+                account: 0,
+                node: parent_index
+            })
+        );
+        assert_eq!(failure.location.address, Some(account_proof.address));
+        // the check of the reference to the child the walk goes on to
+        assert!(
+            failure
+                .oracles
+                .iter()
+                .all(|oracle| oracle.contains("new_hash_val_bits")),
+            "{failure}"
+        );
+    }
+}
+
+#[test]
+fn test_diagnose_reports_unbalanced_mem_tuple() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let account_proof = account_proofs[0].clone();
+    let root = account_proof.account_proof[0].clone();
+    // the first byte of the root is read once more than the tables do, so the memory table
+    // pulls it at a timestamp past the last read
+    let diagnosis =
+        binius_mp3::diagnose_with_fault(vec![account_proof.clone()], Fault::ExtraRootRead).unwrap();
+    assert!(diagnosis.validation_error.is_some());
+    let [pushed, pulled] = diagnosis.channel_imbalances.as_slice() else {
+        panic!("{diagnosis:?}");
+    };
+
+    // the last read of the byte pushes it back, for a read that never comes
+    assert_eq!(pushed.channel, "mem");
+    let ChannelTuple::Mem { addr, val, ts } = pushed.tuple else {
+        panic!("{pushed}");
+    };
+    assert_eq!(val, root[0]);
+    assert_eq!(pushed.multiplicity, 1);
+    match pushed.sources.as_slice() {
+        [Some(source)] => {
+            assert_eq!(
+                source.origin,
+                Some(RowOrigin {
+                    account: 0,
+                    node: 0
+                })
+            );
+            assert_eq!(source.address, Some(account_proof.address));
+        }
+        _ => panic!("{pushed}"),
+    }
+
+    // the memory table pulls it at the timestamp of the extra read
+    assert_eq!(pulled.channel, "mem");
+    assert_eq!(
+        pulled.tuple,
+        ChannelTuple::Mem {
+            addr,
+            val,
+            ts: ts + 1
+        }
+    );
+    assert_eq!(pulled.multiplicity, -1);
+    assert_eq!(
+        pulled.sources,
+        vec![Some(RowLocation {
+            namespace: "populate_mem".to_string(),
+            row: addr as usize,
+            origin: None,
+            address: None,
+        })]
+    );
+}
+
+#[test]
+fn test_diagnose_reports_forged_final_state() {
+    let account_proofs: Vec<EIP1186AccountProofResponse> =
+        bincode::deserialize(include_bytes!("eip1186_proofs_1.bin")).unwrap();
+    let address = account_proofs[0].address;
+    // the verifier pulls the final state of the walk one timestamp past the one it ends at
+    let diagnosis =
+        binius_mp3::diagnose_with_fault(account_proofs[..1].to_vec(), Fault::LateFinalState(0))
+            .unwrap();
+    assert!(diagnosis.validation_error.is_some());
+    assert!(diagnosis.constraint_failures.is_empty(), "{diagnosis:?}");
+    assert!(diagnosis.zero_oracles.is_empty(), "{diagnosis:?}");
+    // the state the walk pushes last is left unpulled, and the forged one unpushed
+    let [pushed, pulled] = diagnosis.channel_imbalances.as_slice() else {
+        panic!("{diagnosis:?}");
+    };
+    assert_eq!((pushed.channel, pushed.multiplicity), ("state", 1));
+    assert_eq!((pulled.channel, pulled.multiplicity), ("state", -1));
+    assert!(pushed.sources.iter().all(|source| source
+        .as_ref()
+        .is_some_and(|location| location.address == Some(address))));
+    assert_eq!(pulled.sources, vec![None]);
+}
+
 #[test]
 #[ignore]
 fn test_prove_verify_cpu_450_mainnet() {
@@ -30,7 +1361,13 @@ fn test_prove_verify_cpu_450_mainnet() {
         serde_json::from_reader(data_file).unwrap();
 
     let _guard = init_tracing().expect("failed to initialize tracing");
-    let (proof, _) = binius_mp3::prove(account_proofs, &make_portable_backend()).unwrap();
+    let (proof, _) = binius_mp3::prove(
+        account_proofs,
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
     binius_mp3::verify(proof).unwrap();
 }
 
@@ -45,10 +1382,454 @@ fn test_prove_verify_cpu_3395_mainnet() {
         serde_json::from_reader(data_file).unwrap();
 
     let _guard = init_tracing().expect("failed to initialize tracing");
-    let (proof, _) = binius_mp3::prove(account_proofs, &make_portable_backend()).unwrap();
+    let (proof, _) = binius_mp3::prove(
+        account_proofs,
+        &ProverConfig::default(),
+        &ProveOptions::default(),
+        &make_portable_backend(),
+    )
+    .unwrap();
     binius_mp3::verify(proof).unwrap();
 }
 
+// the table stats add up to the committed oracles of the constraint system that was proven
+fn assert_table_stats_match(info: &MPTProofInfo) {
+    let committed_columns = info
+        .table_stats
+        .iter()
+        .map(|table_stats| table_stats.committed_columns)
+        .sum::<usize>();
+    let committed_bits = info
+        .table_stats
+        .iter()
+        .map(|table_stats| table_stats.committed_bytes * 8)
+        .sum::<usize>();
+    assert_eq!(committed_columns, info.committed_columns);
+    assert_eq!(committed_bits, info.committed_bits);
+}
+
+// the items of an rlp list, each with its header
+fn rlp_items(list: &[u8]) -> Vec<Vec<u8>> {
+    let mut payload = list;
+    let header = alloy::rlp::Header::decode(&mut payload).unwrap();
+    assert!(header.list);
+    let mut payload = &payload[..header.payload_length];
+    let mut items = vec![];
+    while !payload.is_empty() {
+        let mut rest = payload;
+        let item = alloy::rlp::Header::decode(&mut rest).unwrap();
+        let (encoded_item, remaining) =
+            payload.split_at(payload.len() - rest.len() + item.payload_length);
+        items.push(encoded_item.to_vec());
+        payload = remaining;
+    }
+    items
+}
+
+// an rlp list of items that are already encoded
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    let mut list = vec![];
+    alloy::rlp::Header {
+        list: true,
+        payload_length: payload.len(),
+    }
+    .encode(&mut list);
+    list.extend(payload);
+    list
+}
+
+// replaces the node at index with node, and the hash of the node it replaces in the nodes above
+// it, up to a new root
+fn replace_node(nodes: &mut [Bytes], index: usize, node: Vec<u8>) {
+    let reference = |node: &[u8]| alloy::rlp::encode(keccak256(node));
+    let old_reference = reference(&nodes[index]);
+    let new_reference = reference(&node);
+    nodes[index] = node.into();
+    if index > 0 {
+        let mut parent = nodes[index - 1].to_vec();
+        let position = parent
+            .windows(old_reference.len())
+            .position(|window| window == old_reference)
+            .unwrap();
+        parent[position..position + old_reference.len()].copy_from_slice(&new_reference);
+        replace_node(nodes, index - 1, parent);
+    }
+}
+
+// the value of the leaf of the proof, without its rlp string header
+fn account_value(account_proof: &EIP1186AccountProofResponse) -> Vec<u8> {
+    let mut value = rlp_items(account_proof.account_proof.last().unwrap())
+        .pop()
+        .unwrap();
+    let header = alloy::rlp::Header::decode(&mut value.as_slice()).unwrap();
+    value.split_off(value.len() - header.payload_length)
+}
+
+// the proof of the account under the trie where only the fields set by update are changed,
+// with the hashes of the nodes above its leaf recomputed up to a new root
+fn with_fields(
+    account_proof: &EIP1186AccountProofResponse,
+    update: impl FnOnce(&mut EIP1186AccountProofResponse),
+) -> EIP1186AccountProofResponse {
+    let mut account_proof = account_proof.clone();
+    update(&mut account_proof);
+    let value = rlp_list(&[
+        alloy::rlp::encode(account_proof.nonce),
+        alloy::rlp::encode(account_proof.balance),
+        alloy::rlp::encode(account_proof.storage_hash),
+        alloy::rlp::encode(account_proof.code_hash),
+    ]);
+    let leaf_index = account_proof.account_proof.len() - 1;
+    let mut items = rlp_items(&account_proof.account_proof[leaf_index]);
+    items[1] = alloy::rlp::encode(value);
+    replace_node(
+        &mut account_proof.account_proof,
+        leaf_index,
+        rlp_list(&items),
+    );
+    account_proof
+}
+
+// the proofs of two accounts under the trie where the fields set by update are changed in both,
+// with the nodes their paths share holding both changes
+fn with_fields_of_both(
+    account_proof_a: &EIP1186AccountProofResponse,
+    account_proof_b: &EIP1186AccountProofResponse,
+    update: impl Fn(&mut EIP1186AccountProofResponse),
+) -> (EIP1186AccountProofResponse, EIP1186AccountProofResponse) {
+    let shared = account_proof_a
+        .account_proof
+        .iter()
+        .zip(&account_proof_b.account_proof)
+        .take_while(|(node_a, node_b)| node_a == node_b)
+        .count();
+    let mut account_proof_a = with_fields(account_proof_a, &update);
+    // the change to a goes into the shared nodes before the change to b does
+    let mut account_proof_b = account_proof_b.clone();
+    account_proof_b.account_proof[..shared]
+        .clone_from_slice(&account_proof_a.account_proof[..shared]);
+    let account_proof_b = with_fields(&account_proof_b, &update);
+    account_proof_a.account_proof[..shared]
+        .clone_from_slice(&account_proof_b.account_proof[..shared]);
+    (account_proof_a, account_proof_b)
+}
+
+// the first proof whose leaf is a child of a branch past its first child, with the index of
+// the leaf in the proof and of the child in the branch
+fn leaf_with_siblings_before(
+    account_proofs: &[EIP1186AccountProofResponse],
+) -> (EIP1186AccountProofResponse, usize, usize) {
+    account_proofs
+        .iter()
+        .find_map(|account_proof| {
+            let nodes = &account_proof.account_proof;
+            let leaf_index = nodes.len() - 1;
+            let items = rlp_items(&nodes[leaf_index - 1]);
+            let reference = alloy::rlp::encode(keccak256(&nodes[leaf_index]));
+            match items.len() {
+                17 => items
+                    .iter()
+                    .position(|item| *item == reference)
+                    .filter(|&child_index| child_index > 0)
+                    .map(|child_index| (account_proof.clone(), leaf_index, child_index)),
+                _ => None,
+            }
+        })
+        .unwrap()
+}
+
+// the first proof whose leaf is a child of a branch past its first child, with the leaf, at
+// least 32 bytes long, inlined into the branch rather than referenced by its hash, and the
+// index of the branch
+fn with_hashed_child_inlined(
+    account_proofs: &[EIP1186AccountProofResponse],
+) -> (EIP1186AccountProofResponse, usize) {
+    let (mut account_proof, leaf_index, child_index) = leaf_with_siblings_before(account_proofs);
+    let leaf = account_proof.account_proof.pop().unwrap().to_vec();
+    assert!(leaf.len() >= 32);
+    let parent_index = leaf_index - 1;
+    let mut items = rlp_items(&account_proof.account_proof[parent_index]);
+    items[child_index] = leaf;
+    replace_node(
+        &mut account_proof.account_proof,
+        parent_index,
+        rlp_list(&items),
+    );
+    (account_proof, parent_index)
+}
+
+// the first proof whose leaf is a child of a branch with at least two other children, and the
+// proof of its account absent from the trie where that child is left empty
+fn with_leaf_removed(
+    account_proofs: &[EIP1186AccountProofResponse],
+) -> (EIP1186AccountProofResponse, EIP1186AccountProofResponse) {
+    account_proofs
+        .iter()
+        .find_map(|account_proof| {
+            let nodes = &account_proof.account_proof;
+            let parent_index = nodes.len().checked_sub(2)?;
+            let mut items = rlp_items(&nodes[parent_index]);
+            if items.len() != 17 {
+                return None;
+            }
+            let children = items[..16].iter().filter(|item| **item != [0x80]).count();
+            if children < 3 {
+                return None;
+            }
+            let reference = alloy::rlp::encode(keccak256(&nodes[parent_index + 1]));
+            let child_index = items.iter().position(|item| *item == reference)?;
+            items[child_index] = vec![0x80];
+            let mut absent_account_proof = account_proof.clone();
+            absent_account_proof.account_proof.pop();
+            replace_node(
+                &mut absent_account_proof.account_proof,
+                parent_index,
+                rlp_list(&items),
+            );
+            Some((account_proof.clone(), absent_account_proof))
+        })
+        .unwrap()
+}
+
+// the nibbles of the key of the account
+fn key_nibbles(account_proof: &EIP1186AccountProofResponse) -> Vec<u8> {
+    keccak256(account_proof.address)
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xf])
+        .collect()
+}
+
+// a leaf or an extension over the nibbles, with its last item already encoded
+fn short_node(nibbles: &[u8], leaf: bool, item: Vec<u8>) -> Vec<u8> {
+    let odd = nibbles.len() % 2;
+    let flag = 2 * u8::from(leaf) + odd as u8;
+    let mut path = vec![flag << 4 | if odd == 1 { nibbles[0] } else { 0 }];
+    path.extend(nibbles[odd..].chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    rlp_list(&[alloy::rlp::encode(path.as_slice()), item])
+}
+
+// a branch without a value holding the nodes at the given children, by their hashes
+fn branch_node(children: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut items = vec![vec![0x80]; 17];
+    for (nibble, node) in children {
+        items[*nibble as usize] = alloy::rlp::encode(keccak256(node));
+    }
+    rlp_list(&items)
+}
+
+// two accounts whose keys share their first two nibbles, and a third one whose key does not
+// share the first, under a trie with a branch at its root: the proof of the second account
+// absent from the trie holding the other two, where its path ends at the leaf of the first, and
+// its proof under the trie where that leaf is split into an extension over the nibbles both keys
+// share past the root and a branch holding both leaves
+fn with_leaf_split(
+    account_proofs: &[EIP1186AccountProofResponse],
+) -> (EIP1186AccountProofResponse, EIP1186AccountProofResponse) {
+    let (a, b) = account_proofs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| account_proofs[i + 1..].iter().map(move |b| (a, b)))
+        .find(|(a, b)| key_nibbles(a)[..2] == key_nibbles(b)[..2])
+        .unwrap();
+    let (key_a, key_b) = (key_nibbles(a), key_nibbles(b));
+    let c = account_proofs
+        .iter()
+        .find(|c| key_nibbles(c)[0] != key_a[0])
+        .unwrap();
+    let key_c = key_nibbles(c);
+    let value = |account_proof: &EIP1186AccountProofResponse| {
+        rlp_items(account_proof.account_proof.last().unwrap())
+            .pop()
+            .unwrap()
+    };
+    let leaf_c = short_node(&key_c[1..], true, value(c));
+
+    let leaf_a = short_node(&key_a[1..], true, value(a));
+    let root = branch_node(&[(key_a[0], leaf_a.as_slice()), (key_c[0], leaf_c.as_slice())]);
+    let mut absent_account_proof = b.clone();
+    absent_account_proof.account_proof = vec![root.into(), leaf_a.into()];
+
+    // the nibbles the keys share past the root, then the nibble of each leaf in the new branch
+    let shared = (1..64).take_while(|&i| key_a[i] == key_b[i]).count();
+    let split = 1 + shared;
+    let leaf_a = short_node(&key_a[split + 1..], true, value(a));
+    let leaf_b = short_node(&key_b[split + 1..], true, value(b));
+    let branch = branch_node(&[
+        (key_a[split], leaf_a.as_slice()),
+        (key_b[split], leaf_b.as_slice()),
+    ]);
+    let extension = short_node(
+        &key_a[1..split],
+        false,
+        alloy::rlp::encode(keccak256(&branch)),
+    );
+    let root = branch_node(&[
+        (key_a[0], extension.as_slice()),
+        (key_c[0], leaf_c.as_slice()),
+    ]);
+    let mut account_proof = b.clone();
+    account_proof.account_proof = vec![root.into(), extension.into(), branch.into(), leaf_b.into()];
+    (absent_account_proof, account_proof)
+}
+
+// builds the gadget over the messages with a witness, checked against the boundaries of the
+// gadget the verifier builds over verifier_messages
+fn validate_keccak256(messages: &[Vec<u8>], verifier_messages: &[Vec<u8>]) -> anyhow::Result<()> {
+    let mut gadget = Keccak256::new();
+    for message in messages {
+        gadget.hash(message)?;
+    }
+    let mut verifier_gadget = Keccak256::new();
+    for message in verifier_messages {
+        verifier_gadget.hash(message)?;
+    }
+    validate_keccak256_gadgets(gadget, verifier_gadget, false)
+}
+
+// builds the gadget with a witness, checked against the boundaries of the verifier gadget, over
+// channels of the caller if caller_channels
+fn validate_keccak256_gadgets(
+    gadget: Keccak256,
+    verifier_gadget: Keccak256,
+    caller_channels: bool,
+) -> anyhow::Result<()> {
+    let build =
+        |gadget: Keccak256,
+         builder: &mut ConstraintSystemBuilder<OptimalUnderlier, BinaryField128b>| {
+            match caller_channels {
+                true => {
+                    let channels = Keccak256Channels {
+                        mem: builder.add_channel(),
+                        absorb_block: builder.add_channel(),
+                    };
+                    gadget.build_with(builder, channels)
+                }
+                false => gadget.build(builder),
+            }
+        };
+    let allocator = bumpalo::Bump::new();
+    let mut builder = ConstraintSystemBuilder::new_with_witness(&allocator);
+    build(gadget, &mut builder)?;
+    let witness = builder.take_witness()?;
+    let constraint_system = builder.build()?;
+
+    let mut verifier_builder = ConstraintSystemBuilder::new();
+    let boundaries = build(verifier_gadget, &mut verifier_builder)?;
+
+    binius_core::constraint_system::validate::validate_witness(
+        &constraint_system,
+        &boundaries,
+        &witness,
+    )?;
+    Ok(())
+}
+
+fn tiny_keccak256(message: &[u8]) -> [u8; 32] {
+    let mut digest = [0; 32];
+    let mut keccak = Keccak::v256();
+    keccak.update(message);
+    keccak.finalize(&mut digest);
+    digest
+}
+
+#[test]
+fn test_keccak256_gadget_matches_tiny_keccak() {
+    // around the rate of 136 bytes, where the padding takes a block of its own
+    let messages = [0, 1, 32, 135, 136, 137, 272, 300]
+        .map(|len| (0..len).map(|i| (i * 7 + len) as u8).collect::<Vec<u8>>());
+
+    let mut gadget = Keccak256::new();
+    for message in &messages {
+        let region = gadget.hash(message).unwrap();
+        assert_eq!(region.len, message.len());
+        assert_eq!(gadget.digest(&region), tiny_keccak256(message));
+    }
+    assert_eq!(gadget.regions().len(), messages.len());
+    assert!(gadget.hash(&[0; 255 * 136]).is_err());
+
+    validate_keccak256(&messages, &messages).unwrap();
+}
+
+#[test]
+fn test_keccak256_gadget_rejects_other_message() {
+    let message = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let mut other_message = message.clone();
+    other_message[0] ^= 1;
+    assert!(validate_keccak256(&[message], &[other_message]).is_err());
+    assert!(Keccak256::new()
+        .build(&mut ConstraintSystemBuilder::new())
+        .is_err());
+}
+
+#[test]
+fn test_keccak256_gadget_private_preimage() {
+    let messages = [0, 135, 300].map(|len| (0..len).map(|i| (i * 3) as u8).collect::<Vec<u8>>());
+    let mut gadget = Keccak256::new().with_private_preimage();
+    for message in &messages {
+        gadget.hash(message).unwrap();
+    }
+    // the verifier only knows the lengths and the digests
+    let verifier_gadget = |digests: &[[u8; 32]]| {
+        let mut verifier_gadget = Keccak256::new().with_private_preimage();
+        for (message, digest) in messages.iter().zip(digests) {
+            verifier_gadget
+                .hash_private(message.len(), *digest)
+                .unwrap();
+        }
+        verifier_gadget
+    };
+    let mut digests = messages.clone().map(|message| tiny_keccak256(&message));
+    let public_regions = verifier_gadget(&digests).regions().to_vec();
+    assert_eq!(gadget.regions(), public_regions);
+    validate_keccak256_gadgets(gadget, verifier_gadget(&digests), false).unwrap();
+
+    let mut gadget = Keccak256::new().with_private_preimage();
+    for message in &messages {
+        gadget.hash(message).unwrap();
+    }
+    digests[1][0] ^= 1;
+    assert!(validate_keccak256_gadgets(gadget, verifier_gadget(&digests), false).is_err());
+}
+
+#[test]
+fn test_keccak256_gadget_over_caller_memory() {
+    // a message of the caller, with its keccak padding and its digest, after bytes of its own
+    let message = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let mut padding = vec![0; 136 - message.len()];
+    padding[0] = 0x01;
+    *padding.last_mut().unwrap() |= 0x80;
+    let memory = [
+        vec![7; 10],
+        message.clone(),
+        padding,
+        tiny_keccak256(&message).to_vec(),
+    ]
+    .concat();
+    let gadget = || {
+        let mut gadget = Keccak256::with_memory(&memory);
+        let region = gadget.hash_at(10, message.len()).unwrap();
+        assert_eq!(gadget.digest(&region), tiny_keccak256(&message));
+        gadget
+    };
+    validate_keccak256_gadgets(gadget(), gadget(), true).unwrap();
+
+    // the region has to be followed by its padding and its digest
+    assert!(Keccak256::with_memory(&memory)
+        .hash_at(11, message.len())
+        .is_err());
+    assert!(Keccak256::with_memory(&memory)
+        .hash_at(10, message.len() + 10)
+        .is_err());
+
+    // the caller reads its own bytes through the gadget, at one timestamp after the other
+    let mut gadget = Keccak256::with_memory(&memory);
+    assert_eq!(gadget.memory(), &memory[..]);
+    let first_read = gadget.read(3).unwrap();
+    assert_ne!(gadget.read(3).unwrap(), first_read);
+    assert!(gadget.read(memory.len() as u32).is_err());
+}
+
 #[test]
 #[ignore = "This is only used for generating test data"]
 fn download_test_data() {